@@ -1,68 +1,71 @@
+use crate::voxel::get_voxel_fn;
 use bevy::prelude::*;
 use bevy_voxel_world::prelude::*;
-use serde::Deserialize;
-use crate::voxel::get_voxel_fn;
 
-#[derive(Resource, Clone, Deserialize, Default)]
-pub struct VoxelWorldConfiguration {
+/// A single world that spawns the terrain at full detail close to the camera, and at
+/// progressively lower detail further away.
+#[derive(Resource, Clone)]
+pub struct TerrainWorld {
     pub scale: f32,
     pub height_scale: f32,
     pub height_minus: f32,
-    pub from: u32,
-    pub to: u32,
+    pub lod_distances: Vec<u32>,
 }
 
-macro_rules! impl_voxel_world_config {
-    ($type:ident) => {
-        #[derive(Resource, Clone, Default)]
-        pub struct $type(pub VoxelWorldConfiguration);
-
-        impl VoxelWorldConfig for $type {
-            fn spawning_min_distance(&self) -> u32 {
-                self.0.from
-            }
-            fn spawning_distance(&self) -> u32 {
-                self.0.from
-            }
-            fn spawning_max_distance(&self) -> u32 {
-                self.0.to
-            }
-            fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
-                let scale = self.0.scale as f64;
-                let height_scale = self.0.height_scale as f64;
-                let height_minus = self.0.height_minus as f64;
-                Box::new(move |_chunk_pos| {
-                    let mut voxel_fn = get_voxel_fn(scale, height_scale, height_minus);
-                    Box::new(move |pos| voxel_fn(pos, 0))
-                })
-            }
-            fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
-                ChunkDespawnStrategy::Distance(7)
-            }
-            fn chunk_spawn_strategy(&self) -> ChunkSpawnStrategy {
-                ChunkSpawnStrategy::Distance(5)
-            }
-            fn debug_draw_chunks(&self) -> bool {
-                false
-            }
+impl Default for TerrainWorld {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            height_scale: 1.0,
+            height_minus: 0.0,
+            // Full detail up to 4 chunks away, then four rings of lower detail
+            lod_distances: vec![4, 8, 16, 32, 64],
         }
-    };
+    }
 }
 
-impl_voxel_world_config!(HighDetailWorld);
-impl_voxel_world_config!(LowDetailWorld1);
-impl_voxel_world_config!(LowDetailWorld2);
-impl_voxel_world_config!(LowDetailWorld3);
-impl_voxel_world_config!(LowDetailWorld4);
+impl VoxelWorldConfig for TerrainWorld {
+    type MaterialIndex = u8;
 
-#[derive(Deserialize)]
-pub struct WorldConfigurations {
-    pub high_detail: VoxelWorldConfiguration,
-    pub low_detail: Vec<VoxelWorldConfiguration>,
-}
+    fn lod_distances(&self) -> Vec<u32> {
+        self.lod_distances.clone()
+    }
+
+    // The far rings are too expensive to render into the shadow cascades
+    fn shadow_distance(&self) -> Option<u32> {
+        self.lod_distances.get(1).copied()
+    }
+
+    fn lod_downsampling(&self) -> LodDownsampling {
+        LodDownsampling::Majority
+    }
 
-pub fn load_configurations() -> WorldConfigurations {
-    let config_str =
-        std::fs::read_to_string("voxel_configs.yaml").expect("Failed to read config file");
-    serde_yaml::from_str(&config_str).expect("Failed to parse YAML")
-}
\ No newline at end of file
+    fn lod_fade_duration(&self) -> f32 {
+        0.5
+    }
+
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
+        self.voxel_lookup_delegate_for_lod(0)
+    }
+
+    fn voxel_lookup_delegate_for_lod(&self, lod_level: u8) -> VoxelLookupDelegate {
+        let scale = self.scale as f64;
+        let height_scale = self.height_scale as f64;
+        let height_minus = self.height_minus as f64;
+        // Trees are too small to be seen at lower levels of detail
+        let with_trees = lod_level < 2;
+        Box::new(move |_chunk_pos| get_voxel_fn(scale, height_scale, height_minus, with_trees))
+    }
+
+    fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
+        ChunkDespawnStrategy::FarAway
+    }
+
+    fn chunk_spawn_strategy(&self) -> ChunkSpawnStrategy {
+        ChunkSpawnStrategy::Close
+    }
+
+    fn debug_draw_chunks(&self) -> bool {
+        false
+    }
+}
@@ -23,11 +23,7 @@ pub fn setup(mut commands: Commands) {
 
     commands
         .entity(camera_entity)
-        .insert(VoxelWorldCamera::<HighDetailWorld>::default())
-        .insert(VoxelWorldCamera::<LowDetailWorld1>::default())
-        .insert(VoxelWorldCamera::<LowDetailWorld2>::default())
-        .insert(VoxelWorldCamera::<LowDetailWorld3>::default())
-        .insert(VoxelWorldCamera::<LowDetailWorld4>::default());
+        .insert(VoxelWorldCamera::<TerrainWorld>::default());
 
     let cascade_shadow_config = CascadeShadowConfigBuilder::default().build();
     commands.spawn(DirectionalLightBundle {
@@ -7,6 +7,7 @@ pub fn get_voxel_fn(
     scale: f64,
     height_scale: f64,
     height_minus: f64,
+    with_trees: bool,
 ) -> Box<dyn FnMut(IVec3, u8) -> WorldVoxel + Send + Sync> {
     let mut noise = HybridMulti::<Perlin>::new(1234);
     noise.octaves = 5;
@@ -62,7 +63,7 @@ pub fn get_voxel_fn(
             WorldVoxel::Solid(0) // Ground material
         } else if y < ground_height + 5.0 && ground_height > 5.0 && y > 5.0 {
             // Ensure trees spawn with at least 5 blocks of distance between each other
-            if (pos.x % 5 == 0) && (pos.z % 5 == 0) {
+            if with_trees && (pos.x % 5 == 0) && (pos.z % 5 == 0) {
                 let tree_height = 5; // Fixed tree height for trunk
                 let tree_top_height = ground_height + tree_height as f64;
 
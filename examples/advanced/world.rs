@@ -27,13 +27,15 @@ impl<C: VoxelWorldConfig + Default + 'static> Default for WorldWrapper<C> {
 }
 
 impl<C: VoxelWorldConfig + 'static> VoxelWorldConfig for WorldWrapper<C> {
+    type MaterialIndex = C::MaterialIndex;
+
     fn spawning_min_distance(&self) -> u32 {
         self.config.spawning_min_distance()
     }
     fn spawning_max_distance(&self) -> u32 {
         self.config.spawning_max_distance()
     }
-    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
         self.config.voxel_lookup_delegate()
     }
     fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
@@ -82,6 +84,8 @@ impl Default for HighDetailWorld {
 }
 
 impl VoxelWorldConfig for HighDetailWorld {
+    type MaterialIndex = u8;
+
     fn spawning_min_distance(&self) -> u32 {
         self.from
     }
@@ -97,7 +101,7 @@ impl VoxelWorldConfig for HighDetailWorld {
         let height_minus = self.height_minus as f64;
         Box::new(move |chunk_pos| {
             let mut voxel_fn = get_voxel_fn(scale, height_scale, height_minus);
-            Box::new(move |pos| voxel_fn(pos, 0))
+            Box::new(move |pos, lod_level| voxel_fn(pos, lod_level))
         })
     }
     fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
@@ -134,6 +138,8 @@ impl Default for LowDetailWorld {
 }
 
 impl VoxelWorldConfig for LowDetailWorld {
+    type MaterialIndex = u8;
+
     fn spawning_min_distance(&self) -> u32 {
         self.from
     }
@@ -149,7 +155,7 @@ impl VoxelWorldConfig for LowDetailWorld {
         let height_minus = self.height_minus as f64;
         Box::new(move |chunk_pos| {
             let mut voxel_fn = get_voxel_fn(scale, height_scale, height_minus);
-            Box::new(move |pos| voxel_fn(pos, 0))
+            Box::new(move |pos, lod_level| voxel_fn(pos, lod_level))
         })
     }
     fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
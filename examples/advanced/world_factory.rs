@@ -46,6 +46,8 @@ impl Plugin for LowDetailWorldPlugin {
 
 // Implement VoxelWorldConfig for wrapper structs
 impl VoxelWorldConfig for HighDetailWorldWrapper {
+    type MaterialIndex = u8;
+
     fn spawning_min_distance(&self) -> u32 {
         self.0.spawning_min_distance()
     }
@@ -72,6 +74,8 @@ impl VoxelWorldConfig for HighDetailWorldWrapper {
 }
 
 impl VoxelWorldConfig for LowDetailWorldWrapper {
+    type MaterialIndex = u8;
+
     fn spawning_min_distance(&self) -> u32 {
         self.0.spawning_min_distance()
     }
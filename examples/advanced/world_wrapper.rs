@@ -21,6 +21,8 @@ impl<C: VoxelWorldConfig + Default + 'static> Default for WorldWrapper<C> {
 }
 
 impl<C: VoxelWorldConfig + 'static> VoxelWorldConfig for WorldWrapper<C> {
+    type MaterialIndex = C::MaterialIndex;
+
     fn spawning_min_distance(&self) -> u32 {
         self.config.spawning_min_distance()
     }
@@ -29,7 +31,7 @@ impl<C: VoxelWorldConfig + 'static> VoxelWorldConfig for WorldWrapper<C> {
         self.config.spawning_max_distance()
     }
 
-    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<Self::MaterialIndex> {
         self.config.voxel_lookup_delegate()
     }
 
@@ -68,7 +68,7 @@ fn setup(mut commands: Commands) {
     });
 }
 
-fn get_voxel_fn() -> Box<dyn FnMut(IVec3) -> WorldVoxel + Send + Sync> {
+fn get_voxel_fn() -> Box<dyn FnMut(IVec3, u8) -> WorldVoxel + Send + Sync> {
     // Set up some noise to use as the terrain height map
     let mut noise = HybridMulti::<Perlin>::new(1234);
     noise.octaves = 4;
@@ -82,7 +82,7 @@ fn get_voxel_fn() -> Box<dyn FnMut(IVec3) -> WorldVoxel + Send + Sync> {
 
     // Then we return this boxed closure that captures the noise and the cache
     // This will get sent off to a separate thread for meshing by bevy_voxel_world
-    Box::new(move |pos: IVec3| {
+    Box::new(move |pos: IVec3, _lod_level: u8| {
         // Sea level
         if pos.y < 1 {
             return WorldVoxel::Solid(3);
@@ -153,19 +153,7 @@ fn explosion(
         let vox = voxel_world.get_voxel(impact_point - IVec3::Y);
 
         // Dig out a spherical volume centered around the impact point
-        let radius = 10;
-        for x in -radius..=radius {
-            for y in -radius..=radius {
-                for z in -radius..=radius {
-                    let pos =
-                        IVec3::new(x + impact_point.x, y + impact_point.y, z + impact_point.z);
-
-                    if pos.distance_squared(impact_point) <= radius.pow(2) {
-                        voxel_world.set_voxel(pos, WorldVoxel::Air);
-                    }
-                }
-            }
-        }
+        voxel_world.fill_sphere(impact_point, 10, WorldVoxel::Air);
 
         // Spread some voxels out around the impact zone
         let num_voxels = 50;
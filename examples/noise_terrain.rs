@@ -9,6 +9,10 @@ use bevy::{
 
 use bevy_voxel_world::prelude::*;
 use noise::{HybridMulti, NoiseFn, Perlin};
+use std::sync::Arc;
+
+const WATER: u8 = 3;
+const CANOPY: u8 = 1;
 
 #[derive(Resource, Clone)]
 struct MainWorld {
@@ -41,6 +45,21 @@ impl VoxelWorldConfig for MainWorld {
         ChunkDespawnStrategy::FarAway
     }
 
+    /// Sea level voxels only get a surface mesh
+    fn fluid_materials(&self) -> Arc<dyn Fn(u8) -> bool + Send + Sync> {
+        Arc::new(|mat| mat == WATER)
+    }
+
+    /// The tree canopies sway in the wind
+    fn wind_materials(&self) -> Arc<dyn Fn(u8) -> bool + Send + Sync> {
+        Arc::new(|mat| mat == CANOPY)
+    }
+
+    /// We attach our own animated water material in `attach_water_material`
+    fn custom_fluid_material(&self) -> bool {
+        true
+    }
+
     /// Strategy for spawning chunks
     /// This is only used if the despawn strategy is `FarAway`
     fn chunk_spawn_strategy(&self) -> ChunkSpawnStrategy {
@@ -51,7 +70,7 @@ impl VoxelWorldConfig for MainWorld {
 fn get_voxel_fn(
     scale: f64,
     height_scale: f64,
-) -> Box<dyn FnMut(IVec3) -> WorldVoxel + Send + Sync> {
+) -> Box<dyn FnMut(IVec3, u8) -> WorldVoxel + Send + Sync> {
     let mut noise = HybridMulti::<Perlin>::new(1234);
     noise.octaves = 5;
     noise.frequency = 1.1;
@@ -61,9 +80,9 @@ fn get_voxel_fn(
     let mut cache = HashMap::<(i32, i32), f64>::new();
     let mut canopy_positions = HashMap::<(i32, i32), i32>::new(); // Track positions for canopies
 
-    Box::new(move |pos: IVec3| {
+    Box::new(move |pos: IVec3, _lod_level: u8| {
         if pos.y < 1 {
-            return WorldVoxel::Solid(3); // Sea level voxel
+            return WorldVoxel::Solid(WATER); // Sea level voxel
         }
 
         let [x, y, z] = pos.as_dvec3().to_array();
@@ -96,7 +115,7 @@ fn get_voxel_fn(
         for (dx, dz) in canopy_offsets.iter() {
             if let Some(canopy_base) = canopy_positions.get(&(pos.x + dx, pos.z + dz)) {
                 if y_i32 >= *canopy_base && y_i32 <= *canopy_base + 3 {
-                    return WorldVoxel::Solid(1); // Canopy material (greenery)
+                    return WorldVoxel::Solid(CANOPY); // Canopy material (greenery)
                 }
             }
         }
@@ -162,10 +181,47 @@ fn main() {
         .add_systems(Startup, (setup, grab_mouse))
         //.add_systems(Update, fly_camera)
         .add_systems(Update, (walking_camera, exit_on_esc))
+        .add_systems(Update, (attach_water_material, animate_water_material))
         .run();
 }
 
-fn setup(mut commands: Commands, mut clear_color: ResMut<ClearColor>) {
+#[derive(Resource)]
+struct WaterMaterial(Handle<StandardMaterial>);
+
+fn attach_water_material(
+    mut commands: Commands,
+    water_surfaces: Query<Entity, Added<VoxelFluidSurface<MainWorld>>>,
+    water_material: Res<WaterMaterial>,
+) {
+    for entity in water_surfaces.iter() {
+        commands.entity(entity).insert(water_material.0.clone());
+    }
+}
+
+fn animate_water_material(
+    time: Res<Time>,
+    water_material: Res<WaterMaterial>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Some(material) = materials.get_mut(&water_material.0) else {
+        return;
+    };
+    let wave = (time.elapsed_seconds() * 1.5).sin() * 0.5 + 0.5;
+    material.base_color = Color::srgba(0.1, 0.35 + wave * 0.05, 0.6 + wave * 0.1, 0.75);
+}
+
+fn setup(
+    mut commands: Commands,
+    mut clear_color: ResMut<ClearColor>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(WaterMaterial(materials.add(StandardMaterial {
+        base_color: Color::srgba(0.1, 0.35, 0.6, 0.75),
+        alpha_mode: AlphaMode::Blend,
+        perceptual_roughness: 0.1,
+        ..default()
+    })));
+
     // Set the sky color to a brighter blue
     *clear_color = ClearColor(Color::rgb(0.5, 0.8, 1.0)); // Light blue sky
 
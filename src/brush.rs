@@ -0,0 +1,133 @@
+///
+/// Brushes
+/// Sculpting tools that add or remove voxels around a point. Brushes with falloff fade out
+/// towards their edge by changing voxel densities, for worlds made of `Partial` voxels.
+///
+use bevy::prelude::*;
+
+use crate::voxel::WorldVoxel;
+
+/// The shape of the volume a brush affects
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BrushShape {
+    #[default]
+    Sphere,
+    Cube,
+}
+
+/// What a brush does to the voxels it affects
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BrushMode<I = u8> {
+    /// Adds the given voxel, raising the density of voxels that are already there
+    Add(WorldVoxel<I>),
+    /// Removes voxels, lowering their density until they turn into air
+    Subtract,
+}
+
+/// A brush that edits the voxels around a point, see `VoxelWorld::apply_brush`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VoxelBrush<I = u8> {
+    pub shape: BrushShape,
+    pub mode: BrushMode<I>,
+    /// The distance from the center to the edge of the brush, in voxels
+    pub radius: f32,
+    /// The part of the radius, from 0.0 to 1.0, over which the brush fades out. A brush without
+    /// falloff has a hard edge, and sets every voxel it touches to full density.
+    pub falloff: f32,
+}
+
+impl<I: Copy + PartialEq> VoxelBrush<I> {
+    /// A sphere brush that adds the given voxel
+    pub fn sphere(radius: f32, voxel: WorldVoxel<I>) -> Self {
+        Self {
+            shape: BrushShape::Sphere,
+            mode: BrushMode::Add(voxel),
+            radius,
+            falloff: 0.0,
+        }
+    }
+
+    /// A cube brush that adds the given voxel. The radius is half the width of the cube.
+    pub fn cube(radius: f32, voxel: WorldVoxel<I>) -> Self {
+        Self {
+            shape: BrushShape::Cube,
+            ..Self::sphere(radius, voxel)
+        }
+    }
+
+    /// The same brush, removing voxels instead of adding them
+    pub fn subtractive(self) -> Self {
+        Self {
+            mode: BrushMode::Subtract,
+            ..self
+        }
+    }
+
+    pub fn with_falloff(self, falloff: f32) -> Self {
+        Self {
+            falloff: falloff.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    /// How strongly the brush affects a voxel at the given offset from its center, from 0.0 (not
+    /// at all) to 1.0 (fully)
+    pub fn strength(&self, offset: Vec3) -> f32 {
+        let distance = match self.shape {
+            BrushShape::Sphere => offset.length(),
+            BrushShape::Cube => offset.abs().max_element(),
+        };
+        if self.radius <= 0.0 || distance > self.radius {
+            return 0.0;
+        }
+        if self.falloff <= 0.0 {
+            return 1.0;
+        }
+        ((1.0 - distance / self.radius) / self.falloff).clamp(0.0, 1.0)
+    }
+
+    /// The voxels that change when the brush is applied at `center`, given a lookup of the
+    /// current voxels
+    pub(crate) fn edits(
+        &self,
+        center: IVec3,
+        get_voxel: impl Fn(IVec3) -> WorldVoxel<I>,
+    ) -> Vec<(IVec3, WorldVoxel<I>)> {
+        let extent = IVec3::splat(self.radius.ceil() as i32);
+        let (min, max) = (center - extent, center + extent);
+
+        let mut edits = Vec::new();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let position = IVec3::new(x, y, z);
+                    let strength = self.strength((position - center).as_vec3());
+                    let amount = (strength * u8::MAX as f32).round() as u8;
+                    if amount == 0 {
+                        continue;
+                    }
+
+                    let current = get_voxel(position);
+                    let new = match self.mode {
+                        BrushMode::Add(voxel) => {
+                            if current.is_solid() && current.density() >= amount {
+                                continue;
+                            }
+                            voxel.with_density(amount)
+                        }
+                        BrushMode::Subtract => {
+                            if !current.is_solid() {
+                                continue;
+                            }
+                            current.with_density(current.density().saturating_sub(amount))
+                        }
+                    };
+                    if new != current {
+                        edits.push((position, new));
+                    }
+                }
+            }
+        }
+        edits
+    }
+}
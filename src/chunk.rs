@@ -1,12 +1,26 @@
-use bevy::{prelude::*, render::primitives::Aabb, tasks::Task, utils::HashSet};
+#[cfg(feature = "render")]
+use bevy::render::primitives::Aabb;
+use bevy::{prelude::*, tasks::Task, utils::HashMap};
 use ndshape::{ConstShape, ConstShape3u32};
 use std::{
     hash::{Hash, Hasher},
     marker::PhantomData,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock, Weak,
+    },
 };
 
-use crate::{meshing, voxel::WorldVoxel, voxel_world_internal::ModifiedVoxels};
+#[cfg(feature = "render")]
+use crate::meshing::{self, MeshingConfig};
+use crate::{
+    configuration::{ChunkDecorator, LodDownsampling, WorldConfig},
+    light::{LightArray, VoxelLight},
+    lod::{downsample, geometric_error, lod_scale},
+    rle::CompressedVoxels,
+    voxel::WorldVoxel,
+    voxel_world_internal::ModifiedVoxels,
+};
 
 // The size of a chunk in voxels
 // TODO: implement a way to change this though the configuration
@@ -19,18 +33,50 @@ pub(crate) const PADDED_CHUNK_SIZE: u32 = CHUNK_SIZE_U + 2;
 pub(crate) type PaddedChunkShape =
     ConstShape3u32<PADDED_CHUNK_SIZE, PADDED_CHUNK_SIZE, PADDED_CHUNK_SIZE>;
 
-pub(crate) type VoxelArray = [WorldVoxel; PaddedChunkShape::SIZE as usize];
+pub(crate) type VoxelArray<I = u8> = [WorldVoxel<I>; PaddedChunkShape::SIZE as usize];
+
+/// The biome of each column of a padded chunk, see `biome_index`
+pub(crate) type BiomeArray = [u8; (PADDED_CHUNK_SIZE * PADDED_CHUNK_SIZE) as usize];
+
+/// Index into a `BiomeArray` for the column at the given padded chunk coordinates
+pub(crate) fn biome_index(x: u32, z: u32) -> usize {
+    (z * PADDED_CHUNK_SIZE + x) as usize
+}
 
 #[derive(Component)]
 #[component(storage = "SparseSet")]
-pub(crate) struct ChunkThread<C>(pub Task<ChunkTask<C>>, PhantomData<C>);
+pub(crate) struct ChunkThread<C: WorldConfig>(
+    pub Task<ChunkTask<C>>,
+    Arc<AtomicBool>,
+    #[cfg_attr(not(feature = "render"), allow(dead_code))] Arc<AtomicBool>,
+    PhantomData<C>,
+);
 
 impl<C> ChunkThread<C>
 where
-    C: Send + Sync + 'static,
+    C: WorldConfig,
 {
-    pub fn new(task: Task<ChunkTask<C>>, _pos: IVec3) -> Self {
-        Self(task, PhantomData)
+    pub fn new(
+        task: Task<ChunkTask<C>>,
+        _pos: IVec3,
+        cancelled: Arc<AtomicBool>,
+        generated: Arc<AtomicBool>,
+    ) -> Self {
+        Self(task, cancelled, generated, PhantomData)
+    }
+
+    /// True once the voxels of the chunk have been generated, and it is being meshed
+    #[cfg(feature = "render")]
+    pub fn is_generated(&self) -> bool {
+        self.2.load(Ordering::Relaxed)
+    }
+}
+
+/// Dropping the task only cancels it if it hasn't started yet, so running tasks are also told to
+/// stop, for example when the chunk is despawned or remeshed again before the task finished.
+impl<C: WorldConfig> Drop for ChunkThread<C> {
+    fn drop(&mut self) {
+        self.1.store(true, Ordering::Relaxed);
     }
 }
 
@@ -41,34 +87,81 @@ pub struct NeedsRemesh;
 #[derive(Component)]
 pub struct NeedsDespawn;
 
+/// Points to the child entities holding the transparent and fluid parts of a chunk's mesh
+#[cfg(feature = "render")]
+#[derive(Component)]
+pub(crate) struct ChunkMeshParts(pub Vec<Entity>);
+
+/// Marks a child entity holding a part of a chunk's mesh that should use the transparent material
+#[cfg(feature = "render")]
+#[derive(Component)]
+pub(crate) struct TransparentPart;
+
+/// Marks the child entity holding the fluid surface mesh of a chunk.
+///
+/// If `VoxelWorldConfig::custom_fluid_material` returns true, no material is assigned to these
+/// entities, and a custom (for example animated) material can be added by querying for
+/// `Added<VoxelFluidSurface<C>>`.
+#[derive(Component)]
+pub struct VoxelFluidSurface<C> {
+    pub chunk_position: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> VoxelFluidSurface<C> {
+    pub fn new(chunk_position: IVec3) -> Self {
+        Self {
+            chunk_position,
+            _marker: PhantomData,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
-pub enum FillType {
+pub enum FillType<I = u8> {
     Empty,
     Mixed,
-    Uniform(WorldVoxel),
+    Uniform(WorldVoxel<I>),
 }
 
 /// This is used to lookup voxel data from spawned chunks. Does not persist after
 /// the chunk is despawned.
 #[derive(Clone, Debug)]
-pub struct ChunkData {
+pub struct ChunkData<I = u8> {
     pub position: IVec3,
-    pub voxels: Option<Arc<VoxelArray>>,
+    /// The voxels of a mixed chunk. This is `None` while the chunk is compressed, use
+    /// `voxel_array` to get the voxels either way.
+    pub voxels: Option<Arc<VoxelArray<I>>>,
+    pub(crate) compressed: Option<Arc<CompressedVoxels<I>>>,
+    /// The light level of each voxel, `None` until light is first set in the chunk. Light is
+    /// kept when the chunk is remeshed.
+    pub light: Option<Arc<LightArray>>,
+    /// The biome of each column of the chunk, `None` when there is no
+    /// `VoxelWorldConfig::biome_lookup_delegate`
+    pub biomes: Option<Arc<BiomeArray>>,
     pub voxels_hash: u64,
+    /// Every voxel of the chunk is solid or fluid
     pub is_full: bool,
     pub is_empty: bool,
-    pub fill_type: FillType,
+    /// Every voxel of the chunk is an opaque cube, so nothing behind the chunk can be seen
+    /// through it. Only known in worlds that render.
+    pub is_opaque: bool,
+    pub fill_type: FillType<I>,
     pub entity: Entity,
 }
 
-impl ChunkData {
+impl<I: Copy + Hash + PartialEq> ChunkData<I> {
     pub fn new() -> Self {
         Self {
             position: IVec3::ZERO,
             voxels: None,
+            compressed: None,
+            light: None,
+            biomes: None,
             voxels_hash: 0,
             is_full: false,
             is_empty: true,
+            is_opaque: false,
             fill_type: FillType::Empty,
             entity: Entity::PLACEHOLDER,
         }
@@ -80,16 +173,29 @@ impl ChunkData {
     }
 
     pub fn generate_hash(&mut self) {
-        if let Some(voxels) = &self.voxels {
-            let mut hasher = std::collections::hash_map::DefaultHasher::new();
-            voxels.hash(&mut hasher);
-            self.voxels_hash = hasher.finish();
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        match (&self.voxels, &self.fill_type) {
+            (Some(voxels), _) => voxels.hash(&mut hasher),
+            // Chunks without a voxel array are hashed as their fill type tag, plus the voxel of
+            // uniform chunks
+            (None, FillType::Empty) => std::mem::discriminant(&self.fill_type).hash(&mut hasher),
+            (None, FillType::Uniform(voxel)) => {
+                std::mem::discriminant(&self.fill_type).hash(&mut hasher);
+                voxel.hash(&mut hasher);
+            }
+            // Compressed chunks keep the hash of their voxels
+            (None, FillType::Mixed) => return,
         }
+        // Biomes change the textures of the mesh, so they are part of the mesh cache key
+        self.biomes.hash(&mut hasher);
+        self.voxels_hash = hasher.finish();
     }
 
-    pub fn get_voxel(&self, position: UVec3) -> WorldVoxel {
-        if self.voxels.is_some() {
-            self.voxels.as_ref().unwrap()[PaddedChunkShape::linearize(position.to_array()) as usize]
+    pub fn get_voxel(&self, position: UVec3) -> WorldVoxel<I> {
+        if let Some(voxels) = &self.voxels {
+            voxels[PaddedChunkShape::linearize(position.to_array()) as usize]
+        } else if let Some(compressed) = &self.compressed {
+            compressed.get(PaddedChunkShape::linearize(position.to_array()))
         } else {
             match self.fill_type {
                 FillType::Uniform(voxel) => voxel,
@@ -99,10 +205,102 @@ impl ChunkData {
         }
     }
 
+    /// The biome of the column at the given position within the chunk. Zero when the world has
+    /// no biomes.
+    pub fn get_biome(&self, position: UVec3) -> u8 {
+        self.biomes
+            .as_ref()
+            .map_or(0, |biomes| biomes[biome_index(position.x, position.z)])
+    }
+
+    /// The light level of the voxel at the given position within the chunk
+    pub fn get_light(&self, position: UVec3) -> VoxelLight {
+        self.light.as_ref().map_or(VoxelLight::default(), |light| {
+            light[PaddedChunkShape::linearize(position.to_array()) as usize]
+        })
+    }
+
+    /// Set the light level of the voxel at the given position within the chunk
+    pub fn set_light(&mut self, position: UVec3, light: VoxelLight) {
+        let lights = self.light.get_or_insert_with(|| {
+            Arc::new([VoxelLight::default(); PaddedChunkShape::SIZE as usize])
+        });
+        Arc::make_mut(lights)[PaddedChunkShape::linearize(position.to_array()) as usize] = light;
+    }
+
+    /// Store the voxels of the chunk, given the number of solid and fluid voxels among them
+    pub(crate) fn store_voxels(&mut self, voxels: &VoxelArray<I>, filled_count: u32) {
+        self.is_empty = filled_count == 0;
+        self.is_full = filled_count == PaddedChunkShape::SIZE;
+        self.is_opaque = false;
+        self.compressed = None;
+
+        // Chunks of only air, like the sky, or only one material, like deep underground, are
+        // stored as that one voxel
+        let is_uniform = !voxels[0].is_unset() && voxels.iter().all(|voxel| *voxel == voxels[0]);
+
+        if is_uniform {
+            self.fill_type = FillType::Uniform(voxels[0]);
+            self.voxels = None;
+        } else if filled_count > 0 {
+            self.fill_type = FillType::Mixed;
+            self.voxels = Some(Arc::new(*voxels));
+        } else {
+            self.fill_type = FillType::Empty;
+            self.voxels = None;
+        };
+
+        self.generate_hash();
+
+        // Full chunks are rarely meshed, since they are usually opaque, so their voxels are
+        // mostly needed for lookups
+        if self.is_full {
+            self.compress();
+        }
+    }
+
+    /// The voxels of a mixed chunk, decompressing them if needed
+    pub fn voxel_array(&self) -> Option<Arc<VoxelArray<I>>> {
+        match (&self.voxels, &self.compressed) {
+            (Some(voxels), _) => Some(voxels.clone()),
+            (None, Some(compressed)) => Some(Arc::new(compressed.decode())),
+            (None, None) => None,
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.compressed.is_some()
+    }
+
+    /// Run-length encodes the voxels, to save memory while the chunk is not being edited
+    pub(crate) fn compress(&mut self) {
+        if let Some(voxels) = self.voxels.take() {
+            self.compressed = Some(Arc::new(CompressedVoxels::encode(&voxels)));
+        }
+    }
+
+    /// Replace the voxels with an identical shared copy, if there is one
+    pub(crate) fn share_voxels(&mut self, shared: &SharedVoxelData<I>) {
+        if let Some(voxels) = self.voxels.take() {
+            self.voxels = Some(shared.share(self.voxels_hash, voxels));
+        }
+    }
+
+    /// The number of bytes taken up by the voxels of the chunk
+    #[cfg(feature = "render")]
+    pub(crate) fn voxels_size(&self) -> usize {
+        match (&self.voxels, &self.compressed) {
+            (Some(_), _) => std::mem::size_of::<VoxelArray<I>>(),
+            (None, Some(compressed)) => compressed.size_in_bytes(),
+            (None, None) => 0,
+        }
+    }
+
     pub fn world_position(&self) -> Vec3 {
         self.position.as_vec3() * CHUNK_SIZE_F
     }
 
+    #[cfg(feature = "render")]
     pub fn aabb(&self) -> Aabb {
         let min = Vec3::ZERO;
         let max = min + Vec3::splat(CHUNK_SIZE_F);
@@ -111,29 +309,80 @@ impl ChunkData {
 
     pub fn encloses_point(&self, point: Vec3) -> bool {
         let local_point = point - self.world_position();
-        let aabb = self.aabb();
-        let min = aabb.min();
-        let max = aabb.max();
-        local_point.x >= min.x
-            && local_point.y >= min.y
-            && local_point.z >= min.z
-            && local_point.x <= max.x
-            && local_point.y <= max.y
-            && local_point.z <= max.z
+        local_point.cmpge(Vec3::ZERO).all() && local_point.cmple(Vec3::splat(CHUNK_SIZE_F)).all()
     }
 }
 
-impl Default for ChunkData {
+impl<I: Copy + Hash + PartialEq> Default for ChunkData<I> {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Voxel data that is shared between world instances, for example when the same region is
+/// generated by several worlds in a ring of levels of detail. Chunks with identical voxels share
+/// one copy, which is only kept for as long as one of the worlds uses it. The data is never
+/// modified in place, so a world that changes a shared chunk gets its own copy.
+///
+/// Create one and return clones of it from `VoxelWorldConfig::shared_voxel_data` of each world.
+#[derive(Clone)]
+pub struct SharedVoxelData<I = u8> {
+    arrays: Arc<RwLock<HashMap<u64, Weak<VoxelArray<I>>>>>,
+}
+
+impl<I> Default for SharedVoxelData<I> {
+    fn default() -> Self {
+        Self {
+            arrays: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+}
+
+impl<I: Copy + PartialEq> SharedVoxelData<I> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of voxel arrays that are currently in use
+    pub fn len(&self) -> usize {
+        let arrays = self.arrays.read().unwrap();
+        arrays
+            .values()
+            .filter(|array| array.strong_count() > 0)
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the shared copy of the voxels, if an identical one is in use, or else makes these
+    /// voxels the shared copy
+    pub(crate) fn share(&self, hash: u64, voxels: Arc<VoxelArray<I>>) -> Arc<VoxelArray<I>> {
+        let mut arrays = self.arrays.write().unwrap();
+        if let Some(shared) = arrays.get(&hash).and_then(Weak::upgrade) {
+            if *shared == *voxels {
+                return shared;
+            }
+        }
+
+        // Forget arrays that are no longer in use every now and then
+        if arrays.len() % 1024 == 1023 {
+            arrays.retain(|_, array| array.strong_count() > 0);
+        }
+        arrays.insert(hash, Arc::downgrade(&voxels));
+        voxels
+    }
+}
+
 /// A marker component for chunks, with some helpful data
 #[derive(Component, Clone)]
 pub struct Chunk<C> {
+    /// Position of the chunk, in chunks of its level of detail
     pub position: IVec3,
     pub entity: Entity,
+    /// Level of detail of the chunk, where 0 is full detail. See `VoxelWorldConfig::lod_distances`.
+    pub lod_level: u8,
     _marker: PhantomData<C>,
 }
 
@@ -142,18 +391,25 @@ impl<C> Chunk<C> {
         Self {
             position,
             entity,
+            lod_level: 0,
             _marker: PhantomData,
         }
     }
 
+    pub fn with_lod_level(self, lod_level: u8) -> Self {
+        Self { lod_level, ..self }
+    }
+
     pub fn from(chunk: &Chunk<C>) -> Self {
         Self {
             position: chunk.position,
             entity: chunk.entity,
+            lod_level: chunk.lod_level,
             _marker: PhantomData,
         }
     }
 
+    #[cfg(feature = "render")]
     pub fn aabb(&self) -> Aabb {
         let min = Vec3::ZERO;
         let max = min + Vec3::splat(CHUNK_SIZE_F);
@@ -161,103 +417,329 @@ impl<C> Chunk<C> {
     }
 }
 
+/// The voxels of a chunk that is being generated, passed to `VoxelWorldConfig::chunk_decorator`.
+/// Positions are local to the chunk, from 0 to `CHUNK_SIZE_I - 1` on each axis, with the border
+/// shared with neighbouring chunks at -1 and `CHUNK_SIZE_I`.
+pub struct ChunkVoxelsMut<'a, I = u8> {
+    voxels: &'a mut VoxelArray<I>,
+    chunk_position: IVec3,
+    modified_voxels: &'a HashMap<IVec3, WorldVoxel<I>>,
+}
+
+impl<'a, I: Copy + PartialEq> ChunkVoxelsMut<'a, I> {
+    pub fn chunk_position(&self) -> IVec3 {
+        self.chunk_position
+    }
+
+    /// The world position of a position in the chunk
+    pub fn world_position(&self, local: IVec3) -> IVec3 {
+        self.chunk_position * CHUNK_SIZE_I + local
+    }
+
+    /// Get the voxel at a position in the chunk, or `Unset` if it is outside of it
+    pub fn get(&self, local: IVec3) -> WorldVoxel<I> {
+        Self::index(local).map_or(WorldVoxel::Unset, |i| self.voxels[i])
+    }
+
+    /// Set the voxel at a position in the chunk. Positions outside of the chunk, and voxels set
+    /// with `VoxelWorld::set_voxel`, are left alone.
+    pub fn set(&mut self, local: IVec3, voxel: WorldVoxel<I>) {
+        if self
+            .modified_voxels
+            .contains_key(&self.world_position(local))
+        {
+            return;
+        }
+        if let Some(i) = Self::index(local) {
+            self.voxels[i] = voxel;
+        }
+    }
+
+    fn index(local: IVec3) -> Option<usize> {
+        let padded = local + IVec3::ONE;
+        if padded.cmplt(IVec3::ZERO).any()
+            || padded.cmpge(IVec3::splat(PADDED_CHUNK_SIZE as i32)).any()
+        {
+            return None;
+        }
+        Some(PaddedChunkShape::linearize(padded.as_uvec3().to_array()) as usize)
+    }
+}
+
 /// Holds all data needed to generate and mesh a chunk
 #[derive(Component)]
-pub(crate) struct ChunkTask<C> {
+pub(crate) struct ChunkTask<C: WorldConfig> {
     pub position: IVec3,
-    pub chunk_data: ChunkData,
+    pub lod_level: u8,
+    pub chunk_data: ChunkData<C::MaterialIndex>,
     pub modified_voxels: ModifiedVoxels<C>,
+    #[cfg(feature = "render")]
     pub mesh: Option<Mesh>,
+    #[cfg(feature = "render")]
+    pub transparent_mesh: Option<Mesh>,
+    #[cfg(feature = "render")]
+    pub fluid_mesh: Option<Mesh>,
+    /// Set when the chunk was not meshed because it is enclosed by opaque voxels, see
+    /// `VoxelWorldConfig::occlusion_culling`
+    #[cfg(feature = "render")]
+    pub occluded: bool,
+    /// The light of the chunk and its border, baked into the mesh. Kept apart from
+    /// `chunk_data.light`, which the chunk map keeps up to date on its own.
+    #[cfg(feature = "render")]
+    light: Option<Arc<LightArray>>,
+    /// Estimated error of chunks above full detail, see `lod::geometric_error`
+    pub geometric_error: Option<f32>,
+    decorator: Option<ChunkDecorator<C::MaterialIndex>>,
+    cancelled: Arc<AtomicBool>,
+    generated: Arc<AtomicBool>,
     _marker: PhantomData<C>,
 }
 
-impl<C: Send + Sync + 'static> ChunkTask<C> {
+impl<C: WorldConfig> ChunkTask<C> {
     pub fn new(entity: Entity, position: IVec3, modified_voxels: ModifiedVoxels<C>) -> Self {
         Self {
             position,
+            lod_level: 0,
             chunk_data: ChunkData::with_entity(entity),
             modified_voxels,
+            #[cfg(feature = "render")]
             mesh: None,
+            #[cfg(feature = "render")]
+            transparent_mesh: None,
+            #[cfg(feature = "render")]
+            fluid_mesh: None,
+            #[cfg(feature = "render")]
+            occluded: false,
+            #[cfg(feature = "render")]
+            light: None,
+            geometric_error: None,
+            decorator: None,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            generated: Arc::new(AtomicBool::new(false)),
             _marker: PhantomData,
         }
     }
 
+    /// The flag that is set when the `ChunkThread` running this task is dropped
+    pub fn cancellation_flag(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+
+    /// The flag that is set once the voxels of the chunk have been generated
+    pub fn generated_flag(&self) -> Arc<AtomicBool> {
+        self.generated.clone()
+    }
+
+    /// True if the result of this task is no longer needed
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn with_lod_level(self, lod_level: u8) -> Self {
+        Self { lod_level, ..self }
+    }
+
+    /// Run the decorator after the voxels of a full detail chunk are generated
+    pub fn with_decorator(self, decorator: Option<ChunkDecorator<C::MaterialIndex>>) -> Self {
+        Self { decorator, ..self }
+    }
+
+    /// Bake the given light into the mesh, see `VoxelWorldLightingPlugin`
+    #[cfg(feature = "render")]
+    pub fn with_light(self, light: Option<Arc<LightArray>>) -> Self {
+        Self { light, ..self }
+    }
+
+    /// Look up the biome of each column of the chunk. Needs to be called before `generate`, since
+    /// the biomes are part of the voxel hash.
+    pub fn generate_biomes<F>(&mut self, mut biome_fn: F)
+    where
+        F: FnMut(IVec2) -> u8 + Send + 'static,
+    {
+        let scale = lod_scale(self.lod_level);
+        let mut biomes = [0; (PADDED_CHUNK_SIZE * PADDED_CHUNK_SIZE) as usize];
+        for x in 0..PADDED_CHUNK_SIZE {
+            for z in 0..PADDED_CHUNK_SIZE {
+                let column = IVec2::new(
+                    (x as i32 + self.position.x * CHUNK_SIZE_I - 1) * scale,
+                    (z as i32 + self.position.z * CHUNK_SIZE_I - 1) * scale,
+                );
+                biomes[biome_index(x, z)] = biome_fn(column);
+            }
+        }
+        self.chunk_data.biomes = Some(Arc::new(biomes));
+    }
+
     /// Generate voxel data for the chunk. The supplied `modified_voxels` map is first checked,
     /// and where no voxeles are modified, the `voxel_data_fn` is called to get data from the
-    /// consumer.
-    pub fn generate<F>(&mut self, mut voxel_data_fn: F)
+    /// consumer. Chunks above full detail are sampled according to `downsampling`.
+    pub fn generate<F>(&mut self, mut voxel_data_fn: F, downsampling: LodDownsampling)
     where
-        F: FnMut(IVec3) -> WorldVoxel + Send + 'static,
+        F: FnMut(IVec3, u8) -> WorldVoxel<C::MaterialIndex> + Send + 'static,
     {
         let mut filled_count = 0;
         let modified_voxels = (*self.modified_voxels).read().unwrap();
         let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
-        let mut material_count = HashSet::new();
+
+        // Chunks at coarser levels of detail sample every `scale`th voxel
+        let scale = lod_scale(self.lod_level);
 
         for i in 0..PaddedChunkShape::SIZE {
+            // Stop early if the chunk was despawned in the meantime
+            if i % (PADDED_CHUNK_SIZE * PADDED_CHUNK_SIZE) == 0 && self.is_cancelled() {
+                return;
+            }
+
             let chunk_block = PaddedChunkShape::delinearize(i);
 
             let block_pos = IVec3 {
-                x: chunk_block[0] as i32 + (self.position.x * CHUNK_SIZE_I) - 1,
-                y: chunk_block[1] as i32 + (self.position.y * CHUNK_SIZE_I) - 1,
-                z: chunk_block[2] as i32 + (self.position.z * CHUNK_SIZE_I) - 1,
+                x: (chunk_block[0] as i32 + (self.position.x * CHUNK_SIZE_I) - 1) * scale,
+                y: (chunk_block[1] as i32 + (self.position.y * CHUNK_SIZE_I) - 1) * scale,
+                z: (chunk_block[2] as i32 + (self.position.z * CHUNK_SIZE_I) - 1) * scale,
             };
 
-            if let Some(voxel) = modified_voxels.get(&block_pos) {
-                voxels[i as usize] = *voxel;
-                if !voxel.is_unset() && !voxel.is_air() {
+            if self.lod_level > 0 && downsampling != LodDownsampling::Point {
+                // Sample the corners of the voxel's two halves along each axis
+                let half = scale / 2;
+                let samples = [0, 1, 2, 3, 4, 5, 6, 7].map(|j| {
+                    let sample_pos = block_pos + IVec3::new(j & 1, (j >> 1) & 1, j >> 2) * half;
+                    modified_voxels
+                        .get(&sample_pos)
+                        .copied()
+                        .unwrap_or_else(|| voxel_data_fn(sample_pos, self.lod_level))
+                });
+                let voxel = downsample(&samples, downsampling);
+
+                voxels[i as usize] = voxel;
+                if is_filled(&voxel) {
                     filled_count += 1;
                 }
                 continue;
             }
 
-            let voxel = voxel_data_fn(block_pos);
+            let voxel = match modified_voxels.get(&block_pos) {
+                Some(voxel) => *voxel,
+                None => voxel_data_fn(block_pos, self.lod_level),
+            };
 
             voxels[i as usize] = voxel;
 
-            if let WorldVoxel::Solid(m) = voxel {
+            if is_filled(&voxel) {
                 filled_count += 1;
-                material_count.insert(m);
             }
         }
 
-        self.chunk_data.is_empty = filled_count == 0;
-        self.chunk_data.is_full = filled_count == PaddedChunkShape::SIZE;
+        if let (Some(decorator), 0) = (&self.decorator, self.lod_level) {
+            decorator(&mut ChunkVoxelsMut {
+                voxels: &mut voxels,
+                chunk_position: self.position,
+                modified_voxels: &modified_voxels,
+            });
+            filled_count = voxels.iter().filter(|voxel| is_filled(voxel)).count() as u32;
+        }
 
-        if self.chunk_data.is_full && material_count.len() == 1 {
-            self.chunk_data.fill_type = FillType::Uniform(voxels[0]);
-            self.chunk_data.voxels = None;
-        } else if filled_count > 0 {
-            self.chunk_data.fill_type = FillType::Mixed;
-            self.chunk_data.voxels = Some(Arc::new(voxels));
-        } else {
-            self.chunk_data.fill_type = FillType::Empty;
-            self.chunk_data.voxels = None;
-        };
+        self.chunk_data.store_voxels(&voxels, filled_count);
+        self.generated.store(true, Ordering::Relaxed);
 
-        self.chunk_data.generate_hash();
+        if self.lod_level > 0 {
+            self.geometric_error = Some(geometric_error(&surface_heights(&voxels), self.lod_level));
+        }
+    }
+
+    /// Find out whether every voxel of the chunk is an opaque cube, see `ChunkData::is_opaque`
+    #[cfg(feature = "render")]
+    pub fn find_opaque(&mut self, meshing_config: &MeshingConfig<C::MaterialIndex>) {
+        // Fluids and air are never opaque, so only full chunks can be
+        let chunk_data = &mut self.chunk_data;
+        chunk_data.is_opaque = chunk_data.is_full
+            && match chunk_data.fill_type {
+                FillType::Uniform(voxel) => meshing_config.is_opaque(voxel),
+                _ => chunk_data.voxel_array().is_some_and(|voxels| {
+                    voxels.iter().all(|voxel| meshing_config.is_opaque(*voxel))
+                }),
+            };
     }
 
-    /// Generate a mesh for the chunk based on the currect voxel data
-    pub fn mesh(&mut self, texture_index_mapper: Arc<dyn Fn(u8) -> [u32; 3] + Send + Sync>) {
-        if self.mesh.is_none() && self.chunk_data.voxels.is_some() {
-            self.mesh = Some(meshing::generate_chunk_mesh(
-                self.chunk_data.voxels.as_ref().unwrap().clone(),
+    /// Generate a mesh for the chunk based on the currect voxel data. Faces of transparent
+    /// voxels are put in a separate `transparent_mesh`, and the surface of fluid voxels in
+    /// `fluid_mesh`.
+    #[cfg(feature = "render")]
+    pub fn mesh(&mut self, meshing_config: &MeshingConfig<C::MaterialIndex>) {
+        if self.mesh.is_some() {
+            return;
+        }
+        // Full chunks of fluid or transparent voxels are meshed too, even though they are
+        // compressed, or stored as one voxel
+        let voxels = match self.chunk_data.fill_type {
+            FillType::Uniform(voxel) => Some(Arc::new([voxel; PaddedChunkShape::SIZE as usize])),
+            _ => self.chunk_data.voxel_array(),
+        };
+        if let Some(voxels) = voxels {
+            let meshes = meshing::generate_chunk_mesh(
+                voxels,
+                self.chunk_data.biomes.as_deref(),
+                self.light.as_deref(),
                 self.position,
-                texture_index_mapper,
-            ));
+                meshing_config,
+            );
+            self.mesh = Some(meshes.opaque);
+            self.transparent_mesh = meshes.transparent;
+            self.fluid_mesh = meshes.fluid;
         }
     }
 
+    /// True if the voxels around the chunk hide all of it, so that it doesn't need a mesh
+    #[cfg(feature = "render")]
+    pub fn is_enclosed(&self, meshing_config: &MeshingConfig<C::MaterialIndex>) -> bool {
+        self.chunk_data
+            .voxels
+            .as_ref()
+            .is_some_and(|voxels| meshing::is_enclosed(voxels, meshing_config))
+    }
+
     pub fn is_empty(&self) -> bool {
         self.chunk_data.is_empty
     }
 
-    pub fn is_full(&self) -> bool {
-        self.chunk_data.is_full
+    #[cfg(feature = "render")]
+    pub fn is_opaque(&self) -> bool {
+        self.chunk_data.is_opaque
     }
 
-    pub fn voxels_hash(&self) -> u64 {
-        self.chunk_data.voxels_hash
+    /// The key of the chunk's mesh in the mesh cache. Meshes with baked light are only shared
+    /// between chunks with the same voxels and the same light.
+    #[cfg(feature = "render")]
+    pub fn mesh_hash(&self) -> u64 {
+        match &self.light {
+            Some(light) => {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                self.chunk_data.voxels_hash.hash(&mut hasher);
+                light.hash(&mut hasher);
+                hasher.finish()
+            }
+            None => self.chunk_data.voxels_hash,
+        }
+    }
+}
+
+/// True for the voxels that count towards a full chunk, see `ChunkData::is_full`
+fn is_filled<I>(voxel: &WorldVoxel<I>) -> bool {
+    voxel.is_solid() || voxel.is_fluid()
+}
+
+/// The height of the topmost solid voxel in each column of the chunk, or 0 for empty columns.
+/// Indexed by `x * CHUNK_SIZE_U + z`.
+fn surface_heights<I: Copy + PartialEq>(voxels: &VoxelArray<I>) -> Vec<i32> {
+    let mut heights = vec![0; (CHUNK_SIZE_U * CHUNK_SIZE_U) as usize];
+    for x in 0..CHUNK_SIZE_U {
+        for z in 0..CHUNK_SIZE_U {
+            heights[(x * CHUNK_SIZE_U + z) as usize] = (1..=CHUNK_SIZE_U)
+                .rev()
+                .find(|y| {
+                    voxels[PaddedChunkShape::linearize([x + 1, *y, z + 1]) as usize].is_solid()
+                })
+                .map_or(0, |y| y as i32);
+        }
     }
+    heights
 }
@@ -5,6 +5,7 @@ use std::{
 
 use crate::{
     chunk::{self, ChunkData, CHUNK_SIZE_F},
+    configuration::WorldConfig,
     voxel::VOXEL_SIZE,
     voxel_world::ChunkWillSpawn,
 };
@@ -15,9 +16,9 @@ use bevy::{
 };
 
 #[derive(Deref, DerefMut)]
-pub struct ChunkMapData {
+pub struct ChunkMapData<I = u8> {
     #[deref]
-    data: HashMap<IVec3, chunk::ChunkData>,
+    data: HashMap<IVec3, chunk::ChunkData<I>>,
     bounds: Aabb3d,
 }
 
@@ -25,45 +26,48 @@ pub struct ChunkMapData {
 /// The chunks also exist as entities that can be queried in the ECS,
 /// but having this map in addition allows for faster spatial lookups
 #[derive(Resource)]
-pub struct ChunkMap<C> {
-    map: Arc<RwLock<ChunkMapData>>,
+pub struct ChunkMap<C: WorldConfig> {
+    map: Arc<RwLock<ChunkMapData<C::MaterialIndex>>>,
     _marker: PhantomData<C>,
 }
 
-impl<C: Send + Sync + 'static> ChunkMap<C> {
+impl<C: WorldConfig> ChunkMap<C> {
     pub fn get(
         position: &IVec3,
-        read_lock: &RwLockReadGuard<ChunkMapData>,
-    ) -> Option<chunk::ChunkData> {
+        read_lock: &RwLockReadGuard<ChunkMapData<C::MaterialIndex>>,
+    ) -> Option<chunk::ChunkData<C::MaterialIndex>> {
         read_lock.data.get(position).cloned()
     }
 
-    pub fn contains_chunk(position: &IVec3, read_lock: &RwLockReadGuard<ChunkMapData>) -> bool {
+    pub fn contains_chunk(
+        position: &IVec3,
+        read_lock: &RwLockReadGuard<ChunkMapData<C::MaterialIndex>>,
+    ) -> bool {
         read_lock.data.contains_key(position)
     }
 
     /// Get the current bounding box of loaded chunks in this map.
     ///
     /// Expressed in **chunk coordinates**. Bounds are **inclusive**.
-    pub fn get_bounds(read_lock: &RwLockReadGuard<ChunkMapData>) -> Aabb3d {
+    pub fn get_bounds(read_lock: &RwLockReadGuard<ChunkMapData<C::MaterialIndex>>) -> Aabb3d {
         read_lock.bounds
     }
 
     /// Get the current bounding box of loaded chunks in this map.
     ///
     /// Expressed in **world units**. Bounds are **inclusive**.
-    pub fn get_world_bounds(read_lock: &RwLockReadGuard<ChunkMapData>) -> Aabb3d {
+    pub fn get_world_bounds(read_lock: &RwLockReadGuard<ChunkMapData<C::MaterialIndex>>) -> Aabb3d {
         let mut world_bounds = ChunkMap::<C>::get_bounds(read_lock);
         world_bounds.min *= CHUNK_SIZE_F * VOXEL_SIZE;
         world_bounds.max = (world_bounds.max + Vec3A::ONE) * CHUNK_SIZE_F * VOXEL_SIZE;
         world_bounds
     }
 
-    pub fn get_read_lock(&self) -> RwLockReadGuard<ChunkMapData> {
+    pub fn get_read_lock(&self) -> RwLockReadGuard<'_, ChunkMapData<C::MaterialIndex>> {
         self.map.read().unwrap()
     }
 
-    pub fn get_map(&self) -> Arc<RwLock<ChunkMapData>> {
+    pub fn get_map(&self) -> Arc<RwLock<ChunkMapData<C::MaterialIndex>>> {
         self.map.clone()
     }
 
@@ -98,10 +102,18 @@ impl<C: Send + Sync + 'static> ChunkMap<C> {
             insert_buffer.clear();
 
             for (position, chunk_data, evt) in update_buffer.iter() {
+                // Remeshing regenerates the voxels, but light is only ever set from outside
+                let light = chunk_data.light.clone().or_else(|| {
+                    write_lock
+                        .data
+                        .get(position)
+                        .and_then(|previous| previous.light.clone())
+                });
                 write_lock.data.insert(
                     *position,
                     ChunkData {
                         position: *position,
+                        light,
                         ..chunk_data.clone()
                     },
                 );
@@ -138,7 +150,7 @@ impl<C: Send + Sync + 'static> ChunkMap<C> {
     }
 }
 
-impl<C> Default for ChunkMap<C> {
+impl<C: WorldConfig> Default for ChunkMap<C> {
     fn default() -> Self {
         Self {
             map: Arc::new(RwLock::new(ChunkMapData {
@@ -151,11 +163,14 @@ impl<C> Default for ChunkMap<C> {
 }
 
 #[derive(Resource, Deref, DerefMut, Default, Debug)]
-pub(crate) struct ChunkMapInsertBuffer<C>(#[deref] Vec<(IVec3, chunk::ChunkData)>, PhantomData<C>);
+pub(crate) struct ChunkMapInsertBuffer<C: WorldConfig>(
+    #[deref] Vec<(IVec3, chunk::ChunkData<C::MaterialIndex>)>,
+    PhantomData<C>,
+);
 
 #[derive(Resource, Deref, DerefMut, Default)]
-pub(crate) struct ChunkMapUpdateBuffer<C>(
-    #[deref] Vec<(IVec3, chunk::ChunkData, ChunkWillSpawn<C>)>,
+pub(crate) struct ChunkMapUpdateBuffer<C: WorldConfig>(
+    #[deref] Vec<(IVec3, chunk::ChunkData<C::MaterialIndex>, ChunkWillSpawn<C>)>,
     PhantomData<C>,
 );
 
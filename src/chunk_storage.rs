@@ -0,0 +1,329 @@
+///
+/// Chunk storage
+/// Where `VoxelWorldPersistencePlugin` keeps the saved voxels of chunks. Storage providers only
+/// deal in encoded bytes per chunk position, so a world can be backed by region files, memory, or
+/// anything else, like a database or a game-specific save format.
+///
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+
+/// The number of chunks along each axis of a region file
+pub const REGION_SIZE: i32 = 16;
+
+const REGION_MAGIC: &[u8; 4] = b"BVWR";
+const REGION_VERSION: u8 = 1;
+const METADATA_FILE: &str = "world.bvw";
+
+/// A place to keep the encoded voxels of chunks, by chunk position. Providers are shared between
+/// systems, so they take `&self` and handle their own locking.
+pub trait ChunkStorageProvider: Send + Sync + 'static {
+    /// The saved bytes of a chunk, or `None` if the chunk has not been saved
+    fn load_chunk(&self, chunk_position: IVec3) -> io::Result<Option<Vec<u8>>>;
+
+    /// Save the bytes of a chunk, replacing any that were saved before. Providers may hold on to
+    /// the bytes until `flush` is called.
+    fn save_chunk(&self, chunk_position: IVec3, bytes: Vec<u8>) -> io::Result<()>;
+
+    /// Remove the saved bytes of a chunk, if there are any
+    fn remove_chunk(&self, chunk_position: IVec3) -> io::Result<()>;
+
+    /// The positions of all saved chunks
+    fn list_chunks(&self) -> io::Result<Vec<IVec3>>;
+
+    /// The saved bytes of the world's metadata, see `WorldMetadata`
+    fn load_metadata(&self) -> io::Result<Option<Vec<u8>>>;
+
+    /// Save the bytes of the world's metadata, replacing any that were saved before
+    fn save_metadata(&self, bytes: Vec<u8>) -> io::Result<()>;
+
+    /// Write out any saved chunks that are held in memory
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Keeps chunks in memory, for tests and for worlds that only need to survive chunk streaming.
+/// Clones share the same chunks.
+#[derive(Clone, Default)]
+pub struct MemoryChunkStorage {
+    chunks: Arc<RwLock<HashMap<IVec3, Vec<u8>>>>,
+    metadata: Arc<RwLock<Option<Vec<u8>>>>,
+}
+
+impl MemoryChunkStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ChunkStorageProvider for MemoryChunkStorage {
+    fn load_chunk(&self, chunk_position: IVec3) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.chunks.read().unwrap().get(&chunk_position).cloned())
+    }
+
+    fn save_chunk(&self, chunk_position: IVec3, bytes: Vec<u8>) -> io::Result<()> {
+        self.chunks.write().unwrap().insert(chunk_position, bytes);
+        Ok(())
+    }
+
+    fn remove_chunk(&self, chunk_position: IVec3) -> io::Result<()> {
+        self.chunks.write().unwrap().remove(&chunk_position);
+        Ok(())
+    }
+
+    fn list_chunks(&self) -> io::Result<Vec<IVec3>> {
+        Ok(self.chunks.read().unwrap().keys().copied().collect())
+    }
+
+    fn load_metadata(&self) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.metadata.read().unwrap().clone())
+    }
+
+    fn save_metadata(&self, bytes: Vec<u8>) -> io::Result<()> {
+        *self.metadata.write().unwrap() = Some(bytes);
+        Ok(())
+    }
+}
+
+/// Groups chunks into region files of `REGION_SIZE` chunks along each axis in a directory, so that
+/// large worlds don't end up as one file per chunk. Regions are read the first time one of their
+/// chunks is needed and kept in memory after that. Changed regions are rewritten on `flush`. The
+/// world's metadata is written right away, to a separate file.
+pub struct RegionFileStorage {
+    directory: PathBuf,
+    regions: Mutex<RegionCache>,
+}
+
+#[derive(Default)]
+struct RegionCache {
+    /// The chunks of each region, by their index in the region
+    regions: HashMap<IVec3, HashMap<u16, Vec<u8>>>,
+    /// Regions with chunks that have not been written to disk yet
+    dirty: HashSet<IVec3>,
+}
+
+impl RegionFileStorage {
+    /// Store the region files in the given directory, which is created when the first region is
+    /// written
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            regions: Mutex::new(RegionCache::default()),
+        }
+    }
+
+    fn region_path(&self, region: IVec3) -> PathBuf {
+        self.directory
+            .join(format!("r.{}.{}.{}.bvw", region.x, region.y, region.z))
+    }
+
+    /// The chunks of a region, read from disk if they aren't cached yet
+    fn region<'a>(
+        &self,
+        cache: &'a mut RegionCache,
+        region: IVec3,
+    ) -> io::Result<&'a mut HashMap<u16, Vec<u8>>> {
+        if !cache.regions.contains_key(&region) {
+            let chunks = match fs::read(self.region_path(region)) {
+                Ok(bytes) => decode_region(&bytes).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("corrupt region file for region {region}"),
+                    )
+                })?,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => HashMap::new(),
+                Err(error) => return Err(error),
+            };
+            cache.regions.insert(region, chunks);
+        }
+        Ok(cache.regions.get_mut(&region).unwrap())
+    }
+}
+
+impl ChunkStorageProvider for RegionFileStorage {
+    fn load_chunk(&self, chunk_position: IVec3) -> io::Result<Option<Vec<u8>>> {
+        let (region, index) = region_of(chunk_position);
+        let mut cache = self.regions.lock().unwrap();
+        Ok(self.region(&mut cache, region)?.get(&index).cloned())
+    }
+
+    fn save_chunk(&self, chunk_position: IVec3, bytes: Vec<u8>) -> io::Result<()> {
+        let (region, index) = region_of(chunk_position);
+        let mut cache = self.regions.lock().unwrap();
+        self.region(&mut cache, region)?.insert(index, bytes);
+        cache.dirty.insert(region);
+        Ok(())
+    }
+
+    fn remove_chunk(&self, chunk_position: IVec3) -> io::Result<()> {
+        let (region, index) = region_of(chunk_position);
+        let mut cache = self.regions.lock().unwrap();
+        if self.region(&mut cache, region)?.remove(&index).is_some() {
+            cache.dirty.insert(region);
+        }
+        Ok(())
+    }
+
+    fn list_chunks(&self) -> io::Result<Vec<IVec3>> {
+        let mut cache = self.regions.lock().unwrap();
+        let mut regions: HashSet<IVec3> = cache.regions.keys().copied().collect();
+        match fs::read_dir(&self.directory) {
+            Ok(entries) => {
+                for entry in entries {
+                    let name = entry?.file_name();
+                    if let Some(region) = name.to_str().and_then(parse_region_name) {
+                        regions.insert(region);
+                    }
+                }
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error),
+        }
+
+        let mut chunks = Vec::new();
+        for region in regions {
+            let indices = self.region(&mut cache, region)?.keys();
+            chunks.extend(indices.map(|index| chunk_in_region(region, *index)));
+        }
+        Ok(chunks)
+    }
+
+    fn load_metadata(&self) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.directory.join(METADATA_FILE)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn save_metadata(&self, bytes: Vec<u8>) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+        fs::write(self.directory.join(METADATA_FILE), bytes)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let mut cache = self.regions.lock().unwrap();
+        if cache.dirty.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.directory)?;
+        // Regions stay dirty until they are written, so that a failed flush can be retried
+        let dirty: Vec<IVec3> = cache.dirty.iter().copied().collect();
+        for region in dirty {
+            let path = self.region_path(region);
+            match cache.regions.get(&region) {
+                Some(chunks) if !chunks.is_empty() => write_file(&path, &encode_region(chunks))?,
+                _ => match fs::remove_file(path) {
+                    Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                    _ => {}
+                },
+            }
+            cache.dirty.remove(&region);
+        }
+        Ok(())
+    }
+}
+
+/// Write the file next to its path first, and then move it in place, so that a crash while
+/// writing leaves the previous version of the file intact
+fn write_file(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, bytes)?;
+    fs::rename(temporary, path)
+}
+
+/// The region of a chunk, and the index of the chunk in the region
+fn region_of(chunk_position: IVec3) -> (IVec3, u16) {
+    let region = chunk_position.div_euclid(IVec3::splat(REGION_SIZE));
+    let local = chunk_position.rem_euclid(IVec3::splat(REGION_SIZE));
+    let index = local.x + local.y * REGION_SIZE + local.z * REGION_SIZE * REGION_SIZE;
+    (region, index as u16)
+}
+
+fn chunk_in_region(region: IVec3, index: u16) -> IVec3 {
+    let index = index as i32;
+    let local = IVec3::new(
+        index % REGION_SIZE,
+        (index / REGION_SIZE) % REGION_SIZE,
+        index / (REGION_SIZE * REGION_SIZE),
+    );
+    region * REGION_SIZE + local
+}
+
+/// The region of a file named like `r.{x}.{y}.{z}.bvw`
+fn parse_region_name(name: &str) -> Option<IVec3> {
+    let coordinates = name.strip_prefix("r.")?.strip_suffix(".bvw")?;
+    let mut coordinates = coordinates.split('.').map(|c| c.parse::<i32>().ok());
+    let region = IVec3::new(
+        coordinates.next()??,
+        coordinates.next()??,
+        coordinates.next()??,
+    );
+    coordinates.next().is_none().then_some(region)
+}
+
+/// A region file is a header followed by its chunks, each with its index and length
+fn encode_region(chunks: &HashMap<u16, Vec<u8>>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(REGION_MAGIC);
+    bytes.push(REGION_VERSION);
+    bytes.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    // Chunks are written in order, so the same chunks always give the same file
+    let mut indices: Vec<&u16> = chunks.keys().collect();
+    indices.sort_unstable();
+    for index in indices {
+        let chunk = &chunks[index];
+        bytes.extend_from_slice(&index.to_le_bytes());
+        bytes.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(chunk);
+    }
+    bytes
+}
+
+fn decode_region(bytes: &[u8]) -> Option<HashMap<u16, Vec<u8>>> {
+    let mut reader = Reader(bytes);
+    if reader.take(4)? != REGION_MAGIC || reader.u8()? != REGION_VERSION {
+        return None;
+    }
+    let count = reader.u32()?;
+    let mut chunks = HashMap::new();
+    for _ in 0..count {
+        let index = reader.u16()?;
+        let len = reader.u32()? as usize;
+        chunks.insert(index, reader.take(len)?.to_vec());
+    }
+    Some(chunks)
+}
+
+/// Reads little endian values from the front of a byte slice
+pub(crate) struct Reader<'a>(pub &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    pub fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
@@ -0,0 +1,266 @@
+///
+/// Chunk transport
+/// Lets clients stream chunks from a server instead of generating them. When a full detail chunk
+/// spawns, it is requested through a `ChunkTransport`, and meshed once its snapshot arrives. The
+/// received voxels take the place of the `voxel_lookup_delegate`.
+///
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use std::{
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+#[cfg(feature = "render")]
+use crate::voxel_world_internal::CameraInfo;
+use crate::{
+    chunk::{Chunk, NeedsRemesh, CHUNK_SIZE_I},
+    chunk_map::ChunkMap,
+    configuration::{VoxelLookupFn, WorldConfig},
+    replication::{ChunkVersion, ReplicationMessage},
+    voxel::WorldVoxel,
+    voxel_world::ChunkWillDespawn,
+    voxel_world_internal::{
+        get_chunk_voxel_position, Internals, StreamingAnchors, VoxelWriteBuffer,
+    },
+};
+
+/// The connection of a client to the server it streams chunks from. Implement it on top of any
+/// networking crate.
+pub trait ChunkTransport<I>: Send + 'static {
+    /// Ask the server for a chunk. Clients that had the chunk before pass the version they have,
+    /// so that the server can send a diff against it, see `ChunkVersions::chunk_update`.
+    fn request_chunk(&mut self, chunk_position: IVec3, base_version: Option<u32>);
+
+    /// The next chunk snapshot or diff received from the server, if any
+    fn receive_chunk(&mut self) -> Option<ReplicationMessage<I>>;
+
+    /// The next chunk delta received from the server, if any
+    fn receive_delta(&mut self) -> Option<ReplicationMessage<I>>;
+}
+
+/// Streams the full detail chunks of a world from a server through a `ChunkTransport`, instead of
+/// generating them with the `voxel_lookup_delegate`. Chunks at lower levels of detail are still
+/// generated locally.
+pub struct VoxelWorldClientPlugin<C: WorldConfig> {
+    transport: Arc<Mutex<Box<dyn ChunkTransport<C::MaterialIndex>>>>,
+}
+
+impl<C: WorldConfig> VoxelWorldClientPlugin<C> {
+    pub fn new(transport: impl ChunkTransport<C::MaterialIndex>) -> Self {
+        Self {
+            transport: Arc::new(Mutex::new(Box::new(transport))),
+        }
+    }
+}
+
+impl<C: WorldConfig> Plugin for VoxelWorldClientPlugin<C> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ClientTransport::<C> {
+            transport: self.transport.clone(),
+            _marker: PhantomData,
+        })
+        .init_resource::<RemoteChunks<C>>()
+        .add_systems(
+            PreUpdate,
+            (
+                request_spawned_chunks::<C>,
+                receive_remote_chunks::<C>.before(Internals::<C>::flush_voxel_write_buffer),
+            )
+                .chain()
+                .after(Internals::<C>::spawn_requested_chunks)
+                .before(Internals::<C>::remesh_dirty_chunks),
+        );
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct ClientTransport<C: WorldConfig> {
+    transport: Arc<Mutex<Box<dyn ChunkTransport<C::MaterialIndex>>>>,
+    _marker: PhantomData<C>,
+}
+
+/// The chunks received from the server, with their version, by chunk position. Voxels are indexed
+/// like in a `ReplicationMessage::ChunkSnapshot`. Chunks are kept after they despawn, so that
+/// only a diff has to be sent when they spawn again, until they are beyond the spawning distance
+/// of every camera and streaming anchor.
+#[derive(Resource)]
+pub(crate) struct RemoteChunks<C: WorldConfig> {
+    chunks: HashMap<IVec3, ChunkVersion<C::MaterialIndex>>,
+    /// The entities of chunks that have been requested, and not despawned since
+    requested: HashMap<IVec3, Entity>,
+}
+
+impl<C: WorldConfig> Default for RemoteChunks<C> {
+    fn default() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            requested: HashMap::new(),
+        }
+    }
+}
+
+impl<C: WorldConfig> RemoteChunks<C> {
+    /// Looks up voxels in a received chunk and its neighbours, for generating the chunk and its
+    /// padding. Voxels of chunks that haven't been received are unset.
+    pub fn lookup_fn(&self, chunk_position: IVec3) -> VoxelLookupFn<C::MaterialIndex> {
+        let mut chunks = HashMap::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let position = chunk_position + IVec3::new(x, y, z);
+                    if let Some((_, voxels)) = self.chunks.get(&position) {
+                        chunks.insert(position, voxels.clone());
+                    }
+                }
+            }
+        }
+        Box::new(move |position, _| {
+            let chunk_position = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+            let Some(voxels) = chunks.get(&chunk_position) else {
+                return WorldVoxel::Unset;
+            };
+            let local = position - chunk_position * CHUNK_SIZE_I;
+            voxels[(local.x + local.y * CHUNK_SIZE_I + local.z * CHUNK_SIZE_I * CHUNK_SIZE_I)
+                as usize]
+        })
+    }
+}
+
+/// Request newly spawned full detail chunks from the server. The chunks are not meshed until
+/// their snapshot or diff arrives.
+#[allow(clippy::too_many_arguments)]
+fn request_spawned_chunks<C: WorldConfig>(
+    mut commands: Commands,
+    chunks: Query<&Chunk<C>, Added<Chunk<C>>>,
+    mut ev_chunk_will_despawn: EventReader<ChunkWillDespawn<C>>,
+    mut remote_chunks: ResMut<RemoteChunks<C>>,
+    client_transport: Res<ClientTransport<C>>,
+    anchors: StreamingAnchors<C>,
+    #[cfg(feature = "render")] camera_info: CameraInfo<C>,
+    configuration: Res<C>,
+) {
+    // Despawned chunks are requested again when they respawn, since they may have changed
+    let mut despawned = false;
+    for ev in ev_chunk_will_despawn.read() {
+        remote_chunks.requested.remove(&ev.chunk_key);
+        despawned = true;
+    }
+    if despawned {
+        #[cfg_attr(not(feature = "render"), allow(unused_mut))]
+        let mut centers: Vec<(IVec3, i32)> = anchors
+            .chunks(&configuration)
+            .into_iter()
+            .map(|(_, chunk_position, radius)| (chunk_position, radius))
+            .collect();
+        #[cfg(feature = "render")]
+        centers.extend(camera_info.iter().map(|(_, _, transform)| {
+            let (chunk_position, _) =
+                get_chunk_voxel_position(transform.translation().floor().as_ivec3());
+            (chunk_position, configuration.spawning_distance() as i32)
+        }));
+        let RemoteChunks { chunks, requested } = &mut *remote_chunks;
+        chunks.retain(|chunk_position, _| {
+            requested.contains_key(chunk_position)
+                || centers.iter().any(|(center, radius)| {
+                    (*chunk_position - *center).abs().max_element() <= *radius
+                })
+        });
+    }
+
+    let mut transport = client_transport.transport.lock().unwrap();
+    for chunk in chunks.iter() {
+        if chunk.lod_level > 0 {
+            continue;
+        }
+        if remote_chunks
+            .requested
+            .insert(chunk.position, chunk.entity)
+            .is_none()
+        {
+            let base_version = remote_chunks.chunks.get(&chunk.position).map(|(v, _)| *v);
+            transport.request_chunk(chunk.position, base_version);
+        }
+        commands.entity(chunk.entity).remove::<NeedsRemesh>();
+    }
+}
+
+/// Store received chunk snapshots and diffs and mesh their chunks, and apply received deltas as
+/// edits
+pub(crate) fn receive_remote_chunks<C: WorldConfig>(
+    mut commands: Commands,
+    mut remote_chunks: ResMut<RemoteChunks<C>>,
+    mut buffer: ResMut<VoxelWriteBuffer<C>>,
+    chunk_map: Res<ChunkMap<C>>,
+    client_transport: Res<ClientTransport<C>>,
+) {
+    let mut transport = client_transport.transport.lock().unwrap();
+    let mut received = HashSet::new();
+    while let Some(message) = transport.receive_chunk() {
+        let chunk_position = message.chunk_position();
+        // Snapshots of chunks that despawned while they were requested are no longer needed
+        if !remote_chunks.requested.contains_key(&chunk_position) {
+            continue;
+        }
+        let chunk = match &message {
+            ReplicationMessage::ChunkSnapshot { version, .. } => message
+                .snapshot_voxels()
+                .map(|voxels| (*version, voxels.into())),
+            ReplicationMessage::ChunkDiff {
+                base_version,
+                version,
+                ..
+            } => match remote_chunks.chunks.get(&chunk_position) {
+                Some((current, voxels)) if current == base_version => {
+                    let mut voxels = voxels.to_vec();
+                    message
+                        .apply_diff(&mut voxels)
+                        .then(|| (*version, voxels.into()))
+                }
+                // The server sent a diff against a version we don't have, so start over
+                _ => {
+                    remote_chunks.chunks.remove(&chunk_position);
+                    transport.request_chunk(chunk_position, None);
+                    continue;
+                }
+            },
+            ReplicationMessage::ChunkDelta { .. } => None,
+        };
+        match chunk {
+            Some(chunk) => {
+                remote_chunks.chunks.insert(chunk_position, chunk);
+                received.insert(chunk_position);
+            }
+            None => warn!("Received an invalid chunk update for chunk {chunk_position}"),
+        }
+    }
+    while let Some(message) = transport.receive_delta() {
+        if let ReplicationMessage::ChunkDelta { .. } = message {
+            buffer.remote.extend(message.voxels().into_iter().flatten());
+        }
+    }
+
+    // Spawned neighbours are remeshed too, since their padding comes from the received chunk
+    let read_lock = chunk_map.get_read_lock();
+    let mut remesh = HashSet::new();
+    for chunk_position in received {
+        remesh.insert(remote_chunks.requested[&chunk_position]);
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let neighbour = chunk_position + IVec3::new(x, y, z);
+                    if let Some(chunk_data) = ChunkMap::<C>::get(&neighbour, &read_lock) {
+                        remesh.insert(chunk_data.entity);
+                    }
+                }
+            }
+        }
+    }
+    for entity in remesh {
+        if let Some(mut entity) = commands.get_entity(entity) {
+            entity.try_insert(NeedsRemesh);
+        }
+    }
+}
@@ -1,12 +1,49 @@
-use std::sync::Arc;
+use std::{fmt::Debug, hash::Hash, sync::Arc, time::Duration};
 
+use crate::chunk::{ChunkVoxelsMut, SharedVoxelData};
 use crate::voxel::WorldVoxel;
-use bevy::prelude::*;
+use crate::voxel_shape::{BlockModels, VoxelShape};
+use crate::world_metadata::WorldMetadata;
+use bevy::{prelude::*, tasks::TaskPool};
 
-pub type VoxelLookupFn = Box<dyn FnMut(IVec3) -> WorldVoxel + Send + Sync>;
-pub type VoxelLookupDelegate = Box<dyn Fn(IVec3) -> VoxelLookupFn + Send + Sync>;
+/// Looks up the voxel at a world position. The second argument is the level of detail of the
+/// chunk being generated, see `VoxelWorldConfig::lod_distances`. Generators can use it to skip
+/// features that are too small to be seen at that level.
+pub type VoxelLookupFn<I = u8> = Box<dyn FnMut(IVec3, u8) -> WorldVoxel<I> + Send + Sync>;
+pub type VoxelLookupDelegate<I = u8> = Box<dyn Fn(IVec3) -> VoxelLookupFn<I> + Send + Sync>;
 
-#[derive(Default, PartialEq, Eq)]
+/// Modifies the voxels of a chunk after they are generated, see
+/// `VoxelWorldConfig::chunk_decorator`
+pub type ChunkDecorator<I = u8> = Arc<dyn Fn(&mut ChunkVoxelsMut<I>) + Send + Sync>;
+
+/// Looks up the biome of a world column, given as the x and z of a voxel position
+pub type BiomeLookupFn = Box<dyn FnMut(IVec2) -> u8 + Send + Sync>;
+pub type BiomeLookupDelegate = Box<dyn Fn(IVec3) -> BiomeLookupFn + Send + Sync>;
+
+/// Tints the voxels of a material by the biome of their column, see
+/// `VoxelWorldConfig::biome_tint`
+#[cfg(feature = "render")]
+pub type BiomeTintFn<I = u8> = Arc<dyn Fn(I, u8) -> Color + Send + Sync>;
+
+/// What custom spawn and despawn strategies can base their decisions on
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkStrategyInput {
+    pub chunk_position: IVec3,
+    /// The level of detail of the chunk, see `VoxelWorldConfig::lod_distances`
+    pub lod_level: u8,
+    pub camera_position: Vec3,
+    /// Chebyshev distance, in full detail chunks, between the camera's chunk and the chunk
+    pub distance: u32,
+    /// True if the chunk is inside the camera's view. For chunks that haven't spawned yet,
+    /// this checks if the center of the chunk is in view.
+    pub in_view: bool,
+}
+
+/// Decides whether a chunk should spawn, or despawn, for `ChunkSpawnStrategy::Custom` and
+/// `ChunkDespawnStrategy::Custom`
+pub type ChunkStrategyFn = Arc<dyn Fn(&ChunkStrategyInput) -> bool + Send + Sync>;
+
+#[derive(Default)]
 pub enum ChunkDespawnStrategy {
     /// Despawn chunks that are further than `spawning_distance` away from the camera
     /// or outside of the viewport.
@@ -15,13 +52,36 @@ pub enum ChunkDespawnStrategy {
 
     /// Only despawn chunks that are further than `spawning_distance` away from the camera.
     FarAway,
-    
+
     /// Despawn chunks that are further than the specified distance (in chunks) away from the camera.
     Distance(u32),
 
+    /// Like `FarAway`, but chunks are only despawned once they have been out of range for the
+    /// given time. This way, moving back and forth across the edge of the spawning distance
+    /// doesn't keep regenerating the same chunks.
+    FarAwayAfter(Duration),
+
+    /// Despawn chunks for which the function returns true. Chunks that are replaced by chunks at
+    /// another level of detail are still despawned.
+    Custom(ChunkStrategyFn),
+}
+
+impl PartialEq for ChunkDespawnStrategy {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::FarAwayOrOutOfView, Self::FarAwayOrOutOfView)
+            | (Self::FarAway, Self::FarAway) => true,
+            (Self::Distance(a), Self::Distance(b)) => a == b,
+            (Self::FarAwayAfter(a), Self::FarAwayAfter(b)) => a == b,
+            (Self::Custom(a), Self::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
 }
 
-#[derive(Default, PartialEq, Eq)]
+impl Eq for ChunkDespawnStrategy {}
+
+#[derive(Default)]
 pub enum ChunkSpawnStrategy {
     /// Spawn chunks that are within `spawning_distance` of the camera
     /// and also inside the viewport.
@@ -30,19 +90,133 @@ pub enum ChunkSpawnStrategy {
 
     /// Spawn chunks that are within `spawning_distance` of the camera, regardless of whether
     /// they are in the viewport or not. Will only have an effect if the despawn strategy is
-    /// `FarAway`. If this strategy is used a flood fill will be used to find unspawned chunks
+    /// `FarAway` or `FarAwayAfter`. If this strategy is used a flood fill will be used to find unspawned chunks
     /// and therefore it might make sense to lower the `spawning_rays` option.
     Close,
 
     Always,
-    
-    
+
     /// Spawn chunks that are further than the specified distance (in chunks) away from the camera.
     Distance(u32),
+
+    /// Spawn chunks for which the function returns true. Like with `Close`, a flood fill is used
+    /// to find unspawned chunks. With `lod_distances`, only chunks at the wanted level of detail
+    /// are considered.
+    Custom(ChunkStrategyFn),
+}
+
+impl PartialEq for ChunkSpawnStrategy {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::CloseAndInView, Self::CloseAndInView)
+            | (Self::Close, Self::Close)
+            | (Self::Always, Self::Always) => true,
+            (Self::Distance(a), Self::Distance(b)) => a == b,
+            (Self::Custom(a), Self::Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ChunkSpawnStrategy {}
+
+/// How the level of detail of chunks is selected, when `lod_distances` is configured
+#[derive(Default, PartialEq, Clone, Copy, Debug)]
+pub enum LodSelection {
+    /// Each level of detail is used up to its distance in `lod_distances`
+    #[default]
+    Distance,
+
+    /// Chunks are split into chunks of the next finer level when their geometric error, projected
+    /// to the screen, is larger than this many pixels. This keeps detail on large vertical
+    /// features like cliffs and mountains, while flat terrain drops to lower detail quickly.
+    /// The last entry of `lod_distances` still decides how far the world extends, and the
+    /// number of entries how many levels there are.
+    ScreenSpaceError(f32),
+}
+
+/// How the voxels of chunks above full detail are sampled from the `voxel_lookup_delegate`
+#[derive(Default, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum LodDownsampling {
+    /// Each voxel is looked up once, at its corner. The generator is expected to take the level
+    /// of detail into account.
+    #[default]
+    Point,
+
+    /// Each voxel is looked up at 8 points spread over the space it covers, and the most common
+    /// result is used. Ties go to solid voxels.
+    Majority,
+
+    /// Like `Majority`, but a voxel is solid if any of its samples is solid. This keeps thin
+    /// features from disappearing, at the cost of making terrain slightly larger.
+    Max,
+}
+
+/// The task pool that chunk generation and meshing run on
+#[derive(Default, Clone)]
+pub enum VoxelTaskPool {
+    /// Bevy's `AsyncComputeTaskPool`, shared with the rest of the app
+    #[default]
+    AsyncCompute,
+
+    /// A dedicated pool with the given number of threads, so that heavy world generation doesn't
+    /// starve other users of the `AsyncComputeTaskPool`
+    Threads(usize),
+
+    /// A task pool supplied by the app, for example to share one pool between several worlds
+    Pool(Arc<TaskPool>),
+}
+
+/// The types that can be used as the material of a world, see `VoxelWorldMaterialIndex`
+pub trait MaterialIndexType:
+    Copy + Hash + Eq + Default + Debug + Into<u32> + Send + Sync + 'static
+{
+}
+
+impl<T: Copy + Hash + Eq + Default + Debug + Into<u32> + Send + Sync + 'static> MaterialIndexType
+    for T
+{
+}
+
+/// The material of `WorldVoxel::Solid` and `WorldVoxel::Oriented` voxels in a world.
+///
+/// Worlds that implement `VoxelWorldConfig` get `u8` materials. To store richer data per voxel,
+/// like an enum of block types or bit-packed block state, implement `VoxelWorldConfig` for your
+/// material type, and this trait with the same type:
+///
+/// ```ignore
+/// impl VoxelWorldConfig<Block> for MyWorld {}
+///
+/// impl VoxelWorldMaterialIndex for MyWorld {
+///     type MaterialIndex = Block;
+/// }
+/// ```
+pub trait VoxelWorldMaterialIndex {
+    type MaterialIndex: MaterialIndexType;
+}
+
+impl<T: VoxelWorldConfig<u8>> VoxelWorldMaterialIndex for T {
+    type MaterialIndex = u8;
+}
+
+/// Implemented for every world config, whatever its material type. Use this to bound code that
+/// is generic over worlds, since `C: VoxelWorldConfig` only covers worlds with `u8` materials.
+pub trait WorldConfig:
+    VoxelWorldMaterialIndex + VoxelWorldConfig<<Self as VoxelWorldMaterialIndex>::MaterialIndex>
+{
 }
 
-/// `bevy_voxel_world` configuation structs need to implement this trait
-pub trait VoxelWorldConfig: Resource + Default + Clone {
+impl<T> WorldConfig for T where
+    T: VoxelWorldMaterialIndex + VoxelWorldConfig<<T as VoxelWorldMaterialIndex>::MaterialIndex>
+{
+}
+
+/// `bevy_voxel_world` configuation structs need to implement this trait.
+///
+/// `I` is the material of `WorldVoxel::Solid` and `WorldVoxel::Oriented` voxels, `u8` unless
+/// the world sets another one, see `VoxelWorldMaterialIndex`. The `u32` conversion of the
+/// material is used by the default `texture_index_mapper`.
+pub trait VoxelWorldConfig<I: MaterialIndexType = u8>: Resource + Default + Clone {
     /// Distance in chunks to spawn chunks around the camera
     fn spawning_distance(&self) -> u32 {
         10
@@ -58,6 +232,65 @@ pub trait VoxelWorldConfig: Resource + Default + Clone {
         self.spawning_distance()
     }
 
+    /// Distances in chunks at which each level of detail ends, for spawning the world at lower
+    /// detail further away from the camera.
+    ///
+    /// Level `n` uses voxels that are `2^n` times as large as regular voxels, sampled from every
+    /// `2^n`th voxel of the `voxel_lookup_delegate`. For example, `vec![8, 16, 32]` spawns full
+    /// detail chunks up to 8 chunks away, half detail up to 16 chunks, and quarter detail up to
+    /// 32 chunks.
+    ///
+    /// An empty list (the default) disables LOD. When levels are configured, they replace
+    /// `spawning_distance`, `spawning_min_distance` and `spawning_max_distance`. Only full detail
+    /// chunks are used by `get_voxel` and ray casting.
+    fn lod_distances(&self) -> Vec<u32> {
+        vec![]
+    }
+
+    /// How the level of detail of chunks is selected, see `LodSelection`
+    fn lod_selection(&self) -> LodSelection {
+        LodSelection::default()
+    }
+
+    /// How chunks above full detail are sampled from the `voxel_lookup_delegate`. With the default
+    /// `LodDownsampling::Point`, the generator has to be aware of the level of detail itself.
+    fn lod_downsampling(&self) -> LodDownsampling {
+        LodDownsampling::default()
+    }
+
+    /// Margin in chunks that the camera has to move past a LOD ring boundary, before chunks that
+    /// were split into finer chunks are merged again. The same margin applies before chunks
+    /// outside of the spawning distance (or the outermost ring) are despawned. This keeps chunks
+    /// from flipping back and forth when the camera hovers around a boundary. The default of 0
+    /// switches chunks right at the boundary.
+    fn lod_hysteresis(&self) -> u32 {
+        0
+    }
+
+    /// Duration in seconds of the dithered cross-fade when chunks are replaced by chunks at a
+    /// different level of detail. The default of 0.0 swaps chunks instantly. Chunks coming into
+    /// or leaving the rings are not faded. Only the opaque part of chunks is faded, and only with
+    /// the built-in material.
+    fn lod_fade_duration(&self) -> f32 {
+        0.0
+    }
+
+    /// Depth, in voxels, of the skirts added along chunk borders when `lod_distances` is used.
+    /// Skirts hang down from the terrain surface at the chunk borders, so that the gaps where
+    /// chunks of different levels of detail meet don't show what's behind the terrain.
+    /// Set to `0.0` to disable skirts.
+    fn lod_skirt_depth(&self) -> f32 {
+        2.0
+    }
+
+    /// Disables the automatic spawning and despawning of chunks. Chunks are then only spawned by
+    /// `VoxelWorld::request_chunk`, and despawned again by `VoxelWorld::release_chunk`, for
+    /// example to stream chunks from scripted level data. Cameras, streaming anchors, levels of
+    /// detail and `world_extent` have no effect in manual mode. Defaults to `false`.
+    fn manual_chunk_management(&self) -> bool {
+        false
+    }
+
     /// Strategy for despawning chunks
     fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
         ChunkDespawnStrategy::default()
@@ -89,7 +322,113 @@ pub trait VoxelWorldConfig: Resource + Default + Clone {
         25
     }
 
-    /// Debugging aids
+    /// How many seconds ahead of a moving camera chunks are prefetched. Chunks along the path the
+    /// camera is moving on are queued before any other chunks, so that fast cameras don't outrun
+    /// chunk generation. Set to 0.0 to disable prefetching.
+    fn spawning_prefetch_time(&self) -> f32 {
+        1.0
+    }
+
+    /// The maximum number of chunks prefetched along the path of a moving camera each frame,
+    /// nearest first. This keeps fast cameras from flooding the spawn queue.
+    fn spawning_prefetch_max_chunks(&self) -> usize {
+        128
+    }
+
+    /// Distance in chunks the camera has to move in a single frame to count as a teleport. When
+    /// the camera teleports, chunks around its new position are spawned and meshed before any
+    /// chunks that were still waiting, and chunks that are now out of range are dropped.
+    fn teleport_distance(&self) -> u32 {
+        self.spawning_distance()
+    }
+
+    /// Makes the world finite, by giving the minimum and maximum chunk positions (inclusive) of
+    /// the world. All chunks within the extent are spawned once, regardless of the camera, and
+    /// never despawned. No chunks are streamed in outside of the extent, and levels of detail are
+    /// not used. Defaults to `None`, for an infinite world streamed around the camera.
+    fn world_extent(&self) -> Option<(IVec3, IVec3)> {
+        None
+    }
+
+    /// Maximum number of despawned chunk entities, and their meshes, kept for reuse by newly
+    /// spawned chunks. Pooling reduces allocations and asset events during fast traversal.
+    /// Pooled entities are stripped of the components added by `bevy_voxel_world`, so any
+    /// components added in response to `ChunkWillSpawn` should be removed on `ChunkWillDespawn`.
+    /// Defaults to 0, which disables pooling.
+    fn chunk_pool_size(&self) -> usize {
+        0
+    }
+
+    /// Maximum number of steps kept for `VoxelWorld::undo`. Each `set_voxel` or `set_voxels`
+    /// call is one step, unless edits are grouped with `VoxelWorld::begin_edit_group`.
+    /// Defaults to 0, which disables the edit history.
+    fn edit_history_size(&self) -> usize {
+        0
+    }
+
+    /// Maximum memory, in bytes, taken up by the voxel data of spawned chunks, or `None` for no
+    /// limit. When the budget is exceeded, the chunks that were visible least recently are
+    /// despawned, even within the spawning distance, and only spawned again once they come into
+    /// view. Chunks that are completely empty or filled with one material take up no memory.
+    fn chunk_memory_budget(&self) -> Option<usize> {
+        None
+    }
+
+    /// Voxel data to share with other worlds, or `None` to keep the voxel data of this world to
+    /// itself. Worlds that return clones of the same `SharedVoxelData` keep one copy of chunks
+    /// with identical voxels, instead of one per world.
+    fn shared_voxel_data(&self) -> Option<SharedVoxelData<I>> {
+        None
+    }
+
+    /// Time after which the voxel data of a chunk that hasn't changed is run-length encoded, or
+    /// `None` to keep all voxel data uncompressed. Compressed chunks take up a fraction of the
+    /// memory, which also counts towards `chunk_memory_budget`. Reading voxels from them is a
+    /// bit slower, and they are decompressed again when they are remeshed.
+    fn chunk_compression_delay(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Maximum number of new chunks spawned per frame. Chunks over the budget are picked up again
+    /// in the following frames. Together with `max_meshing_tasks_per_frame` and
+    /// `max_streaming_time_per_frame`, this keeps streaming from causing hitches, for example
+    /// when the camera turns quickly. With several cameras, the budget is split between them.
+    fn max_chunks_spawned_per_frame(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Maximum number of chunk generation and meshing tasks started per frame. When the budget is
+    /// limited, the chunks closest to the camera are started first.
+    fn max_meshing_tasks_per_frame(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Maximum number of voxels written per frame for edits queued with
+    /// `VoxelWorld::queue_edit`
+    fn max_queued_voxels_per_frame(&self) -> usize {
+        16384
+    }
+
+    /// The task pool that chunk generation and meshing run on
+    fn task_pool(&self) -> VoxelTaskPool {
+        VoxelTaskPool::default()
+    }
+
+    /// Maximum time spent on spawning chunks and starting meshing tasks per frame, or `None` for
+    /// no limit. Each of the two steps gets the full budget.
+    fn max_streaming_time_per_frame(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Skip meshing and rendering chunks that are enclosed by opaque voxels on all sides, like
+    /// most chunks deep underground. Edits on chunk borders then also remesh the chunk across the
+    /// border, since it may have been skipped. Chunks that contain a camera are always meshed.
+    fn occlusion_culling(&self) -> bool {
+        false
+    }
+
+    /// Start with the chunk bounds debug overlay switched on. The overlay can be toggled at
+    /// runtime through the `VoxelWorldDebugOverlay<C>` resource.
     fn debug_draw_chunks(&self) -> bool {
         false
     }
@@ -99,8 +438,8 @@ pub trait VoxelWorldConfig: Resource + Default + Clone {
     /// The three values correspond to the top, sides and bottom of the voxel. For example,
     /// if the slice is `[1,2,2]`, the top will use texture index 1 and the sides and bottom will use texture
     /// index 2.
-    fn texture_index_mapper(&self) -> Arc<dyn Fn(u8) -> [u32; 3] + Send + Sync> {
-        Arc::new(|mat| match mat {
+    fn texture_index_mapper(&self) -> Arc<dyn Fn(I) -> [u32; 3] + Send + Sync> {
+        Arc::new(|mat| match Into::<u32>::into(mat) {
             0 => [0, 0, 0],
             1 => [1, 1, 1],
             2 => [2, 2, 2],
@@ -109,21 +448,187 @@ pub trait VoxelWorldConfig: Resource + Default + Clone {
         })
     }
 
+    /// Like `texture_index_mapper`, but also given the biome of the voxel, so that textures can
+    /// vary by biome. Defaults to `texture_index_mapper`, ignoring the biome.
+    fn biome_texture_index_mapper(&self) -> Arc<dyn Fn(I, u8) -> [u32; 3] + Send + Sync> {
+        let texture_index_mapper = self.texture_index_mapper();
+        Arc::new(move |mat, _biome| texture_index_mapper(mat))
+    }
+
+    /// A function that tints the voxels of a material by the biome of their column, for example
+    /// to color grass and leaves with a color map per biome, or `None` for no tinting. Return
+    /// `Color::WHITE` for materials that are not tinted. The tint of each vertex is blended
+    /// between the columns that share it, so that it fades smoothly across biome borders. Only
+    /// cube faces are tinted.
+    #[cfg(feature = "render")]
+    fn biome_tint(&self) -> Option<BiomeTintFn<I>> {
+        None
+    }
+
+    /// A function that returns true for voxel materials that should be rendered in the transparent
+    /// pass, for example water or glass. Faces of transparent voxels are put in a separate mesh,
+    /// and faces between two transparent voxels are culled.
+    fn transparent_materials(&self) -> Arc<dyn Fn(I) -> bool + Send + Sync> {
+        Arc::new(|_| false)
+    }
+
+    /// A function that returns true for voxel materials that are rendered, but that nothing
+    /// collides with, like tall grass. Raycasts pass through these voxels, and
+    /// `VoxelWorld::collides_at` is false for them.
+    fn render_only_materials(&self) -> Arc<dyn Fn(I) -> bool + Send + Sync> {
+        Arc::new(|_| false)
+    }
+
+    /// A function that returns true for voxel materials that collide, but that are not rendered,
+    /// like invisible barriers. These voxels are meshed like air.
+    fn collide_only_materials(&self) -> Arc<dyn Fn(I) -> bool + Send + Sync> {
+        Arc::new(|_| false)
+    }
+
+    /// A function that returns the light level, from 0 to `MAX_LIGHT_LEVEL`, that voxels of a
+    /// material emit, like 14 for torches. Only used with `VoxelWorldLightingPlugin`, which
+    /// spreads the light into the block light of the voxels around them.
+    fn light_emission(&self) -> Arc<dyn Fn(I) -> u8 + Send + Sync> {
+        Arc::new(|_| 0)
+    }
+
+    /// A function that returns the sRGB color of the light that voxels of a material emit, like
+    /// orange for a lamp. Light that passes through voxels of the material, like stained glass, is
+    /// also filtered by this color. Only used with `VoxelWorldLightingPlugin`.
+    fn light_color(&self) -> Arc<dyn Fn(I) -> [u8; 3] + Send + Sync> {
+        Arc::new(|_| [255; 3])
+    }
+
     /// A function that returns a function that returns true if a voxel exists at the given position
     /// The delegate will be called every time a new chunk needs to be computed. The delegate should
     /// return a function that can be called to check if a voxel exists at a given position. This function
     /// needs to be thread-safe, since chunk computation happens on a separate thread.
-    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
-        Box::new(|_| Box::new(|_| WorldVoxel::Unset))
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate<I> {
+        Box::new(|_| Box::new(|_, _| WorldVoxel::Unset))
+    }
+
+    /// A delegate for looking up the biome of each column of the world, or `None` for a world
+    /// without biomes. Like `voxel_lookup_delegate`, it is called for every chunk that is
+    /// generated, and returns a function that is called for each column of the chunk. The biome
+    /// is passed to `biome_texture_index_mapper`, and to materials in the `ATTRIBUTE_BIOME`
+    /// vertex attribute, for example to tint grass by biome.
+    fn biome_lookup_delegate(&self) -> Option<BiomeLookupDelegate> {
+        None
+    }
+
+    /// A function that runs after the voxels of a full detail chunk are generated, and before it
+    /// is meshed, with mutable access to the chunk's voxels. Decorators can place features like
+    /// ores or ruins for the whole chunk at once, instead of checking for them in the
+    /// `voxel_lookup_delegate` for every voxel. Voxels set with `VoxelWorld::set_voxel` are not
+    /// overwritten. The chunk includes a border of one voxel from its neighbours, so decorators
+    /// should place the same voxels there as they do in the neighbouring chunks.
+    fn chunk_decorator(&self) -> Option<ChunkDecorator<I>> {
+        None
+    }
+
+    /// The lookup delegate used for chunks at the given level of detail, when `lod_distances` is
+    /// used. Override this to generate lower levels of detail with a different, usually cheaper,
+    /// delegate, for example one that skips trees and caves. Defaults to `voxel_lookup_delegate`.
+    fn voxel_lookup_delegate_for_lod(&self, _lod_level: u8) -> VoxelLookupDelegate<I> {
+        self.voxel_lookup_delegate()
+    }
+
+    /// A function that maps voxel materials to the shape they are rendered with. Non-cube shapes,
+    /// like `VoxelShape::Cross` for vegetation or `VoxelShape::Stairs`, don't hide the faces of
+    /// neighbouring voxels. Use `WorldVoxel::Oriented` to rotate the shape of a voxel.
+    /// If the texture of a shape has transparent parts, the material should also be flagged in
+    /// `transparent_materials`.
+    fn material_shapes(&self) -> Arc<dyn Fn(I) -> VoxelShape + Send + Sync> {
+        Arc::new(|_| VoxelShape::Cube)
+    }
+
+    /// User-defined block models, used by materials mapped to `VoxelShape::Model` in
+    /// `material_shapes`. Register the models once and return a clone of the `Arc` here.
+    fn block_models(&self) -> Arc<BlockModels> {
+        Arc::new(BlockModels::default())
+    }
+
+    /// A function that returns true for voxel materials that are fluids, like water. Fluid voxels
+    /// don't get a regular cube mesh. Instead, their top surface is put in a separate mesh, which
+    /// is rendered with the transparent material, or a custom one (see `custom_fluid_material`).
+    fn fluid_materials(&self) -> Arc<dyn Fn(I) -> bool + Send + Sync> {
+        Arc::new(|_| false)
+    }
+
+    /// A function that returns true for voxel materials that sway in the wind, like grass and
+    /// leaves. The built-in material moves their vertices back and forth, with the bottom of
+    /// shapes like `VoxelShape::Cross` staying in place, so vegetation moves without separate
+    /// entities.
+    fn wind_materials(&self) -> Arc<dyn Fn(I) -> bool + Send + Sync> {
+        Arc::new(|_| false)
+    }
+
+    /// How far, in voxels, the vertices of `wind_materials` move in the wind
+    fn wind_strength(&self) -> f32 {
+        0.08
+    }
+
+    /// When this is true, no material is assigned to fluid surface meshes, so that a custom
+    /// material can be added to entities with the `VoxelFluidSurface` component instead.
+    fn custom_fluid_material(&self) -> bool {
+        false
+    }
+
+    /// Average normals across vertices that share a position, instead of using flat per-face
+    /// normals. This gives softer lighting, mostly useful for smooth terrain.
+    fn smooth_normals(&self) -> bool {
+        false
+    }
+
+    /// Generate tangents for chunk meshes. This is needed for normal mapping to work with the
+    /// standard material, but increases the memory used by each mesh.
+    fn generate_tangents(&self) -> bool {
+        false
+    }
+
+    /// Use triplanar projection for the built-in material, instead of the UVs generated by the mesher.
+    /// The texture is projected along the world axes and blended by the surface normal, which
+    /// avoids stretching on surfaces that are not axis aligned, like smooth meshed terrain.
+    /// Has no effect when a custom material is used.
+    fn triplanar_texturing(&self) -> bool {
+        false
+    }
+
+    /// Whether the chunks of this world cast shadows. Turn this off for worlds that only make up
+    /// the far background, like low detail copies of a world, so that they are left out of the
+    /// shadow passes entirely.
+    fn cast_shadows(&self) -> bool {
+        true
+    }
+
+    /// Chunks farther than this from the camera, in full detail chunks, don't cast shadows, or
+    /// `None` for no limit. Rendering far levels of detail into the cascades of directional light
+    /// shadows is expensive, and their shadows are barely visible.
+    fn shadow_distance(&self) -> Option<u32> {
+        None
     }
 
     /// A tuple of the path to the texture and the number of indexes in the texture. `None` if no texture is used.
+    /// Each world loads its own texture, so worlds with different configs can look different.
     fn voxel_texture(&self) -> Option<(String, u32)> {
         None
     }
 
+    /// The `StandardMaterial` parameters of the built-in material, like its roughness and
+    /// reflectance. The transparent variant uses the same parameters with `AlphaMode::Blend`.
+    /// Has no effect when a custom material is used.
+    #[cfg(feature = "render")]
+    fn voxel_base_material(&self) -> StandardMaterial {
+        StandardMaterial {
+            reflectance: 0.05,
+            metallic: 0.05,
+            perceptual_roughness: 0.95,
+            ..default()
+        }
+    }
+
     /// Custom material will not get initialized if this returns false. When this is false,
-    /// `VoxelWorldMaterialHandle` needs to be manually added with a reference to the material handle.
+    /// `VoxelWorldMaterialHandle<C, M>` needs to be manually added with a reference to the material handle.
     ///
     /// This can be used for example if you need to wait for a texture image to load before
     /// the material can be used.
@@ -131,6 +636,40 @@ pub trait VoxelWorldConfig: Resource + Default + Clone {
         true
     }
 
+    /// How often `VoxelWorldPersistencePlugin` saves all chunks with unsaved edits, including
+    /// spawned ones, or `None` to only save chunks when they despawn. Defaults to `None`.
+    fn autosave_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether `VoxelWorldPersistencePlugin` saves all chunks with unsaved edits when the app
+    /// exits. Defaults to true.
+    fn autosave_on_exit(&self) -> bool {
+        true
+    }
+
+    /// Identifies the world in `RemoteVoxelEdit`s, see `VoxelWorldReplicationPlugin`. Give each
+    /// world a different key when several worlds are replicated over the same channel.
+    fn replication_key(&self) -> u64 {
+        0
+    }
+
+    /// Describes the world when it is saved by `VoxelWorldPersistencePlugin`, so that its
+    /// configuration can be recreated with `from_world_metadata`. Add the seed and any generator
+    /// parameters here.
+    fn world_metadata(&self) -> WorldMetadata {
+        WorldMetadata::default()
+    }
+
+    /// Recreates the configuration of a saved world from its metadata, see `WorldMetadata::load`.
+    /// Defaults to `None`, for worlds that can't be recreated from their metadata.
+    fn from_world_metadata(_metadata: &WorldMetadata) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
     fn init_root(&self, mut _commands: Commands, _root: Entity) {}
 }
 
@@ -1,8 +1,12 @@
 use std::marker::PhantomData;
 
-use bevy::{prelude::*, render::primitives::Aabb};
+use bevy::{prelude::*, render::primitives::Aabb, utils::HashMap};
 
-use crate::chunk::Chunk;
+use crate::{
+    chunk::{Chunk, ChunkThread, NeedsRemesh},
+    configuration::WorldConfig,
+    voxel_world_internal::ModifiedChunks,
+};
 
 pub struct VoxelWorldGizmoPlugin<C>(PhantomData<C>);
 
@@ -34,3 +38,119 @@ fn aabb_transform(aabb: Aabb, transform: GlobalTransform) -> GlobalTransform {
                 .with_scale((aabb.half_extents * 2.).into()),
         )
 }
+
+/// The state of a chunk, as shown by the debug overlay
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChunkDebugState {
+    /// Waiting for a generating and meshing task to be started
+    Queued,
+    Generating,
+    Meshing,
+    Meshed,
+    /// Meshed, with edits that haven't been saved yet
+    Dirty,
+}
+
+impl ChunkDebugState {
+    /// The color the bounds of chunks in this state are drawn with
+    pub fn color(&self) -> Color {
+        match self {
+            ChunkDebugState::Queued => Color::srgb(0.5, 0.5, 0.5),
+            ChunkDebugState::Generating => Color::srgb(1.0, 0.5, 0.0),
+            ChunkDebugState::Meshing => Color::srgb(1.0, 1.0, 0.0),
+            ChunkDebugState::Meshed => Color::srgb(0.0, 1.0, 0.0),
+            ChunkDebugState::Dirty => Color::srgb(1.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// Draws the bounds of the chunks of the world with config `C`, colored by their state. Starts
+/// out enabled if `VoxelWorldConfig::debug_draw_chunks` returns true. Chunks without a mesh, like
+/// those of only air, are left out once they are done.
+#[derive(Resource)]
+pub struct VoxelWorldDebugOverlay<C> {
+    pub enabled: bool,
+    states: HashMap<Entity, ChunkDebugState>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> VoxelWorldDebugOverlay<C> {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            states: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+    }
+
+    /// The state of a chunk entity, as of the last frame the overlay was enabled
+    pub fn state(&self, chunk: Entity) -> Option<ChunkDebugState> {
+        self.states.get(&chunk).copied()
+    }
+
+    /// The number of chunks in the given state
+    pub fn count(&self, state: ChunkDebugState) -> usize {
+        self.states.values().filter(|s| **s == state).count()
+    }
+}
+
+impl<C> Default for VoxelWorldDebugOverlay<C> {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+pub(crate) fn debug_overlay_enabled<C: WorldConfig>(
+    overlay: Res<VoxelWorldDebugOverlay<C>>,
+) -> bool {
+    overlay.enabled
+}
+
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_chunk_debug_states<C: WorldConfig>(
+    mut overlay: ResMut<VoxelWorldDebugOverlay<C>>,
+    chunks: Query<(
+        Entity,
+        &Chunk<C>,
+        Has<NeedsRemesh>,
+        Option<&ChunkThread<C>>,
+        Has<Handle<Mesh>>,
+    )>,
+    modified_chunks: Res<ModifiedChunks<C>>,
+) {
+    overlay.states.clear();
+    for (entity, chunk, needs_remesh, thread, has_mesh) in chunks.iter() {
+        let state = if needs_remesh {
+            ChunkDebugState::Queued
+        } else if let Some(thread) = thread {
+            if thread.is_generated() {
+                ChunkDebugState::Meshing
+            } else {
+                ChunkDebugState::Generating
+            }
+        } else if !has_mesh {
+            continue;
+        } else if chunk.lod_level == 0 && modified_chunks.contains(&chunk.position) {
+            ChunkDebugState::Dirty
+        } else {
+            ChunkDebugState::Meshed
+        };
+        overlay.states.insert(entity, state);
+    }
+}
+
+pub(crate) fn draw_chunk_debug_overlay<C: WorldConfig>(
+    overlay: Res<VoxelWorldDebugOverlay<C>>,
+    chunks: Query<(Entity, &Chunk<C>, &GlobalTransform)>,
+    mut gizmos: Gizmos,
+) {
+    for (entity, chunk, &transform) in chunks.iter() {
+        if let Some(state) = overlay.state(entity) {
+            gizmos.cuboid(aabb_transform(chunk.aabb(), transform), state.color());
+        }
+    }
+}
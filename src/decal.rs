@@ -0,0 +1,88 @@
+///
+/// Face decals
+/// Decals drawn over single voxel faces, like the cracks of a voxel that is being mined. Each
+/// decal is a quad of its own on top of the face, so decals can be added, changed and removed
+/// without remeshing the chunk.
+///
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use std::marker::PhantomData;
+
+use crate::{configuration::WorldConfig, voxel::VOXEL_SIZE, voxel_world::VoxelWorld};
+
+/// How far decals are lifted off the face, to keep them from z-fighting with it
+const DECAL_OFFSET: f32 = 0.002;
+
+/// A decal over the face of the voxel at `position` that points along `normal`. Spawn it together
+/// with a material, like a `StandardMaterial` with an alpha blended crack texture, and swap the
+/// material to show the next stage. The mesh and transform are added for you, and the decal is
+/// despawned once the voxel is no longer solid.
+///
+/// ```
+/// use bevy::prelude::*;
+/// use bevy_voxel_world::prelude::*;
+///
+/// fn show_cracks(mut commands: Commands, crack_material: Handle<StandardMaterial>) {
+///     commands.spawn((
+///         VoxelFaceDecal::<DefaultWorld>::new(IVec3::new(0, 10, 0), IVec3::Y),
+///         crack_material,
+///     ));
+/// }
+/// ```
+#[derive(Component)]
+pub struct VoxelFaceDecal<C> {
+    pub position: IVec3,
+    /// The normal of the face, one of the six axis directions
+    pub normal: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> VoxelFaceDecal<C> {
+    pub fn new(position: IVec3, normal: IVec3) -> Self {
+        Self {
+            position,
+            normal,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The transform of the decal quad, which faces along +Z before it is rotated
+    pub fn transform(&self) -> Transform {
+        let normal = self.normal.as_vec3().normalize_or_zero();
+        let center = (self.position.as_vec3() + 0.5 + normal * 0.5) * VOXEL_SIZE;
+        Transform::from_translation(center + normal * DECAL_OFFSET)
+            .with_rotation(Quat::from_rotation_arc(Vec3::Z, normal))
+            .with_scale(Vec3::splat(VOXEL_SIZE))
+    }
+}
+
+/// Adds the quad mesh to new decals, and moves decals whose face changed. All decals share one
+/// unit quad.
+pub(crate) fn place_face_decals<C: WorldConfig>(
+    mut commands: Commands,
+    decals: Query<(Entity, &VoxelFaceDecal<C>), Changed<VoxelFaceDecal<C>>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut decal_mesh: Local<Option<Handle<Mesh>>>,
+) {
+    for (entity, decal) in decals.iter() {
+        let mesh = decal_mesh.get_or_insert_with(|| meshes.add(Rectangle::new(1.0, 1.0)));
+        commands.entity(entity).try_insert((
+            mesh.clone(),
+            SpatialBundle::from_transform(decal.transform()),
+            NotShadowCaster,
+        ));
+    }
+}
+
+/// Despawns decals of voxels that were removed, like a voxel that has been mined
+pub(crate) fn despawn_orphaned_face_decals<C: WorldConfig>(
+    mut commands: Commands,
+    decals: Query<(Entity, &VoxelFaceDecal<C>)>,
+    voxel_world: VoxelWorld<C>,
+) {
+    let get_voxel = voxel_world.get_voxel_fn();
+    for (entity, decal) in decals.iter() {
+        if !get_voxel(decal.position).is_solid() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,106 @@
+///
+/// Edit history
+/// Keeps the voxel edits made through `VoxelWorld` so they can be undone and redone, for editor
+/// tooling. Disabled unless `VoxelWorldConfig::edit_history_size` is set.
+///
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+use crate::{configuration::WorldConfig, voxel::WorldVoxel};
+
+/// A voxel edit: the position, and the voxel before and after it
+type VoxelEdit<I> = (IVec3, WorldVoxel<I>, WorldVoxel<I>);
+
+/// The undo and redo steps of a world. Each step holds all the edits of one `set_voxels` call,
+/// or of one edit group.
+#[derive(Resource)]
+pub(crate) struct EditHistory<C: WorldConfig> {
+    undo: VecDeque<Vec<VoxelEdit<C::MaterialIndex>>>,
+    redo: Vec<Vec<VoxelEdit<C::MaterialIndex>>>,
+    group: Option<Vec<VoxelEdit<C::MaterialIndex>>>,
+    max_size: usize,
+}
+
+impl<C: WorldConfig> EditHistory<C> {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            group: None,
+            max_size,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_size > 0
+    }
+
+    pub fn record(&mut self, edits: Vec<VoxelEdit<C::MaterialIndex>>) {
+        match self.group.as_mut() {
+            Some(group) => group.extend(edits),
+            None => self.push_step(edits),
+        }
+    }
+
+    pub fn begin_group(&mut self) {
+        if self.is_enabled() && self.group.is_none() {
+            self.group = Some(Vec::new());
+        }
+    }
+
+    pub fn end_group(&mut self) {
+        if let Some(group) = self.group.take() {
+            self.push_step(group);
+        }
+    }
+
+    /// The writes that revert the most recent step, if there is one
+    pub fn undo(&mut self) -> Option<Vec<(IVec3, WorldVoxel<C::MaterialIndex>)>> {
+        self.end_group();
+        let step = self.undo.pop_back()?;
+        let writes = step
+            .iter()
+            .rev()
+            .map(|(position, before, _)| (*position, *before))
+            .collect();
+        self.redo.push(step);
+        Some(writes)
+    }
+
+    /// The writes that reapply the most recently undone step, if there is one
+    pub fn redo(&mut self) -> Option<Vec<(IVec3, WorldVoxel<C::MaterialIndex>)>> {
+        self.end_group();
+        let step = self.redo.pop()?;
+        let writes = step
+            .iter()
+            .map(|(position, _, after)| (*position, *after))
+            .collect();
+        self.undo.push_back(step);
+        Some(writes)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || self.group.as_ref().is_some_and(|group| !group.is_empty())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.group = None;
+    }
+
+    fn push_step(&mut self, edits: Vec<VoxelEdit<C::MaterialIndex>>) {
+        if !self.is_enabled() || edits.is_empty() {
+            return;
+        }
+        self.undo.push_back(edits);
+        if self.undo.len() > self.max_size {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+    }
+}
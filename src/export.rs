@@ -0,0 +1,260 @@
+///
+/// Mesh export
+/// Meshes a box of the world with the chunk mesher, and writes it as a Wavefront OBJ or a binary
+/// glTF file, for taking voxel builds into modelling tools. See `VoxelWorld::export_region`.
+///
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+use std::{fmt::Write as _, fs, io, path::Path, sync::Arc};
+
+use ndshape::ConstShape;
+
+use crate::{
+    chunk::{PaddedChunkShape, CHUNK_SIZE_I},
+    meshing::{generate_chunk_mesh, MeshingConfig},
+    voxel::WorldVoxel,
+};
+
+/// The file formats that regions can be exported to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshExportFormat {
+    /// Wavefront OBJ, with vertex colors after the positions
+    Obj,
+    /// Binary glTF 2.0
+    Glb,
+}
+
+impl MeshExportFormat {
+    /// The format for a file extension, `.obj` or `.glb`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "obj" => Some(Self::Obj),
+            "glb" => Some(Self::Glb),
+            _ => None,
+        }
+    }
+}
+
+/// The meshes of a region merged into one, in world space
+#[derive(Default)]
+pub(crate) struct ExportMesh {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl ExportMesh {
+    /// Mesh all voxels between `min` and `max`, inclusive. `chunk_voxels` gives the voxel lookup
+    /// for each chunk in the region. Voxels outside of the region are left empty, so the region is
+    /// closed off where it is cut from the world.
+    pub fn from_region<I, F>(
+        min: IVec3,
+        max: IVec3,
+        mut chunk_voxels: impl FnMut(IVec3) -> F,
+        meshing_config: &MeshingConfig<I>,
+    ) -> Self
+    where
+        I: Copy + Eq + 'static,
+        F: FnMut(IVec3) -> WorldVoxel<I>,
+    {
+        let mut export = Self::default();
+        let chunk_min = min.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+        let chunk_max = max.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+        for x in chunk_min.x..=chunk_max.x {
+            for y in chunk_min.y..=chunk_max.y {
+                for z in chunk_min.z..=chunk_max.z {
+                    let chunk_position = IVec3::new(x, y, z);
+                    let mut get_voxel = chunk_voxels(chunk_position);
+                    // Meshes are relative to the padded chunk, which starts one voxel early
+                    let origin = chunk_position * CHUNK_SIZE_I - IVec3::ONE;
+
+                    let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+                    for (i, voxel) in voxels.iter_mut().enumerate() {
+                        let position = origin
+                            + IVec3::from(
+                                PaddedChunkShape::delinearize(i as u32).map(|c| c as i32),
+                            );
+                        if position.cmpge(min).all() && position.cmple(max).all() {
+                            *voxel = get_voxel(position);
+                        }
+                    }
+                    if !voxels
+                        .iter()
+                        .any(|voxel| voxel.is_solid() || voxel.is_fluid())
+                    {
+                        continue;
+                    }
+
+                    let meshes = generate_chunk_mesh(
+                        Arc::new(voxels),
+                        None,
+                        None,
+                        chunk_position,
+                        meshing_config,
+                    );
+                    let offset = origin.as_vec3();
+                    for mesh in [Some(meshes.opaque), meshes.transparent, meshes.fluid]
+                        .iter()
+                        .flatten()
+                    {
+                        export.append(mesh, offset);
+                    }
+                }
+            }
+        }
+        export
+    }
+
+    fn append(&mut self, mesh: &Mesh, offset: Vec3) {
+        let (
+            Some(VertexAttributeValues::Float32x3(positions)),
+            Some(VertexAttributeValues::Float32x3(normals)),
+            Some(VertexAttributeValues::Float32x2(uvs)),
+            Some(VertexAttributeValues::Float32x4(colors)),
+            Some(Indices::U32(indices)),
+        ) = (
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+            mesh.attribute(Mesh::ATTRIBUTE_UV_0),
+            mesh.attribute(Mesh::ATTRIBUTE_COLOR),
+            mesh.indices(),
+        )
+        else {
+            return;
+        };
+        let first_index = self.positions.len() as u32;
+        self.positions.extend(
+            positions
+                .iter()
+                .map(|p| (Vec3::from(*p) + offset).to_array()),
+        );
+        self.normals.extend_from_slice(normals);
+        self.uvs.extend_from_slice(uvs);
+        self.colors.extend_from_slice(colors);
+        self.indices.extend(indices.iter().map(|i| first_index + i));
+    }
+
+    pub fn write(&self, path: &Path, format: MeshExportFormat) -> io::Result<()> {
+        match format {
+            MeshExportFormat::Obj => fs::write(path, self.encode_obj()),
+            MeshExportFormat::Glb => fs::write(path, self.encode_glb()),
+        }
+    }
+
+    fn encode_obj(&self) -> String {
+        let mut obj = String::from("# Exported from bevy_voxel_world\n");
+        for (position, color) in self.positions.iter().zip(&self.colors) {
+            let [x, y, z] = position;
+            let [r, g, b, _] = color;
+            let _ = writeln!(obj, "v {x} {y} {z} {r} {g} {b}");
+        }
+        for [u, v] in &self.uvs {
+            // OBJ texture coordinates start at the bottom
+            let _ = writeln!(obj, "vt {u} {}", 1.0 - v);
+        }
+        for [x, y, z] in &self.normals {
+            let _ = writeln!(obj, "vn {x} {y} {z}");
+        }
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+            let _ = writeln!(obj, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}");
+        }
+        obj
+    }
+
+    /// A glTF file with a single mesh, with its vertex attributes and indices one after another
+    /// in the binary chunk
+    fn encode_glb(&self) -> Vec<u8> {
+        let mut bin = Vec::new();
+        let mut views = Vec::new();
+        let mut view = |bin: &mut Vec<u8>, values: &mut dyn Iterator<Item = [u8; 4]>| {
+            let offset = bin.len();
+            bin.extend(values.flatten());
+            views.push((offset, bin.len() - offset));
+        };
+        view(
+            &mut bin,
+            &mut self.positions.iter().flatten().map(|f| f.to_le_bytes()),
+        );
+        view(
+            &mut bin,
+            &mut self.normals.iter().flatten().map(|f| f.to_le_bytes()),
+        );
+        view(
+            &mut bin,
+            &mut self.uvs.iter().flatten().map(|f| f.to_le_bytes()),
+        );
+        view(
+            &mut bin,
+            &mut self.colors.iter().flatten().map(|f| f.to_le_bytes()),
+        );
+        view(&mut bin, &mut self.indices.iter().map(|i| i.to_le_bytes()));
+
+        let buffer_views = views
+            .iter()
+            .enumerate()
+            .map(|(i, (offset, len))| {
+                // Array buffers for vertex attributes, an element array buffer for indices
+                let target = if i == 4 { 34963 } else { 34962 };
+                format!(
+                    r#"{{"buffer":0,"byteOffset":{offset},"byteLength":{len},"target":{target}}}"#
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let (min, max) = self.positions.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), p| (min.min(Vec3::from(*p)), max.max(Vec3::from(*p))),
+        );
+        let vertex_count = self.positions.len();
+        let accessors = [
+            format!(
+                r#"{{"bufferView":0,"componentType":5126,"count":{vertex_count},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}}"#,
+                min.x, min.y, min.z, max.x, max.y, max.z
+            ),
+            format!(r#"{{"bufferView":1,"componentType":5126,"count":{vertex_count},"type":"VEC3"}}"#),
+            format!(r#"{{"bufferView":2,"componentType":5126,"count":{vertex_count},"type":"VEC2"}}"#),
+            format!(r#"{{"bufferView":3,"componentType":5126,"count":{vertex_count},"type":"VEC4"}}"#),
+            format!(
+                r#"{{"bufferView":4,"componentType":5125,"count":{},"type":"SCALAR"}}"#,
+                self.indices.len()
+            ),
+        ]
+        .join(",");
+
+        // Accessors can't be empty, so an empty region is a scene without nodes
+        let json = if self.indices.is_empty() {
+            r#"{"asset":{"version":"2.0","generator":"bevy_voxel_world"},"scene":0,"scenes":[{"nodes":[]}]}"#.to_string()
+        } else {
+            format!(
+                r#"{{"asset":{{"version":"2.0","generator":"bevy_voxel_world"}},"scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"meshes":[{{"primitives":[{{"attributes":{{"POSITION":0,"NORMAL":1,"TEXCOORD_0":2,"COLOR_0":3}},"indices":4}}]}}],"buffers":[{{"byteLength":{}}}],"bufferViews":[{buffer_views}],"accessors":[{accessors}]}}"#,
+                bin.len()
+            )
+        };
+
+        let mut json = json.into_bytes();
+        while json.len() % 4 != 0 {
+            json.push(b' ');
+        }
+        let mut bytes = Vec::new();
+        let total_len = 12 + 8 + json.len() + if bin.is_empty() { 0 } else { 8 + bin.len() };
+        bytes.extend_from_slice(b"glTF");
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&(total_len as u32).to_le_bytes());
+        bytes.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"JSON");
+        bytes.extend_from_slice(&json);
+        if !bin.is_empty() {
+            bytes.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(b"BIN\0");
+            bytes.extend_from_slice(&bin);
+        }
+        bytes
+    }
+}
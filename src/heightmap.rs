@@ -0,0 +1,76 @@
+///
+/// Heightmaps
+/// Grids of terrain heights that can be stamped into the world with
+/// `VoxelWorld::stamp_heightmap`, for importing terrain or sculpting it in an editor.
+///
+use bevy::prelude::*;
+#[cfg(feature = "render")]
+use bevy::render::render_resource::TextureFormat;
+
+/// A grid of heights, in voxels, indexed by x and z
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heightmap {
+    size: UVec2,
+    heights: Vec<f32>,
+}
+
+impl Heightmap {
+    /// A heightmap from heights in row-major order, `size.x` heights per row. Missing heights
+    /// are 0.
+    pub fn new(size: UVec2, mut heights: Vec<f32>) -> Self {
+        heights.resize((size.x * size.y) as usize, 0.0);
+        Self { size, heights }
+    }
+
+    /// A heightmap from the first channel of a grayscale or color image, where black is 0 and
+    /// white is `max_height`. Returns `None` for image formats other than 8 or 16 bit unsigned
+    /// normalized, or 32 bit float.
+    #[cfg(feature = "render")]
+    pub fn from_image(image: &Image, max_height: f32) -> Option<Self> {
+        let size = image.size();
+        let bytes_per_pixel = image.texture_descriptor.format.block_copy_size(None)? as usize;
+        let sample: fn(&[u8]) -> f32 = match image.texture_descriptor.format {
+            TextureFormat::R8Unorm
+            | TextureFormat::Rg8Unorm
+            | TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb => |pixel| pixel[0] as f32 / u8::MAX as f32,
+            TextureFormat::R16Unorm | TextureFormat::Rg16Unorm | TextureFormat::Rgba16Unorm => {
+                |pixel| u16::from_le_bytes([pixel[0], pixel[1]]) as f32 / u16::MAX as f32
+            }
+            TextureFormat::R32Float => {
+                |pixel| f32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]])
+            }
+            _ => return None,
+        };
+
+        let heights = image
+            .data
+            .chunks_exact(bytes_per_pixel)
+            .map(|pixel| sample(pixel) * max_height)
+            .collect();
+        Some(Self::new(size, heights))
+    }
+
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    /// The height at the given column, or 0 outside the heightmap
+    pub fn get(&self, column: UVec2) -> f32 {
+        if column.cmpge(self.size).any() {
+            return 0.0;
+        }
+        self.heights[(column.y * self.size.x + column.x) as usize]
+    }
+
+    /// The world positions of the voxels below the surface, for a heightmap placed with its
+    /// first column at `origin` and its heights measured from `origin.y`
+    pub(crate) fn filled_positions(&self, origin: IVec3) -> impl Iterator<Item = IVec3> + '_ {
+        (0..self.size.y).flat_map(move |z| {
+            (0..self.size.x).flat_map(move |x| {
+                let height = self.get(UVec2::new(x, z)).round() as i32;
+                (0..height).map(move |y| origin + IVec3::new(x as i32, y, z as i32))
+            })
+        })
+    }
+}
@@ -1,35 +1,112 @@
+mod brush;
 mod chunk;
 mod chunk_map;
+mod chunk_storage;
+mod chunk_transport;
 mod configuration;
+#[cfg(feature = "render")]
 mod debug;
+#[cfg(feature = "render")]
+mod decal;
+mod edit_history;
+#[cfg(feature = "render")]
+mod export;
+mod heightmap;
+mod light;
+mod lod;
+mod material_registry;
+#[cfg(feature = "render")]
 mod mesh_cache;
+#[cfg(feature = "render")]
 mod meshing;
+mod persistence;
 mod plugin;
+mod prediction;
+mod replication;
+mod rle;
+#[cfg(feature = "serde")]
+mod serialization;
+mod snapshot;
+#[cfg(feature = "sqlite")]
+mod sqlite_storage;
 mod voxel;
+mod voxel_buffer;
+#[cfg(feature = "render")]
 mod voxel_material;
+mod voxel_shape;
 mod voxel_traversal;
 mod voxel_world;
 mod voxel_world_internal;
+#[cfg(feature = "render")]
+mod voxelize;
+mod world_metadata;
 
 pub mod prelude {
-    pub use crate::chunk::{Chunk, NeedsDespawn};
+    pub use crate::brush::{BrushMode, BrushShape, VoxelBrush};
+    pub use crate::chunk::{
+        Chunk, ChunkData, ChunkVoxelsMut, FillType, NeedsDespawn, SharedVoxelData,
+        VoxelFluidSurface,
+    };
+    pub use crate::chunk_storage::{
+        ChunkStorageProvider, MemoryChunkStorage, RegionFileStorage, REGION_SIZE,
+    };
+    pub use crate::chunk_transport::{ChunkTransport, VoxelWorldClientPlugin};
     pub use crate::configuration::*;
-    pub use crate::debug::{ChunkAabbGizmo, VoxelWorldGizmoPlugin};
+    #[cfg(feature = "render")]
+    pub use crate::debug::{
+        ChunkAabbGizmo, ChunkDebugState, VoxelWorldDebugOverlay, VoxelWorldGizmoPlugin,
+    };
+    #[cfg(feature = "render")]
+    pub use crate::decal::VoxelFaceDecal;
+    #[cfg(feature = "render")]
+    pub use crate::export::MeshExportFormat;
+    pub use crate::heightmap::Heightmap;
+    pub use crate::light::{VoxelLight, VoxelSkyLight, VoxelWorldLightingPlugin, MAX_LIGHT_LEVEL};
+    pub use crate::material_registry::{VoxelMaterialInfo, VoxelMaterialRegistry};
+    pub use crate::persistence::{
+        ChunkCodec, ChunkLoaded, ChunkSaved, VoxelWorldPersistencePlugin,
+    };
     pub use crate::plugin::VoxelWorldPlugin;
-    pub use crate::voxel::{VoxelFace, WorldVoxel, VOXEL_SIZE};
-    pub use crate::voxel_world::{ChunkWillDespawn, ChunkWillRemesh, ChunkWillSpawn};
-    pub use crate::voxel_world::{VoxelRaycastResult, VoxelWorld, VoxelWorldCamera};
+    pub use crate::prediction::{PredictedEdit, PredictedEdits, VoxelWorldPredictionPlugin};
+    pub use crate::replication::{
+        ChunkVersions, RemoteVoxelEdit, ReplicationConnection, ReplicationMessage,
+        ReplicationOutbox, ReplicationSynced, SyncProgress, VoxelWorldReplicationPlugin,
+    };
+    #[cfg(feature = "serde")]
+    pub use crate::serialization::FORMAT_VERSION;
+    pub use crate::snapshot::WorldSnapshot;
+    #[cfg(feature = "sqlite")]
+    pub use crate::sqlite_storage::SqliteChunkStorage;
+    pub use crate::voxel::{VoxelFace, VoxelFlags, WorldVoxel, MAX_FLUID_LEVEL, VOXEL_SIZE};
+    pub use crate::voxel_buffer::{CsgOperation, VoxelBuffer};
+    pub use crate::voxel_shape::{BlockModels, ShapeQuad, VoxelFacing, VoxelRotation, VoxelShape};
+    pub use crate::voxel_world::{ChunkRequest, ChunkWillDespawn, ChunkWillRemesh, ChunkWillSpawn};
+    pub use crate::voxel_world::{
+        ProtectedRegion, QueuedEdit, QueuedEditFinished, ScheduledEdit, VoxelEditRejected,
+    };
+    pub use crate::voxel_world::{
+        VoxelRaycastResult, VoxelTransaction, VoxelWorld, VoxelWorldCamera,
+        VoxelWorldStreamingAnchor,
+    };
+    pub use crate::world_metadata::{WorldMetadata, SAVE_FORMAT_VERSION};
 }
 
+#[cfg(feature = "render")]
 pub mod rendering {
     pub use crate::plugin::VoxelWorldMaterialHandle;
     pub use crate::voxel_material::vertex_layout;
-    pub use crate::voxel_material::VOXEL_TEXTURE_SHADER_HANDLE;
+    pub use crate::voxel_material::{
+        ExtendedVoxelMaterial, StandardVoxelMaterial, StandardVoxelMaterialType,
+    };
+    pub use crate::voxel_material::{
+        ATTRIBUTE_BIOME, ATTRIBUTE_LIGHT, ATTRIBUTE_PBR, ATTRIBUTE_TEX_INDEX, ATTRIBUTE_WIND,
+        UNTEXTURED_INDEX, VOXEL_TEXTURE_SHADER_HANDLE,
+    };
 }
 
 pub mod traversal_alg {
     pub use crate::voxel_traversal::*;
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "render"))]
 mod test;
@@ -0,0 +1,810 @@
+///
+/// Voxel light
+/// Storage for the light level of each voxel in a chunk. Light is kept as sky light, coming from
+/// the open sky, and block light, emitted by voxels like torches, in red, green and blue. Light is
+/// spread by `VoxelWorldLightingPlugin`.
+///
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use ndshape::ConstShape;
+use std::{collections::VecDeque, hash::Hash, marker::PhantomData, sync::Arc};
+
+use crate::{
+    chunk::{PaddedChunkShape, CHUNK_SIZE_I, CHUNK_SIZE_U},
+    chunk_map::{ChunkMap, ChunkMapData},
+    configuration::{MaterialIndexType, WorldConfig},
+    material_registry::VoxelMaterialRegistry,
+    voxel::WorldVoxel,
+    voxel_shape::VoxelShape,
+    voxel_world::{ChunkWillDespawn, ChunkWillSpawn},
+    voxel_world_internal::{get_chunk_voxel_position, Internals, ModifiedVoxels},
+};
+
+/// The highest light level of any channel
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+/// The light levels of a voxel, packed into two bytes. Sky light has one channel, and block light
+/// has a red, green and blue channel, so that colored lamps and stained glass can tint it. All
+/// channels go from 0 (dark) to `MAX_LIGHT_LEVEL`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VoxelLight(u16);
+
+impl VoxelLight {
+    /// Light with white block light of the given level
+    pub fn new(sky: u8, block: u8) -> Self {
+        Self::new_rgb(sky, [block; 3])
+    }
+
+    pub fn new_rgb(sky: u8, [red, green, blue]: [u8; 3]) -> Self {
+        let channel = |level: u8| level.min(MAX_LIGHT_LEVEL) as u16;
+        Self(channel(sky) << 12 | channel(red) << 8 | channel(green) << 4 | channel(blue))
+    }
+
+    /// Light coming from the open sky
+    pub fn sky(&self) -> u8 {
+        (self.0 >> 12) as u8
+    }
+
+    /// Light emitted by voxels, the brightest of its red, green and blue channels
+    pub fn block(&self) -> u8 {
+        let [red, green, blue] = self.block_rgb();
+        red.max(green).max(blue)
+    }
+
+    /// The red, green and blue channels of the light emitted by voxels
+    pub fn block_rgb(&self) -> [u8; 3] {
+        [8, 4, 0].map(|shift| (self.0 >> shift) as u8 & MAX_LIGHT_LEVEL)
+    }
+
+    /// The brighter of sky and block light
+    pub fn level(&self) -> u8 {
+        self.sky().max(self.block())
+    }
+
+    /// Like `level`, with sky light scaled by the time of day, see `VoxelSkyLight::intensity`
+    pub fn level_at_intensity(&self, sky_intensity: f32) -> u8 {
+        let sky = (self.sky() as f32 * sky_intensity.clamp(0.0, 1.0)).round() as u8;
+        sky.max(self.block())
+    }
+
+    pub fn with_sky(&self, sky: u8) -> Self {
+        Self::new_rgb(sky, self.block_rgb())
+    }
+
+    /// Set all three channels of block light to the same level
+    pub fn with_block(&self, block: u8) -> Self {
+        Self::new(self.sky(), block)
+    }
+
+    pub fn with_block_rgb(&self, block: [u8; 3]) -> Self {
+        Self::new_rgb(self.sky(), block)
+    }
+}
+
+pub(crate) type LightArray = [VoxelLight; PaddedChunkShape::SIZE as usize];
+
+/// Scales the baked sky light of the world with config `C` in the built-in material, from 0.0 for
+/// a moonless night to 1.0 for full daylight. Changing it only updates the materials, so chunks
+/// are not lit again or remeshed, which keeps a day and night cycle cheap. Custom materials can
+/// read it to do the same with the sky light in `ATTRIBUTE_LIGHT`.
+#[derive(Resource)]
+pub struct VoxelSkyLight<C> {
+    pub intensity: f32,
+    _marker: PhantomData<C>,
+}
+
+impl<C> VoxelSkyLight<C> {
+    pub fn new(intensity: f32) -> Self {
+        Self {
+            intensity,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> Default for VoxelSkyLight<C> {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+/// The six directions light spreads in
+const DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+/// Spreads light through the world with config `C`. Sky light comes down from the open sky, and
+/// block light from emissive voxels, like torches. The light level of each material is set with
+/// `VoxelWorldConfig::light_emission` or `VoxelMaterialInfo::light_emission`, and drops by one
+/// for each voxel it travels. Light is spread when chunks spawn and when voxels change, and is
+/// baked into the chunk meshes.
+pub struct VoxelWorldLightingPlugin<C> {
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for VoxelWorldLightingPlugin<C> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: WorldConfig> Plugin for VoxelWorldLightingPlugin<C> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightUpdates<C>>().add_systems(
+            PreUpdate,
+            propagate_light::<C>
+                .after(Internals::<C>::flush_chunk_map_buffers)
+                .before(Internals::<C>::remesh_dirty_chunks),
+        );
+    }
+}
+
+/// The chunks whose light needs to be spread again, the voxels that were edited since the last
+/// update, and the chunks that have been lit since they spawned
+#[derive(Resource)]
+pub(crate) struct LightUpdates<C> {
+    pub pending: HashSet<IVec3>,
+    pub edits: Vec<IVec3>,
+    lit: HashSet<IVec3>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for LightUpdates<C> {
+    fn default() -> Self {
+        Self {
+            pending: HashSet::new(),
+            edits: Vec::new(),
+            lit: HashSet::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// How voxels emit and block light, taken from the registry for registered materials and from
+/// the configuration for others
+struct LightingConfig<I> {
+    emission: Arc<dyn Fn(I) -> u8 + Send + Sync>,
+    colors: Arc<dyn Fn(I) -> [f32; 3] + Send + Sync>,
+    transparent_materials: Arc<dyn Fn(I) -> bool + Send + Sync>,
+    material_shapes: Arc<dyn Fn(I) -> VoxelShape + Send + Sync>,
+}
+
+impl<I: MaterialIndexType> LightingConfig<I> {
+    fn new<C: WorldConfig<MaterialIndex = I>>(
+        configuration: &C,
+        registry: &VoxelMaterialRegistry<C>,
+    ) -> Self {
+        let materials = registry.materials();
+        let (registered, fallback) = (materials.clone(), configuration.light_emission());
+        let emission = Arc::new(move |mt| match registered.get(&mt) {
+            Some(info) => info.light_emission,
+            None => fallback(mt),
+        });
+        let (registered, fallback) = (materials.clone(), configuration.light_color());
+        let colors = Arc::new(move |mt| {
+            let color = match registered.get(&mt) {
+                Some(info) => info.light_color,
+                None => fallback(mt),
+            };
+            color.map(|channel| channel as f32 / 255.0)
+        });
+        let (registered, fallback) = (materials, configuration.transparent_materials());
+        let transparent_materials = Arc::new(move |mt| match registered.get(&mt) {
+            Some(info) => info.transparent,
+            None => fallback(mt),
+        });
+        Self {
+            emission,
+            colors,
+            transparent_materials,
+            material_shapes: configuration.material_shapes(),
+        }
+    }
+
+    /// The level of light a voxel emits in one channel, tinted by its light color
+    fn emission(&self, channel: LightChannel, voxel: WorldVoxel<I>) -> u8 {
+        let (Some(mt), Some(color_index)) = (voxel.material(), channel.color_index()) else {
+            return 0;
+        };
+        let emission = (self.emission)(mt).min(MAX_LIGHT_LEVEL);
+        if emission == 0 {
+            return 0;
+        }
+        (emission as f32 * (self.colors)(mt)[color_index]).round() as u8
+    }
+
+    /// The level of light that makes it into a voxel, zero if the voxel blocks it. Block light is
+    /// filtered by the light color of the material it passes through, like stained glass.
+    fn transmit(&self, channel: LightChannel, voxel: WorldVoxel<I>, level: u8) -> u8 {
+        if self.blocks_light(voxel) {
+            return 0;
+        }
+        match (voxel.material(), channel.color_index()) {
+            (Some(mt), Some(color_index)) => {
+                (level as f32 * (self.colors)(mt)[color_index]).floor() as u8
+            }
+            _ => level,
+        }
+    }
+
+    /// Light passes through air, transparent materials, fluids and voxels that are not cubes
+    fn blocks_light(&self, voxel: WorldVoxel<I>) -> bool {
+        match voxel {
+            WorldVoxel::Colored(_) => true,
+            WorldVoxel::Solid(mt) | WorldVoxel::Oriented(mt, _) | WorldVoxel::Faced(mt, _, _) => {
+                !(self.transparent_materials)(mt) && (self.material_shapes)(mt).is_cube()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The chunk of a voxel, and the index of the voxel within the unpadded chunk
+fn chunk_index(position: IVec3) -> (IVec3, usize) {
+    let chunk_position = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+    let local = position - chunk_position * CHUNK_SIZE_I;
+    let index = local.x + local.y * CHUNK_SIZE_I + local.z * CHUNK_SIZE_I * CHUNK_SIZE_I;
+    (chunk_position, index as usize)
+}
+
+/// Lights the chunks that spawned, and updates the light around edited voxels. Chunks whose
+/// light changed are remeshed.
+#[allow(clippy::too_many_arguments)]
+fn propagate_light<C: WorldConfig>(
+    mut commands: Commands,
+    mut light_updates: ResMut<LightUpdates<C>>,
+    mut ev_chunk_will_spawn: EventReader<ChunkWillSpawn<C>>,
+    mut ev_chunk_will_despawn: EventReader<ChunkWillDespawn<C>>,
+    chunk_map: Res<ChunkMap<C>>,
+    modified_voxels: Res<ModifiedVoxels<C>>,
+    material_registry: Res<VoxelMaterialRegistry<C>>,
+    configuration: Res<C>,
+) {
+    let light_updates = &mut *light_updates;
+    for ev in ev_chunk_will_despawn.read() {
+        light_updates.lit.remove(&ev.chunk_key);
+    }
+    // Chunks are spawned again every time they are remeshed, but only need light the first time
+    for ev in ev_chunk_will_spawn.read() {
+        if !light_updates.lit.contains(&ev.chunk_key) {
+            light_updates.pending.insert(ev.chunk_key);
+        }
+    }
+    if light_updates.pending.is_empty() && light_updates.edits.is_empty() {
+        return;
+    }
+
+    let lighting = LightingConfig::new(&*configuration, &material_registry);
+    let map = chunk_map.get_map();
+    let modified_voxels = modified_voxels.read().unwrap();
+    let mut write_lock = map.write().unwrap();
+    let mut changed = HashSet::new();
+
+    let region = light_chunks(
+        light_updates,
+        &mut write_lock,
+        &modified_voxels,
+        &lighting,
+        &mut changed,
+    );
+
+    // Edits in chunks that were just lit are already accounted for
+    let edits: Vec<IVec3> = light_updates
+        .edits
+        .drain(..)
+        .filter(|position| {
+            let (chunk_position, _) = get_chunk_voxel_position(*position);
+            light_updates.lit.contains(&chunk_position) && !region.contains(&chunk_position)
+        })
+        .collect();
+    if !edits.is_empty() {
+        for channel in LightChannel::ALL {
+            relight_edits(
+                channel,
+                &edits,
+                &mut write_lock,
+                &modified_voxels,
+                &lighting,
+                &mut changed,
+            );
+        }
+    }
+
+    for chunk_position in changed {
+        let entity = write_lock.get(&chunk_position).map(|chunk| chunk.entity);
+        if let Some(mut entity) = entity.and_then(|entity| commands.get_entity(entity)) {
+            entity.try_insert(crate::chunk::NeedsRemesh);
+        }
+    }
+}
+
+/// Clears and spreads again the light of the pending chunks, and of their neighbours. Block
+/// light reaches less than a chunk, so the light of an emitter that was removed can't have spread
+/// past the neighbours of its chunk. The light of the chunks around them flows back in over
+/// their borders. Chunks outside of the region whose border light changed are lit again next, so
+/// that sky light that was cut off further up also goes out further down. Returns the chunks
+/// that were lit.
+fn light_chunks<C, I: Copy + Hash + Eq + Send + Sync + 'static>(
+    light_updates: &mut LightUpdates<C>,
+    chunks: &mut ChunkMapData<I>,
+    modified_voxels: &HashMap<IVec3, WorldVoxel<I>>,
+    lighting: &LightingConfig<I>,
+    changed: &mut HashSet<IVec3>,
+) -> HashSet<IVec3> {
+    let mut region = HashSet::new();
+    for chunk_position in light_updates.pending.drain() {
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let neighbour = chunk_position + IVec3::new(x, y, z);
+                    if chunks.contains_key(&neighbour) {
+                        region.insert(neighbour);
+                    }
+                }
+            }
+        }
+    }
+    if region.is_empty() {
+        return region;
+    }
+    light_updates.lit.extend(region.iter().copied());
+
+    // The edited voxels are looked through once, for the emitters in the region
+    let emitters: Vec<_> = modified_voxels
+        .iter()
+        .filter(|(position, voxel)| {
+            region.contains(&get_chunk_voxel_position(**position).0)
+                && LightChannel::ALL
+                    .iter()
+                    .any(|channel| lighting.emission(*channel, **voxel) > 0)
+        })
+        .map(|(position, voxel)| (*position, *voxel))
+        .collect();
+    let [sky, red, green, blue] = LightChannel::ALL.map(|channel| {
+        spread_light(
+            channel,
+            &region,
+            chunks,
+            modified_voxels,
+            &emitters,
+            lighting,
+        )
+    });
+
+    for chunk_position in region.iter() {
+        let Some(chunk_data) = chunks.get_mut(chunk_position) else {
+            continue;
+        };
+        let chunk_position = *chunk_position;
+        let levels = [&sky, &red, &green, &blue].map(|levels| &levels[&chunk_position]);
+        for index in 0..levels[0].len() {
+            let local = IVec3::new(
+                index as i32 % CHUNK_SIZE_I,
+                index as i32 / CHUNK_SIZE_I % CHUNK_SIZE_I,
+                index as i32 / (CHUNK_SIZE_I * CHUNK_SIZE_I),
+            );
+            let padded = (local + 1).as_uvec3();
+            let [sky_level, red_level, green_level, blue_level] =
+                levels.map(|levels| levels[index]);
+            let light = VoxelLight::new_rgb(sky_level, [red_level, green_level, blue_level]);
+            if chunk_data.get_light(padded) == light {
+                continue;
+            }
+            chunk_data.set_light(padded, light);
+
+            changed.insert(chunk_position);
+            for neighbour in border_neighbours(chunk_position, padded) {
+                changed.insert(neighbour);
+                if !region.contains(&neighbour) {
+                    light_updates.pending.insert(neighbour);
+                }
+            }
+        }
+    }
+    light_updates
+        .pending
+        .retain(|chunk_position| chunks.contains_key(chunk_position));
+
+    region
+}
+
+/// The chunks across the borders of a chunk that a voxel in it lies on. Neighbours see the light
+/// of these voxels in their padding.
+fn border_neighbours(chunk_position: IVec3, padded: UVec3) -> impl Iterator<Item = IVec3> {
+    (0..3).filter_map(move |axis| {
+        let mut step = IVec3::ZERO;
+        if padded[axis] == 1 {
+            step[axis] = -1;
+        } else if padded[axis] == CHUNK_SIZE_U {
+            step[axis] = 1;
+        } else {
+            return None;
+        }
+        Some(chunk_position + step)
+    })
+}
+
+/// Updates one channel of light around the edited voxels, in place. The light that came from or
+/// through the old voxels is taken away first, spreading out until it reaches light that comes
+/// from elsewhere, which is then spread back in. Only the voxels whose light depended on the
+/// edits are visited, and only chunks whose light changed are added to `changed`.
+fn relight_edits<I: Copy + Hash + Eq + Send + Sync + 'static>(
+    channel: LightChannel,
+    edits: &[IVec3],
+    chunks: &mut ChunkMapData<I>,
+    modified_voxels: &HashMap<IVec3, WorldVoxel<I>>,
+    lighting: &LightingConfig<I>,
+    changed: &mut HashSet<IVec3>,
+) {
+    let mut removals = VecDeque::new();
+    let mut additions = VecDeque::new();
+
+    // Emitters keep their own light when the light around them is taken away
+    let clear = |chunks: &mut ChunkMapData<I>,
+                 changed: &mut HashSet<IVec3>,
+                 position: IVec3,
+                 additions: &mut VecDeque<IVec3>| {
+        let emission = lighting.emission(channel, voxel_at(chunks, modified_voxels, position));
+        set_light_level(chunks, channel, position, emission, changed);
+        if emission > 0 {
+            additions.push_back(position);
+        }
+    };
+
+    for position in edits {
+        let Some(level) = light_level(chunks, channel, *position) else {
+            continue;
+        };
+        clear(chunks, changed, *position, &mut additions);
+        if level > 0 {
+            removals.push_back((*position, level));
+        }
+        // Light flows back in from around the voxel, if it lets light through
+        additions.extend(DIRECTIONS.map(|direction| *position + direction));
+        let open_sky = light_level(chunks, channel, *position + IVec3::Y).is_none();
+        if channel == LightChannel::Sky
+            && open_sky
+            && !lighting.blocks_light(voxel_at(chunks, modified_voxels, *position))
+        {
+            set_light_level(chunks, channel, *position, MAX_LIGHT_LEVEL, changed);
+            additions.push_back(*position);
+        }
+    }
+
+    while let Some((position, level)) = removals.pop_front() {
+        for direction in DIRECTIONS {
+            let neighbour = position + direction;
+            let Some(neighbour_level) = light_level(chunks, channel, neighbour) else {
+                continue;
+            };
+            if neighbour_level == 0 {
+                continue;
+            }
+            if neighbour_level <= channel.fade(direction, level) {
+                clear(chunks, changed, neighbour, &mut additions);
+                removals.push_back((neighbour, neighbour_level));
+            } else {
+                additions.push_back(neighbour);
+            }
+        }
+    }
+
+    while let Some(position) = additions.pop_front() {
+        let Some(level) = light_level(chunks, channel, position) else {
+            continue;
+        };
+        if level <= 1 {
+            continue;
+        }
+        for direction in DIRECTIONS {
+            let neighbour = position + direction;
+            let Some(neighbour_level) = light_level(chunks, channel, neighbour) else {
+                continue;
+            };
+            let next_level = lighting.transmit(
+                channel,
+                voxel_at(chunks, modified_voxels, neighbour),
+                channel.fade(direction, level),
+            );
+            if neighbour_level >= next_level {
+                continue;
+            }
+            set_light_level(chunks, channel, neighbour, next_level, changed);
+            additions.push_back(neighbour);
+        }
+    }
+}
+
+/// The voxel at a position, with the edits that have not been meshed yet
+fn voxel_at<I: Copy + Hash + PartialEq>(
+    chunks: &ChunkMapData<I>,
+    modified_voxels: &HashMap<IVec3, WorldVoxel<I>>,
+    position: IVec3,
+) -> WorldVoxel<I> {
+    if let Some(voxel) = modified_voxels.get(&position) {
+        return *voxel;
+    }
+    let (chunk_position, voxel_position) = get_chunk_voxel_position(position);
+    chunks
+        .get(&chunk_position)
+        .map_or(WorldVoxel::Unset, |chunk| chunk.get_voxel(voxel_position))
+}
+
+/// The light level of a voxel in one channel, `None` if its chunk is not loaded
+fn light_level<I: Copy + Hash + PartialEq>(
+    chunks: &ChunkMapData<I>,
+    channel: LightChannel,
+    position: IVec3,
+) -> Option<u8> {
+    let (chunk_position, voxel_position) = get_chunk_voxel_position(position);
+    chunks
+        .get(&chunk_position)
+        .map(|chunk| channel.level(chunk.get_light(voxel_position)))
+}
+
+/// Set the light level of a voxel in one channel, and note which chunks see the change
+fn set_light_level<I: Copy + Hash + PartialEq>(
+    chunks: &mut ChunkMapData<I>,
+    channel: LightChannel,
+    position: IVec3,
+    level: u8,
+    changed: &mut HashSet<IVec3>,
+) {
+    let (chunk_position, voxel_position) = get_chunk_voxel_position(position);
+    let Some(chunk) = chunks.get_mut(&chunk_position) else {
+        return;
+    };
+    let light = chunk.get_light(voxel_position);
+    if channel.level(light) == level {
+        return;
+    }
+    chunk.set_light(voxel_position, channel.with_level(light, level));
+    changed.insert(chunk_position);
+    changed.extend(border_neighbours(chunk_position, voxel_position));
+}
+
+/// The channels of `VoxelLight`, which are spread separately
+#[derive(Clone, Copy, PartialEq)]
+enum LightChannel {
+    Sky,
+    Red,
+    Green,
+    Blue,
+}
+
+impl LightChannel {
+    const ALL: [LightChannel; 4] = [
+        LightChannel::Sky,
+        LightChannel::Red,
+        LightChannel::Green,
+        LightChannel::Blue,
+    ];
+
+    /// The index of the channel in `VoxelLight::block_rgb`, `None` for sky light
+    fn color_index(&self) -> Option<usize> {
+        match self {
+            LightChannel::Sky => None,
+            LightChannel::Red => Some(0),
+            LightChannel::Green => Some(1),
+            LightChannel::Blue => Some(2),
+        }
+    }
+
+    fn level(&self, light: VoxelLight) -> u8 {
+        match self.color_index() {
+            Some(color_index) => light.block_rgb()[color_index],
+            None => light.sky(),
+        }
+    }
+
+    fn with_level(&self, light: VoxelLight, level: u8) -> VoxelLight {
+        match self.color_index() {
+            Some(color_index) => {
+                let mut block = light.block_rgb();
+                block[color_index] = level;
+                light.with_block_rgb(block)
+            }
+            None => light.with_sky(level),
+        }
+    }
+
+    /// The level of light after it travels one voxel in the given direction. Sky light at full
+    /// strength goes straight down without fading.
+    fn fade(&self, direction: IVec3, level: u8) -> u8 {
+        match self {
+            LightChannel::Sky if direction == IVec3::NEG_Y && level == MAX_LIGHT_LEVEL => {
+                MAX_LIGHT_LEVEL
+            }
+            _ => level.saturating_sub(1),
+        }
+    }
+}
+
+/// Spreads one channel of light over the voxels of the region. Block light comes from the
+/// emitters in the region, and sky light comes down from above. Chunks with no loaded chunk above
+/// them are taken to be open to the sky, and open columns are fully lit. The light of the voxels
+/// around the region flows in for both channels. `emitters` are the edited voxels in the region
+/// that emit light. Returns the light level of each voxel of each chunk.
+fn spread_light<I: Copy + Hash + Eq + Send + Sync + 'static>(
+    channel: LightChannel,
+    region: &HashSet<IVec3>,
+    chunks: &ChunkMapData<I>,
+    modified_voxels: &HashMap<IVec3, WorldVoxel<I>>,
+    emitters: &[(IVec3, WorldVoxel<I>)],
+    lighting: &LightingConfig<I>,
+) -> HashMap<IVec3, Vec<u8>> {
+    let volume = (CHUNK_SIZE_U * CHUNK_SIZE_U * CHUNK_SIZE_U) as usize;
+    let mut levels: HashMap<IVec3, Vec<u8>> = region
+        .iter()
+        .map(|chunk_position| (*chunk_position, vec![0; volume]))
+        .collect();
+    let mut queue = VecDeque::new();
+
+    if channel != LightChannel::Sky {
+        let mut seed = |levels: &mut HashMap<IVec3, Vec<u8>>, position: IVec3, level: u8| {
+            let (chunk_position, index) = chunk_index(position);
+            if let Some(light) = levels.get_mut(&chunk_position).map(|l| &mut l[index]) {
+                if *light < level {
+                    *light = level;
+                    queue.push_back((position, level));
+                }
+            }
+        };
+
+        // Emitters among the generated voxels, and among the edited ones
+        for chunk_position in region {
+            let chunk = &chunks[chunk_position];
+            let voxels = chunk.voxel_array();
+            for x in 1..=CHUNK_SIZE_U {
+                for y in 1..=CHUNK_SIZE_U {
+                    for z in 1..=CHUNK_SIZE_U {
+                        let padded = UVec3::new(x, y, z);
+                        let voxel = match &voxels {
+                            Some(voxels) => {
+                                voxels[PaddedChunkShape::linearize(padded.to_array()) as usize]
+                            }
+                            None => chunk.get_voxel(padded),
+                        };
+                        let emission = lighting.emission(channel, voxel);
+                        if emission == 0 {
+                            continue;
+                        }
+                        let position = *chunk_position * CHUNK_SIZE_I + padded.as_ivec3() - 1;
+                        if !modified_voxels.contains_key(&position) {
+                            seed(&mut levels, position, emission);
+                        }
+                    }
+                }
+            }
+        }
+        for (position, voxel) in emitters {
+            let emission = lighting.emission(channel, *voxel);
+            if emission > 0 {
+                seed(&mut levels, *position, emission);
+            }
+        }
+    }
+
+    // The light of the voxels around the region flows in
+    for chunk_position in region {
+        for direction in DIRECTIONS {
+            let neighbour = *chunk_position + direction;
+            if region.contains(&neighbour) {
+                continue;
+            }
+            let neighbour_chunk = chunks.get(&neighbour);
+            let open_sky =
+                channel == LightChannel::Sky && direction == IVec3::Y && neighbour_chunk.is_none();
+            let neighbour_light = neighbour_chunk.and_then(|chunk| chunk.light.as_ref());
+            if !open_sky && neighbour_light.is_none() {
+                continue;
+            }
+            let axis = (0..3).find(|axis| direction[*axis] != 0).unwrap();
+            for u in 0..CHUNK_SIZE_I {
+                for v in 0..CHUNK_SIZE_I {
+                    let mut local = IVec3::ZERO;
+                    local[axis] = if direction[axis] > 0 {
+                        CHUNK_SIZE_I
+                    } else {
+                        -1
+                    };
+                    local[(axis + 1) % 3] = u;
+                    local[(axis + 2) % 3] = v;
+                    let position = *chunk_position * CHUNK_SIZE_I + local;
+                    let level = match neighbour_light {
+                        Some(light) => {
+                            let (_, voxel_position) = get_chunk_voxel_position(position);
+                            let index = PaddedChunkShape::linearize(voxel_position.to_array());
+                            channel.level(light[index as usize])
+                        }
+                        None => MAX_LIGHT_LEVEL,
+                    };
+                    if level > 1 {
+                        queue.push_back((position, level));
+                    }
+                }
+            }
+        }
+    }
+
+    while let Some((position, level)) = queue.pop_front() {
+        if level <= 1 {
+            continue;
+        }
+        for direction in DIRECTIONS {
+            let neighbour = position + direction;
+            let (chunk_position, index) = chunk_index(neighbour);
+            let Some(light) = levels.get_mut(&chunk_position).map(|l| &mut l[index]) else {
+                continue;
+            };
+            let next_level = lighting.transmit(
+                channel,
+                voxel_at(chunks, modified_voxels, neighbour),
+                channel.fade(direction, level),
+            );
+            if *light >= next_level {
+                continue;
+            }
+            *light = next_level;
+            queue.push_back((neighbour, next_level));
+        }
+    }
+
+    levels
+}
+
+/// The light of a chunk and of the voxels around it, for baking into the mesh of the chunk.
+/// `None` if neither the chunk nor its neighbours have any light. The light is gathered in
+/// `scratch`, which is handed out when the chunk is lit, and kept for the next chunk otherwise.
+#[cfg(feature = "render")]
+pub(crate) fn padded_light<I: Copy + Hash + PartialEq>(
+    chunk_position: IVec3,
+    chunks: &ChunkMapData<I>,
+    scratch: &mut Option<Arc<LightArray>>,
+) -> Option<Arc<LightArray>> {
+    let own_light = chunks
+        .get(&chunk_position)
+        .and_then(|chunk| chunk.light.as_ref());
+    let mut has_light = own_light.is_some();
+    let light =
+        Arc::make_mut(scratch.get_or_insert_with(|| {
+            Arc::new([VoxelLight::default(); PaddedChunkShape::SIZE as usize])
+        }));
+    match own_light {
+        Some(own_light) => light.copy_from_slice(&own_light[..]),
+        None => light.fill(VoxelLight::default()),
+    }
+
+    for i in 0..PaddedChunkShape::SIZE {
+        let padded = PaddedChunkShape::delinearize(i);
+        if !padded.iter().any(|c| *c == 0 || *c == CHUNK_SIZE_U + 1) {
+            continue;
+        }
+        let position = chunk_position * CHUNK_SIZE_I + UVec3::from_array(padded).as_ivec3() - 1;
+        let (neighbour, voxel_position) = get_chunk_voxel_position(position);
+        if let Some(neighbour_light) = chunks
+            .get(&neighbour)
+            .and_then(|chunk| chunk.light.as_ref())
+        {
+            light[i as usize] =
+                neighbour_light[PaddedChunkShape::linearize(voxel_position.to_array()) as usize];
+            has_light = true;
+        }
+    }
+
+    if has_light {
+        scratch.take()
+    } else {
+        None
+    }
+}
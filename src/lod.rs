@@ -0,0 +1,393 @@
+// Levels of detail are only streamed around cameras, which headless worlds don't have
+#![cfg_attr(not(feature = "render"), allow(dead_code))]
+///
+/// Level of detail
+/// This module decides which level of detail each part of the world is spawned at, when
+/// `VoxelWorldConfig::lod_distances` is configured.
+///
+use std::marker::PhantomData;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    chunk::{CHUNK_SIZE_F, CHUNK_SIZE_I},
+    configuration::{LodDownsampling, LodSelection, WorldConfig},
+    voxel::WorldVoxel,
+};
+
+/// The rings of detail levels around the camera.
+///
+/// Chunks at level `n` hold voxels that are `2^n` times as large as regular voxels, so a chunk
+/// covers `2^n` times as much space along each axis. The chunk grids of the levels are aligned,
+/// so that every chunk at level `n` covers exactly 8 chunks at level `n - 1`. Starting from the
+/// coarsest level, a chunk is split into its 8 finer chunks when it is closer to the camera than
+/// the distance of the next finer level. This way the levels tile the world without gaps or
+/// overlaps.
+///
+/// With `LodSelection::ScreenSpaceError`, chunks are instead split when their geometric error,
+/// projected to the screen, is too large.
+#[derive(Clone, Debug)]
+pub(crate) struct LodRings {
+    /// The distance, in full detail chunks, at which each level ends
+    distances: Vec<u32>,
+    selection: LodSelection,
+    /// Margin in full detail chunks before a split chunk is merged again, or a spawned chunk
+    /// leaves the rings
+    hysteresis: u32,
+}
+
+impl LodRings {
+    pub fn from_config<C: WorldConfig>(configuration: &C) -> Option<Self> {
+        let distances = configuration.lod_distances();
+        if distances.is_empty() {
+            None
+        } else {
+            Some(Self {
+                distances,
+                selection: configuration.lod_selection(),
+                hysteresis: configuration.lod_hysteresis(),
+            })
+        }
+    }
+
+    /// The coarsest level
+    pub fn max_level(&self) -> u8 {
+        (self.distances.len() - 1) as u8
+    }
+
+    /// The distance in full detail chunks at which the coarsest level ends
+    pub fn max_distance(&self) -> u32 {
+        self.distances[self.max_level() as usize]
+    }
+
+    /// Find the level and chunk position of the chunk that should cover the given world position,
+    /// or `None` if the position is outside of all rings.
+    pub fn chunk_at(&self, world_position: Vec3, view: &LodView) -> Option<(u8, IVec3)> {
+        self.find_chunk(world_position, view, 0)
+    }
+
+    /// True if the chunk at the given level and position is part of the rings, and not split into
+    /// finer chunks. Spawned chunks are kept until they are `hysteresis` chunks outside the rings.
+    pub fn is_wanted(&self, level: u8, chunk_position: IVec3, view: &LodView) -> bool {
+        if level > self.max_level() {
+            return false;
+        }
+
+        let center = (chunk_position.as_vec3() + 0.5) * lod_chunk_size(level);
+        self.find_chunk(center, view, self.hysteresis as i32) == Some((level, chunk_position))
+    }
+
+    /// True if the chunk is within the outermost ring, plus the `hysteresis` margin, whether or
+    /// not it is at the wanted level
+    pub fn is_in_range(&self, level: u8, chunk_position: IVec3, view: &LodView) -> bool {
+        let center = (chunk_position.as_vec3() + 0.5) * lod_chunk_size(level);
+        let max_level = self.max_level();
+        let coarsest_chunk = chunk_position_at_level(center, max_level);
+        self.distance(max_level, coarsest_chunk, view)
+            <= self.max_distance() as i32 + self.hysteresis as i32
+    }
+
+    fn find_chunk(
+        &self,
+        world_position: Vec3,
+        view: &LodView,
+        outer_margin: i32,
+    ) -> Option<(u8, IVec3)> {
+        let mut level = self.max_level();
+        let mut chunk_position = chunk_position_at_level(world_position, level);
+
+        if self.distance(level, chunk_position, view) > self.max_distance() as i32 + outer_margin {
+            return None;
+        }
+
+        while self.is_split(level, chunk_position, view) {
+            level -= 1;
+            chunk_position = chunk_position_at_level(world_position, level);
+        }
+
+        Some((level, chunk_position))
+    }
+
+    /// Chebyshev distance, in full detail chunks, between the closest camera's chunk and the given
+    /// chunk
+    fn distance(&self, level: u8, chunk_position: IVec3, view: &LodView) -> i32 {
+        view.cameras
+            .iter()
+            .map(|camera| {
+                let camera_chunk = chunk_position_at_level(camera.position, level);
+                let dist = (chunk_position - camera_chunk).abs();
+                dist.x.max(dist.y).max(dist.z) << level
+            })
+            .min()
+            .unwrap_or(i32::MAX)
+    }
+
+    fn is_split(&self, level: u8, chunk_position: IVec3, view: &LodView) -> bool {
+        if level == 0 {
+            return false;
+        }
+
+        // Chunks that are already split stay split until the camera has moved `hysteresis`
+        // chunks past the point where they were split
+        let margin = if view.split_chunks.contains_key(&(level, chunk_position)) {
+            self.hysteresis as i32
+        } else {
+            0
+        };
+
+        match self.selection {
+            LodSelection::Distance => {
+                self.distance(level, chunk_position, view)
+                    < self.distances[level as usize - 1] as i32 + margin
+            }
+            LodSelection::ScreenSpaceError(max_error) => {
+                // Chunks that haven't been generated yet have no known error, and are not split
+                let Some(error) = view.geometric_errors.get(&(level, chunk_position)) else {
+                    return false;
+                };
+                let margin = margin as f32 * CHUNK_SIZE_F;
+                view.screen_space_error(*error, level, chunk_position, margin) > max_error
+            }
+        }
+    }
+}
+
+/// What the level of detail is selected for: the cameras, and the state of the spawned chunks.
+///
+/// With several cameras, each chunk gets the finest level that any of them needs, so the levels
+/// still tile the world without overlaps.
+pub(crate) struct LodView<'a> {
+    pub cameras: Vec<LodCamera>,
+    pub geometric_errors: &'a HashMap<(u8, IVec3), f32>,
+    pub split_chunks: &'a HashMap<(u8, IVec3), u32>,
+}
+
+/// A camera that levels of detail are selected for
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct LodCamera {
+    pub position: Vec3,
+    /// Pixels covered by one world unit, one world unit in front of the camera
+    pub pixels_per_unit: f32,
+}
+
+impl<'a> LodView<'a> {
+    #[cfg(feature = "render")]
+    pub fn new<C>(
+        cameras: &[(Entity, &Camera, &GlobalTransform)],
+        lod_chunks: &'a LodChunks<C>,
+    ) -> Self {
+        let cameras = cameras
+            .iter()
+            .map(|(_, camera, cam_gtf)| {
+                let viewport_height = camera.physical_viewport_size().unwrap_or_default().y as f32;
+                LodCamera {
+                    position: cam_gtf.translation(),
+                    pixels_per_unit: viewport_height * camera.clip_from_view().y_axis.y * 0.5,
+                }
+            })
+            .collect();
+        Self {
+            cameras,
+            geometric_errors: &lod_chunks.geometric_errors,
+            split_chunks: &lod_chunks.split,
+        }
+    }
+
+    /// The largest size in pixels of a geometric error in the given chunk, over all cameras, as
+    /// if the cameras were `margin` world units closer
+    fn screen_space_error(&self, error: f32, level: u8, chunk_position: IVec3, margin: f32) -> f32 {
+        let min = lod_chunk_origin(chunk_position, level).as_vec3();
+        let max = min + lod_chunk_size(level);
+        self.cameras
+            .iter()
+            .map(|camera| {
+                let closest_point = camera.position.clamp(min, max);
+                let distance = (camera.position.distance(closest_point) - margin).max(1.0);
+                error * camera.pixels_per_unit / distance
+            })
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Estimate how much a chunk at the given level differs from the full detail voxels it replaces,
+/// in world units. Voxels of coarser levels can be off by up to their size, but on flat terrain
+/// this is barely visible, so the error grows with the height differences of the surface.
+pub(crate) fn geometric_error(surface_heights: &[i32], level: u8) -> f32 {
+    let size = CHUNK_SIZE_I as usize;
+    let mut height_differences = 0;
+    let mut count = 0;
+    for x in 0..size {
+        for z in 0..size {
+            let height = surface_heights[x * size + z];
+            if x + 1 < size {
+                height_differences += (height - surface_heights[(x + 1) * size + z]).abs();
+                count += 1;
+            }
+            if z + 1 < size {
+                height_differences += (height - surface_heights[x * size + z + 1]).abs();
+                count += 1;
+            }
+        }
+    }
+    let mean_height_difference = height_differences as f32 / count as f32;
+    lod_scale(level) as f32 * (0.1 + mean_height_difference)
+}
+
+/// Combine the samples of a voxel above full detail into one voxel
+pub(crate) fn downsample<I: Copy + PartialEq>(
+    samples: &[WorldVoxel<I>],
+    downsampling: LodDownsampling,
+) -> WorldVoxel<I> {
+    let mut counts: Vec<(WorldVoxel<I>, usize)> = Vec::with_capacity(samples.len());
+    for sample in samples {
+        match counts.iter_mut().find(|(voxel, _)| voxel == sample) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((*sample, 1)),
+        }
+    }
+
+    let most_common = |solid_only: bool| {
+        counts
+            .iter()
+            .filter(|(voxel, _)| !solid_only || voxel.is_solid())
+            .max_by_key(|(voxel, count)| (*count, voxel.is_solid()))
+            .map(|(voxel, _)| *voxel)
+    };
+
+    match downsampling {
+        LodDownsampling::Max => most_common(true).or_else(|| most_common(false)),
+        LodDownsampling::Majority | LodDownsampling::Point => most_common(false),
+    }
+    .unwrap_or(WorldVoxel::Unset)
+}
+
+/// The size in world units of a chunk at the given level
+#[inline]
+pub(crate) fn lod_chunk_size(level: u8) -> f32 {
+    CHUNK_SIZE_F * lod_scale(level) as f32
+}
+
+/// The size of a voxel at the given level, in full detail voxels
+#[inline]
+pub(crate) fn lod_scale(level: u8) -> i32 {
+    1 << level
+}
+
+/// The position of the chunk at the given level that contains the world position
+#[inline]
+pub(crate) fn chunk_position_at_level(world_position: Vec3, level: u8) -> IVec3 {
+    (world_position / lod_chunk_size(level)).floor().as_ivec3()
+}
+
+/// The first voxel of a chunk at the given level, in world voxel coordinates
+#[inline]
+pub(crate) fn lod_chunk_origin(chunk_position: IVec3, level: u8) -> IVec3 {
+    chunk_position * CHUNK_SIZE_I * lod_scale(level)
+}
+
+/// The number of dithering steps used for cross-fading between levels of detail
+pub(crate) const LOD_FADE_STEPS: usize = 8;
+
+/// True if chunks should cross-fade when they are replaced by a different level of detail
+pub(crate) fn lod_fade_enabled<C: WorldConfig>(configuration: &C) -> bool {
+    !configuration.lod_distances().is_empty() && configuration.lod_fade_duration() > 0.0
+}
+
+/// Tags retired chunks that are replaced by chunks at a different level of detail, rather than
+/// leaving the rings. Only these chunks cross-fade with their replacements.
+#[derive(Component)]
+pub(crate) struct LodReplaced;
+
+/// A chunk that is dithering in after spawning, or dithering out after it was retired
+#[derive(Component)]
+pub(crate) struct LodFade<C> {
+    /// From 0.0 to 1.0 over `VoxelWorldConfig::lod_fade_duration`
+    pub progress: f32,
+    pub fading_out: bool,
+    /// Time a retired chunk has waited for the chunks that replace it
+    pub waited: f32,
+    /// The level and position of the chunk
+    pub chunk: (u8, IVec3),
+    _marker: PhantomData<C>,
+}
+
+impl<C> LodFade<C> {
+    pub fn fade_in(level: u8, chunk_position: IVec3) -> Self {
+        Self {
+            progress: 0.0,
+            fading_out: false,
+            waited: 0.0,
+            chunk: (level, chunk_position),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn fade_out(level: u8, chunk_position: IVec3) -> Self {
+        Self {
+            fading_out: true,
+            ..Self::fade_in(level, chunk_position)
+        }
+    }
+}
+
+/// True if two chunks, at the same or different levels, cover any of the same space. The chunk
+/// grids of the levels are aligned, so this is the case when the coarser chunk contains the finer.
+pub(crate) fn lod_chunks_overlap(
+    (level_a, position_a): (u8, IVec3),
+    (level_b, position_b): (u8, IVec3),
+) -> bool {
+    let level = level_a.max(level_b);
+    position_a >> (level - level_a) as i32 == position_b >> (level - level_b) as i32
+}
+
+/// Holds the spawned chunks above full detail. Full detail chunks are kept in the `ChunkMap`.
+#[derive(Resource)]
+pub(crate) struct LodChunks<C> {
+    pub spawned: HashMap<(u8, IVec3), Entity>,
+    /// Chunks that are split into finer chunks, with the number of spawned chunks they cover
+    pub split: HashMap<(u8, IVec3), u32>,
+    /// The geometric error of every chunk above full detail that has been generated. These are
+    /// kept after the chunks despawn, so that the selected levels don't flip back and forth, and
+    /// forgotten when the chunks leave the rings.
+    pub geometric_errors: HashMap<(u8, IVec3), f32>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for LodChunks<C> {
+    fn default() -> Self {
+        Self {
+            spawned: HashMap::new(),
+            split: HashMap::new(),
+            geometric_errors: HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> LodChunks<C> {
+    /// Track a spawned chunk. Full detail chunks are only counted towards the coarser chunks
+    /// covering them, since they are kept in the `ChunkMap`.
+    pub fn insert(&mut self, level: u8, chunk_position: IVec3, entity: Entity, max_level: u8) {
+        if level > 0 {
+            self.spawned.insert((level, chunk_position), entity);
+        }
+        for parent_level in level + 1..=max_level {
+            let parent = chunk_position >> (parent_level - level) as i32;
+            *self.split.entry((parent_level, parent)).or_default() += 1;
+        }
+    }
+
+    /// Stop tracking a despawned chunk
+    pub fn remove(&mut self, level: u8, chunk_position: IVec3, max_level: u8) {
+        self.spawned.remove(&(level, chunk_position));
+        for parent_level in level + 1..=max_level {
+            let parent = chunk_position >> (parent_level - level) as i32;
+            if let Some(count) = self.split.get_mut(&(parent_level, parent)) {
+                *count -= 1;
+                if *count == 0 {
+                    self.split.remove(&(parent_level, parent));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+///
+/// Material registry
+/// Describes each voxel material in one place: its texture layers, tint and physical properties.
+/// The mesher takes the texture layers, tint, transparency and fluidity of registered materials
+/// from here, and falls back to the `VoxelWorldConfig` for the others.
+///
+use bevy::{prelude::*, utils::HashMap};
+use std::{marker::PhantomData, sync::Arc};
+
+use crate::configuration::WorldConfig;
+
+/// The properties of a voxel material
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelMaterialInfo {
+    /// Indexes into the array texture for the top, sides and bottom of the voxel, like the values
+    /// of `VoxelWorldConfig::texture_index_mapper`
+    pub texture_layers: [u32; 3],
+    /// Multiplied with the texture, through the vertex color
+    #[cfg(feature = "render")]
+    pub color: Color,
+    /// Rendered in the transparent pass, see `VoxelWorldConfig::transparent_materials`
+    pub transparent: bool,
+    /// Meshed as a fluid surface, see `VoxelWorldConfig::fluid_materials`
+    pub fluid: bool,
+    /// Sways in the wind, see `VoxelWorldConfig::wind_materials`
+    pub sways_in_wind: bool,
+    /// Nothing collides with it, see `VoxelWorldConfig::render_only_materials`
+    pub render_only: bool,
+    /// Not rendered, see `VoxelWorldConfig::collide_only_materials`
+    pub collide_only: bool,
+    /// The roughness of the material in the built-in material, or `None` to use that of
+    /// `VoxelWorldConfig::voxel_base_material`
+    pub perceptual_roughness: Option<f32>,
+    /// Like `perceptual_roughness`, for the metallic value
+    pub metallic: Option<f32>,
+    /// Like `perceptual_roughness`, for the reflectance
+    pub reflectance: Option<f32>,
+    /// The light level the material emits, see `VoxelWorldConfig::light_emission`
+    pub light_emission: u8,
+    /// The sRGB color of the light the material emits and lets through, see
+    /// `VoxelWorldConfig::light_color`
+    pub light_color: [u8; 3],
+    pub friction: f32,
+    pub restitution: f32,
+    /// How hard the voxel is to break, for games to scale digging time by
+    pub hardness: f32,
+}
+
+impl Default for VoxelMaterialInfo {
+    fn default() -> Self {
+        Self {
+            texture_layers: [0; 3],
+            #[cfg(feature = "render")]
+            color: Color::WHITE,
+            transparent: false,
+            fluid: false,
+            sways_in_wind: false,
+            render_only: false,
+            collide_only: false,
+            perceptual_roughness: None,
+            metallic: None,
+            reflectance: None,
+            light_emission: 0,
+            light_color: [255; 3],
+            friction: 0.6,
+            restitution: 0.0,
+            hardness: 1.0,
+        }
+    }
+}
+
+impl VoxelMaterialInfo {
+    /// A material with the same texture layer on all sides
+    pub fn from_texture_layer(layer: u32) -> Self {
+        Self {
+            texture_layers: [layer; 3],
+            ..default()
+        }
+    }
+}
+
+/// The registered materials of the world with config `C`. Chunks are remeshed when the registry
+/// changes.
+#[derive(Resource)]
+pub struct VoxelMaterialRegistry<C: WorldConfig> {
+    /// Shared with the meshing tasks, and copied on write
+    materials: Arc<HashMap<C::MaterialIndex, VoxelMaterialInfo>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C: WorldConfig> Default for VoxelMaterialRegistry<C> {
+    fn default() -> Self {
+        Self {
+            materials: Arc::new(HashMap::new()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: WorldConfig> VoxelMaterialRegistry<C> {
+    /// Register a material, returning the properties it was registered with before
+    pub fn insert(
+        &mut self,
+        material: C::MaterialIndex,
+        info: VoxelMaterialInfo,
+    ) -> Option<VoxelMaterialInfo> {
+        Arc::make_mut(&mut self.materials).insert(material, info)
+    }
+
+    pub fn remove(&mut self, material: C::MaterialIndex) -> Option<VoxelMaterialInfo> {
+        Arc::make_mut(&mut self.materials).remove(&material)
+    }
+
+    pub fn get(&self, material: C::MaterialIndex) -> Option<&VoxelMaterialInfo> {
+        self.materials.get(&material)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (C::MaterialIndex, &VoxelMaterialInfo)> {
+        self.materials
+            .iter()
+            .map(|(material, info)| (*material, info))
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+
+    /// The registered materials, for the meshing tasks
+    pub(crate) fn materials(&self) -> Arc<HashMap<C::MaterialIndex, VoxelMaterialInfo>> {
+        self.materials.clone()
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{any::Any, cell::Cell, sync::Arc};
 
 use block_mesh::{
     visible_block_faces, OrientedBlockFace, UnitQuadBuffer, Voxel, VoxelVisibility,
@@ -16,24 +16,266 @@ use bevy::{
 use ndshape::ConstShape;
 
 use crate::{
-    chunk::{PaddedChunkShape, CHUNK_SIZE_U},
-    voxel::WorldVoxel,
-    voxel_material::ATTRIBUTE_TEX_INDEX,
+    chunk::{biome_index, BiomeArray, PaddedChunkShape, CHUNK_SIZE_U, PADDED_CHUNK_SIZE},
+    configuration::{MaterialIndexType, WorldConfig},
+    light::{LightArray, MAX_LIGHT_LEVEL},
+    material_registry::VoxelMaterialRegistry,
+    voxel::{WorldVoxel, MAX_FLUID_LEVEL},
+    voxel_material::{
+        ATTRIBUTE_BIOME, ATTRIBUTE_LIGHT, ATTRIBUTE_PBR, ATTRIBUTE_TEX_INDEX, ATTRIBUTE_WIND,
+        UNTEXTURED_INDEX,
+    },
+    voxel_shape::{BlockModels, ShapeQuad, VoxelShape},
 };
 
-type VoxelArray = Arc<[WorldVoxel; PaddedChunkShape::SIZE as usize]>;
+type VoxelArray<I> = Arc<[WorldVoxel<I>; PaddedChunkShape::SIZE as usize]>;
+
+/// Maps a material and the biome of the voxel to its texture indexes
+type TextureIndexMapper<I> = Arc<dyn Fn(I, u8) -> [u32; 3] + Send + Sync>;
+
+/// Maps a material and a biome to the linear tint of the material in that biome
+type BiomeTint<I> = Arc<dyn Fn(I, u8) -> [f32; 3] + Send + Sync>;
+
+/// The voxel type used during meshing. Wraps a `WorldVoxel` together with its transparency, so
+/// that `block_mesh` can cull faces between transparent voxels. Fluid voxels are considered empty
+/// by `block_mesh`, since they only get a surface mesh. The same goes for voxels that are not
+/// cube shaped, which are meshed separately.
+#[derive(Clone, Copy, PartialEq)]
+struct MeshingVoxel<I> {
+    voxel: WorldVoxel<I>,
+    transparent: bool,
+    fluid: bool,
+    shape: VoxelShape,
+    /// The linear tint of the voxel's material, see `VoxelMaterialInfo::color`
+    color: [f32; 3],
+    /// Sways in the wind, see `VoxelWorldConfig::wind_materials`
+    wind: bool,
+    /// The roughness, metallic and reflectance of the material, see `ATTRIBUTE_PBR`
+    pbr: [f32; 3],
+    /// The biome of the voxel's column, see `VoxelWorldConfig::biome_lookup_delegate`
+    biome: u8,
+    /// The sky light and red, green and blue block light in the voxel, from 0 to 1, see
+    /// `ATTRIBUTE_LIGHT`
+    light: [f32; 4],
+}
+
+impl<I: Copy + PartialEq> Voxel for MeshingVoxel<I> {
+    fn get_visibility(&self) -> VoxelVisibility {
+        if !self.voxel.is_solid() || self.fluid || !self.shape.is_cube() {
+            VoxelVisibility::Empty
+        } else if self.transparent {
+            VoxelVisibility::Translucent
+        } else {
+            VoxelVisibility::Opaque
+        }
+    }
+}
+
+/// The parts of the world configuration that are used by the mesher. This is cloned into
+/// each meshing task.
+#[derive(Clone)]
+pub(crate) struct MeshingConfig<I> {
+    pub texture_index_mapper: TextureIndexMapper<I>,
+    pub transparent_materials: Arc<dyn Fn(I) -> bool + Send + Sync>,
+    pub fluid_materials: Arc<dyn Fn(I) -> bool + Send + Sync>,
+    pub collide_only_materials: Arc<dyn Fn(I) -> bool + Send + Sync>,
+    pub wind_materials: Arc<dyn Fn(I) -> bool + Send + Sync>,
+    pub material_shapes: Arc<dyn Fn(I) -> VoxelShape + Send + Sync>,
+    pub material_colors: Arc<dyn Fn(I) -> [f32; 3] + Send + Sync>,
+    pub material_pbr: Arc<dyn Fn(I) -> [f32; 3] + Send + Sync>,
+    pub biome_tint: Option<BiomeTint<I>>,
+    pub block_models: Arc<BlockModels>,
+    pub smooth_normals: bool,
+    pub generate_tangents: bool,
+    /// Depth of the skirts along chunk borders, zero when skirts are disabled
+    pub skirt_depth: f32,
+}
+
+impl<I: MaterialIndexType> MeshingConfig<I> {
+    pub fn from_config<C: WorldConfig<MaterialIndex = I>>(configuration: &C) -> Self {
+        Self {
+            texture_index_mapper: configuration.biome_texture_index_mapper(),
+            transparent_materials: configuration.transparent_materials(),
+            fluid_materials: configuration.fluid_materials(),
+            collide_only_materials: configuration.collide_only_materials(),
+            wind_materials: configuration.wind_materials(),
+            material_shapes: configuration.material_shapes(),
+            material_colors: Arc::new(|_| [1.0; 3]),
+            material_pbr: Arc::new(|_| [-1.0; 3]),
+            biome_tint: configuration.biome_tint().map(|biome_tint| {
+                Arc::new(move |mt, biome| {
+                    let color = LinearRgba::from(biome_tint(mt, biome));
+                    [color.red, color.green, color.blue]
+                }) as BiomeTint<I>
+            }),
+            block_models: configuration.block_models(),
+            smooth_normals: configuration.smooth_normals(),
+            generate_tangents: configuration.generate_tangents(),
+            // Skirts are only needed where chunks of different levels of detail meet
+            skirt_depth: if configuration.lod_distances().is_empty() {
+                0.0
+            } else {
+                configuration.lod_skirt_depth()
+            },
+        }
+    }
+}
+
+impl<I: Copy> MeshingConfig<I> {
+    /// True if the voxel is an opaque cube, which hides whatever is behind it
+    pub fn is_opaque(&self, voxel: WorldVoxel<I>) -> bool {
+        match voxel {
+            WorldVoxel::Colored(_) => true,
+            WorldVoxel::Solid(mt) | WorldVoxel::Oriented(mt, _) | WorldVoxel::Faced(mt, _, _) => {
+                !(self.transparent_materials)(mt)
+                    && !(self.fluid_materials)(mt)
+                    && !(self.collide_only_materials)(mt)
+                    && (self.material_shapes)(mt).is_cube()
+            }
+            // Partial voxels and fluids don't fill their space
+            _ => false,
+        }
+    }
+}
+
+impl<I: MaterialIndexType> MeshingConfig<I> {
+    /// Take the textures, tints, transparency and fluidity of registered materials from the
+    /// registry, and those of other materials from the configuration
+    pub fn with_material_registry<C: WorldConfig<MaterialIndex = I>>(
+        mut self,
+        registry: &VoxelMaterialRegistry<C>,
+    ) -> Self {
+        if registry.is_empty() {
+            return self;
+        }
+        let materials = registry.materials();
+
+        let (registered, fallback) = (materials.clone(), self.texture_index_mapper);
+        self.texture_index_mapper = Arc::new(move |mt, biome| match registered.get(&mt) {
+            Some(info) => info.texture_layers,
+            None => fallback(mt, biome),
+        });
+        let (registered, fallback) = (materials.clone(), self.transparent_materials);
+        self.transparent_materials = Arc::new(move |mt| match registered.get(&mt) {
+            Some(info) => info.transparent,
+            None => fallback(mt),
+        });
+        let (registered, fallback) = (materials.clone(), self.fluid_materials);
+        self.fluid_materials = Arc::new(move |mt| match registered.get(&mt) {
+            Some(info) => info.fluid,
+            None => fallback(mt),
+        });
+        let (registered, fallback) = (materials.clone(), self.collide_only_materials);
+        self.collide_only_materials = Arc::new(move |mt| match registered.get(&mt) {
+            Some(info) => info.collide_only,
+            None => fallback(mt),
+        });
+        let (registered, fallback) = (materials.clone(), self.wind_materials);
+        self.wind_materials = Arc::new(move |mt| match registered.get(&mt) {
+            Some(info) => info.sways_in_wind,
+            None => fallback(mt),
+        });
+        let registered = materials.clone();
+        self.material_pbr = Arc::new(move |mt| match registered.get(&mt) {
+            Some(info) => [info.perceptual_roughness, info.metallic, info.reflectance]
+                .map(|value| value.unwrap_or(-1.0)),
+            None => [-1.0; 3],
+        });
+        self.material_colors = Arc::new(move |mt| match materials.get(&mt) {
+            Some(info) => {
+                let color = LinearRgba::from(info.color);
+                [color.red, color.green, color.blue]
+            }
+            None => [1.0; 3],
+        });
+        self
+    }
+}
+
+/// The meshes generated for a chunk
+pub(crate) struct ChunkMeshes {
+    /// All opaque faces
+    pub opaque: Mesh,
+    /// Faces of transparent voxels, `None` if the chunk has no visible transparent faces
+    pub transparent: Option<Mesh>,
+    /// The top surface of fluid voxels, `None` if the chunk has no visible fluid surface
+    pub fluid: Option<Mesh>,
+}
+
+thread_local! {
+    /// The meshing voxels of the last chunk meshed on this thread, kept so the next chunk can
+    /// reuse the allocation. Type erased, since each world has its own material index type.
+    static MESHING_VOXELS: Cell<Option<Box<dyn Any>>> = const { Cell::new(None) };
+}
+
+/// Take this thread's meshing voxel buffer, or a new one if there is none of this type
+fn take_meshing_voxels<I: 'static>() -> Vec<MeshingVoxel<I>> {
+    MESHING_VOXELS
+        .take()
+        .and_then(|buffer| buffer.downcast::<Vec<MeshingVoxel<I>>>().ok())
+        .map_or_else(
+            || Vec::with_capacity(PaddedChunkShape::USIZE),
+            |buffer| *buffer,
+        )
+}
+
+/// Give the buffer back to this thread, for the next chunk meshed on it
+fn recycle_meshing_voxels<I: 'static>(mut meshing_voxels: Vec<MeshingVoxel<I>>) {
+    meshing_voxels.clear();
+    MESHING_VOXELS.set(Some(Box::new(meshing_voxels)));
+}
 
-/// Generate a mesh for the given chunks, or None of the chunk is empty
-pub(super) fn generate_chunk_mesh(
-    voxels: VoxelArray,
+/// Generate meshes for the given chunk
+pub(super) fn generate_chunk_mesh<I: Copy + Eq + 'static>(
+    voxels: VoxelArray<I>,
+    biomes: Option<&BiomeArray>,
+    light: Option<&LightArray>,
     _pos: IVec3,
-    texture_index_mapper: Arc<dyn Fn(u8) -> [u32; 3] + Send + Sync>,
-) -> Mesh {
+    meshing_config: &MeshingConfig<I>,
+) -> ChunkMeshes {
+    let transparent_materials = &meshing_config.transparent_materials;
+    let fluid_materials = &meshing_config.fluid_materials;
+    let collide_only_materials = &meshing_config.collide_only_materials;
+    let material_shapes = &meshing_config.material_shapes;
+    let material_colors = &meshing_config.material_colors;
+    let wind_materials = &meshing_config.wind_materials;
+    let material_pbr = &meshing_config.material_pbr;
     let faces = RIGHT_HANDED_Y_UP_CONFIG.faces;
     let mut buffer = UnitQuadBuffer::new();
 
+    let mut meshing_voxels = take_meshing_voxels();
+    meshing_voxels.extend(voxels.iter().enumerate().map(|(i, voxel)| {
+        // Collide only voxels are invisible, so they are meshed like air
+        let voxel = match voxel.material() {
+            Some(mt) if collide_only_materials(mt) => WorldVoxel::Air,
+            _ => *voxel,
+        };
+        MeshingVoxel {
+            voxel,
+            transparent: voxel.material().is_some_and(|mt| transparent_materials(mt)),
+            fluid: voxel.fluid_level().is_some()
+                || voxel.material().is_some_and(|mt| fluid_materials(mt)),
+            shape: voxel
+                .material()
+                .map_or(VoxelShape::Cube, |mt| material_shapes(mt)),
+            color: voxel.material().map_or([1.0; 3], |mt| material_colors(mt)),
+            wind: voxel.material().is_some_and(|mt| wind_materials(mt)),
+            pbr: voxel.material().map_or([-1.0; 3], |mt| material_pbr(mt)),
+            biome: biomes.map_or(0, |biomes| {
+                let [x, _, z] = PaddedChunkShape::delinearize(i as u32);
+                biomes[biome_index(x, z)]
+            }),
+            // Chunks without light are lit by the sky, like they are without lighting
+            light: light.map_or([1.0, 0.0, 0.0, 0.0], |light| {
+                let max = MAX_LIGHT_LEVEL as f32;
+                let [red, green, blue] = light[i].block_rgb();
+                [light[i].sky(), red, green, blue].map(|level| level as f32 / max)
+            }),
+        }
+    }));
+
     visible_block_faces(
-        &*voxels,
+        &meshing_voxels,
         &PaddedChunkShape {},
         [0; 3],
         [CHUNK_SIZE_U + 1; 3],
@@ -41,100 +283,524 @@ pub(super) fn generate_chunk_mesh(
         &mut buffer,
     );
 
-    mesh_from_quads(buffer, faces, voxels, texture_index_mapper)
+    let (mut opaque, mut transparent) = mesh_buffers_from_quads(
+        buffer,
+        faces,
+        &meshing_voxels,
+        &meshing_config.texture_index_mapper,
+        meshing_config.biome_tint.as_ref().zip(biomes),
+    );
+
+    let fluid = fluid_surface_buffers(&meshing_voxels, &meshing_config.texture_index_mapper);
+
+    if meshing_config.smooth_normals {
+        opaque.smooth_normals(&meshing_voxels, VoxelVisibility::Opaque);
+        transparent.smooth_normals(&meshing_voxels, VoxelVisibility::Translucent);
+    }
+
+    shape_buffers(
+        &meshing_voxels,
+        &meshing_config.texture_index_mapper,
+        &meshing_config.block_models,
+        &mut opaque,
+        &mut transparent,
+    );
+
+    if meshing_config.skirt_depth > 0.0 {
+        skirt_buffers(
+            &meshing_voxels,
+            &meshing_config.texture_index_mapper,
+            meshing_config.skirt_depth,
+            &mut opaque,
+        );
+    }
+
+    recycle_meshing_voxels(meshing_voxels);
+
+    let generate_tangents = meshing_config.generate_tangents;
+    let non_empty_mesh = |buffers: MeshBuffers| {
+        if buffers.is_empty() {
+            None
+        } else {
+            Some(buffers.into_mesh(generate_tangents))
+        }
+    };
+
+    ChunkMeshes {
+        opaque: opaque.into_mesh(generate_tangents),
+        transparent: non_empty_mesh(transparent),
+        fluid: non_empty_mesh(fluid),
+    }
+}
+
+/// True if the voxels around the chunk, in its padding, cover all six sides of it with opaque
+/// cubes. Nothing inside the chunk can then be seen from outside of it.
+pub(super) fn is_enclosed<I: Copy>(
+    voxels: &VoxelArray<I>,
+    meshing_config: &MeshingConfig<I>,
+) -> bool {
+    let is_opaque = |position: [u32; 3]| {
+        meshing_config.is_opaque(voxels[PaddedChunkShape::linearize(position) as usize])
+    };
+
+    (0..3).all(|axis| {
+        [0, CHUNK_SIZE_U + 1].into_iter().all(|side| {
+            (1..=CHUNK_SIZE_U).all(|u| {
+                (1..=CHUNK_SIZE_U).all(|v| {
+                    let mut position = [side; 3];
+                    position[(axis + 1) % 3] = u;
+                    position[(axis + 2) % 3] = v;
+                    is_opaque(position)
+                })
+            })
+        })
+    })
+}
+
+/// Add the quads of all voxels that are not cube shaped to the opaque or transparent buffers
+fn shape_buffers<I: Copy + PartialEq>(
+    voxels: &[MeshingVoxel<I>],
+    texture_index_mapper: &TextureIndexMapper<I>,
+    block_models: &BlockModels,
+    opaque: &mut MeshBuffers,
+    transparent: &mut MeshBuffers,
+) {
+    for x in 1..=CHUNK_SIZE_U {
+        for y in 1..=CHUNK_SIZE_U {
+            for z in 1..=CHUNK_SIZE_U {
+                let voxel = voxels[PaddedChunkShape::linearize([x, y, z]) as usize];
+                if voxel.shape.is_cube() || voxel.fluid {
+                    continue;
+                }
+                let Some(mt) = voxel.voxel.material() else {
+                    continue;
+                };
+
+                let buffers = if voxel.transparent {
+                    &mut *transparent
+                } else {
+                    &mut *opaque
+                };
+
+                let offset = Vec3::new(x as f32, y as f32, z as f32);
+                let material_type = texture_index_mapper(mt, voxel.biome);
+                let quads = block_models.faced_shape_quads(
+                    voxel.shape,
+                    voxel.voxel.facing(),
+                    voxel.voxel.rotation(),
+                );
+                for quad in quads {
+                    // Skip quads on the side of the voxel that is covered by an opaque cube
+                    if let Some(side) = quad.boundary_side() {
+                        let neighbour = IVec3::new(x as i32, y as i32, z as i32) + side;
+                        let neighbour = voxels
+                            [PaddedChunkShape::linearize(neighbour.as_uvec3().to_array()) as usize];
+                        if neighbour.get_visibility() == VoxelVisibility::Opaque {
+                            continue;
+                        }
+                    }
+                    buffers.push_shape_quad(
+                        &quad,
+                        offset,
+                        (material_type, voxel.color),
+                        &voxel,
+                        voxel.wind,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Add skirts along the horizontal borders of the chunk, to hide cracks between chunks at
+/// different levels of detail. A skirt is a side face of a surface voxel on the border, that
+/// hangs `depth` voxels down from the top of the voxel. They are only added where the regular
+/// face was culled by the neighbouring chunk's voxels.
+fn skirt_buffers<I: Copy + PartialEq>(
+    voxels: &[MeshingVoxel<I>],
+    texture_index_mapper: &TextureIndexMapper<I>,
+    depth: f32,
+    opaque: &mut MeshBuffers,
+) {
+    let skirt_quads = BlockModels::cuboid(Vec3::new(0.0, 1.0 - depth, 0.0), Vec3::ONE);
+    let sides = [
+        (IVec3::X, skirt_quads[2]),
+        (IVec3::NEG_X, skirt_quads[3]),
+        (IVec3::Z, skirt_quads[4]),
+        (IVec3::NEG_Z, skirt_quads[5]),
+    ];
+    let is_opaque = |position: IVec3| {
+        let voxel = voxels[PaddedChunkShape::linearize(position.as_uvec3().to_array()) as usize];
+        voxel.get_visibility() == VoxelVisibility::Opaque
+    };
+
+    for x in 1..=CHUNK_SIZE_U {
+        for y in 1..=CHUNK_SIZE_U {
+            for z in 1..=CHUNK_SIZE_U {
+                let on_border = x == 1 || x == CHUNK_SIZE_U || z == 1 || z == CHUNK_SIZE_U;
+                let position = IVec3::new(x as i32, y as i32, z as i32);
+                if !on_border || !is_opaque(position) || is_opaque(position + IVec3::Y) {
+                    continue;
+                }
+
+                // Skirts are lit like the top of the voxel, since they hang below the surface
+                let above = voxels[PaddedChunkShape::linearize([x, y + 1, z]) as usize];
+                let voxel = MeshingVoxel {
+                    light: above.light,
+                    ..voxels[PaddedChunkShape::linearize([x, y, z]) as usize]
+                };
+                let (material_type, color) = voxel_appearance(&voxel, texture_index_mapper);
+                let offset = position.as_vec3();
+
+                for (side, quad) in sides.iter() {
+                    let neighbour = position + *side;
+                    let is_border_side = neighbour.x == 0
+                        || neighbour.x == CHUNK_SIZE_U as i32 + 1
+                        || neighbour.z == 0
+                        || neighbour.z == CHUNK_SIZE_U as i32 + 1;
+                    if is_border_side && is_opaque(neighbour) {
+                        opaque.push_shape_quad(quad, offset, (material_type, color), &voxel, false);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The top face of a fluid voxel
+const FLUID_SURFACE_QUAD: ShapeQuad = ShapeQuad {
+    positions: [
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 1.0),
+        Vec3::new(1.0, 1.0, 0.0),
+        Vec3::new(1.0, 1.0, 1.0),
+    ],
+    uvs: [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(0.0, 1.0),
+        Vec2::new(1.0, 0.0),
+        Vec2::new(1.0, 1.0),
+    ],
+    normal: Vec3::Y,
+};
+
+/// The height of the fluid surface within a voxel, or `None` if the voxel is not a fluid.
+/// Fluid materials without a level are full.
+fn fluid_height<I: Copy + PartialEq>(voxel: &MeshingVoxel<I>) -> Option<f32> {
+    if !voxel.fluid {
+        return None;
+    }
+    let level = voxel.voxel.fluid_level().unwrap_or(MAX_FLUID_LEVEL);
+    Some((level + 1) as f32 / (MAX_FLUID_LEVEL + 1) as f32)
+}
+
+/// Generate the surface of fluid voxels. Only the top face of each fluid voxel that isn't
+/// covered by another fluid or opaque voxel is included. Each corner of the surface is at the
+/// average height of the fluid voxels around it, so the surface slopes down where the fluid
+/// flows to lower levels.
+fn fluid_surface_buffers<I: Copy + PartialEq>(
+    voxels: &[MeshingVoxel<I>],
+    texture_index_mapper: &TextureIndexMapper<I>,
+) -> MeshBuffers {
+    let mut buffers = MeshBuffers::default();
+
+    for x in 1..=CHUNK_SIZE_U {
+        for y in 1..=CHUNK_SIZE_U {
+            for z in 1..=CHUNK_SIZE_U {
+                let voxel = voxels[PaddedChunkShape::linearize([x, y, z]) as usize];
+                let Some(mt) = voxel.voxel.material() else {
+                    continue;
+                };
+                if !voxel.fluid {
+                    continue;
+                }
+
+                let above = voxels[PaddedChunkShape::linearize([x, y + 1, z]) as usize];
+                if above.fluid || above.get_visibility() == VoxelVisibility::Opaque {
+                    continue;
+                }
+
+                // Corners next to fluid that continues upwards are raised to the top
+                let corner_height = |corner_x: u32, corner_z: u32| {
+                    let mut sum = 0.0;
+                    let mut count = 0.0;
+                    for column_x in corner_x - 1..=corner_x {
+                        for column_z in corner_z - 1..=corner_z {
+                            let index = |y| PaddedChunkShape::linearize([column_x, y, column_z]);
+                            if voxels[index(y + 1) as usize].fluid {
+                                return 1.0;
+                            }
+                            if let Some(height) = fluid_height(&voxels[index(y) as usize]) {
+                                sum += height;
+                                count += 1.0;
+                            }
+                        }
+                    }
+                    sum / count
+                };
+
+                let mut quad = FLUID_SURFACE_QUAD;
+                for position in quad.positions.iter_mut() {
+                    position.y = corner_height(x + position.x as u32, z + position.z as u32);
+                }
+                let [h00, h01, h10, h11] = quad.positions.map(|p| p.y);
+                quad.normal = Vec3::new(
+                    (h00 + h01 - h10 - h11) / 2.0,
+                    1.0,
+                    (h00 + h10 - h01 - h11) / 2.0,
+                )
+                .normalize();
+
+                let offset = Vec3::new(x as f32, y as f32, z as f32);
+                buffers.push_shape_quad(
+                    &quad,
+                    offset,
+                    (texture_index_mapper(mt, voxel.biome), voxel.color),
+                    &voxel,
+                    false,
+                );
+            }
+        }
+    }
+
+    buffers
+}
+
+/// Vertex data for one of the meshes generated for a chunk
+#[derive(Default)]
+struct MeshBuffers {
+    indices: Vec<u32>,
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    tex_coords: Vec<[f32; 2]>,
+    material_types: Vec<[u32; 3]>,
+    colors: Vec<[f32; 4]>,
+    biomes: Vec<u32>,
+    /// How much each vertex sways in the wind, see `ATTRIBUTE_WIND`
+    winds: Vec<f32>,
+    pbr: Vec<[f32; 3]>,
+    lights: Vec<[f32; 4]>,
+}
+
+impl MeshBuffers {
+    fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Add a quad of a voxel, with the texture indexes and color it is drawn with
+    fn push_shape_quad<I>(
+        &mut self,
+        quad: &ShapeQuad,
+        offset: Vec3,
+        (material_type, color): ([u32; 3], [f32; 3]),
+        voxel: &MeshingVoxel<I>,
+        wind: bool,
+    ) {
+        let first_index = self.positions.len() as u32;
+        self.indices
+            .extend([0, 1, 2, 2, 1, 3].map(|i| first_index + i));
+        self.positions
+            .extend(quad.positions.map(|p| (p + offset).to_array()));
+        self.normals.extend([quad.normal.to_array(); 4]);
+        self.tex_coords.extend(quad.uvs.map(|uv| uv.to_array()));
+        self.material_types.extend([material_type; 4]);
+        let [r, g, b] = color;
+        self.colors.extend([[r, g, b, 1.0]; 4]);
+        self.biomes.extend([voxel.biome as u32; 4]);
+        self.pbr.extend([voxel.pbr; 4]);
+        self.lights.extend([voxel.light; 4]);
+        // The bottom of a shape stays in place, and its top sways the most
+        self.winds.extend(
+            quad.positions
+                .map(|p| if wind { p.y.clamp(0.0, 1.0) } else { 0.0 }),
+        );
+    }
+
+    /// Replace the flat per-face normals with the average normal of all cube faces sharing
+    /// each vertex position. The faces are found from the voxels around each vertex, rather
+    /// than from this mesh, so that the faces in the padding meshed by neighbouring chunks are
+    /// counted too, and both chunks get the same normals along their shared border.
+    fn smooth_normals<I: Copy + PartialEq>(
+        &mut self,
+        voxels: &[MeshingVoxel<I>],
+        visibility: VoxelVisibility,
+    ) {
+        for (position, normal) in self.positions.iter().zip(self.normals.iter_mut()) {
+            let corner = Vec3::from(*position).round().as_ivec3();
+            *normal = corner_normal(corner, voxels, visibility)
+                .try_normalize()
+                .unwrap_or(Vec3::from(*normal))
+                .to_array();
+        }
+    }
+
+    fn into_mesh(self, generate_tangents: bool) -> Mesh {
+        let is_empty = self.is_empty();
+        let mut render_mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        );
+
+        render_mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            VertexAttributeValues::Float32x3(self.positions),
+        );
+        render_mesh.insert_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            VertexAttributeValues::Float32x3(self.normals),
+        );
+        render_mesh.insert_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            VertexAttributeValues::Float32x2(self.tex_coords),
+        );
+        render_mesh.insert_attribute(
+            ATTRIBUTE_TEX_INDEX,
+            VertexAttributeValues::Uint32x3(self.material_types),
+        );
+        render_mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        render_mesh.insert_attribute(ATTRIBUTE_BIOME, VertexAttributeValues::Uint32(self.biomes));
+        render_mesh.insert_attribute(ATTRIBUTE_WIND, VertexAttributeValues::Float32(self.winds));
+        render_mesh.insert_attribute(ATTRIBUTE_PBR, VertexAttributeValues::Float32x3(self.pbr));
+        render_mesh.insert_attribute(
+            ATTRIBUTE_LIGHT,
+            VertexAttributeValues::Float32x4(self.lights),
+        );
+
+        render_mesh.insert_indices(Indices::U32(self.indices));
+
+        if generate_tangents && !is_empty {
+            if let Err(err) = render_mesh.generate_tangents() {
+                warn!("Failed to generate tangents for chunk mesh: {err}");
+            }
+        }
+
+        render_mesh
+    }
 }
 
-/// Convert a QuadBuffer into a Bevy Mesh
-fn mesh_from_quads(
+/// Convert a QuadBuffer into vertex buffers for the opaque and the transparent mesh
+fn mesh_buffers_from_quads<I: Copy + PartialEq>(
     quads: UnitQuadBuffer,
     faces: [OrientedBlockFace; 6],
-    voxels: VoxelArray,
-    texture_index_mapper: Arc<dyn Fn(u8) -> [u32; 3] + Send + Sync>,
-) -> Mesh {
-    let num_indices = quads.num_quads() * 6;
-    let num_vertices = quads.num_quads() * 4;
-
-    let mut indices = Vec::with_capacity(num_indices);
-    let mut positions = Vec::with_capacity(num_vertices);
-    let mut normals = Vec::with_capacity(num_vertices);
-    let mut tex_coords = Vec::with_capacity(num_vertices);
-    let mut material_types = Vec::with_capacity(num_vertices);
-    let mut aos = Vec::with_capacity(num_vertices);
-
-    for (group, face) in quads.groups.into_iter().zip(faces.into_iter()) {
+    voxels: &[MeshingVoxel<I>],
+    texture_index_mapper: &TextureIndexMapper<I>,
+    biome_tint: Option<(&BiomeTint<I>, &BiomeArray)>,
+) -> (MeshBuffers, MeshBuffers) {
+    let mut opaque = MeshBuffers::default();
+    let mut transparent = MeshBuffers::default();
+
+    for (group, face) in quads.groups.into_iter().zip(faces) {
         for quad in group.into_iter() {
+            let voxel_index = PaddedChunkShape::linearize(quad.minimum) as usize;
+            let meshing_voxel = voxels[voxel_index];
+
+            let buffers = if meshing_voxel.transparent {
+                &mut transparent
+            } else {
+                &mut opaque
+            };
+
             let normal = IVec3::from([
                 face.signed_normal().x,
                 face.signed_normal().y,
                 face.signed_normal().z,
             ]);
 
-            let ao = face_aos(&quad.minimum, &normal, &voxels);
-            aos.extend_from_slice(&ao);
+            let ao = face_aos(&quad.minimum, &normal, voxels);
 
             // TODO: Fix AO anisotropy
-            indices.extend_from_slice(&face.quad_mesh_indices(positions.len() as u32));
+            buffers
+                .indices
+                .extend_from_slice(&face.quad_mesh_indices(buffers.positions.len() as u32));
 
-            positions.extend_from_slice(&face.quad_mesh_positions(&quad.into(), 1.0));
+            let positions = face.quad_mesh_positions(&quad.into(), 1.0);
+            buffers.positions.extend_from_slice(&positions);
 
-            normals.extend_from_slice(&face.quad_mesh_normals());
+            buffers.normals.extend_from_slice(&face.quad_mesh_normals());
 
-            tex_coords.extend_from_slice(&face.tex_coords(
+            buffers.tex_coords.extend_from_slice(&face.tex_coords(
                 RIGHT_HANDED_Y_UP_CONFIG.u_flip_face,
                 true,
                 &quad.into(),
             ));
 
-            let voxel_index = PaddedChunkShape::linearize(quad.minimum) as usize;
-            let material_type = match voxels[voxel_index] {
-                WorldVoxel::Solid(mt) => texture_index_mapper(mt),
-                _ => [0, 0, 0],
+            let (material_type, voxel_color) =
+                voxel_appearance(&meshing_voxel, texture_index_mapper);
+            buffers.material_types.extend([material_type; 4]);
+            buffers.biomes.extend([meshing_voxel.biome as u32; 4]);
+            // Cubes sway as a whole, so that neighbouring leaves stay connected
+            let wind = if meshing_voxel.wind { 1.0 } else { 0.0 };
+            buffers.winds.extend([wind; 4]);
+            buffers.pbr.extend([meshing_voxel.pbr; 4]);
+            // A face is lit by the light in front of it, since light doesn't enter opaque voxels
+            let front = (UVec3::from(quad.minimum).as_ivec3() + normal).as_uvec3();
+            let front_light = voxels[PaddedChunkShape::linearize(front.to_array()) as usize].light;
+            buffers.lights.extend([front_light; 4]);
+
+            let tints = match (biome_tint, meshing_voxel.voxel.material()) {
+                (Some((biome_tint, biomes)), Some(mt)) => {
+                    positions.map(|position| blended_biome_tint(biome_tint, biomes, mt, position))
+                }
+                _ => [[1.0; 3]; 4],
             };
-            material_types.extend(std::iter::repeat(material_type).take(4));
+
+            // Apply ambient occlusion values, tinted by the voxel color and biome
+            buffers
+                .colors
+                .extend(ao.iter().zip(tints).map(|(ao, tint)| {
+                    let ao = match *ao {
+                        0 => 0.1,
+                        1 => 0.3,
+                        2 => 0.5,
+                        _ => 1.0,
+                    };
+                    let [r, g, b] = voxel_color;
+                    [r * tint[0] * ao, g * tint[1] * ao, b * tint[2] * ao, 1.0]
+                }));
         }
     }
 
-    let mut render_mesh = Mesh::new(
-        PrimitiveTopology::TriangleList,
-        RenderAssetUsages::default(),
-    );
-
-    render_mesh.insert_attribute(
-        Mesh::ATTRIBUTE_POSITION,
-        VertexAttributeValues::Float32x3(positions.clone()),
-    );
-    render_mesh.insert_attribute(
-        Mesh::ATTRIBUTE_NORMAL,
-        VertexAttributeValues::Float32x3(normals),
-    );
-    render_mesh.insert_attribute(
-        Mesh::ATTRIBUTE_UV_0,
-        VertexAttributeValues::Float32x2(tex_coords),
-    );
-    render_mesh.insert_attribute(
-        ATTRIBUTE_TEX_INDEX,
-        VertexAttributeValues::Uint32x3(material_types),
-    );
+    (opaque, transparent)
+}
 
-    // Apply ambient occlusion values
-    {
-        let colors: Vec<[f32; 4]> = positions
-            .iter()
-            .enumerate()
-            .map(|(i, _)| match aos[i] {
-                0 => [0.1, 0.1, 0.1, 1.0],
-                1 => [0.3, 0.3, 0.3, 1.0],
-                2 => [0.5, 0.5, 0.5, 1.0],
-                3 => [1.0, 1.0, 1.0, 1.0],
-                _ => [1.0, 1.0, 1.0, 1.0],
-            })
-            .collect();
-        render_mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+/// The biome tint of a material at a vertex, averaged over the four columns that share the
+/// vertex, so that the tint fades between biomes. Vertex positions are in padded coordinates.
+fn blended_biome_tint<I: Copy>(
+    biome_tint: &BiomeTint<I>,
+    biomes: &BiomeArray,
+    material: I,
+    [x, _, z]: [f32; 3],
+) -> [f32; 3] {
+    let max = PADDED_CHUNK_SIZE - 1;
+    let mut tint = [0.0; 3];
+    for (dx, dz) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+        let column_x = (x as u32 + dx).saturating_sub(1).min(max);
+        let column_z = (z as u32 + dz).saturating_sub(1).min(max);
+        let column_tint = biome_tint(material, biomes[biome_index(column_x, column_z)]);
+        for (sum, value) in tint.iter_mut().zip(column_tint) {
+            *sum += value / 4.0;
+        }
     }
+    tint
+}
 
-    render_mesh.insert_indices(Indices::U32(indices.clone()));
-
-    render_mesh
+/// The texture indices and vertex color of a voxel
+fn voxel_appearance<I: Copy + PartialEq>(
+    voxel: &MeshingVoxel<I>,
+    texture_index_mapper: &TextureIndexMapper<I>,
+) -> ([u32; 3], [f32; 3]) {
+    match voxel.voxel {
+        WorldVoxel::Solid(mt)
+        | WorldVoxel::Oriented(mt, _)
+        | WorldVoxel::Faced(mt, _, _)
+        | WorldVoxel::Partial(mt, _)
+        | WorldVoxel::Fluid(mt, _) => (texture_index_mapper(mt, voxel.biome), voxel.color),
+        WorldVoxel::Colored([r, g, b]) => {
+            let color = LinearRgba::from(Color::srgb_u8(r, g, b));
+            ([UNTEXTURED_INDEX; 3], [color.red, color.green, color.blue])
+        }
+        _ => ([0, 0, 0], [1.0; 3]),
+    }
 }
 
 fn ao_value(side1: bool, corner: bool, side2: bool) -> u32 {
@@ -146,7 +812,7 @@ fn ao_value(side1: bool, corner: bool, side2: bool) -> u32 {
     }
 }
 
-fn side_aos(neighbours: [WorldVoxel; 8]) -> [u32; 4] {
+fn side_aos<V: Voxel>(neighbours: [V; 8]) -> [u32; 4] {
     let ns = [
         neighbours[0].get_visibility() == VoxelVisibility::Opaque,
         neighbours[1].get_visibility() == VoxelVisibility::Opaque,
@@ -166,7 +832,51 @@ fn side_aos(neighbours: [WorldVoxel; 8]) -> [u32; 4] {
     ]
 }
 
-fn face_aos(voxel_pos: &[u32; 3], face_normal: &IVec3, voxels: &VoxelArray) -> [u32; 4] {
+/// The sum of the normals of the visible faces of cubes with the given visibility that touch
+/// the corner at the given position. The 8 voxels around the corner decide which faces there are,
+/// with the same culling as `visible_block_faces`.
+fn corner_normal<V: Voxel>(corner: IVec3, voxels: &[V], visibility: VoxelVisibility) -> Vec3 {
+    let visibility_at = |position: IVec3| {
+        if position.cmpge(IVec3::ZERO).all()
+            && position.cmplt(IVec3::splat(PADDED_CHUNK_SIZE as i32)).all()
+        {
+            voxels[PaddedChunkShape::linearize(position.as_uvec3().to_array()) as usize]
+                .get_visibility()
+        } else {
+            VoxelVisibility::Empty
+        }
+    };
+    let has_face = |voxel: VoxelVisibility, neighbour: VoxelVisibility| {
+        voxel == visibility
+            && match neighbour {
+                VoxelVisibility::Empty => true,
+                VoxelVisibility::Translucent => voxel == VoxelVisibility::Opaque,
+                VoxelVisibility::Opaque => false,
+            }
+    };
+
+    let mut sum = Vec3::ZERO;
+    for i in 0..8 {
+        let offset = IVec3::new(i & 1, (i >> 1) & 1, (i >> 2) & 1);
+        let voxel = corner - 1 + offset;
+        for axis in [IVec3::X, IVec3::Y, IVec3::Z] {
+            // Each pair of voxels on either side of a plane through the corner is visited once
+            if offset.dot(axis) != 0 {
+                continue;
+            }
+            let (before, after) = (visibility_at(voxel), visibility_at(voxel + axis));
+            if has_face(before, after) {
+                sum += axis.as_vec3();
+            }
+            if has_face(after, before) {
+                sum -= axis.as_vec3();
+            }
+        }
+    }
+    sum
+}
+
+fn face_aos<V: Voxel + Copy>(voxel_pos: &[u32; 3], face_normal: &IVec3, voxels: &[V]) -> [u32; 4] {
     let [x, y, z] = *voxel_pos;
 
     match *face_normal {
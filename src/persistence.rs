@@ -0,0 +1,735 @@
+///
+/// Persistence
+/// Saves modified voxels to a `ChunkStorageProvider`, and loads them back when their chunks
+/// spawn again. Add `VoxelWorldPersistencePlugin` next to `VoxelWorldPlugin` to enable it.
+/// Saved chunks can be compressed with lz4 or zstd, behind the `lz4` and `zstd` features.
+///
+use bevy::{
+    prelude::*,
+    tasks::{IoTaskPool, Task},
+    utils::{HashMap, HashSet},
+};
+use futures_lite::future;
+use std::{io, marker::PhantomData, path::PathBuf, sync::Arc, time::Duration};
+
+use crate::{
+    chunk::{Chunk, ChunkTask, NeedsRemesh, CHUNK_SIZE_I},
+    chunk_map::ChunkMap,
+    chunk_storage::{ChunkStorageProvider, Reader, RegionFileStorage},
+    configuration::{ChunkDecorator, LodDownsampling, VoxelLookupFn, WorldConfig},
+    voxel::WorldVoxel,
+    voxel_shape::{VoxelFacing, VoxelRotation},
+    voxel_world::ChunkWillDespawn,
+    voxel_world_internal::{
+        get_chunk_voxel_position, Internals, ModifiedChunks, ModifiedVoxels, ProtectedRegions,
+        VoxelEdits, VoxelWriteBuffer,
+    },
+    world_metadata::WorldMetadata,
+};
+
+/// Saves the modified voxels of full detail chunks to a storage provider when the chunks
+/// despawn, and loads them back into the world when the chunks spawn again. Only chunks with
+/// voxels that differ from the `voxel_lookup_delegate` take up space. Storage is accessed on the
+/// `IoTaskPool`, and `ChunkLoaded` and `ChunkSaved` are fired when it is done. Materials are stored as
+/// their `u32` value, so `MaterialIndex` must convert back from it.
+pub struct VoxelWorldPersistencePlugin<C> {
+    storage: Arc<dyn ChunkStorageProvider>,
+    codec: ChunkCodec,
+    _marker: PhantomData<C>,
+}
+
+impl<C> VoxelWorldPersistencePlugin<C> {
+    /// Store the chunks in region files in the given directory, see `RegionFileStorage`
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self::with_storage(RegionFileStorage::new(directory))
+    }
+
+    /// Store the chunks with a custom storage provider
+    pub fn with_storage(storage: impl ChunkStorageProvider) -> Self {
+        Self {
+            storage: Arc::new(storage),
+            codec: ChunkCodec::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Compress saved chunks with the given codec. Chunks saved with other codecs can still be
+    /// loaded, as long as their codec's feature is enabled.
+    pub fn with_codec(mut self, codec: ChunkCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+}
+
+impl<C> Plugin for VoxelWorldPersistencePlugin<C>
+where
+    C: WorldConfig,
+    C::MaterialIndex: TryFrom<u32>,
+{
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ChunkStorage::<C>::new(self.storage.clone(), self.codec))
+            .init_resource::<PersistenceTasks<C>>()
+            .add_systems(
+                PreUpdate,
+                (load_spawned_chunks::<C>, finish_chunk_loads::<C>)
+                    .chain()
+                    .after(Internals::<C>::spawn_requested_chunks)
+                    .before(Internals::<C>::remesh_dirty_chunks),
+            )
+            .add_systems(PostStartup, init_world_metadata::<C>)
+            .add_systems(PostUpdate, save_modified_chunks::<C>)
+            .add_systems(Last, save_on_exit::<C>)
+            .add_event::<ChunkLoaded<C>>()
+            .add_event::<ChunkSaved<C>>();
+    }
+}
+
+/// How saved chunks are compressed. Each saved chunk starts with a byte for its codec, followed by
+/// its compressed voxels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChunkCodec {
+    #[default]
+    None,
+    #[cfg(feature = "lz4")]
+    Lz4,
+    /// zstd with a compression level from 1 to 22
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl ChunkCodec {
+    fn id(&self) -> u8 {
+        match self {
+            ChunkCodec::None => 0,
+            #[cfg(feature = "lz4")]
+            ChunkCodec::Lz4 => 1,
+            #[cfg(feature = "zstd")]
+            ChunkCodec::Zstd(_) => 2,
+        }
+    }
+
+    /// The codec byte followed by the compressed bytes
+    pub(crate) fn compress(&self, bytes: &[u8]) -> io::Result<Vec<u8>> {
+        let mut compressed = vec![self.id()];
+        match self {
+            ChunkCodec::None => compressed.extend_from_slice(bytes),
+            #[cfg(feature = "lz4")]
+            ChunkCodec::Lz4 => compressed.extend(lz4_flex::compress_prepend_size(bytes)),
+            #[cfg(feature = "zstd")]
+            ChunkCodec::Zstd(level) => compressed.extend(zstd::encode_all(bytes, *level)?),
+        }
+        Ok(compressed)
+    }
+
+    /// The bytes of a chunk compressed with any codec
+    pub(crate) fn decompress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+        let (&id, compressed) = bytes
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "empty chunk"))?;
+        match id {
+            0 => Ok(compressed.to_vec()),
+            #[cfg(feature = "lz4")]
+            1 => lz4_flex::decompress_size_prepended(compressed)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+            #[cfg(feature = "zstd")]
+            2 => zstd::decode_all(compressed),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("chunk codec {id} is not enabled"),
+            )),
+        }
+    }
+}
+
+/// Fired when the saved voxels of a full detail chunk have been loaded into the world
+#[derive(Event)]
+pub struct ChunkLoaded<C> {
+    pub chunk_key: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkLoaded<C> {
+    pub fn new(chunk_key: IVec3) -> Self {
+        Self {
+            chunk_key,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Fired when the modified voxels of a chunk have been written to the storage
+#[derive(Event)]
+pub struct ChunkSaved<C> {
+    pub chunk_key: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ChunkSaved<C> {
+    pub fn new(chunk_key: IVec3) -> Self {
+        Self {
+            chunk_key,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// The storage provider of a world. Storage errors are logged, and treated as if the chunk had
+/// not been saved.
+#[derive(Resource)]
+pub(crate) struct ChunkStorage<C> {
+    provider: Arc<dyn ChunkStorageProvider>,
+    codec: ChunkCodec,
+    /// Set when the storage holds a world that can't be loaded, which is then left untouched
+    disabled: bool,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Clone for ChunkStorage<C> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: self.provider.clone(),
+            codec: self.codec,
+            disabled: self.disabled,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> ChunkStorage<C>
+where
+    C: WorldConfig,
+    C::MaterialIndex: TryFrom<u32>,
+{
+    pub fn new(provider: Arc<dyn ChunkStorageProvider>, codec: ChunkCodec) -> Self {
+        Self {
+            provider,
+            codec,
+            disabled: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The saved voxels of a chunk, by world position
+    pub fn load_chunk(&self, chunk_position: IVec3) -> Vec<(IVec3, WorldVoxel<C::MaterialIndex>)> {
+        if self.disabled {
+            return Vec::new();
+        }
+        let bytes = match self.provider.load_chunk(chunk_position).and_then(|bytes| {
+            bytes
+                .map(|bytes| ChunkCodec::decompress(&bytes))
+                .transpose()
+        }) {
+            Ok(Some(bytes)) => bytes,
+            Ok(None) => return Vec::new(),
+            Err(error) => {
+                warn!("Failed to load chunk {chunk_position}: {error}");
+                return Vec::new();
+            }
+        };
+        decode_chunk(&bytes)
+            .unwrap_or_else(|| {
+                warn!("Corrupt voxel data for chunk {chunk_position}");
+                Vec::new()
+            })
+            .into_iter()
+            .map(|(local, voxel)| (chunk_position * CHUNK_SIZE_I + local, voxel))
+            .collect()
+    }
+
+    /// The saved voxels of all chunks in the storage, by world position
+    pub fn load_all_chunks(&self) -> Vec<(IVec3, WorldVoxel<C::MaterialIndex>)> {
+        if self.disabled {
+            return Vec::new();
+        }
+        match self.provider.list_chunks() {
+            Ok(chunks) => chunks
+                .into_iter()
+                .flat_map(|chunk_position| self.load_chunk(chunk_position))
+                .collect(),
+            Err(error) => {
+                warn!("Failed to list saved chunks: {error}");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Replace the saved voxels of a chunk. Chunks without voxels are removed from the storage.
+    pub fn store_chunk(
+        &self,
+        chunk_position: IVec3,
+        voxels: &[(IVec3, WorldVoxel<C::MaterialIndex>)],
+    ) {
+        if self.disabled {
+            return;
+        }
+        let result = if voxels.is_empty() {
+            self.provider.remove_chunk(chunk_position)
+        } else {
+            let local = voxels
+                .iter()
+                .map(|(position, voxel)| (*position - chunk_position * CHUNK_SIZE_I, *voxel));
+            self.codec
+                .compress(&encode_chunk(local))
+                .and_then(|bytes| self.provider.save_chunk(chunk_position, bytes))
+        };
+        if let Err(error) = result {
+            warn!("Failed to save chunk {chunk_position}: {error}");
+        }
+    }
+
+    pub fn flush(&self) {
+        if self.disabled {
+            return;
+        }
+        if let Err(error) = self.provider.flush() {
+            warn!("Failed to flush chunk storage: {error}");
+        }
+    }
+}
+
+/// A chunk whose saved voxels are being loaded: its entity, its position and the read
+type ChunkLoad<I> = (Entity, IVec3, Task<VoxelEdits<I>>);
+
+/// The storage reads and writes that are running on the `IoTaskPool`
+#[derive(Resource)]
+pub(crate) struct PersistenceTasks<C: WorldConfig> {
+    /// Chunks whose saved voxels have been loaded, or are being loaded, into the world
+    loaded: HashSet<IVec3>,
+    loading: Vec<ChunkLoad<C::MaterialIndex>>,
+    /// Only one save runs at a time, so that older saves of a chunk can't overwrite newer ones
+    saving: Option<Task<Vec<IVec3>>>,
+    last_autosave: Duration,
+}
+
+impl<C: WorldConfig> Default for PersistenceTasks<C> {
+    fn default() -> Self {
+        Self {
+            loaded: HashSet::new(),
+            loading: Vec::new(),
+            saving: None,
+            last_autosave: Duration::ZERO,
+        }
+    }
+}
+
+/// Save the metadata of the world's configuration. Worlds saved with another chunk size or format
+/// version would be overwritten as chunks are saved, so persistence is turned off for them.
+fn init_world_metadata<C>(mut chunk_storage: ResMut<ChunkStorage<C>>, configuration: Res<C>)
+where
+    C: WorldConfig,
+    C::MaterialIndex: TryFrom<u32>,
+{
+    let metadata = configuration.world_metadata();
+    if let Err(error) = save_world_metadata(&*chunk_storage.provider, &metadata) {
+        error!("Not loading or saving chunks of the world: {error}");
+        chunk_storage.disabled = true;
+    }
+}
+
+/// Save world metadata, unless the storage holds a world that can't be loaded
+pub(crate) fn save_world_metadata(
+    storage: &dyn ChunkStorageProvider,
+    metadata: &WorldMetadata,
+) -> io::Result<()> {
+    if let Some(saved) = WorldMetadata::load(storage)? {
+        saved.check_compatible()?;
+    }
+    metadata.save(storage)
+}
+
+/// Start loading the saved voxels of newly spawned full detail chunks. The chunks are not meshed
+/// until their voxels have been loaded.
+fn load_spawned_chunks<C>(
+    mut commands: Commands,
+    chunks: Query<&Chunk<C>, Added<Chunk<C>>>,
+    mut tasks: ResMut<PersistenceTasks<C>>,
+    chunk_storage: Res<ChunkStorage<C>>,
+) where
+    C: WorldConfig,
+    C::MaterialIndex: TryFrom<u32>,
+{
+    for chunk in chunks.iter() {
+        if chunk.lod_level > 0 || !tasks.loaded.insert(chunk.position) {
+            continue;
+        }
+        let storage = chunk_storage.clone();
+        let chunk_position = chunk.position;
+        let task = IoTaskPool::get().spawn(async move { storage.load_chunk(chunk_position) });
+        tasks.loading.push((chunk.entity, chunk.position, task));
+        commands.entity(chunk.entity).remove::<NeedsRemesh>();
+    }
+}
+
+/// Insert the loaded voxels into the modified voxels, and mesh their chunks. Voxels that were
+/// modified while the chunk was loading are kept.
+fn finish_chunk_loads<C>(
+    mut commands: Commands,
+    chunks: Query<&Chunk<C>>,
+    mut tasks: ResMut<PersistenceTasks<C>>,
+    modified_voxels: Res<ModifiedVoxels<C>>,
+    mut ev_chunk_loaded: EventWriter<ChunkLoaded<C>>,
+) where
+    C: WorldConfig,
+{
+    tasks.loading.retain_mut(|(entity, chunk_position, task)| {
+        let Some(voxels) = future::block_on(future::poll_once(task)) else {
+            return true;
+        };
+        if !voxels.is_empty() {
+            let mut modified_voxels = modified_voxels.write().unwrap();
+            for (position, voxel) in voxels {
+                modified_voxels.entry(position).or_insert(voxel);
+            }
+        }
+        // The chunk may have despawned, and its entity been reused, while it was loading
+        if chunks
+            .get(*entity)
+            .is_ok_and(|chunk| chunk.position == *chunk_position && chunk.lod_level == 0)
+        {
+            commands.entity(*entity).try_insert(NeedsRemesh);
+        }
+        ev_chunk_loaded.send(ChunkLoaded::new(*chunk_position));
+        false
+    });
+}
+
+/// A chunk to save: its position and modified voxels, whether its saved voxels still have to be
+/// merged in, and the generator to compare its voxels against
+type ChunkSave<I> = (
+    IVec3,
+    Vec<(IVec3, WorldVoxel<I>)>,
+    bool,
+    VoxelLookupFn<I>,
+    Option<ChunkDecorator<I>>,
+);
+
+impl<C: WorldConfig> PersistenceTasks<C> {
+    /// Take the modified chunks that pass the filter out of `modified_chunks`, with their voxels
+    fn take_chunk_saves(
+        &mut self,
+        filter: impl Fn(&IVec3) -> bool,
+        modified_chunks: &mut ModifiedChunks<C>,
+        modified_voxels: &ModifiedVoxels<C>,
+        configuration: &C,
+    ) -> Vec<ChunkSave<C::MaterialIndex>> {
+        let mut unsaved: HashMap<IVec3, VoxelEdits<C::MaterialIndex>> = modified_chunks
+            .iter()
+            .filter(|chunk_position| filter(chunk_position))
+            .map(|chunk_position| (*chunk_position, Vec::new()))
+            .collect();
+        if unsaved.is_empty() {
+            return Vec::new();
+        }
+
+        let modified_voxels = modified_voxels.read().unwrap();
+        for (position, voxel) in modified_voxels.iter() {
+            let chunk_position = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+            if let Some(voxels) = unsaved.get_mut(&chunk_position) {
+                voxels.push((*position, *voxel));
+            }
+        }
+
+        let delegate = configuration.voxel_lookup_delegate();
+        let decorator = configuration.chunk_decorator();
+        unsaved
+            .into_iter()
+            .map(|(chunk_position, voxels)| {
+                modified_chunks.remove(&chunk_position);
+                // Chunks that are still loading haven't had their saved voxels merged in yet
+                let unloaded = self.loaded.insert(chunk_position)
+                    || self.loading.iter().any(|(_, p, _)| *p == chunk_position);
+                (
+                    chunk_position,
+                    voxels,
+                    unloaded,
+                    delegate(chunk_position),
+                    decorator.clone(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Store chunks and flush the storage. Only the voxels that differ from the generator and the
+/// chunk decorator are stored, so chunks whose edits have all been reverted are removed from the
+/// storage.
+fn save_chunks<C>(
+    storage: &ChunkStorage<C>,
+    modified_voxels: &ModifiedVoxels<C>,
+    chunks: Vec<ChunkSave<C::MaterialIndex>>,
+) -> Vec<IVec3>
+where
+    C: WorldConfig,
+    C::MaterialIndex: TryFrom<u32>,
+{
+    if storage.disabled {
+        return Vec::new();
+    }
+    let mut saved = Vec::new();
+    for (chunk_position, mut voxels, unloaded, mut lookup, decorator) in chunks {
+        // Chunks edited before they were ever loaded still have their saved voxels in the
+        // storage, which would be lost when the chunk is stored
+        if unloaded {
+            let stored = storage.load_chunk(chunk_position);
+            let mut modified_voxels = modified_voxels.write().unwrap();
+            for (position, voxel) in stored {
+                if !modified_voxels.contains_key(&position) {
+                    modified_voxels.insert(position, voxel);
+                    voxels.push((position, voxel));
+                }
+            }
+        }
+        match decorator {
+            // The decorator works on whole chunks, so the chunk is generated like it is when it
+            // streams in, without any of the edits
+            Some(decorator) => {
+                let mut chunk_task =
+                    ChunkTask::<C>::new(Entity::PLACEHOLDER, chunk_position, default())
+                        .with_decorator(Some(decorator));
+                chunk_task.generate(lookup, LodDownsampling::Point);
+                voxels.retain(|(position, voxel)| {
+                    let (_, vox_pos) = get_chunk_voxel_position(*position);
+                    chunk_task.chunk_data.get_voxel(vox_pos) != *voxel
+                });
+            }
+            None => voxels.retain(|(position, voxel)| lookup(*position, 0) != *voxel),
+        }
+        storage.store_chunk(chunk_position, &voxels);
+        saved.push(chunk_position);
+    }
+    storage.flush();
+    saved
+}
+
+/// Store the modified chunks that have despawned, or that were edited without being loaded. When
+/// `VoxelWorldConfig::autosave_interval` has passed, all modified chunks are stored.
+#[allow(clippy::too_many_arguments)]
+fn save_modified_chunks<C>(
+    mut ev_chunk_will_despawn: EventReader<ChunkWillDespawn<C>>,
+    mut ev_chunk_saved: EventWriter<ChunkSaved<C>>,
+    mut tasks: ResMut<PersistenceTasks<C>>,
+    mut modified_chunks: ResMut<ModifiedChunks<C>>,
+    chunk_storage: Res<ChunkStorage<C>>,
+    modified_voxels: Res<ModifiedVoxels<C>>,
+    chunk_map: Res<ChunkMap<C>>,
+    time: Res<Time<Real>>,
+    configuration: Res<C>,
+) where
+    C: WorldConfig,
+    C::MaterialIndex: TryFrom<u32>,
+{
+    let despawned: HashSet<IVec3> = ev_chunk_will_despawn
+        .read()
+        .filter(|ev| ev.chunk_data.is_some())
+        .map(|ev| ev.chunk_key)
+        .collect();
+
+    // Chunks that despawn while a save is running are picked up by a later save, since they
+    // are no longer in the chunk map
+    if let Some(task) = tasks.saving.as_mut() {
+        let Some(saved) = future::block_on(future::poll_once(task)) else {
+            return;
+        };
+        tasks.saving = None;
+        ev_chunk_saved.send_batch(saved.into_iter().map(ChunkSaved::new));
+    }
+    if modified_chunks.is_empty() {
+        return;
+    }
+
+    let autosave = configuration
+        .autosave_interval()
+        .is_some_and(|interval| time.elapsed() >= tasks.last_autosave + interval);
+    if autosave {
+        tasks.last_autosave = time.elapsed();
+    }
+
+    let read_lock = chunk_map.get_read_lock();
+    let chunks = tasks.take_chunk_saves(
+        |chunk_position| {
+            autosave
+                || despawned.contains(chunk_position)
+                || !ChunkMap::<C>::contains_chunk(chunk_position, &read_lock)
+        },
+        &mut modified_chunks,
+        &modified_voxels,
+        &configuration,
+    );
+    drop(read_lock);
+    if chunks.is_empty() {
+        return;
+    }
+
+    let storage = chunk_storage.clone();
+    let modified_voxels = modified_voxels.clone();
+    let task =
+        IoTaskPool::get().spawn(async move { save_chunks(&storage, &modified_voxels, chunks) });
+    tasks.saving = Some(task);
+}
+
+/// Store all modified chunks when the app exits, see `VoxelWorldConfig::autosave_on_exit`. The
+/// save blocks, so that it finishes before the app does.
+#[allow(clippy::too_many_arguments)]
+fn save_on_exit<C>(
+    mut ev_app_exit: EventReader<AppExit>,
+    mut ev_chunk_saved: EventWriter<ChunkSaved<C>>,
+    mut tasks: ResMut<PersistenceTasks<C>>,
+    mut modified_chunks: ResMut<ModifiedChunks<C>>,
+    chunk_storage: Res<ChunkStorage<C>>,
+    modified_voxels: Res<ModifiedVoxels<C>>,
+    voxel_write_buffer: Res<VoxelWriteBuffer<C>>,
+    protected_regions: Res<ProtectedRegions<C>>,
+    configuration: Res<C>,
+) where
+    C: WorldConfig,
+    C::MaterialIndex: TryFrom<u32>,
+{
+    if ev_app_exit.read().count() == 0 || !configuration.autosave_on_exit() {
+        return;
+    }
+    if let Some(task) = tasks.saving.take() {
+        let saved = future::block_on(task);
+        ev_chunk_saved.send_batch(saved.into_iter().map(ChunkSaved::new));
+    }
+
+    // Edits made this frame haven't been flushed into the world yet, and won't be
+    {
+        let mut voxels = modified_voxels.write().unwrap();
+        for (i, (position, voxel)) in voxel_write_buffer.pending().enumerate() {
+            if voxel_write_buffer.is_restored(i) || !protected_regions.contains(*position) {
+                voxels.insert(*position, *voxel);
+                modified_chunks.insert(position.div_euclid(IVec3::splat(CHUNK_SIZE_I)));
+            }
+        }
+    }
+    let chunks = tasks.take_chunk_saves(
+        |_| true,
+        &mut modified_chunks,
+        &modified_voxels,
+        &configuration,
+    );
+    let saved = save_chunks(&chunk_storage, &modified_voxels, chunks);
+    ev_chunk_saved.send_batch(saved.into_iter().map(ChunkSaved::new));
+}
+
+/// A chunk is the number of voxels, followed by the index of each voxel in the chunk and the
+/// voxel itself
+pub(crate) fn encode_chunk<I: Into<u32> + Copy>(
+    voxels: impl ExactSizeIterator<Item = (IVec3, WorldVoxel<I>)>,
+) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(voxels.len() as u32).to_le_bytes());
+    for (local, voxel) in voxels {
+        let index = local.x + local.y * CHUNK_SIZE_I + local.z * CHUNK_SIZE_I * CHUNK_SIZE_I;
+        bytes.extend_from_slice(&(index as u16).to_le_bytes());
+        encode_voxel(voxel, &mut bytes);
+    }
+    bytes
+}
+
+pub(crate) fn decode_chunk<I: TryFrom<u32>>(bytes: &[u8]) -> Option<Vec<(IVec3, WorldVoxel<I>)>> {
+    let mut reader = Reader(bytes);
+    let count = reader.u32()?;
+    let mut voxels = Vec::new();
+    for _ in 0..count {
+        let index = reader.u16()? as i32;
+        let local = IVec3::new(
+            index % CHUNK_SIZE_I,
+            (index / CHUNK_SIZE_I) % CHUNK_SIZE_I,
+            index / (CHUNK_SIZE_I * CHUNK_SIZE_I),
+        );
+        voxels.push((local, decode_voxel(&mut reader)?));
+    }
+    Some(voxels)
+}
+
+/// Each voxel is a tag for its kind, followed by its fields
+pub(crate) fn encode_voxel<I: Into<u32> + Copy>(voxel: WorldVoxel<I>, bytes: &mut Vec<u8>) {
+    let material = |bytes: &mut Vec<u8>, material: I| {
+        bytes.extend_from_slice(&material.into().to_le_bytes());
+    };
+    match voxel {
+        WorldVoxel::Unset => bytes.push(0),
+        WorldVoxel::Air => bytes.push(1),
+        WorldVoxel::Solid(m) => {
+            bytes.push(2);
+            material(bytes, m);
+        }
+        WorldVoxel::Colored(color) => {
+            bytes.push(3);
+            bytes.extend_from_slice(&color);
+        }
+        WorldVoxel::Oriented(m, rotation) => {
+            bytes.push(4);
+            material(bytes, m);
+            bytes.push(rotation_to_u8(rotation));
+        }
+        WorldVoxel::Faced(m, facing, rotation) => {
+            bytes.push(5);
+            material(bytes, m);
+            bytes.push(facing_to_u8(facing));
+            bytes.push(rotation_to_u8(rotation));
+        }
+        WorldVoxel::Partial(m, density) => {
+            bytes.push(6);
+            material(bytes, m);
+            bytes.push(density);
+        }
+        WorldVoxel::Fluid(m, level) => {
+            bytes.push(7);
+            material(bytes, m);
+            bytes.push(level);
+        }
+    }
+}
+
+pub(crate) fn decode_voxel<I: TryFrom<u32>>(reader: &mut Reader) -> Option<WorldVoxel<I>> {
+    let material = |reader: &mut Reader| I::try_from(reader.u32()?).ok();
+    Some(match reader.u8()? {
+        0 => WorldVoxel::Unset,
+        1 => WorldVoxel::Air,
+        2 => WorldVoxel::Solid(material(reader)?),
+        3 => WorldVoxel::Colored(reader.take(3)?.try_into().ok()?),
+        4 => WorldVoxel::Oriented(material(reader)?, rotation_from_u8(reader.u8()?)?),
+        5 => WorldVoxel::Faced(
+            material(reader)?,
+            facing_from_u8(reader.u8()?)?,
+            rotation_from_u8(reader.u8()?)?,
+        ),
+        6 => WorldVoxel::Partial(material(reader)?, reader.u8()?),
+        7 => WorldVoxel::Fluid(material(reader)?, reader.u8()?),
+        _ => return None,
+    })
+}
+
+const ROTATIONS: [VoxelRotation; 4] = [
+    VoxelRotation::Deg0,
+    VoxelRotation::Deg90,
+    VoxelRotation::Deg180,
+    VoxelRotation::Deg270,
+];
+
+const FACINGS: [VoxelFacing; 6] = [
+    VoxelFacing::Up,
+    VoxelFacing::Down,
+    VoxelFacing::PosX,
+    VoxelFacing::NegX,
+    VoxelFacing::PosZ,
+    VoxelFacing::NegZ,
+];
+
+fn rotation_to_u8(rotation: VoxelRotation) -> u8 {
+    ROTATIONS.iter().position(|r| *r == rotation).unwrap() as u8
+}
+
+fn rotation_from_u8(value: u8) -> Option<VoxelRotation> {
+    ROTATIONS.get(value as usize).copied()
+}
+
+fn facing_to_u8(facing: VoxelFacing) -> u8 {
+    FACINGS.iter().position(|f| *f == facing).unwrap() as u8
+}
+
+fn facing_from_u8(value: u8) -> Option<VoxelFacing> {
+    FACINGS.get(value as usize).copied()
+}
@@ -1,7 +1,12 @@
+use std::marker::PhantomData;
+#[cfg(feature = "render")]
+use std::{hash::Hash, sync::Arc};
+
+use bevy::prelude::*;
+#[cfg(feature = "render")]
 use bevy::{
     asset::load_internal_asset,
-    pbr::ExtendedMaterial,
-    prelude::*,
+    pbr::{ExtendedMaterial, MaterialExtension},
     render::{
         render_asset::RenderAssetUsages,
         texture::{CompressedImageFormats, ImageSampler, ImageType},
@@ -9,43 +14,97 @@ use bevy::{
 };
 
 use crate::{
-    configuration::{DefaultWorld, VoxelWorldConfig},
-    voxel_material::{
-        prepare_texture, LoadingTexture, StandardVoxelMaterial, TextureLayers,
-        VOXEL_TEXTURE_SHADER_HANDLE,
-    },
+    configuration::{DefaultWorld, WorldConfig},
     voxel_world::*,
     voxel_world_internal::Internals,
 };
+#[cfg(feature = "render")]
+use crate::{
+    debug::{
+        debug_overlay_enabled, draw_chunk_debug_overlay, update_chunk_debug_states,
+        VoxelWorldDebugOverlay,
+    },
+    decal::{despawn_orphaned_face_decals, place_face_decals},
+    light::VoxelSkyLight,
+    lod::{lod_fade_enabled, LOD_FADE_STEPS},
+    voxel_material::{
+        prepare_texture, ExtendedVoxelMaterial, LoadingTexture, StandardVoxelMaterial,
+        StandardVoxelMaterialType, TextureLayers, VOXEL_TEXTURE_SHADER_HANDLE,
+    },
+};
 
+/// Holds the material used to render the chunks of the world with config `C`.
+/// Since it is keyed by the world config, each world instance can have its own material.
+#[cfg(feature = "render")]
 #[derive(Resource)]
-pub struct VoxelWorldMaterialHandle<M: Material> {
+pub struct VoxelWorldMaterialHandle<C, M: Material> {
     pub handle: Handle<M>,
+    /// Material used for the transparent part of chunks. Falls back to `handle` if `None`.
+    pub transparent_handle: Option<Handle<M>>,
+    /// Materials used while chunks dither in, from barely to almost fully visible.
+    /// Chunks pop in without fading if this is empty.
+    pub lod_fade_in: Vec<Handle<M>>,
+    /// Materials used while retired chunks dither out, from almost fully to barely visible.
+    pub lod_fade_out: Vec<Handle<M>>,
+    _marker: PhantomData<C>,
+}
+
+#[cfg(feature = "render")]
+impl<C, M: Material> VoxelWorldMaterialHandle<C, M> {
+    pub fn new(handle: Handle<M>) -> Self {
+        Self {
+            handle,
+            transparent_handle: None,
+            lod_fade_in: Vec::new(),
+            lod_fade_out: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Use a separate material for the transparent part of chunks. This material should
+    /// use a blending `AlphaMode`.
+    pub fn with_transparent(mut self, transparent_handle: Handle<M>) -> Self {
+        self.transparent_handle = Some(transparent_handle);
+        self
+    }
+
+    /// Use these materials, in order, to cross-fade chunks when the level of detail changes.
+    pub fn with_lod_fade(mut self, fade_in: Vec<Handle<M>>, fade_out: Vec<Handle<M>>) -> Self {
+        self.lod_fade_in = fade_in;
+        self.lod_fade_out = fade_out;
+        self
+    }
 }
 
 /// The main plugin for the voxel world. This plugin sets up the voxel world and its dependencies.
 /// The type parameter `C` is used to differentiate between different voxel worlds with different configs.
+#[cfg(feature = "render")]
 pub struct VoxelWorldPlugin<C, M = StandardMaterial>
 where
-    C: VoxelWorldConfig,
+    C: WorldConfig,
     M: Material,
 {
     spawn_meshes: bool,
     use_custom_material: bool,
     config: C,
-    material: M,
+    material: Option<M>,
+    material_handle: Option<Handle<M>>,
+    voxel_material: Option<(VoxelMaterialFn<M>, VoxelMaterialMutFn<M>)>,
 }
 
+#[cfg(feature = "render")]
 impl<C> VoxelWorldPlugin<C, StandardMaterial>
 where
-    C: VoxelWorldConfig,
+    C: WorldConfig,
 {
     pub fn with_config(config: C) -> Self {
         Self {
             config,
             spawn_meshes: true,
             use_custom_material: false,
-            material: StandardMaterial::default(),
+            material: None,
+            material_handle: None,
+            voxel_material: None,
         }
     }
 
@@ -54,14 +113,17 @@ where
             spawn_meshes: false,
             use_custom_material: false,
             config: C::default(),
-            material: StandardMaterial::default(),
+            material: None,
+            material_handle: None,
+            voxel_material: None,
         }
     }
 }
 
+#[cfg(feature = "render")]
 impl<C, M> VoxelWorldPlugin<C, M>
 where
-    C: VoxelWorldConfig,
+    C: WorldConfig,
     M: Material,
 {
     /// Use this to tell `bevy_voxel_world` to use a custom material. This can be any material that
@@ -73,57 +135,118 @@ where
     pub fn with_material<CustomMaterial: Material>(
         self,
         material: CustomMaterial,
-    ) -> VoxelWorldPlugin<C, CustomMaterial> {
+    ) -> VoxelWorldPlugin<C, CustomMaterial>
+    where
+        CustomMaterial::Data: PartialEq + Eq + Hash + Clone,
+    {
         VoxelWorldPlugin {
             spawn_meshes: self.spawn_meshes,
             use_custom_material: true,
             config: self.config,
-            material,
+            material: Some(material),
+            material_handle: None,
+            voxel_material: None,
+        }
+    }
+
+    /// Like `with_material`, but uses an existing material handle instead of adding a new
+    /// material asset. This makes it possible to share a material between some world instances,
+    /// or to modify the material later through `Assets<MyCustomVoxelMaterialType>`.
+    pub fn with_material_handle<CustomMaterial: Material>(
+        self,
+        handle: Handle<CustomMaterial>,
+    ) -> VoxelWorldPlugin<C, CustomMaterial>
+    where
+        CustomMaterial::Data: PartialEq + Eq + Hash + Clone,
+    {
+        VoxelWorldPlugin {
+            spawn_meshes: self.spawn_meshes,
+            use_custom_material: true,
+            config: self.config,
+            material: None,
+            material_handle: Some(handle),
+            voxel_material: None,
+        }
+    }
+
+    /// Keep the built-in voxel material, and extend it with a `MaterialExtension` of your own, to
+    /// add shader effects without replacing the whole render path. The voxel texture, the
+    /// transparent material and the level of detail fades work like with the built-in material.
+    /// The extension's bindings must not overlap those of `StandardVoxelMaterial`, which uses
+    /// bindings 100 to 104.
+    ///
+    /// Register `MaterialPlugin::<ExtendedVoxelMaterial<MyExtension>>` before adding this plugin,
+    /// or let the plugin add it.
+    pub fn with_material_extension<E: MaterialExtension>(
+        self,
+        extension: E,
+    ) -> VoxelWorldPlugin<C, ExtendedVoxelMaterial<E>>
+    where
+        E::Data: PartialEq + Eq + Hash + Clone,
+    {
+        VoxelWorldPlugin {
+            spawn_meshes: self.spawn_meshes,
+            use_custom_material: false,
+            config: self.config,
+            material: None,
+            material_handle: None,
+            voxel_material: Some((
+                Arc::new(
+                    move |base: StandardMaterial, voxel: StandardVoxelMaterial| ExtendedMaterial {
+                        base: ExtendedMaterial {
+                            base,
+                            extension: voxel,
+                        },
+                        extension: extension.clone(),
+                    },
+                ),
+                extended_voxel_material::<E> as VoxelMaterialMutFn<_>,
+            )),
         }
     }
 }
 
+#[cfg(feature = "render")]
 impl Default for VoxelWorldPlugin<DefaultWorld, StandardMaterial> {
     fn default() -> Self {
         Self {
             spawn_meshes: true,
             use_custom_material: false,
             config: DefaultWorld,
-            material: StandardMaterial::default(),
+            material: None,
+            material_handle: None,
+            voxel_material: None,
         }
     }
 }
 
+#[cfg(feature = "render")]
 impl<C, M> Plugin for VoxelWorldPlugin<C, M>
 where
-    C: VoxelWorldConfig,
+    C: WorldConfig,
     M: Material,
+    M::Data: PartialEq + Eq + Hash + Clone,
 {
     fn build(&self, app: &mut App) {
-        app.init_resource::<C>()
-            .add_systems(PreStartup, Internals::<C>::setup)
-            .add_systems(
-                PreUpdate,
-                (
-                    (
-                        (Internals::<C>::spawn_chunks, Internals::<C>::retire_chunks).chain(),
-                        Internals::<C>::remesh_dirty_chunks,
-                    )
-                        .chain(),
-                    (
-                        Internals::<C>::flush_voxel_write_buffer,
-                        Internals::<C>::despawn_retired_chunks,
-                        (
-                            Internals::<C>::flush_chunk_map_buffers,
-                            Internals::<C>::flush_mesh_cache_buffers,
-                        ),
-                    )
-                        .chain(),
-                ),
-            )
-            .add_event::<ChunkWillSpawn<C>>()
-            .add_event::<ChunkWillDespawn<C>>()
-            .add_event::<ChunkWillRemesh<C>>();
+        add_world_systems(app, &self.config);
+        app.add_systems(
+            PreUpdate,
+            (
+                Internals::<C>::flush_mesh_cache_buffers
+                    .after(Internals::<C>::despawn_retired_chunks),
+                Internals::<C>::remesh_changed_materials
+                    .before(Internals::<C>::remesh_dirty_chunks),
+            ),
+        );
+        app.add_systems(Update, Internals::<C>::update_shadow_casters);
+
+        app.insert_resource(VoxelWorldDebugOverlay::<C>::new(
+            self.config.debug_draw_chunks(),
+        ))
+        .add_systems(
+            Update,
+            update_chunk_debug_states::<C>.run_if(debug_overlay_enabled::<C>),
+        );
 
         // Spawning of meshes is optional, mainly to simplify testing.
         // This makes voxel_world work with a MinimalPlugins setup.
@@ -135,94 +258,327 @@ where
                 Shader::from_wgsl
             );
 
-            app.add_systems(Update, Internals::<C>::spawn_meshes);
+            app.add_systems(
+                Update,
+                (
+                    place_face_decals::<C>,
+                    despawn_orphaned_face_decals::<C>,
+                    Internals::<C>::spawn_meshes,
+                    draw_chunk_debug_overlay::<C>
+                        .after(update_chunk_debug_states::<C>)
+                        .run_if(debug_overlay_enabled::<C>),
+                ),
+            );
         }
 
         if !self.use_custom_material && self.spawn_meshes {
-            let mat_plugins = app.get_added_plugins::<MaterialPlugin::<
-                ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>>();
+            match &self.voxel_material {
+                Some((voxel_material, voxel_material_mut)) => add_voxel_materials::<C, M>(
+                    app,
+                    &self.config,
+                    voxel_material.clone(),
+                    *voxel_material_mut,
+                ),
+                None => add_voxel_materials::<C, StandardVoxelMaterialType>(
+                    app,
+                    &self.config,
+                    Arc::new(|base: StandardMaterial, extension: StandardVoxelMaterial| {
+                        ExtendedMaterial { base, extension }
+                    }),
+                    |material| &mut material.extension,
+                ),
+            }
+        }
 
-            if mat_plugins.is_empty() {
-                app.add_plugins(MaterialPlugin::<
-                    ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>,
-                >::default());
+        if self.use_custom_material {
+            if let Some(handle) = &self.material_handle {
+                app.insert_resource(VoxelWorldMaterialHandle::<C, M>::new(handle.clone()));
+            } else if self.config.init_custom_materials() {
+                if let Some(material) = &self.material {
+                    let mut custom_material_assets = app.world_mut().resource_mut::<Assets<M>>();
+                    let handle = custom_material_assets.add(material.clone());
+                    app.insert_resource(VoxelWorldMaterialHandle::<C, M>::new(handle));
+                }
             }
 
-            let mut preloaded_texture = true;
-            let texture_conf = self.config.voxel_texture();
-            let mut texture_layers = 0;
-
-            // Use built-in default texture if no texture is specified.
-            let image_handle = if texture_conf.is_none() {
-                let mut image = Image::from_buffer(
-                    include_bytes!("shaders/default_texture.png"),
-                    ImageType::MimeType("image/png"),
-                    CompressedImageFormats::default(),
-                    false,
-                    ImageSampler::Default,
-                    RenderAssetUsages::default(),
-                )
-                .unwrap();
-                image.reinterpret_stacked_2d_as_array(4);
-                let mut image_assets = app.world_mut().resource_mut::<Assets<Image>>();
-                image_assets.add(image)
-            } else {
-                let (img_path, layers) = texture_conf.unwrap();
-                texture_layers = layers;
-                let asset_server = app.world().get_resource::<AssetServer>().unwrap();
-                preloaded_texture = false;
-                asset_server.load(img_path)
-            };
-
-            let mut material_assets = app
-                .world_mut()
-                .resource_mut::<Assets<ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>>>(
-                );
-
-            let mat_handle = material_assets.add(ExtendedMaterial {
-                base: StandardMaterial {
-                    reflectance: 0.05,
-                    metallic: 0.05,
-                    perceptual_roughness: 0.95,
-                    ..default()
-                },
-                extension: StandardVoxelMaterial {
-                    voxels_texture: image_handle.clone(),
-                },
-            });
-
-            app.insert_resource(LoadingTexture {
-                is_loaded: preloaded_texture,
-                handle: image_handle,
-            });
-            app.insert_resource(VoxelWorldMaterialHandle { handle: mat_handle });
-            app.insert_resource(TextureLayers(texture_layers));
-
-            app.insert_resource(self.config.clone());
-
-            app.add_systems(Update, prepare_texture);
+            app.insert_resource(LoadingTexture::<C>::new(Handle::default(), true));
 
             app.add_systems(
                 Update,
-                Internals::<C>::assign_material::<
-                    ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>,
-                >,
+                (
+                    Internals::<C>::assign_material::<M>,
+                    Internals::<C>::update_lod_fades::<M>,
+                )
+                    .chain(),
             );
         }
+    }
+}
 
-        if self.use_custom_material {
-            if self.config.init_custom_materials() {
-                let mut custom_material_assets = app.world_mut().resource_mut::<Assets<M>>();
-                let handle = custom_material_assets.add(self.material.clone());
-                app.insert_resource(VoxelWorldMaterialHandle { handle });
+/// Builds a material of the world from the standard material and voxel material of one of its
+/// variants, see `VoxelWorldPlugin::with_material_extension`
+#[cfg(feature = "render")]
+type VoxelMaterialFn<M> = Arc<dyn Fn(StandardMaterial, StandardVoxelMaterial) -> M + Send + Sync>;
+
+/// The voxel material of one of the variants built by a `VoxelMaterialFn`
+#[cfg(feature = "render")]
+type VoxelMaterialMutFn<M> = fn(&mut M) -> &mut StandardVoxelMaterial;
+
+#[cfg(feature = "render")]
+fn extended_voxel_material<E: MaterialExtension>(
+    material: &mut ExtendedVoxelMaterial<E>,
+) -> &mut StandardVoxelMaterial {
+    &mut material.base.extension
+}
+
+/// Loads the voxel texture, and adds the opaque, transparent and level of detail fade variants of
+/// the built-in voxel material
+#[cfg(feature = "render")]
+fn add_voxel_materials<C: WorldConfig, M: Material>(
+    app: &mut App,
+    config: &C,
+    voxel_material: VoxelMaterialFn<M>,
+    voxel_material_mut: VoxelMaterialMutFn<M>,
+) where
+    M::Data: PartialEq + Eq + Hash + Clone,
+{
+    let mat_plugins = app.get_added_plugins::<MaterialPlugin<M>>();
+
+    if mat_plugins.is_empty() {
+        app.add_plugins(MaterialPlugin::<M>::default());
+    }
+
+    let mut preloaded_texture = true;
+    let texture_conf = config.voxel_texture();
+    let mut texture_layers = 0;
+
+    let image_handle = if let Some((img_path, layers)) = texture_conf {
+        texture_layers = layers;
+        let asset_server = app.world().get_resource::<AssetServer>().unwrap();
+        preloaded_texture = false;
+        asset_server.load(img_path)
+    } else {
+        // Use built-in default texture if no texture is specified.
+        let mut image = Image::from_buffer(
+            include_bytes!("shaders/default_texture.png"),
+            ImageType::MimeType("image/png"),
+            CompressedImageFormats::default(),
+            false,
+            ImageSampler::Default,
+            RenderAssetUsages::default(),
+        )
+        .unwrap();
+        image.reinterpret_stacked_2d_as_array(4);
+        let mut image_assets = app.world_mut().resource_mut::<Assets<Image>>();
+        image_assets.add(image)
+    };
+
+    let mut material_assets = app.world_mut().resource_mut::<Assets<M>>();
+
+    let base_material = config.voxel_base_material();
+
+    let mat_handle = material_assets.add(voxel_material(
+        base_material.clone(),
+        StandardVoxelMaterial {
+            voxels_texture: image_handle.clone(),
+            triplanar: config.triplanar_texturing(),
+            lod_fade: 1.0,
+            wind_strength: config.wind_strength(),
+            sky_light_intensity: 1.0,
+        },
+    ));
+
+    let mut fade_handles = |lod_fade: f32| {
+        material_assets.add(voxel_material(
+            base_material.clone(),
+            StandardVoxelMaterial {
+                voxels_texture: image_handle.clone(),
+                triplanar: config.triplanar_texturing(),
+                lod_fade,
+                wind_strength: config.wind_strength(),
+                sky_light_intensity: 1.0,
+            },
+        ))
+    };
+    let (lod_fade_in, lod_fade_out) = if lod_fade_enabled(config) {
+        let steps = (1..=LOD_FADE_STEPS).map(|step| step as f32 / LOD_FADE_STEPS as f32);
+        (
+            steps.clone().map(&mut fade_handles).collect(),
+            steps.map(|step| fade_handles(1.0 + step)).collect(),
+        )
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    let transparent_mat_handle = material_assets.add(voxel_material(
+        StandardMaterial {
+            alpha_mode: AlphaMode::Blend,
+            ..base_material
+        },
+        StandardVoxelMaterial {
+            voxels_texture: image_handle.clone(),
+            triplanar: config.triplanar_texturing(),
+            lod_fade: 1.0,
+            wind_strength: config.wind_strength(),
+            sky_light_intensity: 1.0,
+        },
+    ));
+
+    app.insert_resource(LoadingTexture::<C>::new(image_handle, preloaded_texture));
+    app.insert_resource(
+        VoxelWorldMaterialHandle::<C, _>::new(mat_handle)
+            .with_transparent(transparent_mat_handle)
+            .with_lod_fade(lod_fade_in, lod_fade_out),
+    );
+    app.insert_resource(TextureLayers::<C>(texture_layers, PhantomData));
+
+    app.add_systems(Update, prepare_texture::<C>);
+
+    app.add_systems(
+        Update,
+        (
+            Internals::<C>::assign_material::<M>,
+            Internals::<C>::update_lod_fades::<M>,
+        )
+            .chain(),
+    );
+
+    // All variants follow the sky light intensity
+    app.init_resource::<VoxelSkyLight<C>>().add_systems(
+        Update,
+        move |sky_light: Res<VoxelSkyLight<C>>,
+              material_handle: Res<VoxelWorldMaterialHandle<C, M>>,
+              mut materials: ResMut<Assets<M>>| {
+            if !sky_light.is_changed() {
+                return;
+            }
+            let handles = std::iter::once(&material_handle.handle)
+                .chain(material_handle.transparent_handle.iter())
+                .chain(material_handle.lod_fade_in.iter())
+                .chain(material_handle.lod_fade_out.iter());
+            for handle in handles {
+                if let Some(material) = materials.get_mut(handle) {
+                    voxel_material_mut(material).sky_light_intensity = sky_light.intensity;
+                }
             }
+        },
+    );
+}
 
-            app.insert_resource(LoadingTexture {
-                is_loaded: true,
-                handle: Handle::default(),
-            });
+/// The main plugin for the voxel world, without the `render` feature. Chunks are streamed around
+/// streaming anchors and requests, and generated, edited and persisted like in a rendered world,
+/// but they are never meshed. Use this on dedicated servers. There are no materials without
+/// `render`, `M` is only there so that the plugin has the same type parameters either way.
+#[cfg(not(feature = "render"))]
+pub struct VoxelWorldPlugin<C: WorldConfig, M = ()> {
+    config: C,
+    _marker: PhantomData<M>,
+}
 
-            app.add_systems(Update, Internals::<C>::assign_material::<M>);
+#[cfg(not(feature = "render"))]
+impl<C: WorldConfig> VoxelWorldPlugin<C> {
+    pub fn with_config(config: C) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
         }
     }
+
+    pub fn minimal() -> Self {
+        Self::with_config(C::default())
+    }
+}
+
+#[cfg(not(feature = "render"))]
+impl Default for VoxelWorldPlugin<DefaultWorld> {
+    fn default() -> Self {
+        Self::with_config(DefaultWorld)
+    }
+}
+
+#[cfg(not(feature = "render"))]
+impl<C: WorldConfig, M: Send + Sync + 'static> Plugin for VoxelWorldPlugin<C, M> {
+    fn build(&self, app: &mut App) {
+        add_world_systems(app, &self.config);
+        app.add_systems(Update, Internals::<C>::store_chunk_voxels);
+    }
+}
+
+/// Adds the resources, events and systems that stream, generate and edit the chunks of a world,
+/// with or without rendering
+fn add_world_systems<C: WorldConfig>(app: &mut App, config: &C) {
+    app.insert_resource(config.clone())
+        .add_systems(PreStartup, Internals::<C>::setup);
+
+    #[cfg(feature = "render")]
+    app.add_systems(
+        PreUpdate,
+        (
+            (
+                Internals::<C>::track_camera_motion,
+                (
+                    Internals::<C>::spawn_chunks,
+                    Internals::<C>::spawn_anchored_chunks,
+                    Internals::<C>::spawn_finite_world,
+                )
+                    .chain()
+                    .run_if(Internals::<C>::automatic_streaming),
+                Internals::<C>::spawn_requested_chunks,
+                // Chunks retired this frame no longer count against the memory budget, so they
+                // are not evicted, and don't get a second `ChunkWillDespawn`
+                (Internals::<C>::retire_chunks, Internals::<C>::evict_chunks)
+                    .chain()
+                    .run_if(Internals::<C>::automatic_streaming),
+            )
+                .chain(),
+            Internals::<C>::remesh_dirty_chunks,
+        )
+            .chain()
+            .run_if(Internals::<C>::streaming_active),
+    );
+
+    // Without cameras, chunks are only streamed around streaming anchors and requests
+    #[cfg(not(feature = "render"))]
+    app.add_systems(
+        PreUpdate,
+        (
+            (
+                (
+                    Internals::<C>::spawn_anchored_chunks,
+                    Internals::<C>::spawn_finite_world,
+                )
+                    .chain()
+                    .run_if(Internals::<C>::automatic_streaming),
+                Internals::<C>::spawn_requested_chunks,
+                Internals::<C>::retire_chunks.run_if(Internals::<C>::automatic_streaming),
+            )
+                .chain(),
+            Internals::<C>::remesh_dirty_chunks,
+        )
+            .chain()
+            .run_if(Internals::<C>::streaming_active),
+    );
+
+    app.add_systems(
+        PreUpdate,
+        (
+            Internals::<C>::apply_scheduled_edits,
+            Internals::<C>::apply_queued_edits,
+            // Chunks remeshed this frame already include the edits, even those of chunks that
+            // are not in the chunk map yet
+            Internals::<C>::flush_voxel_write_buffer.before(Internals::<C>::remesh_dirty_chunks),
+            Internals::<C>::despawn_retired_chunks,
+            (
+                Internals::<C>::flush_chunk_map_buffers,
+                Internals::<C>::compress_idle_chunks,
+            )
+                .chain(),
+        )
+            .chain(),
+    )
+    .add_event::<ChunkWillSpawn<C>>()
+    .add_event::<ChunkWillDespawn<C>>()
+    .add_event::<ChunkWillRemesh<C>>()
+    .add_event::<QueuedEditFinished<C>>()
+    .add_event::<VoxelEditRejected<C>>();
 }
@@ -0,0 +1,180 @@
+///
+/// Prediction
+/// Lets clients apply their own edits right away, before the server has accepted them. Predicted
+/// voxels are kept on top of the updates received from the server until the edit is acknowledged
+/// or rejected, and rejected edits are rolled back to the server's voxels.
+///
+use bevy::{prelude::*, utils::HashMap};
+use std::marker::PhantomData;
+
+use crate::{
+    chunk_transport::receive_remote_chunks,
+    configuration::WorldConfig,
+    replication::apply_remote_voxel_edits,
+    voxel::WorldVoxel,
+    voxel_world::VoxelWorld,
+    voxel_world_internal::{Internals, VoxelEdits, VoxelWriteBuffer},
+};
+
+/// Keeps track of edits predicted with `PredictedEdits::predict`, for clients that send their
+/// edits to an authoritative server
+pub struct VoxelWorldPredictionPlugin<C> {
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for VoxelWorldPredictionPlugin<C> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: WorldConfig> Plugin for VoxelWorldPredictionPlugin<C> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PredictedEdits<C>>().add_systems(
+            PreUpdate,
+            reconcile_predicted_edits::<C>
+                .after(apply_remote_voxel_edits::<C>)
+                .after(receive_remote_chunks::<C>)
+                .before(Internals::<C>::flush_voxel_write_buffer),
+        );
+    }
+}
+
+/// Handle to an edit made with `PredictedEdits::predict`. Send the id to the server with the
+/// edit, so that it can tell which edit it accepted or rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PredictedEdit {
+    pub id: u64,
+}
+
+/// The edits that have been applied locally, but not acknowledged by the server yet
+#[derive(Resource)]
+pub struct PredictedEdits<C: WorldConfig> {
+    pending: Vec<(PredictedEdit, VoxelEdits<C::MaterialIndex>)>,
+    /// The voxels of the server under the pending edits, to roll back to
+    authoritative: HashMap<IVec3, WorldVoxel<C::MaterialIndex>>,
+    next_id: u64,
+}
+
+impl<C: WorldConfig> Default for PredictedEdits<C> {
+    fn default() -> Self {
+        Self {
+            pending: Vec::new(),
+            authoritative: HashMap::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<C: WorldConfig> PredictedEdits<C> {
+    /// Set voxels right away, and keep track of them until the server acknowledges or rejects the
+    /// edit
+    pub fn predict(
+        &mut self,
+        voxel_world: &mut VoxelWorld<C>,
+        voxels: impl IntoIterator<Item = (IVec3, WorldVoxel<C::MaterialIndex>)>,
+    ) -> PredictedEdit {
+        let voxels: Vec<_> = voxels.into_iter().collect();
+        let get_voxel = voxel_world.get_voxel_fn();
+        for (position, _) in &voxels {
+            self.authoritative
+                .entry(*position)
+                .or_insert_with(|| get_voxel(*position));
+        }
+        voxel_world.set_voxels(voxels.iter().copied());
+
+        let edit = PredictedEdit { id: self.next_id };
+        self.next_id += 1;
+        self.pending.push((edit, voxels));
+        edit
+    }
+
+    /// The server accepted the edit, so its voxels no longer have to be rolled back
+    pub fn acknowledge(&mut self, edit: PredictedEdit) {
+        for (position, _) in self.remove(edit) {
+            if self.predicted_voxel(position).is_none() {
+                self.authoritative.remove(&position);
+            }
+        }
+    }
+
+    /// The server rejected the edit, so set its voxels back to the server's voxels, or to the
+    /// voxels of later pending edits. Their chunks are remeshed like for any other edit, but the
+    /// rollback is not recorded in the edit history, and applies in protected regions too.
+    pub fn reject(&mut self, edit: PredictedEdit, voxel_world: &mut VoxelWorld<C>) {
+        let mut rollback = Vec::new();
+        for (position, _) in self.remove(edit) {
+            match self.predicted_voxel(position) {
+                Some(voxel) => rollback.push((position, voxel)),
+                None => rollback.extend(
+                    self.authoritative
+                        .remove(&position)
+                        .map(|voxel| (position, voxel)),
+                ),
+            }
+        }
+        voxel_world.restore_voxels(rollback);
+    }
+
+    /// True if the edit has been neither acknowledged nor rejected
+    pub fn is_pending(&self, edit: PredictedEdit) -> bool {
+        self.pending.iter().any(|(pending, _)| *pending == edit)
+    }
+
+    /// The edits that have been neither acknowledged nor rejected, oldest first
+    pub fn pending(&self) -> impl Iterator<Item = PredictedEdit> + '_ {
+        self.pending.iter().map(|(edit, _)| *edit)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    fn remove(&mut self, edit: PredictedEdit) -> Vec<(IVec3, WorldVoxel<C::MaterialIndex>)> {
+        match self
+            .pending
+            .iter()
+            .position(|(pending, _)| *pending == edit)
+        {
+            Some(index) => self.pending.remove(index).1,
+            None => Vec::new(),
+        }
+    }
+
+    /// The voxel of the latest pending edit at a position
+    fn predicted_voxel(&self, position: IVec3) -> Option<WorldVoxel<C::MaterialIndex>> {
+        self.pending.iter().rev().find_map(|(_, voxels)| {
+            voxels
+                .iter()
+                .rev()
+                .find(|(pending, _)| *pending == position)
+                .map(|(_, voxel)| *voxel)
+        })
+    }
+}
+
+/// Writes from the server to voxels with pending edits update the voxels to roll back to, and are
+/// replaced with the predicted voxels, so that predictions don't flicker back and forth. Local
+/// writes that weren't predicted are left alone, since they don't come from the server.
+fn reconcile_predicted_edits<C: WorldConfig>(
+    mut predicted_edits: ResMut<PredictedEdits<C>>,
+    mut buffer: ResMut<VoxelWriteBuffer<C>>,
+) {
+    if predicted_edits.is_empty() {
+        return;
+    }
+    for (position, voxel) in buffer.remote.iter_mut() {
+        if !predicted_edits.authoritative.contains_key(position) {
+            continue;
+        }
+        let Some(predicted) = predicted_edits.predicted_voxel(*position) else {
+            continue;
+        };
+        if *voxel != predicted {
+            predicted_edits.authoritative.insert(*position, *voxel);
+            *voxel = predicted;
+        }
+    }
+}
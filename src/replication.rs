@@ -0,0 +1,897 @@
+///
+/// Replication
+/// Network agnostic messages for streaming a world from a server to clients. A server sends a
+/// snapshot of each chunk a client needs, followed by deltas with the voxels that change. The
+/// messages encode to compact bytes, and can be sent over any transport.
+///
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use std::{collections::VecDeque, marker::PhantomData, sync::Arc};
+
+use crate::{
+    chunk::CHUNK_SIZE_I,
+    chunk_storage::Reader,
+    configuration::WorldConfig,
+    persistence::{decode_voxel, encode_voxel},
+    rle::{decode_runs, encode_runs},
+    voxel::WorldVoxel,
+    voxel_world::{ChunkWillDespawn, VoxelWorld, VoxelWorldStreamingAnchor},
+    voxel_world_internal::{
+        get_chunk_voxel_position, Internals, ProtectedRegions, VoxelWriteBuffer,
+    },
+};
+
+/// The number of voxels in a chunk, without padding
+pub(crate) const CHUNK_VOLUME: usize = (CHUNK_SIZE_I * CHUNK_SIZE_I * CHUNK_SIZE_I) as usize;
+
+/// Collects the voxel edits of a server into `ReplicationOutbox` messages. Send chunk snapshots
+/// from `VoxelWorld::chunk_snapshot` to clients that don't have a chunk yet, and apply received
+/// messages on clients with `VoxelWorld::apply_replication_message`.
+///
+/// Alternatively, add a `ReplicationConnection` to the streaming anchor of each connected player,
+/// to have the chunks around it replicated automatically.
+pub struct VoxelWorldReplicationPlugin<C> {
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for VoxelWorldReplicationPlugin<C> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: WorldConfig> Plugin for VoxelWorldReplicationPlugin<C> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplicationOutbox<C>>()
+            .init_resource::<ChunkVersions<C>>()
+            .add_event::<RemoteVoxelEdit<C::MaterialIndex>>()
+            .add_event::<ReplicationSynced<C>>()
+            .add_systems(
+                PreUpdate,
+                (
+                    (apply_remote_voxel_edits::<C>, collect_voxel_deltas::<C>)
+                        .chain()
+                        .before(Internals::<C>::flush_voxel_write_buffer),
+                    replicate_to_connections::<C>.after(Internals::<C>::flush_voxel_write_buffer),
+                ),
+            )
+            .add_systems(PostUpdate, forget_despawned_chunk_versions::<C>);
+    }
+}
+
+/// Voxel edits made by one peer, to be applied by the others. Edits made locally are collected
+/// in the `ReplicationOutbox`, and edits received from other peers are applied when they are sent
+/// as events. `world` is the `VoxelWorldConfig::replication_key` of the edited world, so that the
+/// edits of several worlds can share a channel.
+#[derive(Event, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RemoteVoxelEdit<I> {
+    pub world: u64,
+    pub voxels: Vec<([i32; 3], WorldVoxel<I>)>,
+}
+
+/// Sent when a `ReplicationConnection` has been sent all chunks in range of its anchor for the
+/// first time, or for the first time after `ReplicationConnection::resync`
+#[derive(Event)]
+pub struct ReplicationSynced<C> {
+    pub entity: Entity,
+    _marker: PhantomData<C>,
+}
+
+impl<C> ReplicationSynced<C> {
+    pub fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// How much of the initial sync of a `ReplicationConnection` has been sent
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyncProgress {
+    pub sent_chunks: usize,
+    pub total_chunks: usize,
+}
+
+impl SyncProgress {
+    /// The sent part of the chunks in range, between 0 and 1
+    pub fn fraction(&self) -> f32 {
+        if self.total_chunks == 0 {
+            return 0.0;
+        }
+        self.sent_chunks.min(self.total_chunks) as f32 / self.total_chunks as f32
+    }
+}
+
+/// A message that replicates the voxels of a chunk. Voxels are indexed by
+/// `x + y * CHUNK_SIZE + z * CHUNK_SIZE²`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplicationMessage<I> {
+    /// All voxels of a chunk, as runs of identical voxels with the exclusive end index of each
+    /// run. Snapshots from `ChunkVersions` have a version that later diffs are based on.
+    ChunkSnapshot {
+        chunk_position: IVec3,
+        version: u32,
+        runs: Vec<(u32, WorldVoxel<I>)>,
+    },
+    /// Voxels that changed in a chunk, by their position in the chunk
+    ChunkDelta {
+        chunk_position: IVec3,
+        voxels: Vec<(IVec3, WorldVoxel<I>)>,
+    },
+    /// The runs of voxels that changed in a chunk since `base_version`, each with its start and
+    /// exclusive end index, for a client that already has the chunk at that version
+    ChunkDiff {
+        chunk_position: IVec3,
+        base_version: u32,
+        version: u32,
+        runs: Vec<(u32, u32, WorldVoxel<I>)>,
+    },
+}
+
+impl<I: Copy + PartialEq> ReplicationMessage<I> {
+    /// A snapshot of a chunk, from the voxels at each position in the chunk
+    pub fn snapshot(chunk_position: IVec3, mut voxel: impl FnMut(IVec3) -> WorldVoxel<I>) -> Self {
+        let voxels: Vec<_> = (0..CHUNK_VOLUME as i32)
+            .map(|index| voxel(chunk_position * CHUNK_SIZE_I + local_position(index)))
+            .collect();
+        Self::ChunkSnapshot {
+            chunk_position,
+            version: 0,
+            runs: encode_runs(&voxels),
+        }
+    }
+
+    /// The runs of voxels that differ between two versions of a chunk's voxels
+    pub fn diff(
+        chunk_position: IVec3,
+        (base_version, base): (u32, &[WorldVoxel<I>]),
+        (version, voxels): (u32, &[WorldVoxel<I>]),
+    ) -> Self {
+        let mut runs: Vec<(u32, u32, WorldVoxel<I>)> = Vec::new();
+        for (index, (old, new)) in base.iter().zip(voxels).enumerate() {
+            if old == new {
+                continue;
+            }
+            let index = index as u32;
+            match runs.last_mut() {
+                Some((_, end, voxel)) if *end == index && voxel == new => *end += 1,
+                _ => runs.push((index, index + 1, *new)),
+            }
+        }
+        Self::ChunkDiff {
+            chunk_position,
+            base_version,
+            version,
+            runs,
+        }
+    }
+
+    pub fn chunk_position(&self) -> IVec3 {
+        match self {
+            Self::ChunkSnapshot { chunk_position, .. }
+            | Self::ChunkDelta { chunk_position, .. }
+            | Self::ChunkDiff { chunk_position, .. } => *chunk_position,
+        }
+    }
+
+    /// The voxels of a snapshot, indexed like in the chunk. `None` for other messages, or if the
+    /// runs don't cover the chunk.
+    pub fn snapshot_voxels(&self) -> Option<Vec<WorldVoxel<I>>> {
+        let Self::ChunkSnapshot { runs, .. } = self else {
+            return None;
+        };
+        let (_, first) = runs.first()?;
+        let mut voxels = vec![*first; CHUNK_VOLUME];
+        decode_runs(runs, &mut voxels).then_some(voxels)
+    }
+
+    /// Apply the runs of a diff to the voxels of its base version. Returns false, leaving the
+    /// voxels as they are, for other messages or runs that are out of bounds.
+    pub fn apply_diff(&self, voxels: &mut [WorldVoxel<I>]) -> bool {
+        let Self::ChunkDiff { runs, .. } = self else {
+            return false;
+        };
+        if runs
+            .iter()
+            .any(|(start, end, _)| start >= end || *end as usize > voxels.len())
+        {
+            return false;
+        }
+        for (start, end, voxel) in runs {
+            voxels[*start as usize..*end as usize].fill(*voxel);
+        }
+        true
+    }
+
+    /// The voxels of the message, by world position. `None` if the runs of a snapshot don't
+    /// cover the chunk.
+    pub fn voxels(&self) -> Option<Vec<(IVec3, WorldVoxel<I>)>> {
+        let origin = self.chunk_position() * CHUNK_SIZE_I;
+        match self {
+            Self::ChunkSnapshot { .. } => Some(
+                self.snapshot_voxels()?
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, voxel)| (origin + local_position(index as i32), voxel))
+                    .collect(),
+            ),
+            Self::ChunkDelta { voxels, .. } => Some(
+                voxels
+                    .iter()
+                    .map(|(local, voxel)| (origin + *local, *voxel))
+                    .collect(),
+            ),
+            Self::ChunkDiff { runs, .. } => Some(
+                runs.iter()
+                    .flat_map(|(start, end, voxel)| {
+                        (*start..*end)
+                            .map(move |index| (origin + local_position(index as i32), *voxel))
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl<I: Into<u32> + Copy + PartialEq> ReplicationMessage<I> {
+    /// A tag for the kind of message and the chunk position, followed by the versions and runs
+    /// of a snapshot or diff, or the voxels of a delta
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let tag = match self {
+            Self::ChunkSnapshot { .. } => 0,
+            Self::ChunkDelta { .. } => 1,
+            Self::ChunkDiff { .. } => 2,
+        };
+        bytes.push(tag);
+        for coordinate in self.chunk_position().to_array() {
+            bytes.extend_from_slice(&coordinate.to_le_bytes());
+        }
+        match self {
+            Self::ChunkSnapshot { version, runs, .. } => {
+                bytes.extend_from_slice(&version.to_le_bytes());
+                bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+                for (end, voxel) in runs {
+                    bytes.extend_from_slice(&(*end as u16).to_le_bytes());
+                    encode_voxel(*voxel, &mut bytes);
+                }
+            }
+            Self::ChunkDelta { voxels, .. } => {
+                bytes.extend_from_slice(&(voxels.len() as u32).to_le_bytes());
+                for (local, voxel) in voxels {
+                    let index =
+                        local.x + local.y * CHUNK_SIZE_I + local.z * CHUNK_SIZE_I * CHUNK_SIZE_I;
+                    bytes.extend_from_slice(&(index as u16).to_le_bytes());
+                    encode_voxel(*voxel, &mut bytes);
+                }
+            }
+            Self::ChunkDiff {
+                base_version,
+                version,
+                runs,
+                ..
+            } => {
+                bytes.extend_from_slice(&base_version.to_le_bytes());
+                bytes.extend_from_slice(&version.to_le_bytes());
+                bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+                for (start, end, voxel) in runs {
+                    bytes.extend_from_slice(&(*start as u16).to_le_bytes());
+                    bytes.extend_from_slice(&(*end as u16).to_le_bytes());
+                    encode_voxel(*voxel, &mut bytes);
+                }
+            }
+        }
+        bytes
+    }
+}
+
+impl<I: TryFrom<u32>> ReplicationMessage<I> {
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes);
+        let tag = reader.u8()?;
+        let chunk_position = IVec3::new(
+            reader.u32()? as i32,
+            reader.u32()? as i32,
+            reader.u32()? as i32,
+        );
+        let message = match tag {
+            0 => {
+                let version = reader.u32()?;
+                let mut runs = Vec::new();
+                for _ in 0..reader.u32()? {
+                    let end = reader.u16()? as u32;
+                    runs.push((end, decode_voxel(&mut reader)?));
+                }
+                Self::ChunkSnapshot {
+                    chunk_position,
+                    version,
+                    runs,
+                }
+            }
+            1 => {
+                let mut voxels = Vec::new();
+                for _ in 0..reader.u32()? {
+                    let index = reader.u16()? as i32;
+                    voxels.push((local_position(index), decode_voxel(&mut reader)?));
+                }
+                Self::ChunkDelta {
+                    chunk_position,
+                    voxels,
+                }
+            }
+            2 => {
+                let base_version = reader.u32()?;
+                let version = reader.u32()?;
+                let mut runs = Vec::new();
+                for _ in 0..reader.u32()? {
+                    let start = reader.u16()? as u32;
+                    let end = reader.u16()? as u32;
+                    runs.push((start, end, decode_voxel(&mut reader)?));
+                }
+                Self::ChunkDiff {
+                    chunk_position,
+                    base_version,
+                    version,
+                    runs,
+                }
+            }
+            _ => return None,
+        };
+        reader.0.is_empty().then_some(message)
+    }
+}
+
+/// The position in a chunk of a voxel index
+fn local_position(index: i32) -> IVec3 {
+    IVec3::new(
+        index % CHUNK_SIZE_I,
+        (index / CHUNK_SIZE_I) % CHUNK_SIZE_I,
+        index / (CHUNK_SIZE_I * CHUNK_SIZE_I),
+    )
+}
+
+/// The replication messages and edits that haven't been sent yet. Drain them each frame and send
+/// the messages to the clients, and the edits to the other peers. Messages and edits that are
+/// still there when the next frame's edits are collected are dropped.
+#[derive(Resource)]
+pub struct ReplicationOutbox<C: WorldConfig> {
+    messages: Vec<ReplicationMessage<C::MaterialIndex>>,
+    edits: Vec<RemoteVoxelEdit<C::MaterialIndex>>,
+}
+
+impl<C: WorldConfig> Default for ReplicationOutbox<C> {
+    fn default() -> Self {
+        Self {
+            messages: Vec::new(),
+            edits: Vec::new(),
+        }
+    }
+}
+
+impl<C: WorldConfig> ReplicationOutbox<C> {
+    pub fn push(&mut self, message: ReplicationMessage<C::MaterialIndex>) {
+        self.messages.push(message);
+    }
+
+    pub fn drain(&mut self) -> impl Iterator<Item = ReplicationMessage<C::MaterialIndex>> + '_ {
+        self.messages.drain(..)
+    }
+
+    pub fn drain_edits(&mut self) -> impl Iterator<Item = RemoteVoxelEdit<C::MaterialIndex>> + '_ {
+        self.edits.drain(..)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty() && self.edits.is_empty()
+    }
+}
+
+/// The number of versions of each chunk that are kept to diff against
+const MAX_CHUNK_VERSIONS: usize = 8;
+
+/// A version of the voxels of a chunk, indexed like in a `ReplicationMessage::ChunkSnapshot`
+pub(crate) type ChunkVersion<I> = (u32, Arc<[WorldVoxel<I>]>);
+
+/// The versions kept of a chunk. The latest version is kept whole, and each older version as the
+/// voxels that changed in the version after it, as they were before the change.
+struct ChunkHistory<I> {
+    latest: ChunkVersion<I>,
+    older: VecDeque<(u32, Vec<(u32, WorldVoxel<I>)>)>,
+}
+
+impl<I: Copy> ChunkHistory<I> {
+    /// The voxels of a kept version, rebuilt from the latest one by undoing the versions after it
+    fn voxels(&self, version: u32) -> Option<Vec<WorldVoxel<I>>> {
+        let position = self.older.iter().position(|(older, _)| *older == version);
+        if position.is_none() && version != self.latest.0 {
+            return None;
+        }
+        let mut voxels = self.latest.1.to_vec();
+        for (_, undo) in self
+            .older
+            .range(position.unwrap_or(self.older.len())..)
+            .rev()
+        {
+            for (index, voxel) in undo {
+                voxels[*index as usize] = *voxel;
+            }
+        }
+        Some(voxels)
+    }
+}
+
+/// Versions of chunks as they were sent to clients, so that a client that already has a chunk
+/// can be sent a diff instead of the whole chunk. See `chunk_update`. Versions are numbered
+/// across all chunks, so that a chunk that was forgotten never reuses a version a client has.
+/// The versions of a chunk are forgotten when it despawns, or goes out of range of every
+/// `ReplicationConnection`.
+#[derive(Resource)]
+pub struct ChunkVersions<C: WorldConfig> {
+    chunks: HashMap<IVec3, ChunkHistory<C::MaterialIndex>>,
+    next_version: u32,
+}
+
+impl<C: WorldConfig> Default for ChunkVersions<C> {
+    fn default() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            next_version: 0,
+        }
+    }
+}
+
+impl<C: WorldConfig> ChunkVersions<C> {
+    /// A message that brings a client up to date with a chunk. A client that has the chunk at
+    /// `base_version` gets a diff, if that version is still kept, and other clients get a
+    /// snapshot. The chunk gets a new version if it changed since its last update.
+    pub fn chunk_update(
+        &mut self,
+        voxel_world: &VoxelWorld<C>,
+        chunk_position: IVec3,
+        base_version: Option<u32>,
+    ) -> ReplicationMessage<C::MaterialIndex> {
+        let ReplicationMessage::ChunkSnapshot { runs, .. } =
+            voxel_world.chunk_snapshot(chunk_position)
+        else {
+            unreachable!("chunk_snapshot always returns a snapshot");
+        };
+        let mut voxels = vec![WorldVoxel::Unset; CHUNK_VOLUME];
+        decode_runs(&runs, &mut voxels);
+
+        let Self {
+            chunks,
+            next_version,
+        } = self;
+        let history = match chunks.get_mut(&chunk_position) {
+            Some(history) if *history.latest.1 == *voxels => history,
+            Some(history) => {
+                let undo = history
+                    .latest
+                    .1
+                    .iter()
+                    .zip(&voxels)
+                    .enumerate()
+                    .filter(|(_, (old, new))| old != new)
+                    .map(|(index, (old, _))| (index as u32, *old))
+                    .collect();
+                if history.older.len() + 1 == MAX_CHUNK_VERSIONS {
+                    history.older.pop_front();
+                }
+                let (previous, _) =
+                    std::mem::replace(&mut history.latest, (*next_version, voxels.into()));
+                history.older.push_back((previous, undo));
+                *next_version = next_version.wrapping_add(1);
+                history
+            }
+            None => {
+                let history = chunks.entry(chunk_position).or_insert(ChunkHistory {
+                    latest: (*next_version, voxels.into()),
+                    older: VecDeque::new(),
+                });
+                *next_version = next_version.wrapping_add(1);
+                history
+            }
+        };
+
+        let (version, latest) = &history.latest;
+        let base = base_version
+            .and_then(|base_version| Some((base_version, history.voxels(base_version)?)));
+        match base {
+            Some((base_version, base)) => ReplicationMessage::diff(
+                chunk_position,
+                (base_version, &base[..]),
+                (*version, &latest[..]),
+            ),
+            None => ReplicationMessage::ChunkSnapshot {
+                chunk_position,
+                version: *version,
+                runs,
+            },
+        }
+    }
+
+    /// Stop keeping versions of a chunk, for example when no client has it anymore
+    pub fn forget_chunk(&mut self, chunk_position: IVec3) {
+        self.chunks.remove(&chunk_position);
+    }
+}
+
+/// Forget the versions of despawned chunks, which are sent as a snapshot when they spawn again
+fn forget_despawned_chunk_versions<C: WorldConfig>(
+    mut ev_chunk_will_despawn: EventReader<ChunkWillDespawn<C>>,
+    mut chunk_versions: ResMut<ChunkVersions<C>>,
+) {
+    for ev in ev_chunk_will_despawn.read() {
+        chunk_versions.forget_chunk(ev.chunk_key);
+    }
+}
+
+/// The default `ReplicationConnection::send_budget`
+const DEFAULT_SEND_BUDGET: usize = 64 * 1024;
+
+/// A client connected to a server, on the entity of its player. Together with a
+/// `VoxelWorldStreamingAnchor` on the same entity, it limits replication to the chunks within the
+/// anchor's radius, or `VoxelWorldConfig::spawning_distance`. Chunks are sent as they come into
+/// range, closest first, and deltas are sent for the chunks in range as they change. Drain the
+/// messages each frame and send them to the client.
+///
+/// With a `tick_budget`, each drain only takes the messages that fit in it, nearest chunks
+/// first, and the rest wait for the next tick. Deltas for a chunk that are still waiting are
+/// merged, so that a burst of edits to the same voxels is sent once.
+#[derive(Component)]
+pub struct ReplicationConnection<C: WorldConfig> {
+    /// The number of bytes of encoded messages that can be waiting to be drained. Deltas are
+    /// always queued, and chunks coming into range fill up the rest of the budget, so a slow
+    /// client is not sent more chunks than it can take.
+    pub send_budget: usize,
+    /// The send budget until the initial sync is done, so that a late joiner can catch up faster
+    pub initial_send_budget: Option<usize>,
+    /// The number of bytes of encoded messages taken by each `drain`, or None to take all of them.
+    /// The first message is always taken, even if it is larger.
+    pub tick_budget: Option<usize>,
+    /// The queued messages, with their encoded size
+    messages: Vec<(usize, ReplicationMessage<C::MaterialIndex>)>,
+    queued_bytes: usize,
+    /// The chunk of the anchor, that messages are sent closest to first
+    anchor_chunk: IVec3,
+    /// The chunks the client has, with the version it has, including chunks that went out of
+    /// range while another connection keeps their versions, so that they can be sent as a diff
+    /// when they come back
+    versions: HashMap<IVec3, u32>,
+    /// The chunks that are kept up to date with deltas
+    in_range: HashSet<IVec3>,
+    /// The chunks in range that were sent deltas since their version
+    edited: HashSet<IVec3>,
+    /// The anchor chunk when all chunks around it were last sent
+    completed: Option<IVec3>,
+    /// The number of chunks in range of the anchor
+    total_chunks: usize,
+    /// True once all chunks in range have been sent
+    synced: bool,
+}
+
+impl<C: WorldConfig> Default for ReplicationConnection<C> {
+    fn default() -> Self {
+        Self::with_send_budget(DEFAULT_SEND_BUDGET)
+    }
+}
+
+impl<C: WorldConfig> ReplicationConnection<C> {
+    pub fn with_send_budget(send_budget: usize) -> Self {
+        Self {
+            send_budget,
+            initial_send_budget: None,
+            tick_budget: None,
+            messages: Vec::new(),
+            queued_bytes: 0,
+            anchor_chunk: IVec3::ZERO,
+            versions: HashMap::new(),
+            in_range: HashSet::new(),
+            edited: HashSet::new(),
+            completed: None,
+            total_chunks: 0,
+            synced: false,
+        }
+    }
+
+    /// Use a different send budget until the initial sync is done
+    pub fn with_initial_send_budget(mut self, initial_send_budget: usize) -> Self {
+        self.initial_send_budget = Some(initial_send_budget);
+        self
+    }
+
+    /// Limit the bytes taken by each `drain`
+    pub fn with_tick_budget(mut self, tick_budget: usize) -> Self {
+        self.tick_budget = Some(tick_budget);
+        self
+    }
+
+    /// Take the messages to send this tick, within the `tick_budget`
+    pub fn drain(&mut self) -> impl Iterator<Item = ReplicationMessage<C::MaterialIndex>> + '_ {
+        let count = match self.tick_budget {
+            Some(tick_budget) => {
+                // The sort is stable, so the messages of each chunk stay in order
+                let anchor_chunk = self.anchor_chunk;
+                self.messages.sort_by_key(|(_, message)| {
+                    (message.chunk_position() - anchor_chunk)
+                        .abs()
+                        .max_element()
+                });
+                let mut bytes = 0;
+                self.messages
+                    .iter()
+                    .take_while(|(size, _)| {
+                        let fits = bytes < tick_budget;
+                        bytes += size;
+                        fits
+                    })
+                    .count()
+            }
+            None => self.messages.len(),
+        };
+        let drained: usize = self.messages[..count].iter().map(|(size, _)| size).sum();
+        self.queued_bytes -= drained;
+        self.messages.drain(..count).map(|(_, message)| message)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// True if the chunk is in range of the client, and has been sent to it
+    pub fn has_chunk(&self, chunk_position: IVec3) -> bool {
+        self.in_range.contains(&chunk_position)
+    }
+
+    /// How many of the chunks in range of the anchor have been sent
+    pub fn sync_progress(&self) -> SyncProgress {
+        SyncProgress {
+            sent_chunks: self.in_range.len(),
+            total_chunks: self.total_chunks,
+        }
+    }
+
+    /// True once all chunks in range of the anchor have been sent at least once
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    /// Forget which chunks the client has, to send it a full snapshot of the chunks in range
+    /// again, nearest first, like for a client that just joined
+    pub fn resync(&mut self) {
+        self.versions.clear();
+        self.in_range.clear();
+        self.edited.clear();
+        self.completed = None;
+        self.synced = false;
+    }
+
+    fn current_send_budget(&self) -> usize {
+        match self.initial_send_budget {
+            Some(initial_send_budget) if !self.synced => initial_send_budget,
+            _ => self.send_budget,
+        }
+    }
+
+    fn push(&mut self, message: ReplicationMessage<C::MaterialIndex>) {
+        let chunk_position = message.chunk_position();
+        match message {
+            ReplicationMessage::ChunkDelta { .. } => self.edited.insert(chunk_position),
+            _ => self.edited.remove(&chunk_position),
+        };
+        let last = self
+            .messages
+            .iter_mut()
+            .rev()
+            .find(|(_, queued)| queued.chunk_position() == chunk_position);
+        // A delta following another delta of the same chunk is merged into it
+        if let (
+            Some((size, queued @ ReplicationMessage::ChunkDelta { .. })),
+            ReplicationMessage::ChunkDelta { voxels, .. },
+        ) = (last, &message)
+        {
+            if let ReplicationMessage::ChunkDelta {
+                voxels: queued_voxels,
+                ..
+            } = queued
+            {
+                for (position, voxel) in voxels {
+                    match queued_voxels
+                        .iter_mut()
+                        .find(|(other, _)| other == position)
+                    {
+                        Some((_, other)) => *other = *voxel,
+                        None => queued_voxels.push((*position, *voxel)),
+                    }
+                }
+            }
+            let merged_size = queued.encode().len();
+            self.queued_bytes = self.queued_bytes - *size + merged_size;
+            *size = merged_size;
+            return;
+        }
+        let size = message.encode().len();
+        self.queued_bytes += size;
+        self.messages.push((size, message));
+    }
+}
+
+/// Send the chunks that came into range of each connection, within its send budget, and stop
+/// sending deltas for chunks that went out of range. The versions of chunks that went out of
+/// range of every connection are forgotten.
+fn replicate_to_connections<C: WorldConfig>(
+    mut connections: Query<(
+        Entity,
+        &mut ReplicationConnection<C>,
+        &VoxelWorldStreamingAnchor<C>,
+        &GlobalTransform,
+    )>,
+    voxel_world: VoxelWorld<C>,
+    mut chunk_versions: ResMut<ChunkVersions<C>>,
+    configuration: Res<C>,
+    mut ev_synced: EventWriter<ReplicationSynced<C>>,
+) {
+    let mut left_range = Vec::new();
+    for (entity, mut connection, anchor, transform) in connections.iter_mut() {
+        let (anchor_chunk, _) =
+            get_chunk_voxel_position(transform.translation().floor().as_ivec3());
+        connection.anchor_chunk = anchor_chunk;
+        if connection.completed == Some(anchor_chunk) {
+            continue;
+        }
+        let radius = anchor
+            .radius
+            .unwrap_or_else(|| configuration.spawning_distance()) as i32;
+        connection.total_chunks = ((radius * 2 + 1) as usize).pow(3);
+        let is_in_range =
+            |chunk_position: IVec3| (chunk_position - anchor_chunk).abs().max_element() <= radius;
+
+        // The client keeps the version it was sent of the chunks that go out of range, to base a
+        // diff on when they come back. Chunks that were sent deltas since don't match any version.
+        let out_of_range: Vec<_> = connection
+            .in_range
+            .iter()
+            .copied()
+            .filter(|chunk_position| !is_in_range(*chunk_position))
+            .collect();
+        for chunk_position in out_of_range {
+            connection.in_range.remove(&chunk_position);
+            if connection.edited.remove(&chunk_position) {
+                connection.versions.remove(&chunk_position);
+            }
+            left_range.push(chunk_position);
+        }
+
+        let mut missing = Vec::new();
+        for x in -radius..=radius {
+            for y in -radius..=radius {
+                for z in -radius..=radius {
+                    let chunk_position = anchor_chunk + IVec3::new(x, y, z);
+                    if !connection.in_range.contains(&chunk_position) {
+                        missing.push(chunk_position);
+                    }
+                }
+            }
+        }
+        missing.sort_by_key(|chunk_position| (*chunk_position - anchor_chunk).abs().max_element());
+
+        let mut completed = true;
+        let send_budget = connection.current_send_budget();
+        for chunk_position in missing {
+            // Chunks that don't fit in the budget wait for a later frame, once the queue has been
+            // drained. The first message of an empty queue is always sent, even if it is larger.
+            let base_version = connection.versions.get(&chunk_position).copied();
+            let message = chunk_versions.chunk_update(&voxel_world, chunk_position, base_version);
+            if !connection.messages.is_empty()
+                && connection.queued_bytes + message.encode().len() > send_budget
+            {
+                completed = false;
+                break;
+            }
+            if let ReplicationMessage::ChunkSnapshot { version, .. }
+            | ReplicationMessage::ChunkDiff { version, .. } = message
+            {
+                connection.versions.insert(chunk_position, version);
+            }
+            connection.in_range.insert(chunk_position);
+            connection.push(message);
+        }
+        connection.completed = completed.then_some(anchor_chunk);
+        if completed && !connection.synced {
+            connection.synced = true;
+            ev_synced.send(ReplicationSynced::new(entity));
+        }
+    }
+
+    // Versions of chunks that no connection has in range anymore are not kept to diff against
+    for chunk_position in left_range {
+        if connections
+            .iter()
+            .any(|(_, connection, ..)| connection.in_range.contains(&chunk_position))
+        {
+            continue;
+        }
+        chunk_versions.forget_chunk(chunk_position);
+        for (_, mut connection, ..) in connections.iter_mut() {
+            connection.versions.remove(&chunk_position);
+        }
+    }
+}
+
+/// Turn the edits that are about to be applied into a delta for each chunk, and into an edit for
+/// the other peers. Edits in protected regions are rejected when they are applied, so they are
+/// left out, unless they restore voxels like an imported snapshot. Edits received from other
+/// peers become deltas too, so that a server relays them to its clients, but are not sent back
+/// out as edits. Deltas are also queued for the connections that have their chunk.
+fn collect_voxel_deltas<C: WorldConfig>(
+    mut outbox: ResMut<ReplicationOutbox<C>>,
+    mut connections: Query<&mut ReplicationConnection<C>>,
+    buffer: Res<VoxelWriteBuffer<C>>,
+    protected_regions: Res<ProtectedRegions<C>>,
+    configuration: Res<C>,
+) {
+    // The outbox only holds one frame's worth, in case it is not drained
+    outbox.messages.clear();
+    outbox.edits.clear();
+
+    let mut deltas: HashMap<IVec3, HashMap<IVec3, WorldVoxel<C::MaterialIndex>>> = HashMap::new();
+    let mut edit = Vec::new();
+    for (i, (position, voxel)) in buffer.pending().enumerate() {
+        if !buffer.is_restored(i) && protected_regions.contains(*position) {
+            continue;
+        }
+        if !buffer.is_remote(i) {
+            edit.push((position.to_array(), *voxel));
+        }
+        let chunk_position = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+        deltas
+            .entry(chunk_position)
+            .or_default()
+            .insert(*position - chunk_position * CHUNK_SIZE_I, *voxel);
+    }
+    for (chunk_position, voxels) in deltas {
+        let message = ReplicationMessage::ChunkDelta {
+            chunk_position,
+            voxels: voxels.into_iter().collect(),
+        };
+        for mut connection in connections.iter_mut() {
+            if connection.has_chunk(chunk_position) {
+                connection.push(message.clone());
+            }
+        }
+        outbox.push(message);
+    }
+    if !edit.is_empty() {
+        outbox.edits.push(RemoteVoxelEdit {
+            world: configuration.replication_key(),
+            voxels: edit,
+        });
+    }
+}
+
+/// Apply the edits received from other peers. They are kept apart from the local edits, so that
+/// they are not sent back out as edits. A server that relays edits between peers without
+/// `ReplicationConnection`s forwards the events it receives itself.
+pub(crate) fn apply_remote_voxel_edits<C: WorldConfig>(
+    mut ev_remote_voxel_edit: EventReader<RemoteVoxelEdit<C::MaterialIndex>>,
+    mut buffer: ResMut<VoxelWriteBuffer<C>>,
+    configuration: Res<C>,
+) {
+    let world = configuration.replication_key();
+    for edit in ev_remote_voxel_edit
+        .read()
+        .filter(|edit| edit.world == world)
+    {
+        buffer.remote.extend(
+            edit.voxels
+                .iter()
+                .map(|(position, voxel)| (IVec3::from(*position), *voxel)),
+        );
+    }
+}
@@ -0,0 +1,95 @@
+///
+/// Run-length encoding
+/// Chunks that have not changed for a while keep their voxels run-length encoded, see
+/// `VoxelWorldConfig::chunk_compression_delay`. Most chunks are made up of long runs of air and
+/// stone, so this takes a fraction of the memory of the full voxel array.
+///
+use crate::{
+    chunk::{PaddedChunkShape, VoxelArray},
+    voxel::WorldVoxel,
+};
+use ndshape::ConstShape;
+
+/// The voxels of a chunk, stored as runs of identical voxels in linearized order
+#[derive(Clone, Debug)]
+pub(crate) struct CompressedVoxels<I = u8> {
+    /// The exclusive end index of each run, and the voxel it repeats
+    runs: Vec<(u32, WorldVoxel<I>)>,
+}
+
+/// Encodes items as runs of identical items, each with the exclusive end index of the run
+pub(crate) fn encode_runs<T: Copy + PartialEq>(items: &[T]) -> Vec<(u32, T)> {
+    let mut runs: Vec<(u32, T)> = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        match runs.last_mut() {
+            Some((end, run_item)) if run_item == item => *end = i as u32 + 1,
+            _ => runs.push((i as u32 + 1, *item)),
+        }
+    }
+    runs.shrink_to_fit();
+    runs
+}
+
+/// True if the runs cover exactly `len` items, for checking runs that were read from elsewhere
+pub(crate) fn runs_cover<T>(runs: &[(u32, T)], len: usize) -> bool {
+    let mut start = 0;
+    for (end, _) in runs.iter() {
+        if *end <= start {
+            return false;
+        }
+        start = *end;
+    }
+    start as usize == len
+}
+
+/// Decodes runs into `items`. Returns false, leaving the items as they are, if the runs don't
+/// cover exactly all of the items.
+pub(crate) fn decode_runs<T: Copy>(runs: &[(u32, T)], items: &mut [T]) -> bool {
+    if !runs_cover(runs, items.len()) {
+        return false;
+    }
+    let mut start = 0;
+    for (end, item) in runs.iter() {
+        items[start..*end as usize].fill(*item);
+        start = *end as usize;
+    }
+    true
+}
+
+impl<I: Copy + PartialEq> CompressedVoxels<I> {
+    pub fn encode(voxels: &VoxelArray<I>) -> Self {
+        Self {
+            runs: encode_runs(voxels),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    /// Voxels from runs that were encoded before, or `None` if the runs don't cover exactly
+    /// one chunk
+    pub fn from_runs(runs: Vec<(u32, WorldVoxel<I>)>) -> Option<Self> {
+        runs_cover(&runs, PaddedChunkShape::SIZE as usize).then_some(Self { runs })
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn runs(&self) -> &[(u32, WorldVoxel<I>)] {
+        &self.runs
+    }
+
+    pub fn decode(&self) -> VoxelArray<I> {
+        let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+        decode_runs(&self.runs, &mut voxels);
+        voxels
+    }
+
+    /// Looks up a single voxel by its linearized index, without decoding the whole chunk
+    pub fn get(&self, index: u32) -> WorldVoxel<I> {
+        let run = self.runs.partition_point(|(end, _)| *end <= index);
+        self.runs[run].1
+    }
+
+    /// The number of bytes taken up by the runs
+    #[cfg(feature = "render")]
+    pub fn size_in_bytes(&self) -> usize {
+        std::mem::size_of::<(u32, WorldVoxel<I>)>() * self.runs.len()
+    }
+}
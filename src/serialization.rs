@@ -0,0 +1,140 @@
+///
+/// Serialization
+/// `serde` support for chunk voxel data and voxel buffers, behind the `serde` feature. Voxels are
+/// stored run-length encoded, so that chunks of mostly air or stone take up little space in any
+/// serde format. The layout is versioned with `FORMAT_VERSION`, and only changes along with it,
+/// so it can be relied on for save files and network messages.
+///
+use bevy::prelude::*;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use std::{hash::Hash, sync::Arc};
+
+use crate::{
+    chunk::{ChunkData, FillType, PaddedChunkShape, VoxelArray},
+    light::{LightArray, VoxelLight},
+    rle::{decode_runs, encode_runs, CompressedVoxels},
+    voxel::WorldVoxel,
+    voxel_buffer::VoxelBuffer,
+};
+use ndshape::ConstShape;
+
+/// The version of the serialized layout of chunks and buffers
+pub const FORMAT_VERSION: u8 = 2;
+
+#[derive(Serialize, Deserialize)]
+enum SerializedVoxels<I> {
+    Empty,
+    Uniform(WorldVoxel<I>),
+    /// Runs of identical voxels, each with the exclusive end index of the run
+    Runs(Vec<(u32, WorldVoxel<I>)>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializedChunk<I> {
+    version: u8,
+    position: [i32; 3],
+    voxels: SerializedVoxels<I>,
+    light: Option<Vec<(u32, VoxelLight)>>,
+    biomes: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializedBuffer<I> {
+    version: u8,
+    size: [u32; 3],
+    voxels: Vec<(u32, WorldVoxel<I>)>,
+}
+
+fn check_version<E: Error>(version: u8) -> Result<(), E> {
+    if version != FORMAT_VERSION {
+        return Err(E::custom(format!(
+            "unsupported format version {version}, expected {FORMAT_VERSION}"
+        )));
+    }
+    Ok(())
+}
+
+/// Chunks are serialized with their position, voxels, light and biomes. The entity of the chunk
+/// is not serialized, and is `Entity::PLACEHOLDER` after deserializing.
+impl<I: Serialize + Copy + Hash + PartialEq> Serialize for ChunkData<I> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let voxels = match (&self.voxels, &self.compressed, &self.fill_type) {
+            (Some(voxels), _, _) => SerializedVoxels::Runs(encode_runs(&voxels[..])),
+            (None, Some(compressed), _) => SerializedVoxels::Runs(compressed.runs().to_vec()),
+            (None, None, FillType::Uniform(voxel)) => SerializedVoxels::Uniform(*voxel),
+            (None, None, _) => SerializedVoxels::Empty,
+        };
+        SerializedChunk {
+            version: FORMAT_VERSION,
+            position: self.position.to_array(),
+            voxels,
+            light: self.light.as_ref().map(|light| encode_runs(&light[..])),
+            biomes: self.biomes.as_ref().map(|biomes| biomes.to_vec()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, I: Deserialize<'de> + Copy + Hash + PartialEq> Deserialize<'de> for ChunkData<I> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let chunk = SerializedChunk::<I>::deserialize(deserializer)?;
+        check_version(chunk.version)?;
+
+        let mut chunk_data = ChunkData::new();
+        chunk_data.position = IVec3::from_array(chunk.position);
+
+        if let Some(runs) = chunk.light {
+            let mut light: LightArray = [VoxelLight::default(); PaddedChunkShape::SIZE as usize];
+            if !decode_runs(&runs, &mut light) {
+                return Err(D::Error::custom("light runs don't cover the chunk"));
+            }
+            chunk_data.light = Some(Arc::new(light));
+        }
+
+        if let Some(biomes) = chunk.biomes {
+            let biomes = biomes
+                .try_into()
+                .map_err(|_| D::Error::custom("wrong number of biomes"))?;
+            chunk_data.biomes = Some(Arc::new(biomes));
+        }
+
+        let voxels: VoxelArray<I> = match chunk.voxels {
+            SerializedVoxels::Empty => [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize],
+            SerializedVoxels::Uniform(voxel) => [voxel; PaddedChunkShape::SIZE as usize],
+            SerializedVoxels::Runs(runs) => CompressedVoxels::from_runs(runs)
+                .ok_or_else(|| D::Error::custom("voxel runs don't cover the chunk"))?
+                .decode(),
+        };
+        let filled_count = voxels
+            .iter()
+            .filter(|voxel| voxel.is_solid() || voxel.is_fluid())
+            .count() as u32;
+        chunk_data.store_voxels(&voxels, filled_count);
+
+        Ok(chunk_data)
+    }
+}
+
+impl<I: Serialize + Copy + PartialEq> Serialize for VoxelBuffer<I> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedBuffer {
+            version: FORMAT_VERSION,
+            size: self.size().to_array(),
+            voxels: encode_runs(self.voxels()),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, I: Deserialize<'de> + Copy + PartialEq> Deserialize<'de> for VoxelBuffer<I> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let buffer = SerializedBuffer::<I>::deserialize(deserializer)?;
+        check_version(buffer.version)?;
+
+        let mut voxel_buffer = VoxelBuffer::new(UVec3::from_array(buffer.size));
+        if !decode_runs(&buffer.voxels, voxel_buffer.voxels_mut()) {
+            return Err(D::Error::custom("voxel runs don't cover the buffer"));
+        }
+        Ok(voxel_buffer)
+    }
+}
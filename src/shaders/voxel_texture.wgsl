@@ -2,6 +2,7 @@
     pbr_fragment::pbr_input_from_standard_material,
     pbr_functions::alpha_discard,
     mesh_functions,
+    mesh_view_bindings::globals,
     view_transformations::position_world_to_clip
 }
 #import bevy_render::instance_index::get_instance_index
@@ -18,12 +19,24 @@
 }
 #endif
 
+// The share of the light that still reaches surfaces without any sky light, like cave walls
+const MIN_SKY_LIGHT: f32 = 0.05;
+
 @group(2) @binding(100)
 var mat_array_texture: texture_2d_array<f32>;
 
 @group(2) @binding(101)
 var mat_array_texture_sampler: sampler;
 
+@group(2) @binding(102)
+var<uniform> lod_fade: f32;
+
+@group(2) @binding(103)
+var<uniform> wind_strength: f32;
+
+@group(2) @binding(104)
+var<uniform> sky_light_intensity: f32;
+
 struct Vertex {
     @builtin(instance_index) instance_index: u32,
 #ifdef VERTEX_POSITIONS
@@ -52,7 +65,11 @@ struct Vertex {
     @builtin(vertex_index) index: u32,
 #endif
 
-    @location(8) tex_idx: vec3<u32>
+    @location(8) tex_idx: vec3<u32>,
+    @location(9) biome: u32,
+    @location(10) wind: f32,
+    @location(11) pbr: vec3<f32>,
+    @location(12) light: vec4<f32>,
 };
 
 struct CustomVertexOutput {
@@ -76,6 +93,35 @@ struct CustomVertexOutput {
 #endif
 
     @location(8) tex_idx: vec3<u32>,
+    @location(9) @interpolate(flat) biome: u32,
+    @location(10) @interpolate(flat) pbr: vec3<f32>,
+    @location(11) light: vec4<f32>,
+}
+
+#ifdef VOXEL_TRIPLANAR
+// Samples with explicit gradients, so that wrapping the coordinates doesn't cause seams
+fn sample_triplanar(coords: vec2<f32>, layer: u32) -> vec4<f32> {
+    return textureSampleGrad(
+        mat_array_texture,
+        mat_array_texture_sampler,
+        fract(coords),
+        layer,
+        dpdx(coords),
+        dpdy(coords)
+    );
+}
+#endif
+
+// Ordered dithering threshold in [0, 1) for the given fragment, from a 4x4 Bayer matrix
+fn dither_threshold(frag_coord: vec2<f32>) -> f32 {
+    var bayer = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0
+    );
+    let p = vec2<u32>(frag_coord) % 4u;
+    return (bayer[p.y * 4u + p.x] + 0.5) / 16.0;
 }
 
 @vertex
@@ -89,6 +135,12 @@ fn vertex(vertex: Vertex) -> CustomVertexOutput {
     out.world_position = mesh_functions::mesh_position_local_to_world(
         model, vec4<f32>(vertex.position, 1.0));
 
+    // Sway in the wind. The phase depends on the world position only, so vertices shared by
+    // neighbouring voxels move together.
+    let phase = globals.time * 1.7 + out.world_position.x * 0.35 + out.world_position.z * 0.25;
+    let sway = vec2<f32>(sin(phase), cos(phase * 0.8)) * wind_strength * vertex.wind;
+    out.world_position = vec4<f32>(out.world_position.xyz + vec3<f32>(sway.x, 0.0, sway.y), 1.0);
+
     out.position = position_world_to_clip(out.world_position.xyz);
         
 #ifdef VERTEX_UVS
@@ -110,6 +162,9 @@ fn vertex(vertex: Vertex) -> CustomVertexOutput {
 #endif
 
     out.tex_idx = vertex.tex_idx;
+    out.biome = vertex.biome;
+    out.pbr = vertex.pbr;
+    out.light = vertex.light;
 
     return out;
 }
@@ -124,24 +179,83 @@ fn fragment(
     standard_in.world_normal = in.world_normal;
     standard_in.world_position = in.world_position;
     standard_in.uv = in.uv;
+#ifdef VERTEX_TANGENTS
+    standard_in.world_tangent = in.world_tangent;
+#endif
     standard_in.color = in.color;
     standard_in.instance_index = in.instance_index;
     var pbr_input = pbr_input_from_standard_material(standard_in, is_front);
 
-    var tex_face = 0;
+    // The face is picked from the flat normal of the triangle, since smoothed vertex normals
+    // are rarely axis aligned. It is flipped to the side the interpolated normal points to, as
+    // the sign of the cross product depends on the screen space orientation.
+    var flat_normal = normalize(cross(dpdx(in.world_position.xyz), dpdy(in.world_position.xyz)));
+    flat_normal = select(flat_normal, -flat_normal, dot(flat_normal, in.world_normal) < 0.0);
 
-    // determine texture index based on normal
-    if in.world_normal.y == 0.0 {
+    // determine texture index based on the dominant axis of the face
+    var tex_face = 0;
+    if abs(flat_normal.y) < max(abs(flat_normal.x), abs(flat_normal.z)) {
         tex_face = 1;
-    } else if in.world_normal.y < 0.0 {
+    } else if flat_normal.y < 0.0 {
         tex_face = 2;
     }
 
-    pbr_input.material.base_color = textureSample(mat_array_texture, mat_array_texture_sampler, in.uv, in.tex_idx[tex_face]);
+    // Untextured faces (colored voxels) get their color from the vertex colors only.
+    // Sampling needs to happen in uniform control flow, so we always sample and then select.
+    let untextured = in.tex_idx[tex_face] == 0xFFFFFFFFu;
+    let layer = select(in.tex_idx[tex_face], 0u, untextured);
+
+#ifdef VOXEL_TRIPLANAR
+    // Project along each world axis and blend the samples by the normal
+    let blend = pow(abs(in.world_normal), vec3<f32>(4.0));
+    let weights = blend / (blend.x + blend.y + blend.z);
+    let pos = in.world_position.xyz;
+    let side_layer = select(in.tex_idx[1], 0u, untextured);
+    let top_layer = select(select(in.tex_idx[0], in.tex_idx[2], flat_normal.y < 0.0), 0u, untextured);
+    let texture_color = sample_triplanar(vec2<f32>(pos.z, -pos.y), side_layer) * weights.x
+        + sample_triplanar(pos.xz, top_layer) * weights.y
+        + sample_triplanar(vec2<f32>(pos.x, -pos.y), side_layer) * weights.z;
+#else
+    let texture_color = textureSample(mat_array_texture, mat_array_texture_sampler, in.uv, layer);
+#endif
+
+    pbr_input.material.base_color = select(texture_color, vec4<f32>(1.0), untextured);
     pbr_input.material.base_color = pbr_input.material.base_color * in.color;
 
+    // Negative values keep the properties of the base material
+    if in.pbr.x >= 0.0 {
+        pbr_input.material.perceptual_roughness = in.pbr.x;
+    }
+    if in.pbr.y >= 0.0 {
+        pbr_input.material.metallic = in.pbr.y;
+    }
+    if in.pbr.z >= 0.0 {
+        pbr_input.material.reflectance = in.pbr.z;
+    }
+
+    // Baked block light, from emissive voxels like torches, lights the surface on its own, and
+    // baked sky light, scaled by the time of day, darkens caves and overhangs
+    let block_light = in.light.yzw * in.light.yzw;
+    let sky = in.light.x * sky_light_intensity;
+    let sky_light = mix(MIN_SKY_LIGHT, 1.0, sky * sky);
+    pbr_input.material.emissive = vec4<f32>(
+        pbr_input.material.emissive.rgb + pbr_input.material.base_color.rgb * block_light,
+        pbr_input.material.emissive.a
+    );
+    pbr_input.material.base_color = vec4<f32>(
+        pbr_input.material.base_color.rgb * sky_light,
+        pbr_input.material.base_color.a
+    );
+
     pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
 
+    // Chunks fading in keep the fragments below `lod_fade`, and chunks fading out the fragments
+    // above `lod_fade - 1.0`, so that a chunk and its replacement complement each other
+    let threshold = dither_threshold(in.position.xy);
+    if (lod_fade < 1.0 && threshold >= lod_fade) || (lod_fade > 1.0 && threshold < lod_fade - 1.0) {
+        discard;
+    }
+
 #ifdef PREPASS_PIPELINE
     let out = deferred_output(in, pbr_input);
 #else
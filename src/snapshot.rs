@@ -0,0 +1,148 @@
+///
+/// Snapshots
+/// Whole worlds in a single archive, for sharing maps and for test fixtures. A snapshot holds the
+/// world's metadata and the voxels that differ from its generator, in the same encoding as
+/// persisted chunks. See `VoxelWorld::snapshot_to_file` and `VoxelWorld::load_snapshot`.
+///
+use bevy::{prelude::*, utils::HashMap};
+use std::{fs, io, path::Path};
+
+use crate::{
+    chunk::CHUNK_SIZE_I,
+    chunk_storage::{ChunkStorageProvider, Reader},
+    persistence::{decode_chunk, encode_chunk, ChunkCodec},
+    voxel::WorldVoxel,
+    world_metadata::WorldMetadata,
+};
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"BVWS";
+
+/// The metadata and saved chunks of a world
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorldSnapshot {
+    pub metadata: WorldMetadata,
+    /// The chunks as they are saved in a storage, with their codec byte
+    chunks: HashMap<IVec3, Vec<u8>>,
+}
+
+impl WorldSnapshot {
+    /// A snapshot of all chunks persisted in a storage, see `VoxelWorldPersistencePlugin`
+    pub fn from_storage(storage: &dyn ChunkStorageProvider) -> io::Result<Self> {
+        let mut chunks = HashMap::new();
+        for chunk_position in storage.list_chunks()? {
+            if let Some(bytes) = storage.load_chunk(chunk_position)? {
+                chunks.insert(chunk_position, bytes);
+            }
+        }
+        Ok(Self {
+            metadata: WorldMetadata::load(storage)?.unwrap_or_default(),
+            chunks,
+        })
+    }
+
+    /// Write the snapshot to a storage, replacing the saved chunks it contains
+    pub fn write_to_storage(&self, storage: &dyn ChunkStorageProvider) -> io::Result<()> {
+        for (chunk_position, bytes) in &self.chunks {
+            storage.save_chunk(*chunk_position, bytes.clone())?;
+        }
+        self.metadata.save(storage)?;
+        storage.flush()
+    }
+
+    pub fn read_from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::decode(&fs::read(path)?)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "corrupt world snapshot"))
+    }
+
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.encode())
+    }
+
+    /// The positions of the chunks in the snapshot
+    pub fn chunks(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    pub(crate) fn from_voxels<I: Into<u32> + Copy>(
+        metadata: WorldMetadata,
+        voxels: impl IntoIterator<Item = (IVec3, WorldVoxel<I>)>,
+    ) -> Self {
+        let mut by_chunk: HashMap<IVec3, Vec<(IVec3, WorldVoxel<I>)>> = HashMap::new();
+        for (position, voxel) in voxels {
+            let chunk_position = position.div_euclid(IVec3::splat(CHUNK_SIZE_I));
+            let local = position - chunk_position * CHUNK_SIZE_I;
+            by_chunk
+                .entry(chunk_position)
+                .or_default()
+                .push((local, voxel));
+        }
+        let chunks = by_chunk
+            .into_iter()
+            .map(|(chunk_position, voxels)| {
+                let bytes = encode_chunk(voxels.into_iter());
+                // Uncompressed chunks can't fail to compress
+                let bytes = ChunkCodec::None.compress(&bytes).unwrap();
+                (chunk_position, bytes)
+            })
+            .collect();
+        Self { metadata, chunks }
+    }
+
+    /// All voxels in the snapshot, by world position
+    pub(crate) fn voxels<I: TryFrom<u32>>(&self) -> io::Result<Vec<(IVec3, WorldVoxel<I>)>> {
+        let mut voxels = Vec::new();
+        for (chunk_position, bytes) in &self.chunks {
+            let chunk = decode_chunk(&ChunkCodec::decompress(bytes)?).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("corrupt voxel data for chunk {chunk_position}"),
+                )
+            })?;
+            voxels.extend(
+                chunk
+                    .into_iter()
+                    .map(|(local, voxel)| (*chunk_position * CHUNK_SIZE_I + local, voxel)),
+            );
+        }
+        Ok(voxels)
+    }
+
+    /// The metadata, followed by each chunk with its position and length
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(SNAPSHOT_MAGIC);
+        let metadata = self.metadata.encode();
+        bytes.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&metadata);
+        bytes.extend_from_slice(&(self.chunks.len() as u32).to_le_bytes());
+        for (chunk_position, chunk) in &self.chunks {
+            for coordinate in chunk_position.to_array() {
+                bytes.extend_from_slice(&coordinate.to_le_bytes());
+            }
+            bytes.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(chunk);
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes);
+        if reader.take(4)? != SNAPSHOT_MAGIC {
+            return None;
+        }
+        let len = reader.u32()? as usize;
+        let metadata = WorldMetadata::decode(reader.take(len)?)?;
+        let count = reader.u32()?;
+        let mut chunks = HashMap::new();
+        for _ in 0..count {
+            let chunk_position = IVec3::new(
+                reader.u32()? as i32,
+                reader.u32()? as i32,
+                reader.u32()? as i32,
+            );
+            let len = reader.u32()? as usize;
+            chunks.insert(chunk_position, reader.take(len)?.to_vec());
+        }
+        Some(Self { metadata, chunks })
+    }
+}
@@ -0,0 +1,157 @@
+///
+/// SQLite storage
+/// A `ChunkStorageProvider` that keeps a whole world in a single SQLite database, with one row
+/// per chunk. A single file is easier to back up and sync than a directory of region files.
+///
+use bevy::prelude::*;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::{
+    io,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+};
+
+use crate::chunk_storage::ChunkStorageProvider;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS chunks (
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        z INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (x, y, z)
+    ) WITHOUT ROWID;
+    CREATE TABLE IF NOT EXISTS metadata (
+        id INTEGER PRIMARY KEY CHECK (id = 0),
+        data BLOB NOT NULL
+    );
+";
+
+/// Keeps chunks in an SQLite database, indexed by chunk position. The chunks saved between two
+/// calls to `flush` are written in a single transaction, so a save pass only syncs the database
+/// once.
+pub struct SqliteChunkStorage {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteChunkStorage {
+    /// Open the database at the given path, creating it if it doesn't exist
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_connection(Connection::open(path).map_err(to_io_error)?)
+    }
+
+    /// A database that only lives in memory, for tests
+    pub fn open_in_memory() -> io::Result<Self> {
+        Self::from_connection(Connection::open_in_memory().map_err(to_io_error)?)
+    }
+
+    fn from_connection(connection: Connection) -> io::Result<Self> {
+        connection.execute_batch(SCHEMA).map_err(to_io_error)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// The connection, inside a transaction that lasts until the next `flush`
+    fn transaction(&self) -> rusqlite::Result<MutexGuard<'_, Connection>> {
+        let connection = self.connection.lock().unwrap();
+        if connection.is_autocommit() {
+            connection.execute_batch("BEGIN")?;
+        }
+        Ok(connection)
+    }
+}
+
+impl ChunkStorageProvider for SqliteChunkStorage {
+    fn load_chunk(&self, chunk_position: IVec3) -> io::Result<Option<Vec<u8>>> {
+        let IVec3 { x, y, z } = chunk_position;
+        self.connection
+            .lock()
+            .unwrap()
+            .prepare_cached("SELECT data FROM chunks WHERE x = ?1 AND y = ?2 AND z = ?3")
+            .and_then(|mut statement| {
+                statement
+                    .query_row(params![x, y, z], |row| row.get(0))
+                    .optional()
+            })
+            .map_err(to_io_error)
+    }
+
+    fn save_chunk(&self, chunk_position: IVec3, bytes: Vec<u8>) -> io::Result<()> {
+        let IVec3 { x, y, z } = chunk_position;
+        self.transaction()
+            .map_err(to_io_error)?
+            .prepare_cached("INSERT OR REPLACE INTO chunks (x, y, z, data) VALUES (?1, ?2, ?3, ?4)")
+            .and_then(|mut statement| statement.execute(params![x, y, z, bytes]))
+            .map(|_| ())
+            .map_err(to_io_error)
+    }
+
+    fn remove_chunk(&self, chunk_position: IVec3) -> io::Result<()> {
+        let IVec3 { x, y, z } = chunk_position;
+        self.transaction()
+            .map_err(to_io_error)?
+            .prepare_cached("DELETE FROM chunks WHERE x = ?1 AND y = ?2 AND z = ?3")
+            .and_then(|mut statement| statement.execute(params![x, y, z]))
+            .map(|_| ())
+            .map_err(to_io_error)
+    }
+
+    fn list_chunks(&self) -> io::Result<Vec<IVec3>> {
+        self.connection
+            .lock()
+            .unwrap()
+            .prepare_cached("SELECT x, y, z FROM chunks")
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| {
+                        Ok(IVec3::new(row.get(0)?, row.get(1)?, row.get(2)?))
+                    })?
+                    .collect()
+            })
+            .map_err(to_io_error)
+    }
+
+    fn load_metadata(&self) -> io::Result<Option<Vec<u8>>> {
+        self.connection
+            .lock()
+            .unwrap()
+            .query_row("SELECT data FROM metadata WHERE id = 0", [], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(to_io_error)
+    }
+
+    fn save_metadata(&self, bytes: Vec<u8>) -> io::Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT OR REPLACE INTO metadata (id, data) VALUES (0, ?1)",
+                params![bytes],
+            )
+            .map(|_| ())
+            .map_err(to_io_error)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        if connection.is_autocommit() {
+            return Ok(());
+        }
+        connection.execute_batch("COMMIT").map_err(to_io_error)
+    }
+}
+
+impl Drop for SqliteChunkStorage {
+    fn drop(&mut self) {
+        if let Err(error) = self.flush() {
+            warn!("Failed to commit saved chunks: {error}");
+        }
+    }
+}
+
+fn to_io_error(error: rusqlite::Error) -> io::Error {
+    io::Error::other(error)
+}
@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use std::time::Duration;
 
 use crate::chunk_map::ChunkMapUpdateBuffer;
 use crate::mesh_cache::MeshCacheInsertBuffer;
@@ -54,6 +55,334 @@ fn can_set_get_voxels() {
     app.update();
 }
 
+#[test]
+fn can_set_many_voxels_at_once() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let region = (0..4).flat_map(|x| (0..4).map(move |z| IVec3::new(x, 0, z)));
+        voxel_world.set_voxels(region.map(|pos| (pos, WorldVoxel::Solid(1))));
+        voxel_world.set_voxels([(IVec3::ZERO, WorldVoxel::Solid(2))]);
+
+        // The last voxel set at a position wins
+        assert_eq!(voxel_world.get_voxel(IVec3::ZERO), WorldVoxel::Solid(2));
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(3, 0, 3)),
+            WorldVoxel::Solid(1)
+        );
+        let get_voxel = voxel_world.get_voxel_fn();
+        assert_eq!(get_voxel(IVec3::ZERO), WorldVoxel::Solid(2));
+        assert_eq!(get_voxel(IVec3::new(3, 0, 3)), WorldVoxel::Solid(1));
+    });
+
+    app.update();
+}
+
+#[test]
+fn fill_shapes_set_voxels_inside() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.fill_box(IVec3::ZERO, IVec3::new(2, 2, 2), WorldVoxel::Solid(1));
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(2, 2, 2)),
+            WorldVoxel::Solid(1)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(3, 2, 2)),
+            WorldVoxel::Unset
+        );
+
+        voxel_world.fill_sphere(IVec3::new(20, 0, 0), 3, WorldVoxel::Solid(2));
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(23, 0, 0)),
+            WorldVoxel::Solid(2)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(22, 2, 2)),
+            WorldVoxel::Unset
+        );
+
+        voxel_world.fill_cylinder(IVec3::new(40, 0, 0), 2, 5, WorldVoxel::Solid(3));
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(42, 4, 0)),
+            WorldVoxel::Solid(3)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(40, 5, 0)),
+            WorldVoxel::Unset
+        );
+    });
+
+    app.update();
+}
+
+#[test]
+fn fill_line_sets_voxels_along_segment() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let start = Vec3::new(0.5, 0.5, 0.5);
+        voxel_world.fill_line(start, Vec3::new(10.5, 5.5, 0.5), 0, WorldVoxel::Solid(1));
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(0, 0, 0)),
+            WorldVoxel::Solid(1)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(10, 5, 0)),
+            WorldVoxel::Solid(1)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(0, 5, 0)),
+            WorldVoxel::Unset
+        );
+
+        voxel_world.fill_line(start, Vec3::new(0.5, 0.5, -20.5), 2, WorldVoxel::Air);
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(2, 0, -10)),
+            WorldVoxel::Air
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(2, 2, -10)),
+            WorldVoxel::Unset
+        );
+    });
+
+    app.update();
+}
+
+#[test]
+fn heightmap_fills_columns() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let heightmap = Heightmap::new(UVec2::new(2, 2), vec![1.0, 2.0, 0.0, 3.4]);
+        voxel_world.stamp_heightmap(&heightmap, IVec3::new(0, 5, 0), WorldVoxel::Solid(1));
+
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(1, 6, 0)),
+            WorldVoxel::Solid(1)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(1, 7, 0)),
+            WorldVoxel::Unset
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(0, 5, 1)),
+            WorldVoxel::Unset
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(1, 7, 1)),
+            WorldVoxel::Solid(1)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(1, 8, 1)),
+            WorldVoxel::Unset
+        );
+    });
+
+    app.update();
+}
+
+#[test]
+fn closed_mesh_is_voxelized_solid() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let mesh = Mesh::from(Cuboid::new(8.0, 8.0, 8.0));
+        let transform = Transform::from_xyz(4.0, 4.0, 4.0);
+        voxel_world.voxelize_mesh(&mesh, &transform, WorldVoxel::Solid(1));
+
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(0, 0, 0)),
+            WorldVoxel::Solid(1)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(4, 4, 4)),
+            WorldVoxel::Solid(1)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(7, 7, 7)),
+            WorldVoxel::Solid(1)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(4, 8, 4)),
+            WorldVoxel::Unset
+        );
+    });
+
+    app.update();
+}
+
+#[test]
+fn brush_falloff_changes_density() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let brush = VoxelBrush::sphere(4.0, WorldVoxel::Solid(1)).with_falloff(1.0);
+        voxel_world.apply_brush(IVec3::ZERO, &brush);
+        assert_eq!(voxel_world.get_voxel(IVec3::ZERO), WorldVoxel::Solid(1));
+        assert_eq!(voxel_world.get_voxel(IVec3::new(2, 0, 0)).density(), 128);
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(5, 0, 0)),
+            WorldVoxel::Unset
+        );
+
+        voxel_world.apply_brush(
+            IVec3::ZERO,
+            &VoxelBrush::cube(1.0, WorldVoxel::Air).subtractive(),
+        );
+        assert_eq!(voxel_world.get_voxel(IVec3::new(1, 1, 1)), WorldVoxel::Air);
+        assert_eq!(voxel_world.get_voxel(IVec3::new(2, 0, 0)).density(), 128);
+    });
+
+    app.update();
+}
+
+#[test]
+fn buffer_can_be_pasted_rotated() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let mut buffer = VoxelBuffer::new(UVec3::new(3, 2, 1));
+        buffer.fill(UVec3::ZERO, UVec3::new(2, 0, 0), WorldVoxel::Solid(1));
+        buffer.set(
+            UVec3::new(2, 1, 0),
+            WorldVoxel::Oriented(2, VoxelRotation::Deg0),
+        );
+
+        let origin = IVec3::new(31, 0, 0);
+        voxel_world.paste(&buffer, origin, VoxelRotation::Deg0);
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(33, 0, 0)),
+            WorldVoxel::Solid(1)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(31, 1, 0)),
+            WorldVoxel::Unset
+        );
+
+        let origin = IVec3::new(0, 10, 0);
+        voxel_world.paste(&buffer, origin, VoxelRotation::Deg90);
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(0, 10, -2)),
+            WorldVoxel::Solid(1)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(0, 11, -2)),
+            WorldVoxel::Oriented(2, VoxelRotation::Deg90)
+        );
+    });
+
+    app.update();
+}
+
+#[test]
+fn buffers_can_be_combined() {
+    let mut shape = VoxelBuffer::new(UVec3::new(4, 1, 1));
+    shape.fill(UVec3::ZERO, UVec3::new(3, 0, 0), WorldVoxel::Solid(1));
+    let mut tool = VoxelBuffer::new(UVec3::new(2, 1, 1));
+    tool.fill(UVec3::ZERO, UVec3::new(1, 0, 0), WorldVoxel::Solid(2));
+
+    let mut union = shape.clone();
+    union.combine(CsgOperation::Union, &tool, IVec3::new(1, 0, 0));
+    assert_eq!(union.get(UVec3::new(0, 0, 0)), WorldVoxel::Solid(1));
+    assert_eq!(union.get(UVec3::new(2, 0, 0)), WorldVoxel::Solid(2));
+
+    let mut intersection = shape.clone();
+    intersection.combine(CsgOperation::Intersection, &tool, IVec3::new(1, 0, 0));
+    assert_eq!(intersection.get(UVec3::new(0, 0, 0)), WorldVoxel::Unset);
+    assert_eq!(intersection.get(UVec3::new(1, 0, 0)), WorldVoxel::Solid(1));
+
+    let mut subtraction = shape;
+    subtraction.combine(CsgOperation::Subtraction, &tool, IVec3::new(1, 0, 0));
+    assert_eq!(subtraction.get(UVec3::new(0, 0, 0)), WorldVoxel::Solid(1));
+    assert_eq!(subtraction.get(UVec3::new(2, 0, 0)), WorldVoxel::Unset);
+    assert_eq!(subtraction.get(UVec3::new(3, 0, 0)), WorldVoxel::Solid(1));
+}
+
+#[test]
+fn copied_region_can_be_pasted() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.fill_box(IVec3::ZERO, IVec3::new(1, 1, 1), WorldVoxel::Solid(1));
+        voxel_world.set_voxel(IVec3::new(1, 1, 1), WorldVoxel::Solid(2));
+
+        let buffer = voxel_world.cut_region(IVec3::ZERO, IVec3::new(1, 1, 1));
+        assert_eq!(buffer.size(), UVec3::splat(2));
+        assert_eq!(buffer.get(UVec3::ONE), WorldVoxel::Solid(2));
+        assert_eq!(voxel_world.get_voxel(IVec3::ONE), WorldVoxel::Air);
+
+        voxel_world.paste(&buffer, IVec3::new(100, 0, 0), VoxelRotation::Deg0);
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(101, 1, 1)),
+            WorldVoxel::Solid(2)
+        );
+    });
+
+    app.update();
+}
+
+#[test]
+fn transaction_commits_or_aborts_all_edits() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let position = IVec3::new(0, 0, 0);
+        voxel_world.edit(|tx| {
+            tx.set_voxel(position, WorldVoxel::Solid(1));
+            assert_eq!(tx.get_voxel(position), WorldVoxel::Solid(1));
+            tx.set_voxel(position + IVec3::X, WorldVoxel::Solid(2));
+        });
+        assert_eq!(voxel_world.get_voxel(position), WorldVoxel::Solid(1));
+        assert_eq!(
+            voxel_world.get_voxel(position + IVec3::X),
+            WorldVoxel::Solid(2)
+        );
+
+        voxel_world.edit(|tx| {
+            tx.set_voxel(position, WorldVoxel::Air);
+            tx.abort();
+        });
+        assert_eq!(voxel_world.get_voxel(position), WorldVoxel::Solid(1));
+    });
+
+    app.update();
+}
+
+#[derive(Resource, Clone, Default)]
+struct DecoratedWorld;
+
+impl VoxelWorldConfig for DecoratedWorld {
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
+        Box::new(|_| Box::new(|_, _| WorldVoxel::Air))
+    }
+
+    fn chunk_decorator(&self) -> Option<ChunkDecorator> {
+        Some(std::sync::Arc::new(|voxels| {
+            voxels.set(IVec3::ZERO, WorldVoxel::Solid(7));
+        }))
+    }
+}
+
+#[test]
+fn copied_region_is_decorated_when_not_loaded() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<DecoratedWorld>::minimal(),
+    ));
+
+    app.add_systems(Update, |voxel_world: VoxelWorld<DecoratedWorld>| {
+        let buffer = voxel_world.copy_region(IVec3::new(320, 0, 0), IVec3::new(321, 0, 0));
+        assert_eq!(buffer.get(UVec3::ZERO), WorldVoxel::Solid(7));
+        assert_eq!(buffer.get(UVec3::X), WorldVoxel::Air);
+    });
+
+    app.update();
+}
+
 #[test]
 fn set_voxel_can_be_found_by_2d_coordinate() {
     let mut app = _test_setup_app();
@@ -102,346 +431,2012 @@ fn set_voxel_can_be_found_by_2d_coordinate() {
     app.update();
 }
 
-// ChunkWillSpawn event now fires from the mesh spawning system, which cannot run in tests.
-#[ignore]
 #[test]
-fn chunk_will_spawn_events() {
-    let mut app = _test_setup_app();
+fn colored_voxel_packed_rgb() {
+    let voxel = WorldVoxel::<u8>::from_packed_rgb(0x12ab34);
+    assert_eq!(voxel, WorldVoxel::Colored([0x12, 0xab, 0x34]));
+    assert_eq!(voxel.packed_rgb(), Some(0x12ab34));
+    assert!(voxel.is_solid());
+    assert_eq!(WorldVoxel::Solid(1).packed_rgb(), None);
+}
 
-    app.add_systems(
-        Update,
-        |mut ev_chunk_will_spawn: EventReader<ChunkWillSpawn<DefaultWorld>>| {
-            let spawn_count = ev_chunk_will_spawn.read().count();
-            assert!(spawn_count > 0);
-        },
-    );
+#[test]
+fn reused_meshing_buffer_does_not_leak_between_chunks() {
+    use crate::chunk::PaddedChunkShape;
+    use crate::meshing::{generate_chunk_mesh, MeshingConfig};
+    use ndshape::ConstShape;
+    use std::sync::Arc;
 
-    app.update();
+    let mut full = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+    for i in 0..PaddedChunkShape::SIZE {
+        let [x, y, z] = PaddedChunkShape::delinearize(i);
+        if (x + y + z) % 2 == 0 {
+            full[i as usize] = WorldVoxel::Solid(1);
+        }
+    }
+    let mut single = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+    single[PaddedChunkShape::linearize([5, 5, 5]) as usize] = WorldVoxel::Solid(1);
+    let meshing_config = MeshingConfig::from_config(&DefaultWorld);
+
+    // Both chunks are meshed on this thread, the second one with the first one's buffer
+    let full_mesh = generate_chunk_mesh(Arc::new(full), None, None, IVec3::ZERO, &meshing_config);
+    let single_mesh =
+        generate_chunk_mesh(Arc::new(single), None, None, IVec3::ZERO, &meshing_config);
+    assert!(full_mesh.opaque.count_vertices() > 24);
+    assert_eq!(single_mesh.opaque.count_vertices(), 24);
 }
 
 #[test]
-fn chunk_will_remesh_event_after_set_voxel() {
-    let mut app = _test_setup_app();
+fn smoothed_normals_match_across_chunk_borders() {
+    use crate::chunk::PaddedChunkShape;
+    use crate::meshing::{generate_chunk_mesh, MeshingConfig};
+    use bevy::render::mesh::VertexAttributeValues;
+    use bevy::utils::HashMap;
+    use ndshape::ConstShape;
+    use std::sync::Arc;
 
-    // Run the world a 100 cycles
-    for _ in 0..100 {
-        app.update();
+    // Terrain stepping up along the x axis, with a step on the border of the two chunks
+    let height = |x: i32, z: i32| 8 + x / 2 + z % 3;
+    let mut meshing_config = MeshingConfig::from_config(&DefaultWorld);
+    meshing_config.smooth_normals = true;
+
+    // The normals of each vertex of the chunk's mesh, by world position
+    let chunk_normals = |chunk_position: IVec3| {
+        let origin = chunk_position * 32 - 1;
+        let mut voxels = [WorldVoxel::Air; PaddedChunkShape::SIZE as usize];
+        for (i, voxel) in voxels.iter_mut().enumerate() {
+            let position =
+                origin + IVec3::from(PaddedChunkShape::delinearize(i as u32).map(|c| c as i32));
+            if position.y < height(position.x, position.z) {
+                *voxel = WorldVoxel::Solid(1);
+            }
+        }
+        let meshes = generate_chunk_mesh(
+            Arc::new(voxels),
+            None,
+            None,
+            chunk_position,
+            &meshing_config,
+        );
+        let (
+            Some(VertexAttributeValues::Float32x3(positions)),
+            Some(VertexAttributeValues::Float32x3(normals)),
+        ) = (
+            meshes.opaque.attribute(Mesh::ATTRIBUTE_POSITION),
+            meshes.opaque.attribute(Mesh::ATTRIBUTE_NORMAL),
+        )
+        else {
+            panic!("chunk meshes should have positions and normals");
+        };
+        positions
+            .iter()
+            .zip(normals)
+            .map(|(position, normal)| {
+                let position = Vec3::from(*position).round().as_ivec3() + origin;
+                (position, Vec3::from(*normal))
+            })
+            .collect::<HashMap<IVec3, Vec3>>()
+    };
+
+    let left = chunk_normals(IVec3::ZERO);
+    let right = chunk_normals(IVec3::X);
+    let mut shared = 0;
+    for (position, normal) in left.iter().filter(|(position, _)| position.x == 32) {
+        if let Some(other) = right.get(position) {
+            assert!(
+                normal.distance(*other) < 1e-5,
+                "{position}: {normal} != {other}"
+            );
+            shared += 1;
+        }
     }
+    assert!(shared > 0);
+}
 
-    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
-        voxel_world.set_voxel(IVec3::new(0, 0, 0), WorldVoxel::Solid(1));
-    });
+fn _spawned_chunks<C: WorldConfig>(app: &mut App) -> Vec<crate::chunk::Chunk<C>> {
+    let mut chunks = app.world_mut().query::<&crate::chunk::Chunk<C>>();
+    chunks.iter(app.world()).cloned().collect()
+}
 
-    app.update();
+#[derive(Resource, Clone, Default)]
+struct LodRingsWorld;
 
-    app.add_systems(
-        Update,
-        |mut ev_chunk_will_remesh: EventReader<ChunkWillRemesh<DefaultWorld>>| {
-            let count = ev_chunk_will_remesh.read().count();
-            assert!(count > 0)
-        },
-    );
+impl VoxelWorldConfig for LodRingsWorld {
+    fn lod_distances(&self) -> Vec<u32> {
+        vec![2, 4]
+    }
 
-    app.update();
+    fn chunk_spawn_strategy(&self) -> ChunkSpawnStrategy {
+        ChunkSpawnStrategy::Close
+    }
 }
 
 #[test]
-fn chunk_will_despawn_event() {
-    let mut app = _test_setup_app();
+fn lod_rings_tile_the_world_around_the_camera() {
+    use crate::lod::lod_chunks_overlap;
 
-    // move camera to simulate chunks going out of view
-    app.add_systems(
-        Update,
-        |mut query: Query<&mut GlobalTransform, With<VoxelWorldCamera<DefaultWorld>>>| {
-            for mut transform in query.iter_mut() {
-                // Not sure why, but when running tests, bevy won't update the GlobalTransform
-                // when a Transform has changed, so as a workaround we set it here directly.
-                let tf = Transform::from_xyz(1000.0, 1000.0, 1000.0);
-                *transform = GlobalTransform::from(tf);
-            }
-        },
-    );
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<LodRingsWorld>::minimal()));
+    app.world_mut().spawn((
+        Camera3dBundle::default(),
+        VoxelWorldCamera::<LodRingsWorld>::default(),
+    ));
 
-    app.update();
+    for _ in 0..3 {
+        app.update();
+    }
 
-    app.add_systems(
-        Update,
-        |mut ev_chunk_will_despawn: EventReader<ChunkWillDespawn<DefaultWorld>>| {
-            let count = ev_chunk_will_despawn.read().count();
-            assert!(count > 0)
-        },
-    );
+    let chunks: Vec<(u8, IVec3)> = _spawned_chunks::<LodRingsWorld>(&mut app)
+        .iter()
+        .map(|chunk| (chunk.lod_level, chunk.position))
+        .collect();
+    assert!(chunks.iter().any(|(level, _)| *level == 0));
+    assert!(chunks.iter().any(|(level, _)| *level == 1));
+    // Full detail close to the camera, half detail further out
+    assert!(chunks
+        .iter()
+        .filter(|(level, _)| *level == 0)
+        .all(|(_, position)| position.abs().max_element() <= 2));
 
-    app.update();
+    // Every part of the world within the rings is covered by exactly one chunk
+    for x in -4..=5 {
+        for y in -4..=5 {
+            for z in -4..=5 {
+                let covering = chunks
+                    .iter()
+                    .filter(|chunk| lod_chunks_overlap(**chunk, (0, IVec3::new(x, y, z))))
+                    .count();
+                assert_eq!(covering, 1, "chunk {:?}", IVec3::new(x, y, z));
+            }
+        }
+    }
 }
 
 #[test]
-fn raycast_finds_voxel() {
-    let mut app = _test_setup_app();
-
-    // Set up vector och positions to test
-    let positions = vec![IVec3::new(0, 0, -1), IVec3::new(0, 0, 0)];
-
-    let make_pos = positions.clone();
+fn lod_rings_follow_every_camera() {
+    use crate::lod::lod_chunks_overlap;
 
-    app.add_systems(
-        Startup,
-        move |mut voxel_world: VoxelWorld<DefaultWorld>,
-              buffers: (
-            ResMut<ChunkMapUpdateBuffer<DefaultWorld>>,
-            ResMut<MeshCacheInsertBuffer<DefaultWorld>>,
-        )| {
-            let test_voxel = crate::voxel::WorldVoxel::Solid(1);
-
-            for pos in make_pos.clone() {
-                voxel_world.set_voxel(pos, test_voxel);
-            }
-
-            let (mut chunk_map_update_buffer, _) = buffers;
-
-            chunk_map_update_buffer.push((
-                IVec3::new(0, 0, 0),
-                ChunkData {
-                    position: IVec3::new(0, 0, 0),
-                    voxels: Some(std::sync::Arc::new([WorldVoxel::Unset; 39304])),
-                    voxels_hash: 0,
-                    is_full: false,
-                    is_empty: false,
-                    fill_type: FillType::Mixed,
-                    entity: Entity::PLACEHOLDER,
-                },
-                ChunkWillSpawn::<DefaultWorld>::new(IVec3::new(0, 0, 0), Entity::PLACEHOLDER),
-            ));
-        },
-    );
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<LodRingsWorld>::minimal()));
+    for camera_x in [0.0, 6.0 * 32.0] {
+        app.world_mut().spawn((
+            Camera3dBundle {
+                transform: Transform::from_xyz(camera_x, 0.0, 0.0),
+                ..default()
+            },
+            VoxelWorldCamera::<LodRingsWorld>::default(),
+        ));
+    }
 
-    app.update();
+    let chunks = |app: &mut App| -> Vec<(u8, IVec3)> {
+        _spawned_chunks::<LodRingsWorld>(app)
+            .iter()
+            .map(|chunk| (chunk.lod_level, chunk.position))
+            .collect()
+    };
+    let covering = |chunks: &[(u8, IVec3)], position: IVec3| {
+        chunks
+            .iter()
+            .filter(|chunk| lod_chunks_overlap(**chunk, (0, position)))
+            .count()
+    };
 
-    app.add_systems(Update, move |voxel_world: VoxelWorld<DefaultWorld>| {
-        let test_voxel = crate::voxel::WorldVoxel::Solid(1);
+    // Both cameras have full detail around them, and every part of the world within the rings
+    // of either camera is covered by exactly one chunk
+    _update_until(&mut app, |app| {
+        let chunks = chunks(app);
+        (-4..=11).all(|x| {
+            (-4..=5).all(|y| (-4..=5).all(|z| covering(&chunks, IVec3::new(x, y, z)) == 1))
+        })
+    });
+    let chunks = chunks(&mut app);
+    assert!(chunks.contains(&(0, IVec3::ZERO)));
+    assert!(chunks.contains(&(0, IVec3::new(6, 0, 0))));
+}
 
-        let ray = Ray3d {
-            origin: Vec3::new(0.5, 0.5, 70.0),
-            direction: -Dir3::Z,
-        };
+#[test]
+fn skirts_hang_from_chunk_borders_with_levels_of_detail() {
+    use crate::chunk::PaddedChunkShape;
+    use crate::meshing::{generate_chunk_mesh, MeshingConfig};
+    use bevy::render::mesh::VertexAttributeValues;
+    use ndshape::ConstShape;
+    use std::sync::Arc;
 
-        let Some(result) = voxel_world.raycast(ray, &|(_pos, _vox)| true) else {
-            panic!("No voxel found")
+    // Flat ground, continuing into the neighbouring chunks
+    let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+    for i in 0..PaddedChunkShape::SIZE {
+        let [_, y, _] = PaddedChunkShape::delinearize(i);
+        if y <= 10 {
+            voxels[i as usize] = WorldVoxel::Solid(1);
+        }
+    }
+    let heights = |meshing_config: &MeshingConfig<u8>| -> (f32, f32) {
+        let meshes = generate_chunk_mesh(Arc::new(voxels), None, None, IVec3::ZERO, meshing_config);
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            meshes.opaque.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("chunk meshes should have positions");
         };
+        positions
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), [_, y, _]| {
+                (min.min(*y), max.max(*y))
+            })
+    };
 
-        assert_eq!(
-            result,
-            VoxelRaycastResult {
-                position: Vec3::ZERO,
-                normal: Some(Vec3::new(0.0, 0.0, 1.0)),
-                voxel: test_voxel,
-            }
-        )
-    });
+    // Without levels of detail, only the top of the ground is meshed
+    let (min, max) = heights(&MeshingConfig::from_config(&DefaultWorld));
+    assert_eq!(min, max);
 
-    app.update();
+    // With levels of detail, skirts hang down from the top along the borders of the chunk
+    let (min, max) = heights(&MeshingConfig::from_config(&LodRingsWorld));
+    assert_eq!(max - min, LodRingsWorld.lod_skirt_depth());
 }
 
-struct VisitVoxelTestState<'a> {
-    test_name: &'a str,
-    expected_path: &'a [IVec3],
-    expected_face: Option<VoxelFace>,
-    path_step_index: usize,
-    traversal_time_out: Timer,
-}
+#[derive(Resource, Clone, Default)]
+struct ScreenSpaceErrorWorld;
 
-impl<'a> VisitVoxelTestState<'a> {
-    fn new(
-        test_name: &'a str,
-        expected_path: &'a [IVec3],
-        expected_face: Option<VoxelFace>,
-    ) -> Self {
-        VisitVoxelTestState {
-            test_name,
-            expected_path,
-            expected_face,
-            path_step_index: 0,
-            traversal_time_out: Timer::from_seconds(1., TimerMode::Once),
-        }
+impl VoxelWorldConfig for ScreenSpaceErrorWorld {
+    fn lod_distances(&self) -> Vec<u32> {
+        vec![2, 4, 8]
+    }
+
+    fn lod_selection(&self) -> LodSelection {
+        LodSelection::ScreenSpaceError(4.0)
     }
 }
 
-fn visit_voxel_check(
-    test_state: &mut VisitVoxelTestState,
-    voxel_coords: IVec3,
-    time: f32,
-    face: VoxelFace,
-) -> bool {
-    // println!(
-    //     "Traversed {:?} at {} through {:?}",
-    //     voxel_coords, time, face
-    // );
+#[test]
+fn screen_space_error_splits_rough_chunks() {
+    use crate::lod::{LodCamera, LodRings, LodView};
+    use bevy::utils::HashMap;
 
-    assert!(
-        0. <= time,
-        "{}: Time must always be >= 0",
-        test_state.test_name
-    );
-    assert!(
-        time <= 1.,
-        "{}: Time must always be <= 1",
-        test_state.test_name
-    );
-    assert!(
-        !test_state.traversal_time_out.finished(),
-        "{}: Infinite loop detected (bc. such a simple trace should be much faster than 1s)",
-        test_state.test_name
-    );
-    assert!(
-        test_state.path_step_index == 0 || test_state.expected_face.unwrap_or(face) == face,
-        "{}: Expected entering through {:?}",
-        test_state.test_name,
-        test_state.expected_face
+    let rings = LodRings::from_config(&ScreenSpaceErrorWorld).unwrap();
+    let split_chunks = HashMap::new();
+    let mut geometric_errors = HashMap::new();
+    // A cliff on one side of the camera, and flat terrain on the other
+    geometric_errors.insert((2, IVec3::new(1, 0, 0)), 10.0);
+    geometric_errors.insert((2, IVec3::new(-2, 0, 0)), 0.1);
+
+    let view = LodView {
+        cameras: vec![LodCamera {
+            position: Vec3::ZERO,
+            pixels_per_unit: 500.0,
+        }],
+        geometric_errors: &geometric_errors,
+        split_chunks: &split_chunks,
+    };
+
+    // The cliff is split into finer chunks, the flat terrain stays coarse
+    assert_eq!(
+        rings.chunk_at(Vec3::new(200.0, 10.0, 10.0), &view),
+        Some((1, IVec3::new(3, 0, 0)))
     );
-    assert!(
-        test_state.path_step_index < test_state.expected_path.len(),
-        "{}: Expected path is not same length",
-        test_state.test_name
+    assert_eq!(
+        rings.chunk_at(Vec3::new(-200.0, 10.0, 10.0), &view),
+        Some((2, IVec3::new(-2, 0, 0)))
     );
+
+    // Chunks that haven't been generated have no known error, and are not split
     assert_eq!(
-        voxel_coords, test_state.expected_path[test_state.path_step_index],
-        "{}: Found unexpected step in path",
-        test_state.test_name
+        rings.chunk_at(Vec3::new(10.0, 10.0, 10.0), &view),
+        Some((2, IVec3::ZERO))
     );
-    test_state.path_step_index += 1;
+}
 
-    true
+#[derive(Resource, Clone, Default)]
+struct HysteresisWorld;
+
+impl VoxelWorldConfig for HysteresisWorld {
+    fn spawning_distance(&self) -> u32 {
+        2
+    }
+
+    fn chunk_spawn_strategy(&self) -> ChunkSpawnStrategy {
+        ChunkSpawnStrategy::Close
+    }
+
+    fn lod_hysteresis(&self) -> u32 {
+        1
+    }
 }
 
 #[test]
-fn voxel_line_traversal_along_cartesian_axes() {
-    let start = Vec3::splat(VOXEL_SIZE / 2.);
+fn chunks_just_out_of_range_are_kept_by_hysteresis() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<HysteresisWorld>::minimal(),
+    ));
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<HysteresisWorld>::default(),
+        ))
+        .id();
 
-    {
-        let end = Vec3::new(
-            2. * VOXEL_SIZE + VOXEL_SIZE / 2.,
-            VOXEL_SIZE / 2.,
-            VOXEL_SIZE / 2.,
-        );
-        let expected_path = [
-            IVec3::new(0, 0, 0),
-            IVec3::new(1, 0, 0),
-            IVec3::new(2, 0, 0),
-        ];
+    for _ in 0..3 {
+        app.update();
+    }
 
-        let mut test_state = VisitVoxelTestState::new(
-            "Aligned with cartesian X",
-            &expected_path,
-            Some(VoxelFace::Left),
-        );
-        voxel_line_traversal(start, end, |voxel_coords, time, face| {
-            visit_voxel_check(&mut test_state, voxel_coords, time, face)
-        });
-        assert_eq!(
-            test_state.path_step_index,
-            expected_path.len(),
-            "{}: Expected end voxel reached",
-            test_state.test_name
-        );
+    // Three chunks further, the chunks that were right at the camera are one chunk past the
+    // spawning distance
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_xyz(3.0 * 32.0 + 16.0, 0.0, 0.0);
+    for _ in 0..3 {
+        app.update();
     }
 
-    {
-        let end = Vec3::new(
-            VOXEL_SIZE / 2.,
-            2. * VOXEL_SIZE + VOXEL_SIZE / 2.,
-            VOXEL_SIZE / 2.,
-        );
-        let expected_path = [
-            IVec3::new(0, 0, 0),
-            IVec3::new(0, 1, 0),
-            IVec3::new(0, 2, 0),
-        ];
+    let positions: Vec<IVec3> = _spawned_chunks::<HysteresisWorld>(&mut app)
+        .iter()
+        .map(|chunk| chunk.position)
+        .collect();
+    assert!(positions.contains(&IVec3::ZERO));
+    assert!(!positions.contains(&IVec3::new(-1, 0, 0)));
+    assert!(positions.contains(&IVec3::new(5, 0, 0)));
+}
 
-        let mut test_state = VisitVoxelTestState::new(
-            "Aligned with cartesian Y",
-            &expected_path,
-            Some(VoxelFace::Bottom),
-        );
-        voxel_line_traversal(start, end, |voxel_coords, time, face| {
-            visit_voxel_check(&mut test_state, voxel_coords, time, face)
-        });
-        assert_eq!(
-            test_state.path_step_index,
-            expected_path.len(),
-            "{}: Expected end voxel reached",
-            test_state.test_name
-        );
+#[derive(Resource, Clone, Default)]
+struct LodFadeWorld;
+
+impl VoxelWorldConfig for LodFadeWorld {
+    fn lod_distances(&self) -> Vec<u32> {
+        vec![1, 2]
     }
 
-    {
-        let end = Vec3::new(
-            VOXEL_SIZE / 2.,
-            VOXEL_SIZE / 2.,
-            2. * VOXEL_SIZE + VOXEL_SIZE / 2.,
-        );
-        let expected_path = [
-            IVec3::new(0, 0, 0),
-            IVec3::new(0, 0, 1),
-            IVec3::new(0, 0, 2),
-        ];
+    fn lod_fade_duration(&self) -> f32 {
+        0.5
+    }
 
-        let mut test_state = VisitVoxelTestState::new(
-            "Aligned with cartesian Z",
-            &expected_path,
-            Some(VoxelFace::Back),
-        );
-        voxel_line_traversal(start, end, |voxel_coords, time, face| {
-            visit_voxel_check(&mut test_state, voxel_coords, time, face)
-        });
-        assert_eq!(
-            test_state.path_step_index,
-            expected_path.len(),
-            "{}: Expected end voxel reached",
-            test_state.test_name
-        );
+    fn teleport_distance(&self) -> u32 {
+        1000
     }
 }
 
 #[test]
-fn voxel_line_traversal_ending_on_voxel_boundary() {
-    let start = Vec3::new(-5. * VOXEL_SIZE, VOXEL_SIZE / 2., 1.9815);
-    let end = Vec3::new(0., 0., 50. * VOXEL_SIZE);
-    let expected_path = [
-        IVec3::new(-5, 0, 1),
-        IVec3::new(-5, 0, 2),
-        IVec3::new(-5, 0, 3),
-        IVec3::new(-5, 0, 4),
-        IVec3::new(-5, 0, 5),
-        IVec3::new(-5, 0, 6),
-        IVec3::new(-5, 0, 7),
-        IVec3::new(-5, 0, 8),
-        IVec3::new(-5, 0, 9),
-        IVec3::new(-5, 0, 10),
-        IVec3::new(-5, 0, 11),
-        IVec3::new(-4, 0, 11),
-        IVec3::new(-4, 0, 12),
-        IVec3::new(-4, 0, 13),
-        IVec3::new(-4, 0, 14),
-        IVec3::new(-4, 0, 15),
-        IVec3::new(-4, 0, 16),
-        IVec3::new(-4, 0, 17),
-        IVec3::new(-4, 0, 18),
-        IVec3::new(-4, 0, 19),
-        IVec3::new(-4, 0, 20),
-        IVec3::new(-4, 0, 21),
-        IVec3::new(-3, 0, 21),
-        IVec3::new(-3, 0, 22),
-        IVec3::new(-3, 0, 23),
-        IVec3::new(-3, 0, 24),
-        IVec3::new(-3, 0, 25),
-        IVec3::new(-3, 0, 26),
-        IVec3::new(-3, 0, 27),
-        IVec3::new(-3, 0, 28),
-        IVec3::new(-3, 0, 29),
-        IVec3::new(-3, 0, 30),
-        IVec3::new(-2, 0, 30),
-        IVec3::new(-2, 0, 31),
-        IVec3::new(-2, 0, 32),
+fn only_replaced_lod_chunks_fade_out() {
+    use crate::chunk::Chunk;
+    use crate::lod::{lod_chunks_overlap, LodFade};
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<LodFadeWorld>::minimal()));
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<LodFadeWorld>::default(),
+        ))
+        .id();
+
+    // The minimal plugin doesn't render, so give the chunks a mesh to fade out with
+    let update_with_meshes = |app: &mut App| {
+        app.update();
+        let mut chunks = app
+            .world_mut()
+            .query_filtered::<Entity, (With<Chunk<LodFadeWorld>>, Without<Handle<Mesh>>)>();
+        let chunks: Vec<Entity> = chunks.iter(app.world()).collect();
+        for entity in chunks {
+            app.world_mut()
+                .entity_mut(entity)
+                .insert(Handle::<Mesh>::default());
+        }
+    };
+    let move_camera = |app: &mut App, x: f32| {
+        *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+            GlobalTransform::from_xyz(x, 0.0, 0.0);
+    };
+    let fading_out = |app: &mut App| -> Vec<(u8, IVec3)> {
+        let mut fades = app.world_mut().query::<&LodFade<LodFadeWorld>>();
+        fades
+            .iter(app.world())
+            .filter(|fade| fade.fading_out)
+            .map(|fade| fade.chunk)
+            .collect()
+    };
+
+    for _ in 0..3 {
+        update_with_meshes(&mut app);
+    }
+
+    // Moving two chunks changes the level of detail of the chunks the camera left behind,
+    // and those cross-fade with the chunks replacing them
+    move_camera(&mut app, 64.0);
+    for _ in 0..3 {
+        update_with_meshes(&mut app);
+    }
+    let replaced = fading_out(&mut app);
+    assert!(!replaced.is_empty());
+    let mut chunks = app.world_mut().query::<&Chunk<LodFadeWorld>>();
+    let spawned: Vec<(u8, IVec3)> = chunks
+        .iter(app.world())
+        .map(|chunk| (chunk.lod_level, chunk.position))
+        .collect();
+    assert!(replaced.iter().all(|fade| spawned
+        .iter()
+        .any(|chunk| lod_chunks_overlap(*fade, *chunk))));
+
+    // Chunks that leave the rings have no replacement, and are despawned without fading
+    move_camera(&mut app, 10000.0);
+    for _ in 0..3 {
+        update_with_meshes(&mut app);
+    }
+    assert_eq!(fading_out(&mut app).len(), replaced.len());
+}
+
+#[derive(Resource, Clone, Default)]
+struct BudgetWorld;
+
+impl VoxelWorldConfig for BudgetWorld {
+    fn max_chunks_spawned_per_frame(&self) -> usize {
+        5
+    }
+
+    fn max_meshing_tasks_per_frame(&self) -> usize {
+        2
+    }
+}
+
+#[test]
+fn chunk_spawning_and_meshing_keep_to_frame_budgets() {
+    use crate::chunk::{Chunk, ChunkThread};
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<BudgetWorld>::minimal()));
+    app.world_mut().spawn((
+        Camera3dBundle::default(),
+        VoxelWorldCamera::<BudgetWorld>::default(),
+    ));
+
+    // The minimal plugin doesn't pick up finished tasks, so every started task stays around
+    let counts = |app: &mut App| {
+        let mut tasks = app
+            .world_mut()
+            .query_filtered::<(), (With<Chunk<BudgetWorld>>, With<ChunkThread<BudgetWorld>>)>();
+        let tasks = tasks.iter(app.world()).count();
+        (_spawned_chunks::<BudgetWorld>(app).len(), tasks)
+    };
+
+    app.update();
+    assert_eq!(counts(&mut app), (5, 2));
+    app.update();
+    assert_eq!(counts(&mut app), (10, 4));
+}
+
+#[derive(Resource, Clone, Default)]
+struct PooledWorld;
+
+impl VoxelWorldConfig for PooledWorld {
+    fn spawning_distance(&self) -> u32 {
+        1
+    }
+
+    fn chunk_pool_size(&self) -> usize {
+        100
+    }
+}
+
+#[test]
+fn despawned_chunk_entities_are_reused() {
+    use bevy::utils::HashSet;
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<PooledWorld>::minimal()));
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<PooledWorld>::default(),
+        ))
+        .id();
+
+    let visit = |app: &mut App, x: f32| -> HashSet<Entity> {
+        *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+            GlobalTransform::from_xyz(x, 0.0, 0.0);
+        for _ in 0..3 {
+            app.update();
+        }
+        _spawned_chunks::<PooledWorld>(app)
+            .iter()
+            .map(|chunk| chunk.entity)
+            .collect()
+    };
+
+    let first = visit(&mut app, 0.0);
+    let second = visit(&mut app, 10000.0);
+    assert_eq!(first.len(), 27);
+    assert!(first.is_disjoint(&second));
+
+    // The chunks of the first place were pooled when the camera left, and are reused for the
+    // chunks of the third place
+    let entity_count = app.world().entities().len();
+    let third = visit(&mut app, 20000.0);
+    assert_eq!(third.len(), 27);
+    assert!(third.is_subset(&first));
+    assert!(app.world().entities().len() <= entity_count);
+}
+
+#[derive(Resource, Clone, Default)]
+struct MovableFiniteWorld {
+    min: IVec3,
+}
+
+impl VoxelWorldConfig for MovableFiniteWorld {
+    fn world_extent(&self) -> Option<(IVec3, IVec3)> {
+        Some((self.min, self.min + IVec3::ONE))
+    }
+}
+
+#[test]
+fn finite_world_spawns_missing_chunks_when_config_changes() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<MovableFiniteWorld>::minimal(),
+    ));
+
+    for _ in 0..5 {
+        app.update();
+    }
+
+    app.world_mut().resource_mut::<MovableFiniteWorld>().min = IVec3::NEG_ONE;
+    for _ in 0..5 {
+        app.update();
+    }
+
+    // The chunk at the origin is part of both extents, and is only spawned once
+    let mut chunks = app
+        .world_mut()
+        .query::<&crate::chunk::Chunk<MovableFiniteWorld>>();
+    let positions: Vec<IVec3> = chunks
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect();
+    assert_eq!(positions.len(), 15);
+    assert!(positions.contains(&IVec3::NEG_ONE));
+    assert!(positions.contains(&IVec3::ONE));
+}
+
+#[derive(Resource, Clone, Default)]
+struct GracePeriodWorld;
+
+impl VoxelWorldConfig for GracePeriodWorld {
+    fn spawning_distance(&self) -> u32 {
+        1
+    }
+
+    fn chunk_despawn_strategy(&self) -> ChunkDespawnStrategy {
+        ChunkDespawnStrategy::FarAwayAfter(Duration::from_secs(1))
+    }
+}
+
+#[test]
+fn chunks_are_despawned_after_grace_period() {
+    use bevy::time::TimeUpdateStrategy;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<GracePeriodWorld>::minimal(),
+    ));
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        100,
+    )));
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<GracePeriodWorld>::default(),
+        ))
+        .id();
+
+    for _ in 0..3 {
+        app.update();
+    }
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_xyz(10000.0, 0.0, 0.0);
+
+    let has_origin_chunk = |app: &mut App| {
+        _spawned_chunks::<GracePeriodWorld>(app)
+            .iter()
+            .any(|chunk| chunk.position == IVec3::ZERO)
+    };
+
+    // Still within the grace period
+    for _ in 0..5 {
+        app.update();
+    }
+    assert!(has_origin_chunk(&mut app));
+
+    for _ in 0..10 {
+        app.update();
+    }
+    assert!(!has_origin_chunk(&mut app));
+}
+
+#[derive(Resource, Clone, Default)]
+struct CustomStrategyWorld;
+
+impl VoxelWorldConfig for CustomStrategyWorld {
+    fn chunk_spawn_strategy(&self) -> ChunkSpawnStrategy {
+        ChunkSpawnStrategy::Custom(std::sync::Arc::new(|input| {
+            input.chunk_position.y == 0 && input.distance <= 2
+        }))
+    }
+}
+
+#[test]
+fn custom_spawn_strategy_decides_which_chunks_spawn() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<CustomStrategyWorld>::minimal(),
+    ));
+    app.world_mut().spawn((
+        Camera3dBundle::default(),
+        VoxelWorldCamera::<CustomStrategyWorld>::default(),
+    ));
+
+    for _ in 0..3 {
+        app.update();
+    }
+
+    // A flat layer of chunks, two chunks out from the camera
+    let positions: Vec<IVec3> = _spawned_chunks::<CustomStrategyWorld>(&mut app)
+        .iter()
+        .map(|chunk| chunk.position)
+        .collect();
+    assert_eq!(positions.len(), 25);
+    assert!(positions
+        .iter()
+        .all(|position| position.y == 0 && position.abs().max_element() <= 2));
+}
+
+/// Updates the app until `done` returns true, giving chunk tasks time to finish
+fn _update_until(app: &mut App, mut done: impl FnMut(&mut App) -> bool) {
+    for _ in 0..500 {
+        app.update();
+        if done(app) {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    panic!("the app didn't reach the expected state");
+}
+
+static TASK_POOL_THREADS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+#[derive(Resource, Clone, Default)]
+struct DedicatedPoolWorld;
+
+impl VoxelWorldConfig for DedicatedPoolWorld {
+    fn spawning_distance(&self) -> u32 {
+        0
+    }
+
+    fn task_pool(&self) -> VoxelTaskPool {
+        VoxelTaskPool::Threads(1)
+    }
+
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
+        Box::new(|_| {
+            let mut recorded = false;
+            Box::new(move |_, _| {
+                if !recorded {
+                    TASK_POOL_THREADS.lock().unwrap().push(
+                        std::thread::current()
+                            .name()
+                            .unwrap_or_default()
+                            .to_string(),
+                    );
+                    recorded = true;
+                }
+                WorldVoxel::Unset
+            })
+        })
+    }
+}
+
+#[test]
+fn chunks_are_generated_on_dedicated_task_pool() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<DedicatedPoolWorld>::minimal(),
+    ));
+    app.world_mut().spawn((
+        Camera3dBundle::default(),
+        VoxelWorldCamera::<DedicatedPoolWorld>::default(),
+    ));
+
+    _update_until(&mut app, |_| !TASK_POOL_THREADS.lock().unwrap().is_empty());
+    let threads = TASK_POOL_THREADS.lock().unwrap();
+    assert!(threads
+        .iter()
+        .all(|thread| thread.starts_with("Voxel World Task Pool")));
+}
+
+#[derive(Resource, Clone, Default)]
+struct RingsWorld;
+
+impl VoxelWorldConfig for RingsWorld {
+    fn spawning_distance(&self) -> u32 {
+        3
+    }
+
+    fn chunk_spawn_strategy(&self) -> ChunkSpawnStrategy {
+        ChunkSpawnStrategy::Close
+    }
+
+    fn max_meshing_tasks_per_frame(&self) -> usize {
+        27
+    }
+}
+
+#[test]
+fn chunks_are_meshed_in_rings_around_the_camera() {
+    use crate::chunk::{Chunk, ChunkThread};
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<RingsWorld>::minimal()));
+    app.world_mut().spawn((
+        Camera3dBundle::default(),
+        VoxelWorldCamera::<RingsWorld>::default(),
+    ));
+
+    let started_rings = |app: &mut App| -> Vec<i32> {
+        let mut tasks = app
+            .world_mut()
+            .query_filtered::<&Chunk<RingsWorld>, With<ChunkThread<RingsWorld>>>();
+        tasks
+            .iter(app.world())
+            .map(|chunk| chunk.position.abs().max_element())
+            .collect()
+    };
+
+    // All chunks within the spawning distance spawn at once, but the tasks of the closest
+    // chunks are started first
+    app.update();
+    assert_eq!(_spawned_chunks::<RingsWorld>(&mut app).len(), 343);
+    let rings = started_rings(&mut app);
+    assert_eq!(rings.len(), 27);
+    assert!(rings.iter().all(|ring| *ring <= 1));
+
+    app.update();
+    let rings = started_rings(&mut app);
+    assert_eq!(rings.len(), 54);
+    assert!(rings.iter().all(|ring| *ring <= 2));
+}
+
+static CANCELLED_LOOKUPS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+static CANCELLED_LOOKUPS_RELEASED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+#[derive(Resource, Clone, Default)]
+struct CancelledGenerationWorld;
+
+impl VoxelWorldConfig for CancelledGenerationWorld {
+    fn spawning_distance(&self) -> u32 {
+        0
+    }
+
+    fn task_pool(&self) -> VoxelTaskPool {
+        VoxelTaskPool::Threads(1)
+    }
+
+    // Generating the chunk at the origin blocks until the test releases it
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
+        use std::sync::atomic::Ordering;
+
+        Box::new(|chunk_position| {
+            Box::new(move |_, _| {
+                if chunk_position == IVec3::ZERO {
+                    CANCELLED_LOOKUPS.fetch_add(1, Ordering::Relaxed);
+                    while !CANCELLED_LOOKUPS_RELEASED.load(Ordering::Relaxed) {
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                }
+                WorldVoxel::Solid(1)
+            })
+        })
+    }
+}
+
+#[test]
+fn generation_stops_for_chunks_that_left_range() {
+    use crate::chunk::PADDED_CHUNK_SIZE;
+    use std::sync::atomic::Ordering;
+
+    // Released when the test ends, even if it fails, so the task pool can shut down
+    struct Release;
+    impl Drop for Release {
+        fn drop(&mut self) {
+            CANCELLED_LOOKUPS_RELEASED.store(true, Ordering::Relaxed);
+        }
+    }
+    let release = Release;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<CancelledGenerationWorld>::minimal(),
+    ));
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<CancelledGenerationWorld>::default(),
+        ))
+        .id();
+
+    _update_until(&mut app, |_| CANCELLED_LOOKUPS.load(Ordering::Relaxed) > 0);
+
+    // The camera leaves while the chunk is still generating
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_xyz(10000.0, 0.0, 0.0);
+    _update_until(&mut app, |app| {
+        _spawned_chunks::<CancelledGenerationWorld>(app)
+            .iter()
+            .all(|chunk| chunk.position != IVec3::ZERO)
+    });
+    drop(release);
+
+    // Generation stops at the next layer of voxels, instead of finishing the chunk
+    std::thread::sleep(Duration::from_millis(200));
+    let lookups = CANCELLED_LOOKUPS.load(Ordering::Relaxed);
+    assert!(lookups <= (PADDED_CHUNK_SIZE * PADDED_CHUNK_SIZE) as usize);
+}
+
+/// Runs the chunk tasks of world `C` to completion, like the full plugin does when it spawns
+/// meshes, so that generated voxels end up in the chunk map
+fn _mesh_generated_chunks<C: WorldConfig>(app: &mut App) {
+    use crate::voxel_material::LoadingTexture;
+    use crate::voxel_world_internal::Internals;
+
+    app.add_plugins(AssetPlugin::default()).init_asset::<Mesh>();
+    app.insert_resource(LoadingTexture::<C>::new(Handle::default(), true));
+    app.add_systems(Update, Internals::<C>::spawn_meshes);
+}
+
+#[derive(Resource, Clone, Default)]
+struct DespawnDataWorld;
+
+impl VoxelWorldConfig for DespawnDataWorld {
+    fn spawning_distance(&self) -> u32 {
+        1
+    }
+
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
+        Box::new(|_| {
+            Box::new(|position, _| {
+                if position.y < 4 {
+                    WorldVoxel::Solid(1)
+                } else {
+                    WorldVoxel::Air
+                }
+            })
+        })
+    }
+}
+
+#[test]
+fn chunk_will_despawn_carries_chunk_voxels() {
+    use crate::chunk::{Chunk, ChunkThread, NeedsRemesh};
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<DespawnDataWorld>::minimal(),
+    ));
+    _mesh_generated_chunks::<DespawnDataWorld>(&mut app);
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<DespawnDataWorld>::default(),
+        ))
+        .id();
+
+    _update_until(&mut app, |app| {
+        let mut pending = app.world_mut().query_filtered::<(), (
+            With<Chunk<DespawnDataWorld>>,
+            Or<(With<NeedsRemesh>, With<ChunkThread<DespawnDataWorld>>)>,
+        )>();
+        !_spawned_chunks::<DespawnDataWorld>(app).is_empty()
+            && pending.iter(app.world()).count() == 0
+    });
+    app.update();
+
+    let mut reader = app
+        .world()
+        .resource::<Events<ChunkWillDespawn<DespawnDataWorld>>>()
+        .get_reader();
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_xyz(10000.0, 0.0, 0.0);
+    app.update();
+
+    let events = app
+        .world()
+        .resource::<Events<ChunkWillDespawn<DespawnDataWorld>>>();
+    let despawned = reader
+        .read(events)
+        .find(|event| event.chunk_key == IVec3::ZERO)
+        .expect("the chunk at the origin should despawn");
+    let chunk_data = despawned.chunk_data.as_ref().unwrap();
+    // The voxel data is padded by one voxel on each side
+    assert_eq!(
+        chunk_data.get_voxel(UVec3::new(1, 4, 1)),
+        WorldVoxel::Solid(1)
+    );
+    assert_eq!(chunk_data.get_voxel(UVec3::new(1, 5, 1)), WorldVoxel::Air);
+}
+
+#[derive(Resource, Clone, Default)]
+struct MemoryBudgetWorld;
+
+impl VoxelWorldConfig for MemoryBudgetWorld {
+    fn spawning_distance(&self) -> u32 {
+        1
+    }
+
+    // Room for the voxels of two chunks
+    fn chunk_memory_budget(&self) -> Option<usize> {
+        Some(2 * std::mem::size_of::<crate::chunk::VoxelArray<u8>>())
+    }
+
+    // The ground crosses the chunks at y = 0, the chunks below are full and those above empty,
+    // which takes no memory
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
+        Box::new(|_| {
+            Box::new(|position, _| {
+                if position.y < 4 {
+                    WorldVoxel::Solid(1)
+                } else {
+                    WorldVoxel::Air
+                }
+            })
+        })
+    }
+}
+
+#[test]
+fn chunks_over_memory_budget_are_evicted_least_recently_visible_first() {
+    use crate::chunk::Chunk;
+    use bevy::render::view::ViewVisibility;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<MemoryBudgetWorld>::minimal(),
+    ));
+    _mesh_generated_chunks::<MemoryBudgetWorld>(&mut app);
+    app.world_mut().spawn((
+        Camera3dBundle::default(),
+        VoxelWorldCamera::<MemoryBudgetWorld>::default(),
+    ));
+
+    // The chunk at the origin is always in view
+    app.add_systems(
+        First,
+        |mut commands: Commands, chunks: Query<(Entity, &Chunk<MemoryBudgetWorld>)>| {
+            for (entity, chunk) in chunks.iter() {
+                if chunk.position == IVec3::ZERO {
+                    let mut visibility = ViewVisibility::default();
+                    visibility.set();
+                    commands.entity(entity).insert(visibility);
+                }
+            }
+        },
+    );
+
+    let ground_chunks = |app: &mut App| -> Vec<IVec3> {
+        _spawned_chunks::<MemoryBudgetWorld>(app)
+            .iter()
+            .map(|chunk| chunk.position)
+            .filter(|position| position.y == 0)
+            .collect()
+    };
+    _update_until(&mut app, |app| ground_chunks(app).len() <= 2);
+
+    // The evicted chunks are out of view, so they are not spawned again
+    for _ in 0..20 {
+        app.update();
+    }
+    let ground = ground_chunks(&mut app);
+    assert!(ground.len() <= 2);
+    assert!(ground.contains(&IVec3::ZERO));
+    let spawned = _spawned_chunks::<MemoryBudgetWorld>(&mut app);
+    assert_eq!(
+        spawned.iter().filter(|chunk| chunk.position.y != 0).count(),
+        18
+    );
+}
+
+#[test]
+fn evicted_or_retired_chunks_get_one_will_despawn_event() {
+    use bevy::utils::HashSet;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<MemoryBudgetWorld>::minimal(),
+    ));
+    _mesh_generated_chunks::<MemoryBudgetWorld>(&mut app);
+    let camera = app
+        .world_mut()
+        .spawn((
+            Camera3dBundle::default(),
+            VoxelWorldCamera::<MemoryBudgetWorld>::default(),
+        ))
+        .id();
+
+    app.init_resource::<DespawnEvents>().add_systems(
+        Last,
+        |mut events: EventReader<ChunkWillDespawn<MemoryBudgetWorld>>,
+         mut despawned: ResMut<DespawnEvents>| {
+            despawned
+                .0
+                .extend(events.read().map(|event| (event.chunk_key, event.entity)));
+        },
+    );
+
+    for _ in 0..10 {
+        app.update();
+    }
+
+    // Moving away retires the chunks that are still over the budget
+    *app.world_mut().get_mut::<GlobalTransform>(camera).unwrap() =
+        GlobalTransform::from_translation(Vec3::X * 32.0 * 2.0);
+    for _ in 0..10 {
+        app.update();
+    }
+
+    let despawned = &app.world().resource::<DespawnEvents>().0;
+    assert!(!despawned.is_empty());
+    let unique: HashSet<(IVec3, Entity)> = despawned.iter().copied().collect();
+    assert_eq!(unique.len(), despawned.len());
+}
+
+#[derive(Resource, Default)]
+struct DespawnEvents(Vec<(IVec3, Entity)>);
+
+// ChunkWillSpawn event now fires from the mesh spawning system, which cannot run in tests.
+#[ignore]
+#[test]
+fn chunk_will_spawn_events() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(
+        Update,
+        |mut ev_chunk_will_spawn: EventReader<ChunkWillSpawn<DefaultWorld>>| {
+            let spawn_count = ev_chunk_will_spawn.read().count();
+            assert!(spawn_count > 0);
+        },
+    );
+
+    app.update();
+}
+
+#[derive(Resource, Clone, Default)]
+struct FiniteWorld;
+
+impl VoxelWorldConfig for FiniteWorld {
+    fn world_extent(&self) -> Option<(IVec3, IVec3)> {
+        Some((IVec3::new(-1, 0, -1), IVec3::new(1, 1, 1)))
+    }
+}
+
+#[test]
+fn finite_world_spawns_all_chunks_once() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<FiniteWorld>::minimal()));
+
+    for _ in 0..10 {
+        app.update();
+    }
+
+    let mut chunks = app.world_mut().query::<&crate::chunk::Chunk<FiniteWorld>>();
+    let positions: Vec<IVec3> = chunks
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect();
+    assert_eq!(positions.len(), 18);
+    assert!(positions
+        .iter()
+        .all(|pos| pos.cmpge(IVec3::new(-1, 0, -1)).all() && pos.cmple(IVec3::ONE).all()));
+}
+
+#[derive(Resource, Clone, Default)]
+struct WideMaterialWorld;
+
+impl VoxelWorldConfig<u16> for WideMaterialWorld {}
+
+impl VoxelWorldMaterialIndex for WideMaterialWorld {
+    type MaterialIndex = u16;
+}
+
+#[test]
+fn can_set_get_16_bit_materials() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<WideMaterialWorld>::minimal(),
+    ));
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<WideMaterialWorld>| {
+        let test_voxel = WorldVoxel::Solid(1000u16);
+        voxel_world.set_voxel(IVec3::ZERO, test_voxel);
+        assert_eq!(voxel_world.get_voxel(IVec3::ZERO), test_voxel);
+        assert_eq!(test_voxel.material(), Some(1000));
+    });
+
+    app.update();
+}
+
+#[derive(Resource, Clone, Default)]
+struct HistoryWorld;
+
+impl VoxelWorldConfig for HistoryWorld {
+    fn edit_history_size(&self) -> usize {
+        2
+    }
+}
+
+#[test]
+fn edits_can_be_undone_and_redone() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<HistoryWorld>::minimal()));
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<HistoryWorld>| {
+        let position = IVec3::new(0, 0, 0);
+        voxel_world.set_voxel(position, WorldVoxel::Solid(1));
+        voxel_world.begin_edit_group();
+        voxel_world.set_voxel(position, WorldVoxel::Solid(2));
+        voxel_world.set_voxel(position, WorldVoxel::Solid(3));
+        voxel_world.end_edit_group();
+
+        assert!(voxel_world.undo());
+        assert_eq!(voxel_world.get_voxel(position), WorldVoxel::Solid(1));
+        assert!(voxel_world.redo());
+        assert_eq!(voxel_world.get_voxel(position), WorldVoxel::Solid(3));
+        assert!(!voxel_world.redo());
+
+        voxel_world.set_voxel(position, WorldVoxel::Air);
+        assert!(voxel_world.undo());
+        assert!(voxel_world.undo());
+        // Only the two most recent steps are kept
+        assert!(!voxel_world.undo());
+        assert_eq!(voxel_world.get_voxel(position), WorldVoxel::Solid(1));
+    });
+
+    app.update();
+}
+
+#[derive(Resource, Clone, Default)]
+struct SlicedEditWorld;
+
+impl VoxelWorldConfig for SlicedEditWorld {
+    fn max_queued_voxels_per_frame(&self) -> usize {
+        4
+    }
+}
+
+#[test]
+fn queued_edit_is_spread_over_frames() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<SlicedEditWorld>::minimal(),
+    ));
+
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<SlicedEditWorld>| {
+        let voxels = (0..10).map(|x| (IVec3::new(x, 0, 0), WorldVoxel::Solid(1)));
+        voxel_world.queue_edit(voxels);
+    });
+
+    let finished = |app: &App| {
+        app.world()
+            .resource::<Events<QueuedEditFinished<SlicedEditWorld>>>()
+            .len()
+    };
+    let written = |app: &App| {
+        let modified = app
+            .world()
+            .resource::<crate::voxel_world_internal::ModifiedVoxels<SlicedEditWorld>>();
+        (0..10)
+            .filter(|x| modified.get_voxel(&IVec3::new(*x, 0, 0)).is_some())
+            .count()
+    };
+
+    app.update();
+    assert_eq!(written(&app), 4);
+    app.update();
+    assert_eq!(written(&app), 8);
+    assert_eq!(finished(&app), 0);
+    app.update();
+    assert_eq!(written(&app), 10);
+    assert_eq!(finished(&app), 1);
+}
+
+#[test]
+fn scheduled_edits_apply_when_due() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let voxel = WorldVoxel::Solid(1);
+        voxel_world.schedule_edit(Duration::ZERO, [(IVec3::new(0, 0, 0), voxel)]);
+        voxel_world.schedule_edit(Duration::from_secs(3600), [(IVec3::new(1, 0, 0), voxel)]);
+        let cancelled = voxel_world.schedule_edit(Duration::ZERO, [(IVec3::new(2, 0, 0), voxel)]);
+        assert!(voxel_world.cancel_scheduled_edit(cancelled));
+    });
+
+    app.update();
+
+    let modified = app
+        .world()
+        .resource::<crate::voxel_world_internal::ModifiedVoxels<DefaultWorld>>();
+    assert_eq!(
+        modified.get_voxel(&IVec3::new(0, 0, 0)),
+        Some(WorldVoxel::Solid(1))
+    );
+    assert_eq!(modified.get_voxel(&IVec3::new(1, 0, 0)), None);
+    assert_eq!(modified.get_voxel(&IVec3::new(2, 0, 0)), None);
+}
+
+#[test]
+fn protected_voxels_are_not_set() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.protect_region(IVec3::ZERO, IVec3::new(3, 3, 3));
+        voxel_world.protect_chunk(IVec3::new(1, 0, 0));
+        voxel_world.fill_box(
+            IVec3::new(2, 0, 0),
+            IVec3::new(4, 0, 0),
+            WorldVoxel::Solid(1),
+        );
+        voxel_world.queue_edit([(IVec3::new(40, 0, 0), WorldVoxel::Solid(1))]);
+        assert!(voxel_world.is_protected(IVec3::new(40, 0, 0)));
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(3, 0, 0)),
+            WorldVoxel::Unset
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(4, 0, 0)),
+            WorldVoxel::Solid(1)
+        );
+    });
+
+    app.update();
+
+    let rejected = app
+        .world()
+        .resource::<Events<VoxelEditRejected<DefaultWorld>>>();
+    let positions: Vec<IVec3> = rejected
+        .get_reader()
+        .read(rejected)
+        .map(|ev| ev.position)
+        .collect();
+    assert_eq!(
+        positions,
+        vec![
+            IVec3::new(2, 0, 0),
+            IVec3::new(3, 0, 0),
+            IVec3::new(40, 0, 0)
+        ]
+    );
+}
+
+#[derive(Resource, Clone, Default)]
+struct ManualWorld;
+
+impl VoxelWorldConfig for ManualWorld {
+    fn manual_chunk_management(&self) -> bool {
+        true
+    }
+}
+
+#[test]
+fn manual_world_only_spawns_requested_chunks() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<ManualWorld>::minimal()));
+    app.world_mut().spawn((
+        Camera3dBundle::default(),
+        VoxelWorldCamera::<ManualWorld>::default(),
+    ));
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<ManualWorld>| {
+        voxel_world.request_chunk(IVec3::new(2, 0, 0));
+    });
+
+    for _ in 0..10 {
+        app.update();
+    }
+
+    let mut chunks = app.world_mut().query::<&crate::chunk::Chunk<ManualWorld>>();
+    let positions: Vec<IVec3> = chunks
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect();
+    assert_eq!(positions, vec![IVec3::new(2, 0, 0)]);
+}
+
+#[test]
+fn prefetch_is_limited_to_nearest_chunks_ahead() {
+    use crate::voxel_world_internal::prefetched_chunks;
+
+    let chunk_at = |point: Vec3| Some((0, (point / 32.0).floor().as_ivec3()));
+
+    // A camera that would cross a hundred chunks within the prefetch time
+    let lookahead = Vec3::X * 3200.0;
+    let chunks = prefetched_chunks(Vec3::splat(16.0), lookahead, 40, chunk_at);
+    assert_eq!(chunks.len(), 40);
+    assert!(chunks.contains(&(0, IVec3::new(1, 0, 0))));
+    assert!(chunks.iter().all(|(_, position)| position.x <= 4));
+
+    // A camera standing still prefetches nothing
+    assert!(prefetched_chunks(Vec3::ZERO, Vec3::ZERO, 40, chunk_at).is_empty());
+}
+
+#[test]
+fn teleport_drops_stale_queued_chunks() {
+    use crate::voxel_world_internal::SpawnQueue;
+
+    // Chunks queued around the camera's new position, and further out, like the chunks that
+    // were picked up before the camera jumped
+    let camera_position = Vec3::new(313.0, 0.0, 0.0) * 32.0 + 16.0;
+    let mut queue = SpawnQueue::new(camera_position);
+    queue.push((0, IVec3::new(313, 0, 0)));
+    queue.push((0, IVec3::new(312, 1, -1)));
+    queue.push((0, IVec3::new(316, 0, 0)));
+    queue.push((0, IVec3::ZERO));
+    queue.push((1, IVec3::new(156, 0, 0)));
+    queue.push((1, IVec3::new(100, 0, 0)));
+
+    queue.flush();
+
+    let mut remaining = Vec::new();
+    while let Some(chunk) = queue.pop() {
+        remaining.push(chunk);
+    }
+    assert_eq!(
+        remaining,
+        vec![
+            (0, IVec3::new(313, 0, 0)),
+            (1, IVec3::new(156, 0, 0)),
+            (0, IVec3::new(312, 1, -1)),
+        ]
+    );
+}
+
+#[test]
+fn paused_streaming_spawns_no_chunks() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.pause_streaming();
+    });
+
+    app.update();
+    assert_eq!(
+        app.world()
+            .resource::<Events<ChunkWillSpawn<DefaultWorld>>>()
+            .len(),
+        0
+    );
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.resume_streaming();
+    });
+
+    app.update();
+    app.update();
+    assert!(!app
+        .world()
+        .resource::<Events<ChunkWillSpawn<DefaultWorld>>>()
+        .is_empty());
+}
+
+#[test]
+fn streaming_anchor_spawns_chunks_without_camera() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<DefaultWorld>::minimal()));
+    app.world_mut().spawn((
+        VoxelWorldStreamingAnchor::<DefaultWorld>::with_radius(1),
+        GlobalTransform::from_xyz(100.0, 0.0, 0.0),
+    ));
+
+    app.update();
+    app.update();
+
+    let mut chunks = app
+        .world_mut()
+        .query::<&crate::chunk::Chunk<DefaultWorld>>();
+    let positions: Vec<IVec3> = chunks
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect();
+    assert_eq!(positions.len(), 27);
+    assert!(positions.contains(&IVec3::new(3, 0, 0)));
+}
+
+#[test]
+fn streaming_anchor_respawns_despawned_chunks() {
+    use crate::chunk::{Chunk, NeedsDespawn};
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<DefaultWorld>::minimal()));
+    app.world_mut().spawn((
+        VoxelWorldStreamingAnchor::<DefaultWorld>::with_radius(1),
+        GlobalTransform::default(),
+    ));
+    _update_until(&mut app, |app| {
+        _spawned_chunks::<DefaultWorld>(app).len() == 27
+    });
+
+    // Despawn a chunk around the anchor while the anchor stays in place
+    let chunk = _spawned_chunks::<DefaultWorld>(&mut app)
+        .into_iter()
+        .find(|chunk| chunk.position == IVec3::ONE)
+        .unwrap();
+    app.world_mut()
+        .entity_mut(chunk.entity)
+        .insert(NeedsDespawn);
+    app.world_mut()
+        .send_event(ChunkWillDespawn::<DefaultWorld>::new(
+            chunk.position,
+            chunk.entity,
+            None,
+        ));
+
+    // Pooled entities can be reused, so the respawn is told apart by the chunk being added again
+    app.init_resource::<AddedChunks>().add_systems(
+        Last,
+        |chunks: Query<&Chunk<DefaultWorld>, Added<Chunk<DefaultWorld>>>,
+         mut added: ResMut<AddedChunks>| {
+            added.0.extend(chunks.iter().map(|chunk| chunk.position));
+        },
+    );
+    _update_until(&mut app, |app| {
+        app.world()
+            .resource::<AddedChunks>()
+            .0
+            .contains(&IVec3::ONE)
+    });
+    assert_eq!(_spawned_chunks::<DefaultWorld>(&mut app).len(), 27);
+}
+
+#[derive(Resource, Default)]
+struct AddedChunks(Vec<IVec3>);
+
+#[derive(Resource, Clone, Default)]
+struct LodWorld;
+
+impl VoxelWorldConfig for LodWorld {
+    fn lod_distances(&self) -> Vec<u32> {
+        vec![2, 4]
+    }
+}
+
+#[test]
+fn streaming_anchors_are_ignored_with_levels_of_detail() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<LodWorld>::minimal()));
+    app.world_mut().spawn((
+        VoxelWorldStreamingAnchor::<LodWorld>::with_radius(1),
+        GlobalTransform::default(),
+    ));
+
+    app.update();
+    app.update();
+
+    let mut chunks = app.world_mut().query::<&crate::chunk::Chunk<LodWorld>>();
+    assert_eq!(chunks.iter(app.world()).count(), 0);
+}
+
+#[test]
+fn multiple_cameras_spawn_chunks_around_each() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<DefaultWorld>::minimal()));
+    for x in [0.0, 1000.0] {
+        app.world_mut().spawn((
+            Camera3dBundle {
+                global_transform: GlobalTransform::from_xyz(x, 0.0, 0.0),
+                ..default()
+            },
+            VoxelWorldCamera::<DefaultWorld>::default(),
+        ));
+    }
+
+    app.update();
+    app.update();
+
+    let mut chunks = app
+        .world_mut()
+        .query::<&crate::chunk::Chunk<DefaultWorld>>();
+    let positions: Vec<IVec3> = chunks
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect();
+    assert!(positions.contains(&IVec3::ZERO));
+    assert!(positions.contains(&IVec3::new(31, 0, 0)));
+}
+
+#[derive(Resource, Clone, Default)]
+struct SplitScreenWorld;
+
+impl VoxelWorldConfig for SplitScreenWorld {
+    fn spawning_distance(&self) -> u32 {
+        2
+    }
+
+    fn chunk_spawn_strategy(&self) -> ChunkSpawnStrategy {
+        ChunkSpawnStrategy::Close
+    }
+
+    fn max_chunks_spawned_per_frame(&self) -> usize {
+        20
+    }
+}
+
+#[test]
+fn overlapping_cameras_share_the_spawn_budget() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<SplitScreenWorld>::minimal(),
+    ));
+    // Three chunks apart, so the spawning distances of the cameras overlap
+    let cameras = [IVec3::ZERO, IVec3::new(3, 0, 0)];
+    for camera_chunk in cameras {
+        app.world_mut().spawn((
+            Camera3dBundle {
+                global_transform: GlobalTransform::from_translation(
+                    camera_chunk.as_vec3() * 32.0 + 16.0,
+                ),
+                ..default()
+            },
+            VoxelWorldCamera::<SplitScreenWorld>::default(),
+        ));
+    }
+
+    let positions = |app: &mut App| -> Vec<IVec3> {
+        _spawned_chunks::<SplitScreenWorld>(app)
+            .iter()
+            .map(|chunk| chunk.position)
+            .collect()
+    };
+    let near = |positions: &[IVec3], camera_chunk: IVec3| {
+        positions
+            .iter()
+            .filter(|position| (**position - camera_chunk).abs().max_element() <= 1)
+            .count()
+    };
+
+    // Both cameras get half of the first frame's budget
+    app.update();
+    let first_frame = positions(&mut app);
+    assert_eq!(first_frame.len(), 20);
+    assert_eq!(near(&first_frame, cameras[0]), 10);
+    assert_eq!(near(&first_frame, cameras[1]), 10);
+
+    // Chunks in range of both cameras are spawned once, and the chunks out of range of the
+    // first camera are still spawned for the second
+    for _ in 0..10 {
+        app.update();
+    }
+    let spawned = positions(&mut app);
+    let unique: bevy::utils::HashSet<IVec3> = spawned.iter().copied().collect();
+    assert_eq!(unique.len(), spawned.len());
+    assert_eq!(near(&spawned, cameras[0]), 27);
+    assert_eq!(near(&spawned, cameras[1]), 27);
+    assert!(unique.contains(&IVec3::new(4, 0, 0)));
+}
+
+#[test]
+fn requested_chunk_spawns_without_camera() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<DefaultWorld>::minimal()));
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let request = voxel_world.request_chunk(IVec3::new(5, 0, 0));
+        assert!(!voxel_world.is_chunk_loaded(&request));
+    });
+
+    app.update();
+    app.update();
+
+    let mut chunks = app
+        .world_mut()
+        .query::<&crate::chunk::Chunk<DefaultWorld>>();
+    let positions: Vec<IVec3> = chunks
+        .iter(app.world())
+        .map(|chunk| chunk.position)
+        .collect();
+    assert_eq!(positions, vec![IVec3::new(5, 0, 0)]);
+}
+
+#[test]
+fn chunk_will_remesh_event_after_set_voxel() {
+    let mut app = _test_setup_app();
+
+    // Run the world a 100 cycles
+    for _ in 0..100 {
+        app.update();
+    }
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(0, 0, 0), WorldVoxel::Solid(1));
+    });
+
+    app.update();
+
+    app.add_systems(
+        Update,
+        |mut ev_chunk_will_remesh: EventReader<ChunkWillRemesh<DefaultWorld>>| {
+            let count = ev_chunk_will_remesh.read().count();
+            assert!(count > 0)
+        },
+    );
+
+    app.update();
+}
+
+#[test]
+fn chunk_will_despawn_event() {
+    let mut app = _test_setup_app();
+
+    // move camera to simulate chunks going out of view
+    app.add_systems(
+        Update,
+        |mut query: Query<&mut GlobalTransform, With<VoxelWorldCamera<DefaultWorld>>>| {
+            for mut transform in query.iter_mut() {
+                // Not sure why, but when running tests, bevy won't update the GlobalTransform
+                // when a Transform has changed, so as a workaround we set it here directly.
+                let tf = Transform::from_xyz(1000.0, 1000.0, 1000.0);
+                *transform = GlobalTransform::from(tf);
+            }
+        },
+    );
+
+    app.update();
+
+    app.add_systems(
+        Update,
+        |mut ev_chunk_will_despawn: EventReader<ChunkWillDespawn<DefaultWorld>>| {
+            let count = ev_chunk_will_despawn.read().count();
+            assert!(count > 0)
+        },
+    );
+
+    app.update();
+}
+
+#[test]
+fn raycast_finds_voxel() {
+    let mut app = _test_setup_app();
+
+    // Set up vector och positions to test
+    let positions = vec![IVec3::new(0, 0, -1), IVec3::new(0, 0, 0)];
+
+    let make_pos = positions.clone();
+
+    app.add_systems(
+        Startup,
+        move |mut voxel_world: VoxelWorld<DefaultWorld>,
+              buffers: (
+            ResMut<ChunkMapUpdateBuffer<DefaultWorld>>,
+            ResMut<MeshCacheInsertBuffer<DefaultWorld>>,
+        )| {
+            let test_voxel = crate::voxel::WorldVoxel::Solid(1);
+
+            for pos in make_pos.clone() {
+                voxel_world.set_voxel(pos, test_voxel);
+            }
+
+            let (mut chunk_map_update_buffer, _) = buffers;
+
+            chunk_map_update_buffer.push((
+                IVec3::new(0, 0, 0),
+                ChunkData {
+                    position: IVec3::new(0, 0, 0),
+                    voxels: Some(std::sync::Arc::new([WorldVoxel::Unset; 39304])),
+                    compressed: None,
+                    light: None,
+                    biomes: None,
+                    voxels_hash: 0,
+                    is_full: false,
+                    is_empty: false,
+                    is_opaque: false,
+                    fill_type: FillType::Mixed,
+                    entity: Entity::PLACEHOLDER,
+                },
+                ChunkWillSpawn::<DefaultWorld>::new(IVec3::new(0, 0, 0), Entity::PLACEHOLDER),
+            ));
+        },
+    );
+
+    app.update();
+
+    app.add_systems(Update, move |voxel_world: VoxelWorld<DefaultWorld>| {
+        let test_voxel = crate::voxel::WorldVoxel::Solid(1);
+
+        let ray = Ray3d {
+            origin: Vec3::new(0.5, 0.5, 70.0),
+            direction: -Dir3::Z,
+        };
+
+        let Some(result) = voxel_world.raycast(ray, &|(_pos, _vox)| true) else {
+            panic!("No voxel found")
+        };
+
+        assert_eq!(
+            result,
+            VoxelRaycastResult {
+                position: Vec3::ZERO,
+                normal: Some(Vec3::new(0.0, 0.0, 1.0)),
+                voxel: test_voxel,
+            }
+        )
+    });
+
+    app.update();
+}
+
+#[test]
+fn raycast_passes_through_fluid() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(
+        Startup,
+        move |mut voxel_world: VoxelWorld<DefaultWorld>,
+              mut chunk_map_update_buffer: ResMut<ChunkMapUpdateBuffer<DefaultWorld>>| {
+            voxel_world.set_voxel(IVec3::new(0, 0, 0), WorldVoxel::Fluid(1, MAX_FLUID_LEVEL));
+            voxel_world.set_voxel(IVec3::new(0, 0, -1), WorldVoxel::Solid(1));
+
+            chunk_map_update_buffer.push((
+                IVec3::new(0, 0, 0),
+                ChunkData {
+                    position: IVec3::new(0, 0, 0),
+                    voxels: Some(std::sync::Arc::new([WorldVoxel::Unset; 39304])),
+                    compressed: None,
+                    light: None,
+                    biomes: None,
+                    voxels_hash: 0,
+                    is_full: false,
+                    is_empty: false,
+                    is_opaque: false,
+                    fill_type: FillType::Mixed,
+                    entity: Entity::PLACEHOLDER,
+                },
+                ChunkWillSpawn::<DefaultWorld>::new(IVec3::new(0, 0, 0), Entity::PLACEHOLDER),
+            ));
+        },
+    );
+
+    app.update();
+
+    app.add_systems(Update, move |voxel_world: VoxelWorld<DefaultWorld>| {
+        let ray = Ray3d {
+            origin: Vec3::new(0.5, 0.5, 70.0),
+            direction: -Dir3::Z,
+        };
+
+        let Some(result) = voxel_world.raycast(ray, &|(_pos, _vox)| true) else {
+            panic!("No voxel found")
+        };
+
+        assert_eq!(
+            result,
+            VoxelRaycastResult {
+                position: Vec3::new(0.0, 0.0, -1.0),
+                normal: Some(Vec3::new(0.0, 0.0, 1.0)),
+                voxel: WorldVoxel::Solid(1),
+            }
+        )
+    });
+
+    app.update();
+}
+
+struct VisitVoxelTestState<'a> {
+    test_name: &'a str,
+    expected_path: &'a [IVec3],
+    expected_face: Option<VoxelFace>,
+    path_step_index: usize,
+    traversal_time_out: Timer,
+}
+
+impl<'a> VisitVoxelTestState<'a> {
+    fn new(
+        test_name: &'a str,
+        expected_path: &'a [IVec3],
+        expected_face: Option<VoxelFace>,
+    ) -> Self {
+        VisitVoxelTestState {
+            test_name,
+            expected_path,
+            expected_face,
+            path_step_index: 0,
+            traversal_time_out: Timer::from_seconds(1., TimerMode::Once),
+        }
+    }
+}
+
+fn visit_voxel_check(
+    test_state: &mut VisitVoxelTestState,
+    voxel_coords: IVec3,
+    time: f32,
+    face: VoxelFace,
+) -> bool {
+    // println!(
+    //     "Traversed {:?} at {} through {:?}",
+    //     voxel_coords, time, face
+    // );
+
+    assert!(
+        0. <= time,
+        "{}: Time must always be >= 0",
+        test_state.test_name
+    );
+    assert!(
+        time <= 1.,
+        "{}: Time must always be <= 1",
+        test_state.test_name
+    );
+    assert!(
+        !test_state.traversal_time_out.finished(),
+        "{}: Infinite loop detected (bc. such a simple trace should be much faster than 1s)",
+        test_state.test_name
+    );
+    assert!(
+        test_state.path_step_index == 0 || test_state.expected_face.unwrap_or(face) == face,
+        "{}: Expected entering through {:?}",
+        test_state.test_name,
+        test_state.expected_face
+    );
+    assert!(
+        test_state.path_step_index < test_state.expected_path.len(),
+        "{}: Expected path is not same length",
+        test_state.test_name
+    );
+    assert_eq!(
+        voxel_coords, test_state.expected_path[test_state.path_step_index],
+        "{}: Found unexpected step in path",
+        test_state.test_name
+    );
+    test_state.path_step_index += 1;
+
+    true
+}
+
+#[test]
+fn voxel_line_traversal_along_cartesian_axes() {
+    let start = Vec3::splat(VOXEL_SIZE / 2.);
+
+    {
+        let end = Vec3::new(
+            2. * VOXEL_SIZE + VOXEL_SIZE / 2.,
+            VOXEL_SIZE / 2.,
+            VOXEL_SIZE / 2.,
+        );
+        let expected_path = [
+            IVec3::new(0, 0, 0),
+            IVec3::new(1, 0, 0),
+            IVec3::new(2, 0, 0),
+        ];
+
+        let mut test_state = VisitVoxelTestState::new(
+            "Aligned with cartesian X",
+            &expected_path,
+            Some(VoxelFace::Left),
+        );
+        voxel_line_traversal(start, end, |voxel_coords, time, face| {
+            visit_voxel_check(&mut test_state, voxel_coords, time, face)
+        });
+        assert_eq!(
+            test_state.path_step_index,
+            expected_path.len(),
+            "{}: Expected end voxel reached",
+            test_state.test_name
+        );
+    }
+
+    {
+        let end = Vec3::new(
+            VOXEL_SIZE / 2.,
+            2. * VOXEL_SIZE + VOXEL_SIZE / 2.,
+            VOXEL_SIZE / 2.,
+        );
+        let expected_path = [
+            IVec3::new(0, 0, 0),
+            IVec3::new(0, 1, 0),
+            IVec3::new(0, 2, 0),
+        ];
+
+        let mut test_state = VisitVoxelTestState::new(
+            "Aligned with cartesian Y",
+            &expected_path,
+            Some(VoxelFace::Bottom),
+        );
+        voxel_line_traversal(start, end, |voxel_coords, time, face| {
+            visit_voxel_check(&mut test_state, voxel_coords, time, face)
+        });
+        assert_eq!(
+            test_state.path_step_index,
+            expected_path.len(),
+            "{}: Expected end voxel reached",
+            test_state.test_name
+        );
+    }
+
+    {
+        let end = Vec3::new(
+            VOXEL_SIZE / 2.,
+            VOXEL_SIZE / 2.,
+            2. * VOXEL_SIZE + VOXEL_SIZE / 2.,
+        );
+        let expected_path = [
+            IVec3::new(0, 0, 0),
+            IVec3::new(0, 0, 1),
+            IVec3::new(0, 0, 2),
+        ];
+
+        let mut test_state = VisitVoxelTestState::new(
+            "Aligned with cartesian Z",
+            &expected_path,
+            Some(VoxelFace::Back),
+        );
+        voxel_line_traversal(start, end, |voxel_coords, time, face| {
+            visit_voxel_check(&mut test_state, voxel_coords, time, face)
+        });
+        assert_eq!(
+            test_state.path_step_index,
+            expected_path.len(),
+            "{}: Expected end voxel reached",
+            test_state.test_name
+        );
+    }
+}
+
+#[test]
+fn voxel_line_traversal_ending_on_voxel_boundary() {
+    let start = Vec3::new(-5. * VOXEL_SIZE, VOXEL_SIZE / 2., 1.9815);
+    let end = Vec3::new(0., 0., 50. * VOXEL_SIZE);
+    let expected_path = [
+        IVec3::new(-5, 0, 1),
+        IVec3::new(-5, 0, 2),
+        IVec3::new(-5, 0, 3),
+        IVec3::new(-5, 0, 4),
+        IVec3::new(-5, 0, 5),
+        IVec3::new(-5, 0, 6),
+        IVec3::new(-5, 0, 7),
+        IVec3::new(-5, 0, 8),
+        IVec3::new(-5, 0, 9),
+        IVec3::new(-5, 0, 10),
+        IVec3::new(-5, 0, 11),
+        IVec3::new(-4, 0, 11),
+        IVec3::new(-4, 0, 12),
+        IVec3::new(-4, 0, 13),
+        IVec3::new(-4, 0, 14),
+        IVec3::new(-4, 0, 15),
+        IVec3::new(-4, 0, 16),
+        IVec3::new(-4, 0, 17),
+        IVec3::new(-4, 0, 18),
+        IVec3::new(-4, 0, 19),
+        IVec3::new(-4, 0, 20),
+        IVec3::new(-4, 0, 21),
+        IVec3::new(-3, 0, 21),
+        IVec3::new(-3, 0, 22),
+        IVec3::new(-3, 0, 23),
+        IVec3::new(-3, 0, 24),
+        IVec3::new(-3, 0, 25),
+        IVec3::new(-3, 0, 26),
+        IVec3::new(-3, 0, 27),
+        IVec3::new(-3, 0, 28),
+        IVec3::new(-3, 0, 29),
+        IVec3::new(-3, 0, 30),
+        IVec3::new(-2, 0, 30),
+        IVec3::new(-2, 0, 31),
+        IVec3::new(-2, 0, 32),
         IVec3::new(-2, 0, 33),
         IVec3::new(-2, 0, 34),
         IVec3::new(-2, 0, 35),
@@ -464,14 +2459,2299 @@ fn voxel_line_traversal_ending_on_voxel_boundary() {
         IVec3::new(0, 0, 50),
     ];
 
-    let mut test_state = VisitVoxelTestState::new("Ending on voxel boundary", &expected_path, None);
-    voxel_line_traversal(start, end, |voxel_coords, time, face| {
-        visit_voxel_check(&mut test_state, voxel_coords, time, face)
+    let mut test_state = VisitVoxelTestState::new("Ending on voxel boundary", &expected_path, None);
+    voxel_line_traversal(start, end, |voxel_coords, time, face| {
+        visit_voxel_check(&mut test_state, voxel_coords, time, face)
+    });
+    assert_eq!(
+        test_state.path_step_index,
+        expected_path.len(),
+        "{}: Expected end voxel reached",
+        test_state.test_name
+    );
+}
+
+#[test]
+fn rotated_shape_keeps_boundary_sides() {
+    let stairs = VoxelShape::Stairs.rotated_quads(VoxelRotation::Deg90);
+    let sides: Vec<IVec3> = stairs.iter().filter_map(|q| q.boundary_side()).collect();
+
+    // The tall back of the stairs faced +Z before rotation, and should now face +X
+    assert!(sides.contains(&IVec3::X));
+    assert!(sides.contains(&IVec3::NEG_Y));
+
+    // The top of a slab is inside the voxel, so it is never culled
+    let slab = VoxelShape::Slab.rotated_quads(VoxelRotation::Deg180);
+    assert!(!slab.iter().any(|q| q.boundary_side() == Some(IVec3::Y)));
+}
+
+#[test]
+fn downsampling_filters() {
+    use crate::lod::downsample;
+
+    let mut samples = [WorldVoxel::Air; 8];
+    samples[0] = WorldVoxel::Solid(1);
+    samples[1] = WorldVoxel::Solid(2);
+    samples[2] = WorldVoxel::Solid(2);
+
+    assert_eq!(
+        downsample(&samples, LodDownsampling::Majority),
+        WorldVoxel::Air
+    );
+    assert_eq!(
+        downsample(&samples, LodDownsampling::Max),
+        WorldVoxel::Solid(2)
+    );
+
+    // Ties go to solid voxels
+    samples[0] = WorldVoxel::Solid(2);
+    samples[3] = WorldVoxel::Solid(2);
+    assert_eq!(
+        downsample(&samples, LodDownsampling::Majority),
+        WorldVoxel::Solid(2)
+    );
+}
+
+#[test]
+fn compressed_chunk_data_keeps_voxels() {
+    let mut voxels = [WorldVoxel::Air; 39304];
+    voxels[..1000].fill(WorldVoxel::Solid(1));
+    voxels[5000] = WorldVoxel::Solid(2);
+
+    let mut chunk_data = ChunkData::<u8> {
+        voxels: Some(std::sync::Arc::new(voxels)),
+        fill_type: FillType::Mixed,
+        ..default()
+    };
+    let uncompressed_size = chunk_data.voxels_size();
+    chunk_data.compress();
+
+    assert!(chunk_data.is_compressed());
+    assert!(chunk_data.voxels_size() < uncompressed_size / 100);
+    assert_eq!(chunk_data.get_voxel(UVec3::ZERO), WorldVoxel::Solid(1));
+    assert_eq!(*chunk_data.voxel_array().unwrap(), voxels);
+}
+
+#[test]
+fn partial_voxel_density() {
+    let voxel = WorldVoxel::Solid(1u8).with_density(100);
+    assert_eq!(voxel, WorldVoxel::Partial(1, 100));
+    assert_eq!(voxel.density(), 100);
+    assert!(voxel.is_solid());
+    assert_eq!(voxel.material(), Some(1));
+
+    assert_eq!(voxel.with_density(u8::MAX), WorldVoxel::Solid(1));
+    assert_eq!(voxel.with_density(0), WorldVoxel::Air);
+    assert_eq!(WorldVoxel::<u8>::Air.with_density(100).density(), 0);
+}
+
+#[test]
+fn faced_shape_keeps_boundary_sides() {
+    let block_models = BlockModels::default();
+
+    // Upside down, the bottom of a slab is at the top of the voxel
+    let slab =
+        block_models.faced_shape_quads(VoxelShape::Slab, VoxelFacing::Down, VoxelRotation::Deg0);
+    let sides: Vec<IVec3> = slab.iter().filter_map(|q| q.boundary_side()).collect();
+    assert!(sides.contains(&IVec3::Y));
+    assert!(!sides.contains(&IVec3::NEG_Y));
+
+    // Tipped over towards +X, the slab fills the -X half of the voxel
+    let slab =
+        block_models.faced_shape_quads(VoxelShape::Slab, VoxelFacing::PosX, VoxelRotation::Deg0);
+    let sides: Vec<IVec3> = slab.iter().filter_map(|q| q.boundary_side()).collect();
+    assert!(sides.contains(&IVec3::NEG_X));
+    assert!(!sides.contains(&IVec3::X));
+    assert_eq!(
+        VoxelFacing::from_direction(IVec3::X),
+        Some(VoxelFacing::PosX)
+    );
+}
+
+#[test]
+fn fluid_voxel_level() {
+    let voxel = WorldVoxel::Fluid(1u8, 3);
+    assert_eq!(voxel.fluid_level(), Some(3));
+    assert_eq!(voxel.material(), Some(1));
+    assert!(voxel.is_fluid() && !voxel.is_solid());
+    assert_eq!(
+        WorldVoxel::Fluid(1u8, 20).fluid_level(),
+        Some(MAX_FLUID_LEVEL)
+    );
+    assert_eq!(WorldVoxel::Solid(1u8).fluid_level(), None);
+}
+
+#[test]
+fn chunk_data_stores_light() {
+    let light = VoxelLight::new(MAX_LIGHT_LEVEL, 7);
+    assert_eq!(light.sky(), MAX_LIGHT_LEVEL);
+    assert_eq!(light.block(), 7);
+    assert_eq!(light.with_sky(2).level(), 7);
+
+    let mut chunk_data = ChunkData::<u8>::new();
+    assert_eq!(chunk_data.get_light(UVec3::ONE), VoxelLight::default());
+    chunk_data.set_light(UVec3::ONE, light);
+    assert_eq!(chunk_data.get_light(UVec3::ONE), light);
+    assert_eq!(
+        chunk_data.get_light(UVec3::new(2, 1, 1)),
+        VoxelLight::default()
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn chunk_data_and_buffers_roundtrip_through_serde() {
+    use crate::chunk::ChunkTask;
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    let mut chunk_task =
+        ChunkTask::<DefaultWorld>::new(Entity::PLACEHOLDER, IVec3::ONE, ModifiedVoxels::default());
+    chunk_task.generate(
+        |pos, _| match pos.y {
+            y if y < 40 => WorldVoxel::Solid(1),
+            40 => WorldVoxel::Oriented(2, VoxelRotation::Deg90),
+            _ => WorldVoxel::Air,
+        },
+        LodDownsampling::Point,
+    );
+    let mut chunk_data = chunk_task.chunk_data;
+    chunk_data.set_light(UVec3::new(1, 20, 1), VoxelLight::new(15, 3));
+
+    let yaml = serde_yaml::to_string(&chunk_data).unwrap();
+    let loaded: ChunkData = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(loaded.position, IVec3::ONE);
+    assert_eq!(loaded.voxels_hash, chunk_data.voxels_hash);
+    assert_eq!(
+        loaded.get_voxel(UVec3::new(1, 9, 1)),
+        WorldVoxel::Oriented(2, VoxelRotation::Deg90)
+    );
+    assert_eq!(
+        loaded.get_light(UVec3::new(1, 20, 1)),
+        VoxelLight::new(15, 3)
+    );
+
+    let mut buffer = VoxelBuffer::new(UVec3::new(3, 2, 1));
+    buffer.fill(UVec3::ZERO, UVec3::new(2, 0, 0), WorldVoxel::Solid(1));
+    let yaml = serde_yaml::to_string(&buffer).unwrap();
+    assert_eq!(serde_yaml::from_str::<VoxelBuffer>(&yaml).unwrap(), buffer);
+}
+
+#[test]
+fn region_files_roundtrip_chunk_voxels() {
+    use crate::persistence::ChunkStorage;
+    use std::sync::Arc;
+
+    let directory = std::env::temp_dir().join(format!("bvw_regions_{}", std::process::id()));
+    let chunk_position = IVec3::new(-1, 2, 17);
+    let voxels = vec![
+        (IVec3::new(-32, 64, 544), WorldVoxel::Solid(3)),
+        (IVec3::new(-1, 95, 575), WorldVoxel::Air),
+        (
+            IVec3::new(-20, 70, 550),
+            WorldVoxel::Faced(1, VoxelFacing::NegX, VoxelRotation::Deg270),
+        ),
+        (IVec3::new(-10, 80, 560), WorldVoxel::Fluid(2, 5)),
+    ];
+
+    let storage = ChunkStorage::<DefaultWorld>::new(
+        Arc::new(RegionFileStorage::new(&directory)),
+        ChunkCodec::None,
+    );
+    storage.store_chunk(chunk_position, &voxels);
+    storage.flush();
+
+    // A fresh instance reads the region back from disk
+    let region_files = RegionFileStorage::new(&directory);
+    assert_eq!(region_files.list_chunks().unwrap(), vec![chunk_position]);
+    let storage = ChunkStorage::<DefaultWorld>::new(Arc::new(region_files), ChunkCodec::None);
+    let mut loaded = storage.load_chunk(chunk_position);
+    loaded.sort_by_key(|(position, _)| position.to_array());
+    let mut expected = voxels.clone();
+    expected.sort_by_key(|(position, _)| position.to_array());
+    assert_eq!(loaded, expected);
+    assert!(storage.load_chunk(IVec3::ZERO).is_empty());
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn region_files_are_written_in_chunk_order() {
+    let directory = std::env::temp_dir().join(format!("bvw_region_order_{}", std::process::id()));
+    let chunks = [
+        (IVec3::new(3, 0, 0), vec![1]),
+        (IVec3::new(0, 1, 0), vec![2, 3]),
+    ];
+
+    let write = |name: &str, chunks: &mut dyn Iterator<Item = &(IVec3, Vec<u8>)>| {
+        let region_files = RegionFileStorage::new(directory.join(name));
+        for (position, bytes) in chunks {
+            region_files.save_chunk(*position, bytes.clone()).unwrap();
+        }
+        region_files.flush().unwrap();
+        std::fs::read_dir(directory.join(name))
+            .unwrap()
+            .map(|entry| std::fs::read(entry.unwrap().path()).unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // Only the region file is left, and it doesn't depend on the order chunks were saved in
+    let forward = write("forward", &mut chunks.iter());
+    let backward = write("backward", &mut chunks.iter().rev());
+    assert_eq!(forward.len(), 1);
+    assert_eq!(forward, backward);
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_storage_roundtrips_chunks() {
+    let storage = SqliteChunkStorage::open_in_memory().unwrap();
+    storage
+        .save_chunk(IVec3::new(-3, 0, 7), vec![1, 2, 3])
+        .unwrap();
+    storage
+        .save_chunk(IVec3::new(-3, 0, 7), vec![4, 5])
+        .unwrap();
+    storage.save_chunk(IVec3::ZERO, vec![6]).unwrap();
+    storage.remove_chunk(IVec3::ZERO).unwrap();
+    storage.save_metadata(vec![7, 8]).unwrap();
+
+    assert_eq!(storage.list_chunks().unwrap(), vec![IVec3::new(-3, 0, 7)]);
+    assert_eq!(
+        storage.load_chunk(IVec3::new(-3, 0, 7)).unwrap(),
+        Some(vec![4, 5])
+    );
+    assert_eq!(storage.load_chunk(IVec3::ZERO).unwrap(), None);
+    assert_eq!(storage.load_metadata().unwrap(), Some(vec![7, 8]));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_storage_commits_saves_on_flush() {
+    let path = std::env::temp_dir().join(format!("bvw_sqlite_{}.db", std::process::id()));
+    let storage = SqliteChunkStorage::open(&path).unwrap();
+    let reader = SqliteChunkStorage::open(&path).unwrap();
+
+    storage.save_chunk(IVec3::ZERO, vec![1]).unwrap();
+    storage.save_chunk(IVec3::ONE, vec![2]).unwrap();
+    assert!(reader.list_chunks().unwrap().is_empty());
+
+    storage.flush().unwrap();
+    assert_eq!(reader.list_chunks().unwrap().len(), 2);
+
+    drop((storage, reader));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn chunk_codecs_roundtrip() {
+    let bytes: Vec<u8> = (0..4096).map(|i| (i / 64) as u8).collect();
+    let codecs = [
+        ChunkCodec::None,
+        #[cfg(feature = "lz4")]
+        ChunkCodec::Lz4,
+        #[cfg(feature = "zstd")]
+        ChunkCodec::Zstd(3),
+    ];
+
+    for codec in codecs {
+        let compressed = codec.compress(&bytes).unwrap();
+        assert_eq!(ChunkCodec::decompress(&compressed).unwrap(), bytes);
+    }
+    assert!(ChunkCodec::decompress(&[200, 1, 2]).is_err());
+}
+
+#[derive(Resource, Clone, Default)]
+struct PersistentWorld;
+
+impl VoxelWorldConfig for PersistentWorld {
+    fn manual_chunk_management(&self) -> bool {
+        true
+    }
+
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
+        Box::new(|_| {
+            Box::new(|pos, _| match pos.y {
+                y if y < 0 => WorldVoxel::Solid(1),
+                _ => WorldVoxel::Air,
+            })
+        })
+    }
+}
+
+#[test]
+fn only_voxels_differing_from_generation_are_saved() {
+    use crate::persistence::ChunkStorage;
+    use std::sync::Arc;
+
+    let storage = MemoryChunkStorage::new();
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<PersistentWorld>::minimal(),
+        VoxelWorldPersistencePlugin::<PersistentWorld>::with_storage(storage.clone()),
+    ));
+
+    // No chunks are spawned, so the edited chunk is saved right away
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<PersistentWorld>| {
+        voxel_world.set_voxel(IVec3::new(5, -1, 5), WorldVoxel::Solid(1));
+        voxel_world.set_voxel(IVec3::new(6, -1, 5), WorldVoxel::Air);
+        voxel_world.set_voxel(IVec3::new(5, 0, 5), WorldVoxel::Solid(2));
+        voxel_world.set_voxel(IVec3::new(40, 0, 5), WorldVoxel::Air);
+    });
+
+    // Chunks are saved on the IoTaskPool
+    let saved = |app: &App| {
+        app.world()
+            .resource::<Events<ChunkSaved<PersistentWorld>>>()
+            .len()
+    };
+    for _ in 0..100 {
+        app.update();
+        if saved(&app) > 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(saved(&app), 3);
+
+    // The chunk with only unchanged voxels is not stored at all
+    let mut chunks = storage.list_chunks().unwrap();
+    chunks.sort_by_key(|chunk| chunk.to_array());
+    assert_eq!(chunks, vec![IVec3::new(0, -1, 0), IVec3::ZERO]);
+
+    let storage = ChunkStorage::<PersistentWorld>::new(Arc::new(storage), ChunkCodec::None);
+    let mut saved = storage.load_chunk(IVec3::new(0, -1, 0));
+    saved.extend(storage.load_chunk(IVec3::ZERO));
+    saved.sort_by_key(|(position, _)| position.to_array());
+    assert_eq!(
+        saved,
+        vec![
+            (IVec3::new(5, 0, 5), WorldVoxel::Solid(2)),
+            (IVec3::new(6, -1, 5), WorldVoxel::Air),
+        ]
+    );
+}
+
+#[derive(Resource, Clone, Default)]
+struct DecoratedPersistentWorld;
+
+impl VoxelWorldConfig for DecoratedPersistentWorld {
+    fn manual_chunk_management(&self) -> bool {
+        true
+    }
+
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
+        PersistentWorld.voxel_lookup_delegate()
+    }
+
+    fn chunk_decorator(&self) -> Option<ChunkDecorator> {
+        Some(std::sync::Arc::new(|voxels| {
+            voxels.set(IVec3::new(5, 0, 5), WorldVoxel::Solid(3));
+        }))
+    }
+}
+
+#[test]
+fn decorated_voxels_are_not_saved() {
+    use crate::persistence::ChunkStorage;
+    use std::sync::Arc;
+
+    let storage = MemoryChunkStorage::new();
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<DecoratedPersistentWorld>::minimal(),
+        VoxelWorldPersistencePlugin::<DecoratedPersistentWorld>::with_storage(storage.clone()),
+    ));
+
+    // Setting the voxel the decorator places there is no change at all
+    app.add_systems(
+        Startup,
+        |mut voxel_world: VoxelWorld<DecoratedPersistentWorld>| {
+            voxel_world.set_voxel(IVec3::new(5, 0, 5), WorldVoxel::Solid(3));
+            voxel_world.set_voxel(IVec3::new(6, 0, 5), WorldVoxel::Solid(3));
+        },
+    );
+
+    let saved = |app: &App| {
+        app.world()
+            .resource::<Events<ChunkSaved<DecoratedPersistentWorld>>>()
+            .len()
+    };
+    for _ in 0..100 {
+        app.update();
+        if saved(&app) > 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(saved(&app), 1);
+
+    let storage =
+        ChunkStorage::<DecoratedPersistentWorld>::new(Arc::new(storage), ChunkCodec::None);
+    assert_eq!(
+        storage.load_chunk(IVec3::ZERO),
+        vec![(IVec3::new(6, 0, 5), WorldVoxel::Solid(3))]
+    );
+}
+
+#[test]
+fn modified_chunks_are_saved_on_exit() {
+    let storage = MemoryChunkStorage::new();
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<PersistentWorld>::minimal(),
+        VoxelWorldPersistencePlugin::<PersistentWorld>::with_storage(storage.clone()),
+    ));
+
+    app.add_systems(
+        Startup,
+        |mut voxel_world: VoxelWorld<PersistentWorld>, mut ev_app_exit: EventWriter<AppExit>| {
+            voxel_world.set_voxel(IVec3::new(5, 0, 5), WorldVoxel::Solid(2));
+            ev_app_exit.send(AppExit::Success);
+        },
+    );
+
+    // The save on exit blocks, so the chunk is stored by the end of the frame
+    app.update();
+    assert_eq!(storage.list_chunks().unwrap(), vec![IVec3::ZERO]);
+}
+
+#[derive(Resource, Clone, Default)]
+struct SeededWorld {
+    seed: u64,
+    scale: f32,
+}
+
+impl VoxelWorldConfig for SeededWorld {
+    fn world_metadata(&self) -> WorldMetadata {
+        WorldMetadata::new(self.seed).with_parameter("scale", self.scale)
+    }
+
+    fn from_world_metadata(metadata: &WorldMetadata) -> Option<Self> {
+        Some(Self {
+            seed: metadata.seed,
+            scale: metadata.parse_parameter("scale")?,
+        })
+    }
+}
+
+#[test]
+fn world_config_is_recreated_from_saved_metadata() {
+    let storage = MemoryChunkStorage::new();
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<SeededWorld>::minimal(),
+        VoxelWorldPersistencePlugin::<SeededWorld>::with_storage(storage.clone()),
+    ));
+    app.insert_resource(SeededWorld {
+        seed: 42,
+        scale: 0.5,
+    });
+    app.update();
+
+    let metadata = WorldMetadata::load(&storage).unwrap().unwrap();
+    assert!(metadata.is_compatible());
+    let config = SeededWorld::from_world_metadata(&metadata).unwrap();
+    assert_eq!(config.seed, 42);
+    assert_eq!(config.scale, 0.5);
+}
+
+#[test]
+fn incompatible_saved_worlds_are_not_overwritten() {
+    let storage = MemoryChunkStorage::new();
+    let saved = WorldMetadata {
+        chunk_size: 16,
+        ..WorldMetadata::new(7)
+    };
+    saved.save(&storage).unwrap();
+    assert!(crate::persistence::save_world_metadata(&storage, &WorldMetadata::default()).is_err());
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<PersistentWorld>::minimal(),
+        VoxelWorldPersistencePlugin::<PersistentWorld>::with_storage(storage.clone()),
+    ));
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<PersistentWorld>| {
+        voxel_world.set_voxel(IVec3::new(5, 0, 5), WorldVoxel::Solid(2));
+    });
+    for _ in 0..10 {
+        app.update();
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    assert_eq!(WorldMetadata::load(&storage).unwrap(), Some(saved));
+    assert!(storage.list_chunks().unwrap().is_empty());
+}
+
+#[test]
+fn snapshot_restores_set_voxels() {
+    let path = std::env::temp_dir().join(format!("bvw_snapshot_{}.bvw", std::process::id()));
+    let voxels = [
+        (IVec3::new(0, 0, 0), WorldVoxel::Solid(1)),
+        (IVec3::new(-40, 3, 70), WorldVoxel::Colored([10, 20, 30])),
+        (IVec3::new(500, -2, 9), WorldVoxel::Air),
+    ];
+
+    let mut app = _test_setup_app();
+    app.add_systems(Update, move |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxels(voxels);
+    });
+    app.update();
+    let snapshot_path = path.clone();
+    app.add_systems(PostUpdate, move |voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.snapshot_to_file(&snapshot_path).unwrap();
+    });
+    app.update();
+
+    let mut app = _test_setup_app();
+    let snapshot_path = path.clone();
+    app.add_systems(Update, move |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.load_snapshot(&snapshot_path).unwrap();
+        for (position, voxel) in voxels {
+            assert_eq!(voxel_world.get_voxel(position), voxel);
+        }
+    });
+    app.update();
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[derive(Resource, Clone, Default)]
+struct SnapshotWorld;
+
+impl VoxelWorldConfig for SnapshotWorld {
+    fn manual_chunk_management(&self) -> bool {
+        true
+    }
+
+    fn edit_history_size(&self) -> usize {
+        8
+    }
+}
+
+#[test]
+fn snapshots_include_saved_chunks_and_restore_protected_voxels() {
+    use crate::persistence::ChunkStorage;
+    use crate::snapshot::WorldSnapshot;
+    use bevy::ecs::system::RunSystemOnce;
+    use std::sync::Arc;
+
+    // A chunk that was saved and isn't loaded
+    let storage = MemoryChunkStorage::new();
+    ChunkStorage::<SnapshotWorld>::new(Arc::new(storage.clone()), ChunkCodec::None)
+        .store_chunk(IVec3::ZERO, &[(IVec3::new(5, 0, 5), WorldVoxel::Solid(2))]);
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<SnapshotWorld>::minimal(),
+        VoxelWorldPersistencePlugin::<SnapshotWorld>::with_storage(storage),
+    ));
+    app.update();
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<SnapshotWorld>| {
+            let voxels = voxel_world.snapshot().voxels::<u8>().unwrap();
+            assert_eq!(voxels, vec![(IVec3::new(5, 0, 5), WorldVoxel::Solid(2))]);
+
+            let other_world = WorldSnapshot::from_voxels(WorldMetadata::new(9), voxels);
+            assert!(voxel_world.apply_snapshot(&other_world).is_err());
+
+            voxel_world.protect_chunk(IVec3::ZERO);
+            let snapshot = WorldSnapshot::from_voxels(
+                SnapshotWorld.world_metadata(),
+                [(IVec3::new(5, 0, 5), WorldVoxel::Solid(3))],
+            );
+            voxel_world.apply_snapshot(&snapshot).unwrap();
+        });
+    app.update();
+
+    // The voxel is written inside the protected chunk, and can't be undone
+    let voxel = app
+        .world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<SnapshotWorld>| {
+            voxel_world.get_voxel(IVec3::new(5, 0, 5))
+        });
+    assert_eq!(voxel, WorldVoxel::Solid(3));
+    let undone = app
+        .world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<SnapshotWorld>| voxel_world.undo());
+    assert!(!undone);
+}
+
+#[test]
+fn region_exports_to_obj() {
+    let path = std::env::temp_dir().join(format!("bvw_export_{}.obj", std::process::id()));
+
+    let mut app = _test_setup_app();
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(1, 1, 1), WorldVoxel::Solid(1));
+        voxel_world.set_voxel(IVec3::new(31, 1, 1), WorldVoxel::Solid(1));
+        voxel_world.set_voxel(IVec3::new(32, 1, 1), WorldVoxel::Solid(1));
+        voxel_world.set_voxel(IVec3::new(40, 1, 1), WorldVoxel::Solid(1));
+    });
+    app.update();
+    let export_path = path.clone();
+    app.add_systems(Update, move |voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world
+            .export_region(IVec3::ZERO, IVec3::new(32, 2, 2), &export_path)
+            .unwrap();
+        assert!(voxel_world
+            .export_region(IVec3::ZERO, IVec3::ONE, "region.fbx")
+            .is_err());
+    });
+    app.update();
+
+    // A lone cube, and two cubes across a chunk border that share a face. The cube outside of the
+    // region is left out.
+    let obj = std::fs::read_to_string(&path).unwrap();
+    let count = |prefix: &str| obj.lines().filter(|l| l.starts_with(prefix)).count();
+    assert_eq!(count("v "), (6 + 10) * 4);
+    assert_eq!(count("f "), (6 + 10) * 2);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn replication_messages_sync_client_world() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut server = _test_setup_app();
+    server.add_plugins(VoxelWorldReplicationPlugin::<DefaultWorld>::default());
+    server.add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(1, 2, 3), WorldVoxel::Solid(4));
+    });
+    server.update();
+
+    // The snapshot of a chunk, followed by a delta for an edit made after it
+    let snapshot = server
+        .world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<DefaultWorld>| {
+            voxel_world.chunk_snapshot(IVec3::ZERO).encode()
+        });
+    server
+        .world_mut()
+        .resource_mut::<ReplicationOutbox<DefaultWorld>>()
+        .drain()
+        .for_each(drop);
+    server.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(-1, 0, 0), WorldVoxel::Colored([1, 2, 3]));
+    });
+    server.update();
+    server.update();
+    let deltas: Vec<_> = server
+        .world_mut()
+        .resource_mut::<ReplicationOutbox<DefaultWorld>>()
+        .drain()
+        .map(|message| message.encode())
+        .collect();
+    assert_eq!(deltas.len(), 1);
+
+    let mut client = _test_setup_app();
+    client.add_systems(Update, move |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        for bytes in std::iter::once(&snapshot).chain(&deltas) {
+            let message = ReplicationMessage::decode(bytes).unwrap();
+            voxel_world.apply_replication_message(&message).unwrap();
+        }
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(1, 2, 3)),
+            WorldVoxel::Solid(4)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(-1, 0, 0)),
+            WorldVoxel::Colored([1, 2, 3])
+        );
+    });
+    client.update();
+    assert!(ReplicationMessage::<u8>::decode(&[1, 0, 0]).is_none());
+}
+
+#[derive(Resource, Clone, Default)]
+struct TerrainWorld;
+
+impl VoxelWorldConfig for TerrainWorld {
+    fn voxel_lookup_delegate(&self) -> VoxelLookupDelegate {
+        Box::new(|_| {
+            Box::new(|pos, _| {
+                if pos.y < 0 {
+                    WorldVoxel::Solid(1)
+                } else {
+                    WorldVoxel::Air
+                }
+            })
+        })
+    }
+}
+
+#[test]
+fn replication_only_stores_voxels_that_differ_from_generated_terrain() {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<TerrainWorld>::minimal()));
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<TerrainWorld>| {
+        let hole = IVec3::new(0, -2, 0);
+        let ground = ReplicationMessage::snapshot(IVec3::new(0, -1, 0), |pos| {
+            if pos == hole {
+                WorldVoxel::Air
+            } else {
+                WorldVoxel::Solid(1)
+            }
+        });
+        let sky = ReplicationMessage::snapshot(IVec3::ZERO, |_| WorldVoxel::Air);
+        voxel_world.apply_replication_message(&ground).unwrap();
+        voxel_world.apply_replication_message(&sky).unwrap();
+
+        // Only the voxel that was dug out on the server is set
+        assert_eq!(voxel_world.dirty_chunks(), vec![IVec3::new(0, -1, 0)]);
+        assert_eq!(voxel_world.get_voxel(hole), WorldVoxel::Air);
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(0, -3, 0)),
+            WorldVoxel::Unset
+        );
+    });
+
+    app.update();
+}
+
+#[test]
+fn remote_voxel_edits_are_applied_to_matching_world() {
+    let mut app = _test_setup_app();
+    app.add_plugins(VoxelWorldReplicationPlugin::<DefaultWorld>::default());
+    app.world_mut().send_event(RemoteVoxelEdit::<u8> {
+        world: DefaultWorld.replication_key(),
+        voxels: vec![([1, 2, 3], WorldVoxel::Solid(5))],
+    });
+    app.world_mut().send_event(RemoteVoxelEdit::<u8> {
+        world: 7,
+        voxels: vec![([4, 5, 6], WorldVoxel::Solid(5))],
+    });
+    app.update();
+
+    // Received edits are applied and become deltas, but are not sent back out as edits
+    let mut outbox = app
+        .world_mut()
+        .resource_mut::<ReplicationOutbox<DefaultWorld>>();
+    assert_eq!(outbox.drain_edits().count(), 0);
+    assert_eq!(outbox.drain().count(), 1);
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(1, 2, 3)),
+            WorldVoxel::Solid(5)
+        );
+        assert_eq!(
+            voxel_world.get_voxel(IVec3::new(4, 5, 6)),
+            WorldVoxel::Unset
+        );
+        voxel_world.set_voxel(IVec3::new(7, 8, 9), WorldVoxel::Air);
+    });
+    app.update();
+    app.update();
+
+    let edits: Vec<_> = app
+        .world_mut()
+        .resource_mut::<ReplicationOutbox<DefaultWorld>>()
+        .drain_edits()
+        .collect();
+    assert_eq!(
+        edits,
+        vec![RemoteVoxelEdit {
+            world: 0,
+            voxels: vec![([7, 8, 9], WorldVoxel::Air)]
+        }]
+    );
+}
+
+#[test]
+fn servers_relay_remote_edits_to_every_connection() {
+    let mut app = _test_setup_app();
+    app.add_plugins(VoxelWorldReplicationPlugin::<DefaultWorld>::default());
+    let players: Vec<Entity> = (0..2)
+        .map(|_| {
+            app.world_mut()
+                .spawn((
+                    VoxelWorldStreamingAnchor::<DefaultWorld>::with_radius(1),
+                    ReplicationConnection::<DefaultWorld>::with_send_budget(usize::MAX),
+                    GlobalTransform::default(),
+                ))
+                .id()
+        })
+        .collect();
+    let drain = |app: &mut App, player: Entity| -> Vec<ReplicationMessage<u8>> {
+        app.world_mut()
+            .get_mut::<ReplicationConnection<DefaultWorld>>(player)
+            .unwrap()
+            .drain()
+            .collect()
+    };
+    app.update();
+    for player in &players {
+        assert_eq!(drain(&mut app, *player).len(), 27);
+    }
+
+    // An edit of a third peer reaches both clients
+    app.world_mut().send_event(RemoteVoxelEdit::<u8> {
+        world: DefaultWorld.replication_key(),
+        voxels: vec![([1, 2, 3], WorldVoxel::Solid(5))],
+    });
+    app.update();
+    for player in &players {
+        assert_eq!(
+            drain(&mut app, *player),
+            vec![ReplicationMessage::ChunkDelta {
+                chunk_position: IVec3::ZERO,
+                voxels: vec![(IVec3::new(1, 2, 3), WorldVoxel::Solid(5))],
+            }]
+        );
+    }
+    assert_eq!(
+        app.world_mut()
+            .resource_mut::<ReplicationOutbox<DefaultWorld>>()
+            .drain_edits()
+            .count(),
+        0
+    );
+}
+
+/// Answers chunk requests right away, with chunks that are solid below y = 0
+#[derive(Clone, Default)]
+struct LoopbackTransport {
+    requested: std::sync::Arc<std::sync::Mutex<Vec<IVec3>>>,
+    base_versions: std::sync::Arc<std::sync::Mutex<Vec<(IVec3, Option<u32>)>>>,
+    deltas: std::sync::Arc<std::sync::Mutex<Vec<ReplicationMessage<u8>>>>,
+}
+
+impl ChunkTransport<u8> for LoopbackTransport {
+    fn request_chunk(&mut self, chunk_position: IVec3, base_version: Option<u32>) {
+        self.requested.lock().unwrap().push(chunk_position);
+        self.base_versions
+            .lock()
+            .unwrap()
+            .push((chunk_position, base_version));
+    }
+
+    fn receive_chunk(&mut self) -> Option<ReplicationMessage<u8>> {
+        let chunk_position = self.requested.lock().unwrap().pop()?;
+        Some(ReplicationMessage::snapshot(chunk_position, |position| {
+            if position.y < 0 {
+                WorldVoxel::Solid(3)
+            } else {
+                WorldVoxel::Air
+            }
+        }))
+    }
+
+    fn receive_delta(&mut self) -> Option<ReplicationMessage<u8>> {
+        self.deltas.lock().unwrap().pop()
+    }
+}
+
+#[test]
+fn client_streams_chunks_through_transport() {
+    let transport = LoopbackTransport::default();
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<ManualWorld>::minimal(),
+        VoxelWorldClientPlugin::<ManualWorld>::new(transport.clone()),
+    ));
+    app.world_mut().spawn((
+        Camera3dBundle::default(),
+        VoxelWorldCamera::<ManualWorld>::default(),
+    ));
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<ManualWorld>| {
+        voxel_world.request_chunk(IVec3::new(0, -1, 0));
+    });
+    transport
+        .deltas
+        .lock()
+        .unwrap()
+        .push(ReplicationMessage::ChunkDelta {
+            chunk_position: IVec3::new(0, -1, 0),
+            voxels: vec![(IVec3::new(4, 31, 4), WorldVoxel::Air)],
+        });
+
+    for _ in 0..10 {
+        app.update();
+    }
+    assert!(transport.requested.lock().unwrap().is_empty());
+
+    // The chunk is generated from the received snapshot, with the delta applied on top
+    let mut threads = app.world_mut().query::<(
+        &crate::chunk::Chunk<ManualWorld>,
+        &mut crate::chunk::ChunkThread<ManualWorld>,
+    )>();
+    let (chunk, mut thread) = threads.single_mut(app.world_mut());
+    assert_eq!(chunk.position, IVec3::new(0, -1, 0));
+    let chunk_task = futures_lite::future::block_on(&mut thread.0);
+    let chunk_data = chunk_task.chunk_data;
+    assert_eq!(
+        chunk_data.get_voxel(UVec3::new(4, 32, 5)),
+        WorldVoxel::Solid(3)
+    );
+    assert_eq!(chunk_data.get_voxel(UVec3::new(5, 32, 5)), WorldVoxel::Air);
+    assert_eq!(
+        chunk_data.get_voxel(UVec3::new(4, 33, 5)),
+        WorldVoxel::Unset
+    );
+}
+
+#[test]
+fn client_forgets_chunks_beyond_spawning_distance() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let transport = LoopbackTransport::default();
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<ManualWorld>::minimal(),
+        VoxelWorldClientPlugin::<ManualWorld>::new(transport.clone()),
+    ));
+    app.world_mut().spawn((
+        Camera3dBundle::default(),
+        VoxelWorldCamera::<ManualWorld>::default(),
+    ));
+    let chunks = [IVec3::new(0, -1, 0), IVec3::new(50, 0, 0)];
+    let request = |app: &mut App| {
+        app.world_mut()
+            .run_system_once(move |mut voxel_world: VoxelWorld<ManualWorld>| {
+                for chunk_position in chunks {
+                    voxel_world.request_chunk(chunk_position);
+                }
+            });
+        for _ in 0..10 {
+            app.update();
+        }
+    };
+    app.update();
+    request(&mut app);
+    transport.base_versions.lock().unwrap().clear();
+
+    app.world_mut()
+        .run_system_once(move |mut voxel_world: VoxelWorld<ManualWorld>| {
+            for chunk_position in chunks {
+                voxel_world.release_chunk(ChunkRequest { chunk_position });
+            }
+        });
+    for _ in 0..10 {
+        app.update();
+    }
+
+    // Only the chunk near the camera is still kept to diff against
+    request(&mut app);
+    let mut base_versions = transport.base_versions.lock().unwrap().clone();
+    base_versions.sort_by_key(|(chunk_position, _)| chunk_position.x);
+    assert_eq!(base_versions, vec![(chunks[0], Some(0)), (chunks[1], None)]);
+}
+
+#[test]
+fn chunk_updates_are_diffed_against_base_version() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = _test_setup_app();
+    app.add_plugins(VoxelWorldReplicationPlugin::<DefaultWorld>::default());
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(1, 1, 1), WorldVoxel::Solid(1));
+    });
+    app.update();
+
+    let update = |app: &mut App, base_version: Option<u32>| {
+        app.world_mut().run_system_once(
+            move |voxel_world: VoxelWorld<DefaultWorld>,
+                  mut versions: ResMut<ChunkVersions<DefaultWorld>>| {
+                versions.chunk_update(&voxel_world, IVec3::ZERO, base_version)
+            },
+        )
+    };
+    let snapshot = update(&mut app, None);
+    let ReplicationMessage::ChunkSnapshot { version: 0, .. } = snapshot else {
+        panic!("expected a snapshot of the first version, got {snapshot:?}");
+    };
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(2, 1, 1), WorldVoxel::Solid(2));
+        voxel_world.set_voxel(IVec3::new(3, 1, 1), WorldVoxel::Solid(2));
+    });
+    app.update();
+    app.update();
+
+    // Only the changed run is sent, and it brings the base version up to date
+    let diff = update(&mut app, Some(0));
+    assert_eq!(
+        diff,
+        ReplicationMessage::ChunkDiff {
+            chunk_position: IVec3::ZERO,
+            base_version: 0,
+            version: 1,
+            runs: vec![(2 + 32 + 1024, 4 + 32 + 1024, WorldVoxel::Solid(2))],
+        }
+    );
+    assert_eq!(
+        ReplicationMessage::decode(&diff.encode()),
+        Some(diff.clone())
+    );
+    let mut voxels = snapshot.snapshot_voxels().unwrap();
+    assert!(diff.apply_diff(&mut voxels));
+    assert_eq!(voxels[3 + 32 + 1024], WorldVoxel::Solid(2));
+
+    // Older versions are rebuilt from the versions after them
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(4, 1, 1), WorldVoxel::Solid(3));
+    });
+    app.update();
+    app.update();
+    let ReplicationMessage::ChunkDiff {
+        version: 2, runs, ..
+    } = update(&mut app, Some(0))
+    else {
+        panic!("expected a diff to the third version");
+    };
+    assert_eq!(
+        runs,
+        vec![
+            (2 + 32 + 1024, 4 + 32 + 1024, WorldVoxel::Solid(2)),
+            (4 + 32 + 1024, 5 + 32 + 1024, WorldVoxel::Solid(3)),
+        ]
+    );
+
+    // Unknown versions get a snapshot
+    let ReplicationMessage::ChunkSnapshot { version: 2, .. } = update(&mut app, Some(5)) else {
+        panic!("expected a snapshot of the third version");
+    };
+}
+
+#[test]
+fn chunk_versions_are_forgotten_out_of_range() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = _test_setup_app();
+    app.add_plugins(VoxelWorldReplicationPlugin::<DefaultWorld>::default());
+    let player = app
+        .world_mut()
+        .spawn((
+            VoxelWorldStreamingAnchor::<DefaultWorld>::with_radius(0),
+            ReplicationConnection::<DefaultWorld>::with_send_budget(usize::MAX),
+            GlobalTransform::default(),
+        ))
+        .id();
+    app.update();
+    let messages: Vec<_> = app
+        .world_mut()
+        .get_mut::<ReplicationConnection<DefaultWorld>>(player)
+        .unwrap()
+        .drain()
+        .collect();
+    let [ReplicationMessage::ChunkSnapshot { version, .. }] = messages[..] else {
+        panic!("expected a snapshot of the chunk in range, got {messages:?}");
+    };
+
+    *app.world_mut().get_mut::<GlobalTransform>(player).unwrap() =
+        GlobalTransform::from_translation(Vec3::splat(1000.0));
+    app.update();
+
+    // The chunk is sent whole when it comes back, with a version the client never had
+    let update = app.world_mut().run_system_once(
+        move |voxel_world: VoxelWorld<DefaultWorld>,
+              mut versions: ResMut<ChunkVersions<DefaultWorld>>| {
+            versions.chunk_update(&voxel_world, IVec3::ZERO, Some(version))
+        },
+    );
+    let ReplicationMessage::ChunkSnapshot {
+        version: new_version,
+        ..
+    } = update
+    else {
+        panic!("expected a snapshot, got {update:?}");
+    };
+    assert_ne!(new_version, version);
+}
+
+#[test]
+fn connections_are_sent_chunks_in_range_within_budget() {
+    let mut app = _test_setup_app();
+    app.add_plugins(VoxelWorldReplicationPlugin::<DefaultWorld>::default());
+    let player = app
+        .world_mut()
+        .spawn((
+            VoxelWorldStreamingAnchor::<DefaultWorld>::with_radius(1),
+            ReplicationConnection::<DefaultWorld>::with_send_budget(1),
+            GlobalTransform::default(),
+        ))
+        .id();
+    let drain = |app: &mut App| -> Vec<ReplicationMessage<u8>> {
+        app.world_mut()
+            .get_mut::<ReplicationConnection<DefaultWorld>>(player)
+            .unwrap()
+            .drain()
+            .collect()
+    };
+
+    // The closest chunk comes first, and the budget holds back the rest until it is drained
+    app.update();
+    let messages = drain(&mut app);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].chunk_position(), IVec3::ZERO);
+    app.update();
+    assert_eq!(drain(&mut app).len(), 1);
+
+    app.world_mut()
+        .get_mut::<ReplicationConnection<DefaultWorld>>(player)
+        .unwrap()
+        .send_budget = usize::MAX;
+    app.update();
+    assert_eq!(drain(&mut app).len(), 25);
+
+    // Only chunks in range get deltas
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(1, 2, 3), WorldVoxel::Solid(4));
+        voxel_world.set_voxel(IVec3::new(100, 2, 3), WorldVoxel::Solid(4));
+    });
+    app.update();
+    app.update();
+    let messages = drain(&mut app);
+    assert!(!messages.is_empty());
+    assert!(messages.iter().all(|message| matches!(
+        message,
+        ReplicationMessage::ChunkDelta { chunk_position, .. } if *chunk_position == IVec3::ZERO
+    )));
+}
+
+#[test]
+fn teleports_and_undrained_outboxes_stay_within_budget() {
+    let mut app = _test_setup_app();
+    app.add_plugins(VoxelWorldReplicationPlugin::<DefaultWorld>::default());
+    let player = app
+        .world_mut()
+        .spawn((
+            VoxelWorldStreamingAnchor::<DefaultWorld>::with_radius(1),
+            ReplicationConnection::<DefaultWorld>::with_send_budget(usize::MAX),
+            GlobalTransform::default(),
+        ))
+        .id();
+    let drain = |app: &mut App| -> Vec<ReplicationMessage<u8>> {
+        app.world_mut()
+            .get_mut::<ReplicationConnection<DefaultWorld>>(player)
+            .unwrap()
+            .drain()
+            .collect()
+    };
+    app.update();
+    assert_eq!(drain(&mut app).len(), 27);
+
+    // The chunks around the new position are sent one budget at a time
+    let mut connection = app
+        .world_mut()
+        .get_mut::<ReplicationConnection<DefaultWorld>>(player)
+        .unwrap();
+    connection.send_budget = 1;
+    *app.world_mut().get_mut::<GlobalTransform>(player).unwrap() =
+        GlobalTransform::from_translation(Vec3::splat(1000.0));
+    app.update();
+    app.update();
+    assert_eq!(drain(&mut app).len(), 1);
+    let mut sent = 1;
+    for _ in 0..40 {
+        app.update();
+        let messages = drain(&mut app);
+        assert!(messages.len() <= 1);
+        sent += messages.len();
+    }
+    assert_eq!(sent, 27);
+
+    // The outbox only keeps the last frame's messages when it isn't drained
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(1, 2, 3), WorldVoxel::Solid(4));
+    });
+    for _ in 0..5 {
+        app.update();
+    }
+    assert_eq!(
+        app.world_mut()
+            .resource_mut::<ReplicationOutbox<DefaultWorld>>()
+            .drain()
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn late_joiners_are_synced_with_progress() {
+    let mut app = _test_setup_app();
+    app.add_plugins(VoxelWorldReplicationPlugin::<DefaultWorld>::default());
+    let player = app
+        .world_mut()
+        .spawn((
+            VoxelWorldStreamingAnchor::<DefaultWorld>::with_radius(1),
+            ReplicationConnection::<DefaultWorld>::with_send_budget(1)
+                .with_initial_send_budget(usize::MAX),
+            GlobalTransform::default(),
+        ))
+        .id();
+    let progress = |app: &mut App| {
+        let connection = app
+            .world()
+            .get::<ReplicationConnection<DefaultWorld>>(player)
+            .unwrap();
+        (connection.sync_progress(), connection.is_synced())
+    };
+    let synced_events = |app: &mut App| {
+        app.world_mut()
+            .resource_mut::<Events<ReplicationSynced<DefaultWorld>>>()
+            .drain()
+            .map(|ev| ev.entity)
+            .collect::<Vec<_>>()
+    };
+
+    // The initial budget lets all chunks in range through at once
+    app.update();
+    let (sync_progress, synced) = progress(&mut app);
+    assert_eq!(sync_progress.sent_chunks, 27);
+    assert_eq!(sync_progress.total_chunks, 27);
+    assert_eq!(sync_progress.fraction(), 1.0);
+    assert!(synced);
+    assert_eq!(synced_events(&mut app), vec![player]);
+
+    // A resync sends the full snapshot again
+    let mut connection = app
+        .world_mut()
+        .get_mut::<ReplicationConnection<DefaultWorld>>(player)
+        .unwrap();
+    let _ = connection.drain();
+    connection.resync();
+    assert_eq!(connection.sync_progress().sent_chunks, 0);
+    app.update();
+    let mut connection = app
+        .world_mut()
+        .get_mut::<ReplicationConnection<DefaultWorld>>(player)
+        .unwrap();
+    assert_eq!(connection.drain().count(), 27);
+    assert_eq!(synced_events(&mut app), vec![player]);
+}
+
+#[test]
+fn rejected_predicted_edits_are_rolled_back() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = _test_setup_app();
+    app.add_plugins((
+        VoxelWorldReplicationPlugin::<DefaultWorld>::default(),
+        VoxelWorldPredictionPlugin::<DefaultWorld>::default(),
+    ));
+    let predict = |app: &mut App, voxel: WorldVoxel| {
+        app.world_mut().run_system_once(
+            move |mut voxel_world: VoxelWorld<DefaultWorld>,
+                  mut predicted_edits: ResMut<PredictedEdits<DefaultWorld>>| {
+                predicted_edits.predict(&mut voxel_world, [(IVec3::new(1, 2, 3), voxel)])
+            },
+        )
+    };
+    let get_voxel = |app: &mut App| {
+        app.world_mut()
+            .run_system_once(|voxel_world: VoxelWorld<DefaultWorld>| {
+                voxel_world.get_voxel(IVec3::new(1, 2, 3))
+            })
+    };
+
+    app.update();
+    let accepted = predict(&mut app, WorldVoxel::Solid(4));
+    app.update();
+    assert_eq!(get_voxel(&mut app), WorldVoxel::Solid(4));
+    let rejected = predict(&mut app, WorldVoxel::Solid(5));
+    app.update();
+    assert_eq!(get_voxel(&mut app), WorldVoxel::Solid(5));
+
+    // Updates from the server don't override the prediction, but are rolled back to
+    app.world_mut().send_event(RemoteVoxelEdit::<u8> {
+        world: DefaultWorld.replication_key(),
+        voxels: vec![([1, 2, 3], WorldVoxel::Solid(7))],
+    });
+    app.update();
+    assert_eq!(get_voxel(&mut app), WorldVoxel::Solid(5));
+
+    let mut predicted_edits = app
+        .world_mut()
+        .resource_mut::<PredictedEdits<DefaultWorld>>();
+    predicted_edits.acknowledge(accepted);
+    assert!(!predicted_edits.is_pending(accepted));
+    assert_eq!(
+        predicted_edits.pending().collect::<Vec<_>>(),
+        vec![rejected]
+    );
+    app.world_mut().run_system_once(
+        move |mut voxel_world: VoxelWorld<DefaultWorld>,
+              mut predicted_edits: ResMut<PredictedEdits<DefaultWorld>>| {
+            predicted_edits.reject(rejected, &mut voxel_world);
+        },
+    );
+    app.update();
+    assert_eq!(get_voxel(&mut app), WorldVoxel::Solid(7));
+    assert!(app
+        .world()
+        .resource::<PredictedEdits<DefaultWorld>>()
+        .is_empty());
+}
+
+#[test]
+fn predictions_only_reconcile_server_voxels_and_roll_back_outside_history() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<HistoryWorld>::minimal(),
+        VoxelWorldPredictionPlugin::<HistoryWorld>::default(),
+    ));
+    let position = IVec3::new(1, 2, 3);
+    let get_voxel = |app: &mut App| {
+        app.world_mut()
+            .run_system_once(move |voxel_world: VoxelWorld<HistoryWorld>| {
+                voxel_world.get_voxel(position)
+            })
+    };
+    app.update();
+    app.world_mut()
+        .run_system_once(move |mut voxel_world: VoxelWorld<HistoryWorld>| {
+            let message = ReplicationMessage::ChunkDelta {
+                chunk_position: IVec3::ZERO,
+                voxels: vec![(position, WorldVoxel::Solid(7))],
+            };
+            voxel_world.apply_replication_message(&message).unwrap();
+        });
+    app.update();
+    let edit = app.world_mut().run_system_once(
+        move |mut voxel_world: VoxelWorld<HistoryWorld>,
+              mut predicted_edits: ResMut<PredictedEdits<HistoryWorld>>| {
+            predicted_edits.predict(&mut voxel_world, [(position, WorldVoxel::Solid(4))])
+        },
+    );
+    app.update();
+    assert_eq!(get_voxel(&mut app), WorldVoxel::Solid(4));
+
+    // A local edit that wasn't predicted is not taken for the server's voxel
+    app.world_mut()
+        .run_system_once(move |mut voxel_world: VoxelWorld<HistoryWorld>| {
+            voxel_world.set_voxel(position, WorldVoxel::Solid(6));
+        });
+    app.update();
+    assert_eq!(get_voxel(&mut app), WorldVoxel::Solid(6));
+
+    // The rollback applies in a region that was protected since, and can't be undone
+    let region = app.world_mut().run_system_once(
+        move |mut voxel_world: VoxelWorld<HistoryWorld>,
+              mut predicted_edits: ResMut<PredictedEdits<HistoryWorld>>| {
+            let region = voxel_world.protect_region(position, position);
+            predicted_edits.reject(edit, &mut voxel_world);
+            region
+        },
+    );
+    app.update();
+    assert_eq!(get_voxel(&mut app), WorldVoxel::Solid(7));
+    app.world_mut()
+        .run_system_once(move |mut voxel_world: VoxelWorld<HistoryWorld>| {
+            voxel_world.unprotect_region(region);
+            assert!(voxel_world.undo());
+        });
+    app.update();
+    assert_eq!(get_voxel(&mut app), WorldVoxel::Solid(4));
+}
+
+#[test]
+fn connections_send_nearest_messages_within_tick_budget() {
+    let mut app = _test_setup_app();
+    app.add_plugins(VoxelWorldReplicationPlugin::<DefaultWorld>::default());
+    let player = app
+        .world_mut()
+        .spawn((
+            VoxelWorldStreamingAnchor::<DefaultWorld>::with_radius(1),
+            ReplicationConnection::<DefaultWorld>::with_send_budget(usize::MAX).with_tick_budget(1),
+            GlobalTransform::default(),
+        ))
+        .id();
+    let drain = |app: &mut App| -> Vec<ReplicationMessage<u8>> {
+        app.world_mut()
+            .get_mut::<ReplicationConnection<DefaultWorld>>(player)
+            .unwrap()
+            .drain()
+            .collect()
+    };
+
+    // Each tick takes one message, nearest chunk first
+    app.update();
+    let messages = drain(&mut app);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].chunk_position(), IVec3::ZERO);
+    for _ in 0..26 {
+        assert_eq!(drain(&mut app).len(), 1);
+    }
+    assert!(drain(&mut app).is_empty());
+
+    // A burst of edits to the same voxel is merged into one delta, and edits near the anchor
+    // are sent before those further away
+    app.add_systems(
+        Update,
+        |mut voxel_world: VoxelWorld<DefaultWorld>, mut material: Local<u8>| {
+            *material += 1;
+            voxel_world.set_voxel(IVec3::new(33, 2, 3), WorldVoxel::Solid(*material));
+            voxel_world.set_voxel(IVec3::new(1, 2, 3), WorldVoxel::Solid(*material));
+        },
+    );
+    for _ in 0..4 {
+        app.update();
+    }
+    let messages = drain(&mut app);
+    assert_eq!(
+        messages,
+        vec![ReplicationMessage::ChunkDelta {
+            chunk_position: IVec3::ZERO,
+            voxels: vec![(IVec3::new(1, 2, 3), WorldVoxel::Solid(3))],
+        }]
+    );
+    let messages = drain(&mut app);
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].chunk_position(), IVec3::X);
+}
+
+#[test]
+fn material_info_is_looked_up_in_registry() {
+    let mut app = _test_setup_app();
+    app.update();
+    let mut registry = app
+        .world_mut()
+        .resource_mut::<VoxelMaterialRegistry<DefaultWorld>>();
+    registry.insert(
+        1,
+        VoxelMaterialInfo {
+            friction: 0.1,
+            ..VoxelMaterialInfo::from_texture_layer(3)
+        },
+    );
+    assert_eq!(registry.get(1).unwrap().texture_layers, [3, 3, 3]);
+    assert!(registry.get(2).is_none());
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(1, 2, 3), WorldVoxel::Solid(1));
+        voxel_world.set_voxel(IVec3::new(4, 5, 6), WorldVoxel::Solid(2));
+        let info = voxel_world.get_material_info(IVec3::new(1, 2, 3)).unwrap();
+        assert_eq!(info.friction, 0.1);
+        assert!(voxel_world.get_material_info(IVec3::new(4, 5, 6)).is_none());
+        assert!(voxel_world.get_material_info(IVec3::ZERO).is_none());
+    });
+    app.update();
+}
+
+#[test]
+fn wind_materials_sway_in_mesh() {
+    use crate::chunk::PaddedChunkShape;
+    use crate::meshing::{generate_chunk_mesh, MeshingConfig};
+    use crate::rendering::ATTRIBUTE_WIND;
+    use bevy::render::mesh::VertexAttributeValues;
+    use ndshape::ConstShape;
+    use std::sync::Arc;
+
+    let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+    voxels[PaddedChunkShape::linearize([5, 5, 5]) as usize] = WorldVoxel::Solid(1);
+    voxels[PaddedChunkShape::linearize([9, 5, 5]) as usize] = WorldVoxel::Solid(2);
+    let mut meshing_config = MeshingConfig::from_config(&DefaultWorld);
+    meshing_config.wind_materials = Arc::new(|material| material == 1);
+
+    let meshes = generate_chunk_mesh(Arc::new(voxels), None, None, IVec3::ZERO, &meshing_config);
+    let Some(VertexAttributeValues::Float32(winds)) = meshes.opaque.attribute(ATTRIBUTE_WIND)
+    else {
+        panic!("chunk meshes should have a wind attribute");
+    };
+    assert_eq!(winds.len(), 48);
+    assert_eq!(winds.iter().filter(|wind| **wind == 1.0).count(), 24);
+    assert_eq!(winds.iter().filter(|wind| **wind == 0.0).count(), 24);
+}
+
+#[test]
+fn material_pbr_properties_are_in_mesh() {
+    use crate::chunk::PaddedChunkShape;
+    use crate::meshing::{generate_chunk_mesh, MeshingConfig};
+    use crate::rendering::ATTRIBUTE_PBR;
+    use bevy::render::mesh::VertexAttributeValues;
+    use ndshape::ConstShape;
+    use std::sync::Arc;
+
+    let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+    voxels[PaddedChunkShape::linearize([5, 5, 5]) as usize] = WorldVoxel::Solid(1);
+    voxels[PaddedChunkShape::linearize([9, 5, 5]) as usize] = WorldVoxel::Solid(2);
+    let mut registry = VoxelMaterialRegistry::<DefaultWorld>::default();
+    registry.insert(
+        1,
+        VoxelMaterialInfo {
+            perceptual_roughness: Some(0.2),
+            metallic: Some(1.0),
+            ..default()
+        },
+    );
+    let meshing_config =
+        MeshingConfig::from_config(&DefaultWorld).with_material_registry(&registry);
+
+    let meshes = generate_chunk_mesh(Arc::new(voxels), None, None, IVec3::ZERO, &meshing_config);
+    let Some(VertexAttributeValues::Float32x3(pbr)) = meshes.opaque.attribute(ATTRIBUTE_PBR) else {
+        panic!("chunk meshes should have a pbr attribute");
+    };
+    assert_eq!(pbr.len(), 48);
+    assert_eq!(
+        pbr.iter().filter(|pbr| **pbr == [0.2, 1.0, -1.0]).count(),
+        24
+    );
+    assert_eq!(pbr.iter().filter(|pbr| **pbr == [-1.0; 3]).count(), 24);
+}
+
+#[derive(Resource, Clone, Default)]
+struct ShadowWorld;
+
+impl VoxelWorldConfig for ShadowWorld {
+    fn manual_chunk_management(&self) -> bool {
+        true
+    }
+
+    fn shadow_distance(&self) -> Option<u32> {
+        Some(2)
+    }
+}
+
+#[test]
+fn far_chunks_dont_cast_shadows() {
+    use bevy::pbr::NotShadowCaster;
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, VoxelWorldPlugin::<ShadowWorld>::minimal()));
+    app.world_mut().spawn((
+        Camera3dBundle::default(),
+        VoxelWorldCamera::<ShadowWorld>::default(),
+    ));
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<ShadowWorld>| {
+        voxel_world.request_chunk(IVec3::new(2, 0, 0));
+        voxel_world.request_chunk(IVec3::new(3, 0, 0));
     });
+
+    for _ in 0..10 {
+        app.update();
+    }
+
+    let mut chunks = app
+        .world_mut()
+        .query::<(&crate::chunk::Chunk<ShadowWorld>, Has<NotShadowCaster>)>();
+    let mut not_shadow_casters: Vec<(IVec3, bool)> = chunks
+        .iter(app.world())
+        .map(|(chunk, not_shadow_caster)| (chunk.position, not_shadow_caster))
+        .collect();
+    not_shadow_casters.sort_by_key(|(position, _)| position.x);
     assert_eq!(
-        test_state.path_step_index,
-        expected_path.len(),
-        "{}: Expected end voxel reached",
-        test_state.test_name
+        not_shadow_casters,
+        vec![(IVec3::new(2, 0, 0), false), (IVec3::new(3, 0, 0), true)]
+    );
+}
+
+#[test]
+fn chunks_enclosed_by_opaque_voxels_are_occluded() {
+    use crate::chunk::{PaddedChunkShape, CHUNK_SIZE_U};
+    use crate::meshing::{is_enclosed, MeshingConfig};
+    use ndshape::ConstShape;
+    use std::sync::Arc;
+
+    // A hollow chunk, with solid voxels all around it in its padding
+    let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+    for (i, voxel) in voxels.iter_mut().enumerate() {
+        let position = PaddedChunkShape::delinearize(i as u32);
+        if position.contains(&0) || position.contains(&(CHUNK_SIZE_U + 1)) {
+            *voxel = WorldVoxel::Solid(1);
+        }
+    }
+    let mut meshing_config = MeshingConfig::from_config(&DefaultWorld);
+    assert!(is_enclosed(&Arc::new(voxels), &meshing_config));
+
+    // Transparent voxels don't hide what is behind them
+    meshing_config.transparent_materials = Arc::new(|material| material == 2);
+    let hole = PaddedChunkShape::linearize([5, CHUNK_SIZE_U + 1, 5]) as usize;
+    voxels[hole] = WorldVoxel::Solid(2);
+    assert!(!is_enclosed(&Arc::new(voxels), &meshing_config));
+
+    voxels[hole] = WorldVoxel::Air;
+    assert!(!is_enclosed(&Arc::new(voxels), &meshing_config));
+}
+
+#[test]
+fn debug_overlay_tracks_chunk_states() {
+    use crate::chunk::Chunk;
+    use crate::prelude::{ChunkDebugState, VoxelWorldDebugOverlay};
+
+    let mut app = _test_setup_app();
+    app.update();
+    let overlay = app
+        .world()
+        .resource::<VoxelWorldDebugOverlay<DefaultWorld>>();
+    assert!(!overlay.enabled);
+    assert_eq!(overlay.count(ChunkDebugState::Queued), 0);
+
+    app.world_mut()
+        .resource_mut::<VoxelWorldDebugOverlay<DefaultWorld>>()
+        .toggle();
+    app.update();
+
+    let chunks = app
+        .world_mut()
+        .query::<&Chunk<DefaultWorld>>()
+        .iter(app.world())
+        .count();
+    let overlay = app
+        .world()
+        .resource::<VoxelWorldDebugOverlay<DefaultWorld>>();
+    assert!(chunks > 0);
+    assert_eq!(
+        overlay.count(ChunkDebugState::Queued)
+            + overlay.count(ChunkDebugState::Generating)
+            + overlay.count(ChunkDebugState::Meshing),
+        chunks
+    );
+}
+
+#[test]
+fn render_only_and_collide_only_materials() {
+    use crate::chunk::PaddedChunkShape;
+    use crate::meshing::{generate_chunk_mesh, MeshingConfig};
+    use ndshape::ConstShape;
+    use std::sync::Arc;
+
+    let mut registry = VoxelMaterialRegistry::<DefaultWorld>::default();
+    registry.insert(
+        2,
+        VoxelMaterialInfo {
+            render_only: true,
+            ..default()
+        },
+    );
+    registry.insert(
+        3,
+        VoxelMaterialInfo {
+            collide_only: true,
+            ..default()
+        },
+    );
+
+    // Collide only voxels are not meshed
+    let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+    voxels[PaddedChunkShape::linearize([5, 5, 5]) as usize] = WorldVoxel::Solid(2);
+    voxels[PaddedChunkShape::linearize([9, 5, 5]) as usize] = WorldVoxel::Solid(3);
+    let meshing_config =
+        MeshingConfig::from_config(&DefaultWorld).with_material_registry(&registry);
+    let meshes = generate_chunk_mesh(Arc::new(voxels), None, None, IVec3::ZERO, &meshing_config);
+    assert_eq!(meshes.opaque.count_vertices(), 24);
+
+    // Render only voxels don't collide
+    let mut app = _test_setup_app();
+    app.insert_resource(registry);
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        voxel_world.set_voxel(IVec3::new(0, 0, 0), WorldVoxel::Solid(1));
+        voxel_world.set_voxel(IVec3::new(1, 0, 0), WorldVoxel::Solid(2));
+        voxel_world.set_voxel(IVec3::new(2, 0, 0), WorldVoxel::Solid(3));
+    });
+    app.update();
+
+    app.add_systems(Update, |voxel_world: VoxelWorld<DefaultWorld>| {
+        assert!(voxel_world.collides_at(IVec3::new(0, 0, 0)));
+        assert!(!voxel_world.collides_at(IVec3::new(1, 0, 0)));
+        assert!(voxel_world.collides_at(IVec3::new(2, 0, 0)));
+        assert!(!voxel_world.collides_at(IVec3::new(3, 0, 0)));
+    });
+    app.update();
+}
+
+#[test]
+fn face_decals_cover_their_face() {
+    use crate::prelude::VoxelFaceDecal;
+
+    let top = VoxelFaceDecal::<DefaultWorld>::new(IVec3::new(1, 2, 3), IVec3::Y).transform();
+    assert!(top.translation.abs_diff_eq(Vec3::new(1.5, 3.0, 3.5), 0.01));
+    assert!((top.rotation * Vec3::Z).abs_diff_eq(Vec3::Y, 1e-5));
+    assert!(top.translation.y > 3.0);
+
+    let back = VoxelFaceDecal::<DefaultWorld>::new(IVec3::ZERO, IVec3::NEG_Z).transform();
+    assert!(back.translation.abs_diff_eq(Vec3::new(0.5, 0.5, 0.0), 0.01));
+    assert!((back.rotation * Vec3::Z).abs_diff_eq(Vec3::NEG_Z, 1e-5));
+}
+
+#[test]
+fn biome_tint_blends_between_columns() {
+    use crate::chunk::{biome_index, PaddedChunkShape, PADDED_CHUNK_SIZE};
+    use crate::meshing::{generate_chunk_mesh, MeshingConfig};
+    use bevy::render::mesh::VertexAttributeValues;
+    use ndshape::ConstShape;
+    use std::sync::Arc;
+
+    // Columns from x = 10 on are in biome 1
+    let mut biomes = [0; (PADDED_CHUNK_SIZE * PADDED_CHUNK_SIZE) as usize];
+    for x in 10..PADDED_CHUNK_SIZE {
+        for z in 0..PADDED_CHUNK_SIZE {
+            biomes[biome_index(x, z)] = 1;
+        }
+    }
+    let mut voxels = [WorldVoxel::Unset; PaddedChunkShape::SIZE as usize];
+    voxels[PaddedChunkShape::linearize([9, 5, 5]) as usize] = WorldVoxel::Solid(1);
+    let mut meshing_config = MeshingConfig::from_config(&DefaultWorld);
+    meshing_config.biome_tint = Some(Arc::new(|_, biome| {
+        if biome == 0 {
+            [1.0, 0.0, 0.0]
+        } else {
+            [0.0, 0.0, 1.0]
+        }
+    }));
+
+    let meshes = generate_chunk_mesh(
+        Arc::new(voxels),
+        Some(&biomes),
+        None,
+        IVec3::ZERO,
+        &meshing_config,
+    );
+    let Some(VertexAttributeValues::Float32x4(colors)) =
+        meshes.opaque.attribute(Mesh::ATTRIBUTE_COLOR)
+    else {
+        panic!("chunk meshes should have vertex colors");
+    };
+    // The side facing biome 0 is red, the side on the border fades to blue
+    assert!(colors.contains(&[1.0, 0.0, 0.0, 1.0]));
+    assert!(colors.contains(&[0.5, 0.0, 0.5, 1.0]));
+    assert!(!colors.contains(&[0.0, 0.0, 1.0, 1.0]));
+}
+
+#[derive(Resource, Clone, Default)]
+struct LitWorld;
+
+impl VoxelWorldConfig for LitWorld {
+    fn manual_chunk_management(&self) -> bool {
+        true
+    }
+
+    fn light_emission(&self) -> std::sync::Arc<dyn Fn(u8) -> u8 + Send + Sync> {
+        std::sync::Arc::new(|mt| if matches!(mt, 2 | 3) { 14 } else { 0 })
+    }
+
+    // A red lamp, and red glass
+    fn light_color(&self) -> std::sync::Arc<dyn Fn(u8) -> [u8; 3] + Send + Sync> {
+        std::sync::Arc::new(|mt| match mt {
+            3 | 4 => [255, 0, 0],
+            _ => [255; 3],
+        })
+    }
+
+    fn transparent_materials(&self) -> std::sync::Arc<dyn Fn(u8) -> bool + Send + Sync> {
+        std::sync::Arc::new(|mt| mt == 4)
+    }
+}
+
+#[test]
+fn block_light_spreads_from_emitters() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<LitWorld>::minimal(),
+        VoxelWorldLightingPlugin::<LitWorld>::default(),
+    ));
+    app.add_systems(
+        Startup,
+        |mut voxel_world: VoxelWorld<LitWorld>,
+         mut chunk_map_update_buffer: ResMut<ChunkMapUpdateBuffer<LitWorld>>| {
+            // A torch, with a wall between it and one of the voxels
+            voxel_world.set_voxel(IVec3::new(5, 5, 5), WorldVoxel::Solid(2));
+            voxel_world.set_voxel(IVec3::new(5, 5, 7), WorldVoxel::Solid(1));
+            chunk_map_update_buffer.push((
+                IVec3::ZERO,
+                ChunkData {
+                    voxels: Some(std::sync::Arc::new([WorldVoxel::Unset; 39304])),
+                    is_empty: false,
+                    fill_type: FillType::Mixed,
+                    ..ChunkData::new()
+                },
+                ChunkWillSpawn::<LitWorld>::new(IVec3::ZERO, Entity::PLACEHOLDER),
+            ));
+        },
+    );
+    app.update();
+
+    let levels = app
+        .world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<LitWorld>| {
+            [
+                IVec3::new(5, 5, 5),
+                IVec3::new(8, 5, 5),
+                IVec3::new(5, 5, 7),
+                IVec3::new(5, 5, 8),
+            ]
+            .map(|position| voxel_world.get_light(position).block())
+        });
+    // The light goes around the wall, which stays dark itself
+    assert_eq!(levels, [14, 11, 0, 9]);
+}
+
+#[test]
+fn sky_light_fades_under_overhangs() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<LitWorld>::minimal(),
+        VoxelWorldLightingPlugin::<LitWorld>::default(),
+    ));
+    app.add_systems(
+        Startup,
+        |mut voxel_world: VoxelWorld<LitWorld>,
+         mut chunk_map_update_buffer: ResMut<ChunkMapUpdateBuffer<LitWorld>>| {
+            // A roof over the columns around (5, 5)
+            for x in 4..=6 {
+                for z in 4..=6 {
+                    voxel_world.set_voxel(IVec3::new(x, 20, z), WorldVoxel::Solid(1));
+                }
+            }
+            chunk_map_update_buffer.push((
+                IVec3::ZERO,
+                ChunkData {
+                    voxels: Some(std::sync::Arc::new([WorldVoxel::Unset; 39304])),
+                    is_empty: false,
+                    fill_type: FillType::Mixed,
+                    ..ChunkData::new()
+                },
+                ChunkWillSpawn::<LitWorld>::new(IVec3::ZERO, Entity::PLACEHOLDER),
+            ));
+        },
+    );
+    app.update();
+
+    let levels = app
+        .world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<LitWorld>| {
+            [
+                IVec3::new(5, 25, 5),
+                IVec3::new(5, 20, 5),
+                IVec3::new(3, 10, 5),
+                IVec3::new(4, 10, 5),
+                IVec3::new(5, 10, 5),
+            ]
+            .map(|position| voxel_world.get_light(position).sky())
+        });
+    // Open columns are lit all the way down, and light fades towards the middle of the roof
+    assert_eq!(levels, [15, 0, 15, 14, 13]);
+}
+
+#[test]
+fn light_is_updated_around_edits() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<LitWorld>::minimal(),
+        VoxelWorldLightingPlugin::<LitWorld>::default(),
+    ));
+    app.add_systems(
+        Startup,
+        |mut voxel_world: VoxelWorld<LitWorld>,
+         mut chunk_map_update_buffer: ResMut<ChunkMapUpdateBuffer<LitWorld>>| {
+            voxel_world.set_voxel(IVec3::new(5, 5, 5), WorldVoxel::Solid(2));
+            chunk_map_update_buffer.push((
+                IVec3::ZERO,
+                ChunkData {
+                    voxels: Some(std::sync::Arc::new([WorldVoxel::Unset; 39304])),
+                    is_empty: false,
+                    fill_type: FillType::Mixed,
+                    ..ChunkData::new()
+                },
+                ChunkWillSpawn::<LitWorld>::new(IVec3::ZERO, Entity::PLACEHOLDER),
+            ));
+        },
     );
+    app.update();
+
+    // Move the torch, and cover a column
+    app.world_mut()
+        .run_system_once(|mut voxel_world: VoxelWorld<LitWorld>| {
+            voxel_world.set_voxel(IVec3::new(5, 5, 5), WorldVoxel::Air);
+            voxel_world.set_voxel(IVec3::new(20, 5, 5), WorldVoxel::Solid(2));
+            voxel_world.set_voxel(IVec3::new(10, 25, 10), WorldVoxel::Solid(1));
+        });
+    app.update();
+
+    let levels = app
+        .world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<LitWorld>| {
+            [
+                voxel_world.get_light(IVec3::new(5, 5, 5)).block(),
+                voxel_world.get_light(IVec3::new(8, 5, 5)).block(),
+                voxel_world.get_light(IVec3::new(20, 5, 5)).block(),
+                voxel_world.get_light(IVec3::new(10, 24, 10)).sky(),
+                voxel_world.get_light(IVec3::new(10, 26, 10)).sky(),
+            ]
+        });
+    assert_eq!(levels, [0, 2, 14, 14, 15]);
+}
+
+#[test]
+fn colored_light_is_tinted_by_lamps_and_glass() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<LitWorld>::minimal(),
+        VoxelWorldLightingPlugin::<LitWorld>::default(),
+    ));
+    app.add_systems(
+        Startup,
+        |mut voxel_world: VoxelWorld<LitWorld>,
+         mut chunk_map_update_buffer: ResMut<ChunkMapUpdateBuffer<LitWorld>>| {
+            // A white torch behind red glass, and a red lamp
+            voxel_world.set_voxel(IVec3::new(5, 5, 5), WorldVoxel::Solid(2));
+            voxel_world.set_voxel(IVec3::new(6, 5, 5), WorldVoxel::Solid(4));
+            voxel_world.set_voxel(IVec3::new(20, 20, 20), WorldVoxel::Solid(3));
+            chunk_map_update_buffer.push((
+                IVec3::ZERO,
+                ChunkData {
+                    voxels: Some(std::sync::Arc::new([WorldVoxel::Unset; 39304])),
+                    is_empty: false,
+                    fill_type: FillType::Mixed,
+                    ..ChunkData::new()
+                },
+                ChunkWillSpawn::<LitWorld>::new(IVec3::ZERO, Entity::PLACEHOLDER),
+            ));
+        },
+    );
+    app.update();
+
+    let levels = app
+        .world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<LitWorld>| {
+            [
+                IVec3::new(6, 5, 5),
+                IVec3::new(7, 5, 5),
+                IVec3::new(22, 20, 20),
+            ]
+            .map(|position| voxel_world.get_light(position).block_rgb())
+        });
+    // Behind the glass, the red light shining through is brighter than the white light around it
+    assert_eq!(levels, [[13, 0, 0], [12, 10, 10], [12, 0, 0]]);
+}
+
+#[test]
+fn light_can_be_queried_for_gameplay() {
+    use bevy::ecs::system::RunSystemOnce;
+
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        VoxelWorldPlugin::<LitWorld>::minimal(),
+        VoxelWorldLightingPlugin::<LitWorld>::default(),
+    ));
+    app.add_systems(
+        Startup,
+        |mut voxel_world: VoxelWorld<LitWorld>,
+         mut chunk_map_update_buffer: ResMut<ChunkMapUpdateBuffer<LitWorld>>| {
+            voxel_world.set_voxel(IVec3::new(5, 5, 5), WorldVoxel::Solid(2));
+            chunk_map_update_buffer.push((
+                IVec3::ZERO,
+                ChunkData {
+                    voxels: Some(std::sync::Arc::new([WorldVoxel::Unset; 39304])),
+                    is_empty: false,
+                    fill_type: FillType::Mixed,
+                    ..ChunkData::new()
+                },
+                ChunkWillSpawn::<LitWorld>::new(IVec3::ZERO, Entity::PLACEHOLDER),
+            ));
+        },
+    );
+    app.update();
+
+    app.world_mut()
+        .run_system_once(|voxel_world: VoxelWorld<LitWorld>| {
+            let light = voxel_world.get_light(IVec3::new(7, 5, 5));
+            assert_eq!((light.sky(), light.block()), (15, 12));
+            // At night, the torch is brighter than the sky
+            assert_eq!(light.level_at_intensity(1.0), 15);
+            assert_eq!(light.level_at_intensity(0.2), 12);
+
+            // Light of chunks that are not spawned is unknown
+            assert!(voxel_world.try_get_light(IVec3::new(7, 5, 5)).is_some());
+            assert_eq!(voxel_world.try_get_light(IVec3::new(7, 50, 5)), None);
+            assert_eq!(
+                voxel_world.get_light(IVec3::new(7, 50, 5)),
+                VoxelLight::default()
+            );
+        });
+}
+
+#[test]
+fn dirty_chunks_can_be_marked_clean() {
+    let mut app = _test_setup_app();
+    app.add_systems(Startup, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        assert!(!voxel_world.has_unsaved_changes());
+        voxel_world.set_voxel(IVec3::new(1, 1, 1), WorldVoxel::Solid(1));
+        voxel_world.set_voxel(IVec3::new(-1, 1, 1), WorldVoxel::Solid(1));
+        assert!(voxel_world.has_unsaved_changes());
+    });
+    app.update();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let mut dirty = voxel_world.dirty_chunks();
+        if dirty.is_empty() {
+            return;
+        }
+        dirty.sort_by_key(|chunk| chunk.to_array());
+        assert_eq!(dirty, vec![IVec3::new(-1, 0, 0), IVec3::ZERO]);
+
+        voxel_world.mark_chunk_clean(IVec3::ZERO);
+        assert_eq!(voxel_world.dirty_chunks(), vec![IVec3::new(-1, 0, 0)]);
+        voxel_world.mark_all_clean();
+        assert!(!voxel_world.has_unsaved_changes());
+    });
+    app.update();
+    app.update();
+}
+
+#[test]
+fn chunk_task_generates_biomes() {
+    use crate::chunk::ChunkTask;
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    let mut chunk_task =
+        ChunkTask::<DefaultWorld>::new(Entity::PLACEHOLDER, IVec3::ZERO, ModifiedVoxels::default());
+    chunk_task.generate_biomes(|column| if column.x < 0 { 1 } else { 2 });
+    chunk_task.generate(|_, _| WorldVoxel::Air, LodDownsampling::Point);
+
+    // The padding column at x = -1 belongs to the neighbouring chunk
+    assert_eq!(chunk_task.chunk_data.get_biome(UVec3::ZERO), 1);
+    assert_eq!(chunk_task.chunk_data.get_biome(UVec3::ONE), 2);
+}
+
+#[test]
+fn chunk_decorator_keeps_modified_voxels() {
+    use crate::chunk::ChunkTask;
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    let modified_voxels = ModifiedVoxels::<DefaultWorld>::default();
+    modified_voxels
+        .write()
+        .unwrap()
+        .insert(IVec3::new(1, 0, 0), WorldVoxel::Solid(9));
+
+    let decorator: ChunkDecorator = std::sync::Arc::new(|voxels| {
+        voxels.set(IVec3::new(0, 0, 0), WorldVoxel::Solid(5));
+        voxels.set(IVec3::new(1, 0, 0), WorldVoxel::Solid(5));
+    });
+    let mut chunk_task =
+        ChunkTask::<DefaultWorld>::new(Entity::PLACEHOLDER, IVec3::ZERO, modified_voxels)
+            .with_decorator(Some(decorator));
+    chunk_task.generate(|_, _| WorldVoxel::Air, LodDownsampling::Point);
+
+    assert!(!chunk_task.is_empty());
+    assert_eq!(
+        chunk_task.chunk_data.get_voxel(UVec3::new(1, 1, 1)),
+        WorldVoxel::Solid(5)
+    );
+    assert_eq!(
+        chunk_task.chunk_data.get_voxel(UVec3::new(2, 1, 1)),
+        WorldVoxel::Solid(9)
+    );
+}
+
+#[test]
+fn uniform_chunks_are_stored_as_one_voxel() {
+    use crate::chunk::ChunkTask;
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    let generate = |voxel_fn: fn(IVec3, u8) -> WorldVoxel| {
+        let mut chunk_task = ChunkTask::<DefaultWorld>::new(
+            Entity::PLACEHOLDER,
+            IVec3::ZERO,
+            ModifiedVoxels::default(),
+        );
+        chunk_task.generate(voxel_fn, LodDownsampling::Point);
+        chunk_task.chunk_data
+    };
+
+    let sky = generate(|_, _| WorldVoxel::Air);
+    assert!(sky.voxels.is_none());
+    assert_eq!(sky.get_voxel(UVec3::ONE), WorldVoxel::Air);
+
+    let rock = generate(|_, _| WorldVoxel::Solid(3));
+    assert!(rock.voxels.is_none() && rock.is_full);
+    assert_eq!(rock.get_voxel(UVec3::ONE), WorldVoxel::Solid(3));
+
+    // Uniform chunks of different voxels must not share a mesh cache key
+    let dirt = generate(|_, _| WorldVoxel::Solid(4));
+    assert_ne!(rock.voxels_hash, dirt.voxels_hash);
+    assert_ne!(rock.voxels_hash, sky.voxels_hash);
+
+    // Buried chunks of several materials are never meshed, so they are kept compressed
+    let layers = generate(|pos, _| WorldVoxel::Solid(if pos.y < 16 { 1 } else { 2 }));
+    assert!(layers.is_compressed());
+    assert_eq!(layers.get_voxel(UVec3::new(1, 20, 1)), WorldVoxel::Solid(2));
+}
+
+#[test]
+fn full_fluid_chunks_do_not_hide_the_terrain_behind_them() {
+    use crate::chunk::ChunkTask;
+    use crate::meshing::MeshingConfig;
+    use crate::voxel_world_internal::ModifiedVoxels;
+
+    // Terrain below y = -20, and a lake up to y = 100 above it
+    let meshing_config = MeshingConfig::from_config(&DefaultWorld);
+    let generate = |chunk_position: IVec3| {
+        let mut chunk_task = ChunkTask::<DefaultWorld>::new(
+            Entity::PLACEHOLDER,
+            chunk_position,
+            ModifiedVoxels::default(),
+        );
+        chunk_task.generate(
+            |position: IVec3, _| {
+                if position.y < -20 {
+                    WorldVoxel::Solid(1)
+                } else if position.y < 100 {
+                    WorldVoxel::Fluid(2, MAX_FLUID_LEVEL)
+                } else {
+                    WorldVoxel::Air
+                }
+            },
+            LodDownsampling::Point,
+        );
+        chunk_task.find_opaque(&meshing_config);
+        chunk_task
+    };
+
+    // The chunk of water is full, but spawning rays and meshing don't stop at it
+    let mut water = generate(IVec3::new(0, 1, 0));
+    assert!(water.chunk_data.is_full);
+    assert!(!water.is_opaque());
+    water.mesh(&meshing_config);
+    assert!(water.mesh.is_some());
+
+    // The terrain chunk below it is mixed, and solid rock is opaque
+    let terrain = generate(IVec3::new(0, -1, 0));
+    assert!(!terrain.chunk_data.is_full && !terrain.is_empty());
+    let rock = generate(IVec3::new(0, -2, 0));
+    assert!(rock.is_opaque());
+}
+
+#[test]
+fn shared_voxel_data_keeps_one_copy() {
+    use std::sync::Arc;
+
+    let shared = SharedVoxelData::<u8>::new();
+    let mut voxels = [WorldVoxel::Air; 39304];
+    voxels[0] = WorldVoxel::Solid(1);
+
+    let mut first = ChunkData::<u8> {
+        voxels: Some(Arc::new(voxels)),
+        fill_type: FillType::Mixed,
+        ..default()
+    };
+    first.generate_hash();
+    let mut second = first.clone();
+    second.voxels = Some(Arc::new(voxels));
+
+    first.share_voxels(&shared);
+    second.share_voxels(&shared);
+    assert!(Arc::ptr_eq(
+        first.voxels.as_ref().unwrap(),
+        second.voxels.as_ref().unwrap()
+    ));
+    assert_eq!(shared.len(), 1);
+
+    drop(first);
+    drop(second);
+    assert!(shared.is_empty());
+}
+
+#[test]
+fn voxel_flags_can_be_queried() {
+    let mut app = _test_setup_app();
+
+    app.add_systems(Update, |mut voxel_world: VoxelWorld<DefaultWorld>| {
+        let custom_flag = VoxelFlags::custom(0);
+        voxel_world.set_flags(IVec3::ZERO, VoxelFlags::UNBREAKABLE | custom_flag);
+        voxel_world.set_flags(IVec3::new(50, 0, 0), VoxelFlags::UNBREAKABLE);
+
+        assert!(voxel_world.has_flags(IVec3::ZERO, custom_flag));
+        assert!(!voxel_world.has_flags(IVec3::ONE, VoxelFlags::UNBREAKABLE));
+        assert_eq!(
+            voxel_world.voxels_with_flags(VoxelFlags::UNBREAKABLE).len(),
+            2
+        );
+        assert_eq!(
+            voxel_world.voxels_with_flags_in(
+                IVec3::ZERO,
+                IVec3::splat(10),
+                VoxelFlags::UNBREAKABLE
+            ),
+            vec![IVec3::ZERO]
+        );
+
+        voxel_world.set_flags(IVec3::ZERO, VoxelFlags::NONE);
+        assert!(voxel_world.get_flags(IVec3::ZERO).is_empty());
+    });
+
+    app.update();
+}
+
+#[test]
+fn material_extension_extends_all_voxel_material_variants() {
+    use crate::light::VoxelSkyLight;
+    use crate::rendering::{ExtendedVoxelMaterial, VoxelWorldMaterialHandle};
+    use bevy::pbr::MaterialExtension;
+    use bevy::render::render_resource::AsBindGroup;
+
+    #[derive(Asset, AsBindGroup, TypePath, Clone, Debug)]
+    struct TintExtension {
+        #[uniform(103)]
+        tint: LinearRgba,
+    }
+
+    impl MaterialExtension for TintExtension {}
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Shader>()
+        .init_asset::<Image>()
+        .init_asset::<Mesh>();
+    app.add_plugins(
+        VoxelWorldPlugin::with_config(DefaultWorld).with_material_extension(TintExtension {
+            tint: LinearRgba::RED,
+        }),
+    );
+    app.update();
+
+    let extended = |app: &App| {
+        let handle = app
+            .world()
+            .resource::<VoxelWorldMaterialHandle<DefaultWorld, ExtendedVoxelMaterial<TintExtension>>>();
+        let materials = app
+            .world()
+            .resource::<Assets<ExtendedVoxelMaterial<TintExtension>>>();
+        let opaque = materials.get(&handle.handle).unwrap().clone();
+        let transparent = materials
+            .get(handle.transparent_handle.as_ref().unwrap())
+            .unwrap()
+            .clone();
+        (opaque, transparent)
+    };
+
+    let (opaque, transparent) = extended(&app);
+    assert_eq!(opaque.extension.tint, LinearRgba::RED);
+    assert_eq!(transparent.extension.tint, LinearRgba::RED);
+    assert_eq!(transparent.base.base.alpha_mode, AlphaMode::Blend);
+
+    // The voxel material inside the extended material follows the sky light
+    app.insert_resource(VoxelSkyLight::<DefaultWorld>::new(0.25));
+    app.update();
+    let (opaque, transparent) = extended(&app);
+    assert_eq!(opaque.base.extension.sky_light_intensity, 0.25);
+    assert_eq!(transparent.base.extension.sky_light_intensity, 0.25);
 }
@@ -1,17 +1,45 @@
-use bevy::{prelude::*, render::primitives::Aabb};
+use bevy::prelude::*;
+#[cfg(feature = "render")]
+use bevy::render::primitives::Aabb;
 use block_mesh::{MergeVoxel, Voxel, VoxelVisibility};
 
+use crate::voxel_shape::{VoxelFacing, VoxelRotation};
+
 pub const VOXEL_SIZE: f32 = 1.;
 
+/// The level of a full fluid voxel, see `WorldVoxel::Fluid`
+pub const MAX_FLUID_LEVEL: u8 = 7;
+
+/// A voxel in the world. The material of solid voxels is a `u8` by default, but can be any type,
+/// like an enum of block types or bit-packed block state. See `VoxelWorldMaterialIndex`.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
-pub enum WorldVoxel {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WorldVoxel<I = u8> {
     #[default]
     Unset,
     Air,
-    Solid(u8),
+    Solid(I),
+    /// A solid voxel with an sRGB color, emitted as vertex colors by the mesher.
+    /// Colored voxels are not textured.
+    Colored([u8; 3]),
+    /// A solid voxel with a rotation. The rotation is applied to the voxel's shape, see
+    /// `VoxelWorldConfig::material_shapes`.
+    Oriented(I, VoxelRotation),
+    /// A solid voxel whose shape is rotated around its up axis, and then tipped over to face the
+    /// given direction. For shapes like logs and pipes that can lie along any axis. Cube shaped
+    /// voxels are not affected.
+    Faced(I, VoxelFacing, VoxelRotation),
+    /// A solid voxel that only partly fills its space, with a density from 0 (empty) to 255
+    /// (full). The built-in mesher renders it as a full voxel, the density is there for smooth
+    /// meshers and effects like partial destruction.
+    Partial(I, u8),
+    /// A fluid voxel with a level from 0 to `MAX_FLUID_LEVEL`, for flowing fluids. The surface of
+    /// a fluid voxel is at `(level + 1) / 8` of its height, and sloped towards lower neighbours.
+    /// The material doesn't have to be flagged in `VoxelWorldConfig::fluid_materials`.
+    Fluid(I, u8),
 }
 
-impl WorldVoxel {
+impl<I: Copy + PartialEq> WorldVoxel<I> {
     pub fn is_unset(&self) -> bool {
         *self == WorldVoxel::Unset
     }
@@ -21,11 +49,108 @@ impl WorldVoxel {
     }
 
     pub fn is_solid(&self) -> bool {
-        matches!(self, WorldVoxel::Solid(_))
+        matches!(
+            self,
+            WorldVoxel::Solid(_)
+                | WorldVoxel::Colored(_)
+                | WorldVoxel::Oriented(_, _)
+                | WorldVoxel::Faced(_, _, _)
+                | WorldVoxel::Partial(_, _)
+        )
+    }
+
+    /// True for `Fluid` voxels. Fluids are not solid, so raycasts and colliders pass through them.
+    pub fn is_fluid(&self) -> bool {
+        matches!(self, WorldVoxel::Fluid(_, _))
+    }
+
+    /// The material of all solid and fluid voxels, except `Colored` ones
+    pub fn material(&self) -> Option<I> {
+        match self {
+            WorldVoxel::Solid(mt)
+            | WorldVoxel::Oriented(mt, _)
+            | WorldVoxel::Faced(mt, _, _)
+            | WorldVoxel::Partial(mt, _)
+            | WorldVoxel::Fluid(mt, _) => Some(*mt),
+            _ => None,
+        }
+    }
+
+    /// The level of `Fluid` voxels, clamped to `MAX_FLUID_LEVEL`
+    pub fn fluid_level(&self) -> Option<u8> {
+        match self {
+            WorldVoxel::Fluid(_, level) => Some((*level).min(MAX_FLUID_LEVEL)),
+            _ => None,
+        }
+    }
+
+    /// How much of its space the voxel fills, from 0 (empty) to 255 (full). Only `Partial`
+    /// voxels have a density in between.
+    pub fn density(&self) -> u8 {
+        match self {
+            WorldVoxel::Partial(_, density) => *density,
+            _ if self.is_solid() => u8::MAX,
+            _ => 0,
+        }
+    }
+
+    /// The same voxel with the given density. Voxels with a material become `Partial`, `Solid`
+    /// again at full density, or `Air` at zero density. Voxels without a material are returned
+    /// unchanged.
+    pub fn with_density(&self, density: u8) -> Self {
+        match (self.material(), density) {
+            (Some(_), 0) => WorldVoxel::Air,
+            (Some(mt), u8::MAX) => WorldVoxel::Solid(mt),
+            (Some(mt), _) => WorldVoxel::Partial(mt, density),
+            (None, _) => *self,
+        }
+    }
+
+    /// The rotation of `Oriented` and `Faced` voxels. Other voxels are not rotated.
+    pub fn rotation(&self) -> VoxelRotation {
+        match self {
+            WorldVoxel::Oriented(_, rotation) | WorldVoxel::Faced(_, _, rotation) => *rotation,
+            _ => VoxelRotation::Deg0,
+        }
+    }
+
+    /// The direction the top of `Faced` voxels faces. Other voxels face up.
+    pub fn facing(&self) -> VoxelFacing {
+        match self {
+            WorldVoxel::Faced(_, facing, _) => *facing,
+            _ => VoxelFacing::Up,
+        }
+    }
+
+    /// The same voxel rotated around the Y axis. Only `Oriented` and `Faced` voxels change.
+    pub fn rotated(&self, rotation: VoxelRotation) -> Self {
+        match self {
+            WorldVoxel::Oriented(mt, r) => WorldVoxel::Oriented(*mt, r.then(rotation)),
+            WorldVoxel::Faced(mt, facing, r) if facing.direction().y == 0 => {
+                WorldVoxel::Faced(*mt, facing.rotated(rotation), *r)
+            }
+            WorldVoxel::Faced(mt, facing, r) => WorldVoxel::Faced(*mt, *facing, r.then(rotation)),
+            _ => *self,
+        }
+    }
+
+    /// Create a colored voxel from a color packed as `0xRRGGBB`
+    pub fn from_packed_rgb(rgb: u32) -> Self {
+        WorldVoxel::Colored([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+    }
+
+    /// Get the color of a colored voxel packed as `0xRRGGBB`
+    pub fn packed_rgb(&self) -> Option<u32> {
+        match self {
+            WorldVoxel::Colored([r, g, b]) => {
+                Some(((*r as u32) << 16) | ((*g as u32) << 8) | *b as u32)
+            }
+            _ => None,
+        }
     }
 }
 
-impl Voxel for WorldVoxel {
+impl<I: PartialEq> Voxel for WorldVoxel<I> {
     fn get_visibility(&self) -> VoxelVisibility {
         if *self == WorldVoxel::Air || *self == WorldVoxel::Unset {
             VoxelVisibility::Empty
@@ -35,14 +160,68 @@ impl Voxel for WorldVoxel {
     }
 }
 
-impl MergeVoxel for WorldVoxel {
-    type MergeValue = u8;
+impl<I: Copy + Eq> MergeVoxel for WorldVoxel<I> {
+    type MergeValue = Self;
 
     fn merge_value(&self) -> Self::MergeValue {
-        match self {
-            WorldVoxel::Solid(v) => *v,
-            _ => 0,
-        }
+        *self
+    }
+}
+
+/// A small set of flags per voxel, like `WATERLOGGED` or `UNBREAKABLE`. Flags are stored apart
+/// from the voxels and persist like modified voxels, see `VoxelWorld::set_flags`. The flags
+/// don't change how the world is meshed; what they mean is up to the game. Bits from
+/// `FIRST_CUSTOM_BIT` up are free for flags of your own.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VoxelFlags(pub u8);
+
+impl VoxelFlags {
+    pub const NONE: Self = Self(0);
+    pub const WATERLOGGED: Self = Self(1);
+    pub const UNBREAKABLE: Self = Self(1 << 1);
+    pub const NO_COLLIDE: Self = Self(1 << 2);
+    pub const FIRST_CUSTOM_BIT: u8 = 3;
+
+    /// A flag of your own, `bit` counting from `FIRST_CUSTOM_BIT`
+    pub const fn custom(bit: u8) -> Self {
+        Self(1 << (Self::FIRST_CUSTOM_BIT + bit))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// True if all of the given flags are set
+    pub fn contains(&self, flags: VoxelFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    /// True if any of the given flags are set
+    pub fn intersects(&self, flags: VoxelFlags) -> bool {
+        self.0 & flags.0 != 0
+    }
+
+    pub fn insert(&mut self, flags: VoxelFlags) {
+        self.0 |= flags.0;
+    }
+
+    pub fn remove(&mut self, flags: VoxelFlags) {
+        self.0 &= !flags.0;
+    }
+}
+
+impl std::ops::BitOr for VoxelFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for VoxelFlags {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
     }
 }
 
@@ -73,11 +252,13 @@ impl TryFrom<VoxelFace> for Vec3 {
     }
 }
 
+#[cfg(feature = "render")]
 #[allow(unused)]
 pub(crate) trait VoxelAabb {
     fn ray_intersection(&self, ray: Ray3d) -> Option<(Vec3, Vec3)>;
 }
 
+#[cfg(feature = "render")]
 impl VoxelAabb for Aabb {
     fn ray_intersection(&self, ray: Ray3d) -> Option<(Vec3, Vec3)> {
         let min = self.min();
@@ -118,6 +299,7 @@ impl VoxelAabb for Aabb {
     }
 }
 
+#[cfg(feature = "render")]
 #[inline]
 fn floor_with_tolerance(value: f32, tolerance: f32) -> f32 {
     if (value.ceil() - value).abs() >= tolerance {
@@ -127,6 +309,7 @@ fn floor_with_tolerance(value: f32, tolerance: f32) -> f32 {
     }
 }
 
+#[cfg(feature = "render")]
 #[inline]
 fn vec3_floor_with_tolerance(value: Vec3, tolerance: f32) -> Vec3 {
     Vec3::new(
@@ -0,0 +1,151 @@
+///
+/// Voxel buffers
+/// Standalone boxes of voxels, not tied to any chunk. Used for prefabs like houses and trees that
+/// are stamped into the world with `VoxelWorld::paste`.
+///
+use bevy::prelude::*;
+
+use crate::{voxel::WorldVoxel, voxel_shape::VoxelRotation};
+
+/// A boolean operation between the solid voxels of two shapes, see `VoxelBuffer::combine` and
+/// `VoxelWorld::combine`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CsgOperation {
+    /// Keep the solid voxels of both shapes. Where both are solid, the other shape wins.
+    Union,
+    /// Keep the solid voxels that are also solid in the other shape
+    Intersection,
+    /// Remove the solid voxels that are solid in the other shape
+    Subtraction,
+}
+
+impl CsgOperation {
+    /// The result of the operation for one voxel, or `None` if the voxel is removed
+    pub(crate) fn apply<I: Copy + PartialEq>(
+        &self,
+        voxel: WorldVoxel<I>,
+        other: WorldVoxel<I>,
+    ) -> Option<WorldVoxel<I>> {
+        match self {
+            CsgOperation::Union if other.is_solid() => Some(other),
+            CsgOperation::Intersection if voxel.is_solid() && !other.is_solid() => None,
+            CsgOperation::Subtraction if voxel.is_solid() && other.is_solid() => None,
+            _ => Some(voxel),
+        }
+    }
+}
+
+/// A box of voxels, indexed from `UVec3::ZERO` up to, but not including, its size. All voxels
+/// start out `Unset`, and `Unset` voxels are left alone when the buffer is pasted, so only the
+/// voxels that were set replace those in the world.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoxelBuffer<I = u8> {
+    size: UVec3,
+    voxels: Vec<WorldVoxel<I>>,
+}
+
+impl<I: Copy + PartialEq> VoxelBuffer<I> {
+    pub fn new(size: UVec3) -> Self {
+        Self {
+            size,
+            voxels: vec![WorldVoxel::Unset; (size.x * size.y * size.z) as usize],
+        }
+    }
+
+    pub fn size(&self) -> UVec3 {
+        self.size
+    }
+
+    /// Get the voxel at the given position, or `Unset` if it is outside the buffer
+    pub fn get(&self, position: UVec3) -> WorldVoxel<I> {
+        self.index(position)
+            .map_or(WorldVoxel::Unset, |index| self.voxels[index])
+    }
+
+    /// Set the voxel at the given position. Positions outside the buffer are ignored.
+    pub fn set(&mut self, position: UVec3, voxel: WorldVoxel<I>) {
+        if let Some(index) = self.index(position) {
+            self.voxels[index] = voxel;
+        }
+    }
+
+    /// Set all voxels in the box between `min` and `max`, inclusive
+    pub fn fill(&mut self, min: UVec3, max: UVec3, voxel: WorldVoxel<I>) {
+        let max = max.min(self.size.saturating_sub(UVec3::ONE));
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    self.set(UVec3::new(x, y, z), voxel);
+                }
+            }
+        }
+    }
+
+    /// All positions in the buffer with their voxels
+    pub fn iter(&self) -> impl Iterator<Item = (UVec3, WorldVoxel<I>)> + '_ {
+        (0..self.voxels.len()).map(|index| self.position_of(index))
+    }
+
+    /// Combine this buffer with another one, placed with its `UVec3::ZERO` corner at `offset` in
+    /// this buffer. The buffer keeps its size, so parts of the other buffer outside of it are
+    /// ignored. Removed voxels become `Unset`.
+    pub fn combine(&mut self, operation: CsgOperation, other: &VoxelBuffer<I>, offset: IVec3) {
+        for index in 0..self.voxels.len() {
+            let (position, voxel) = self.position_of(index);
+            let other_position = position.as_ivec3() - offset;
+            let other_voxel = if other_position.cmplt(IVec3::ZERO).any() {
+                WorldVoxel::Unset
+            } else {
+                other.get(other_position.as_uvec3())
+            };
+            self.voxels[index] = operation
+                .apply(voxel, other_voxel)
+                .unwrap_or(WorldVoxel::Unset);
+        }
+    }
+
+    /// The world positions and voxels of the buffer, when pasted at `origin` with the given
+    /// rotation around the Y axis through `origin`. `Unset` voxels are skipped.
+    pub(crate) fn placed(
+        &self,
+        origin: IVec3,
+        rotation: VoxelRotation,
+    ) -> impl Iterator<Item = (IVec3, WorldVoxel<I>)> + '_ {
+        self.iter()
+            .filter(|(_, voxel)| !voxel.is_unset())
+            .map(move |(position, voxel)| {
+                let position = origin + rotation.rotate(position.as_ivec3());
+                (position, voxel.rotated(rotation))
+            })
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn voxels(&self) -> &[WorldVoxel<I>] {
+        &self.voxels
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn voxels_mut(&mut self) -> &mut [WorldVoxel<I>] {
+        &mut self.voxels
+    }
+
+    fn position_of(&self, index: usize) -> (UVec3, WorldVoxel<I>) {
+        let i = index as u32;
+        let position = UVec3::new(
+            i % self.size.x,
+            (i / self.size.x) % self.size.y,
+            i / (self.size.x * self.size.y),
+        );
+        (position, self.voxels[index])
+    }
+
+    fn index(&self, position: UVec3) -> Option<usize> {
+        if position.cmpge(self.size).any() {
+            return None;
+        }
+        Some(
+            (position.x + position.y * self.size.x + position.z * self.size.x * self.size.y)
+                as usize,
+        )
+    }
+}
@@ -1,5 +1,5 @@
 use bevy::{
-    pbr::{MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline},
+    pbr::{ExtendedMaterial, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline},
     prelude::*,
     reflect::TypePath,
     render::{
@@ -10,22 +10,64 @@ use bevy::{
         },
     },
 };
+use std::marker::PhantomData;
 
-/// Keeps track of the loading status of the image used for the voxel texture
+use crate::configuration::WorldConfig;
+
+/// Keeps track of the loading status of the image used for the voxel texture of the world with
+/// config `C`. Each world loads its own texture.
 #[derive(Resource)]
-pub(crate) struct LoadingTexture {
+pub(crate) struct LoadingTexture<C> {
     pub is_loaded: bool,
     pub handle: Handle<Image>,
+    pub _marker: PhantomData<C>,
+}
+
+impl<C> LoadingTexture<C> {
+    pub fn new(handle: Handle<Image>, is_loaded: bool) -> Self {
+        Self {
+            is_loaded,
+            handle,
+            _marker: PhantomData,
+        }
+    }
 }
 
+/// The number of layers in the voxel texture of the world with config `C`
 #[derive(Resource)]
-pub(crate) struct TextureLayers(pub u32);
+pub(crate) struct TextureLayers<C>(pub u32, pub PhantomData<C>);
 
 pub const VOXEL_TEXTURE_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(6998301138411443008);
 
-pub(crate) const ATTRIBUTE_TEX_INDEX: MeshVertexAttribute =
+/// Per-vertex texture layer indexes for the top, sides and bottom of a voxel face, as
+/// produced by `VoxelWorldConfig::texture_index_mapper`.
+pub const ATTRIBUTE_TEX_INDEX: MeshVertexAttribute =
     MeshVertexAttribute::new("TextureIndex", 989640910, VertexFormat::Uint32x3);
 
+/// Per-vertex biome of the voxel column a face belongs to, as produced by
+/// `VoxelWorldConfig::biome_lookup_delegate`. Zero when the world has no biomes.
+pub const ATTRIBUTE_BIOME: MeshVertexAttribute =
+    MeshVertexAttribute::new("Biome", 989640911, VertexFormat::Uint32);
+
+/// Per-vertex weight of the wind sway, from 0 for vertices that stay in place to 1 for those
+/// that move the most. Only non-zero for `VoxelWorldConfig::wind_materials`.
+pub const ATTRIBUTE_WIND: MeshVertexAttribute =
+    MeshVertexAttribute::new("Wind", 989640912, VertexFormat::Float32);
+
+/// Per-vertex perceptual roughness, metallic and reflectance of the material, from
+/// `VoxelMaterialInfo`. Negative values mean the value of the material is used.
+pub const ATTRIBUTE_PBR: MeshVertexAttribute =
+    MeshVertexAttribute::new("Pbr", 989640913, VertexFormat::Float32x3);
+
+/// Per-vertex sky light and red, green and blue block light, from 0 to 1, baked by
+/// `VoxelWorldLightingPlugin`. Chunks without light get full sky light and no block light.
+pub const ATTRIBUTE_LIGHT: MeshVertexAttribute =
+    MeshVertexAttribute::new("Light", 989640914, VertexFormat::Float32x4);
+
+/// Texture index used for faces that should not be textured, such as `WorldVoxel::Colored` voxels.
+/// The shader will use plain white instead of sampling the array texture.
+pub const UNTEXTURED_INDEX: u32 = u32::MAX;
+
 pub fn vertex_layout() -> Vec<VertexAttributeDescriptor> {
     vec![
         Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
@@ -37,15 +79,64 @@ pub fn vertex_layout() -> Vec<VertexAttributeDescriptor> {
         //Mesh::ATTRIBUTE_JOINT_INDEX.at_shader_location(6),
         //Mesh::ATTRIBUTE_JOINT_WEIGHT.at_shader_location(7),
         ATTRIBUTE_TEX_INDEX.at_shader_location(8),
+        ATTRIBUTE_BIOME.at_shader_location(9),
+        ATTRIBUTE_WIND.at_shader_location(10),
+        ATTRIBUTE_PBR.at_shader_location(11),
+        ATTRIBUTE_LIGHT.at_shader_location(12),
     ]
 }
+/// The built-in voxel material extension. Samples a `texture_2d_array`, where the layer is selected
+/// by the `ATTRIBUTE_TEX_INDEX` vertex attribute. Using an array texture rather than an atlas
+/// avoids bleeding between neighbouring textures at lower mip levels.
+///
+/// Used as `ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>`, see
+/// `StandardVoxelMaterialType`.
 #[derive(Asset, AsBindGroup, Debug, Clone, TypePath)]
-pub(crate) struct StandardVoxelMaterial {
+#[bind_group_data(StandardVoxelMaterialKey)]
+pub struct StandardVoxelMaterial {
     #[texture(100, dimension = "2d_array")]
     #[sampler(101)]
     pub voxels_texture: Handle<Image>,
+
+    /// Project the texture along the world axes and blend by the surface normal, instead of
+    /// using the mesh UVs. This avoids stretched textures on surfaces that are not axis aligned.
+    pub triplanar: bool,
+
+    /// Dithers the material in or out while chunks cross-fade between levels of detail.
+    /// Values below 1.0 show that fraction of the fragments, values above 1.0 hide
+    /// `lod_fade - 1.0` of them. Fully visible chunks use 1.0.
+    #[uniform(102)]
+    pub lod_fade: f32,
+
+    /// How far, in voxels, the vertices of `VoxelWorldConfig::wind_materials` sway
+    #[uniform(103)]
+    pub wind_strength: f32,
+
+    /// Scales the baked sky light, kept in sync with `VoxelSkyLight`
+    #[uniform(104)]
+    pub sky_light_intensity: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StandardVoxelMaterialKey {
+    triplanar: bool,
+}
+
+impl From<&StandardVoxelMaterial> for StandardVoxelMaterialKey {
+    fn from(material: &StandardVoxelMaterial) -> Self {
+        Self {
+            triplanar: material.triplanar,
+        }
+    }
 }
 
+/// The full material type used for voxel worlds that don't supply their own material.
+pub type StandardVoxelMaterialType = ExtendedMaterial<StandardMaterial, StandardVoxelMaterial>;
+
+/// The built-in voxel material, extended with a `MaterialExtension` of your own, see
+/// `VoxelWorldPlugin::with_material_extension`
+pub type ExtendedVoxelMaterial<E> = ExtendedMaterial<StandardVoxelMaterialType, E>;
+
 impl MaterialExtension for StandardVoxelMaterial {
     fn fragment_shader() -> ShaderRef {
         VOXEL_TEXTURE_SHADER_HANDLE.into()
@@ -59,18 +150,29 @@ impl MaterialExtension for StandardVoxelMaterial {
         _pipeline: &MaterialExtensionPipeline,
         descriptor: &mut RenderPipelineDescriptor,
         layout: &MeshVertexBufferLayoutRef,
-        _key: MaterialExtensionKey<Self>,
+        key: MaterialExtensionKey<Self>,
     ) -> Result<(), SpecializedMeshPipelineError> {
-        let vertex_layout = layout.0.get_layout(&vertex_layout())?;
+        let mut attributes = vertex_layout();
+        if layout.0.contains(Mesh::ATTRIBUTE_TANGENT) {
+            attributes.push(Mesh::ATTRIBUTE_TANGENT.at_shader_location(4));
+        }
+        let vertex_layout = layout.0.get_layout(&attributes)?;
         descriptor.vertex.buffers = vec![vertex_layout];
+
+        if key.bind_group_data.triplanar {
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("VOXEL_TRIPLANAR".into());
+            }
+        }
+
         Ok(())
     }
 }
 
-pub(crate) fn prepare_texture(
+pub(crate) fn prepare_texture<C: WorldConfig>(
     asset_server: Res<AssetServer>,
-    texture_layers: Res<TextureLayers>,
-    mut loading_texture: ResMut<LoadingTexture>,
+    texture_layers: Res<TextureLayers<C>>,
+    mut loading_texture: ResMut<LoadingTexture<C>>,
     mut images: ResMut<Assets<Image>>,
 ) {
     if loading_texture.is_loaded
@@ -81,6 +183,9 @@ pub(crate) fn prepare_texture(
     }
     loading_texture.is_loaded = true;
 
+    // Worlds that use the same texture share the image, which is only reinterpreted once
     let image = images.get_mut(&loading_texture.handle).unwrap();
-    image.reinterpret_stacked_2d_as_array(texture_layers.0);
+    if image.texture_descriptor.size.depth_or_array_layers == 1 {
+        image.reinterpret_stacked_2d_as_array(texture_layers.0);
+    }
 }
@@ -0,0 +1,390 @@
+use bevy::prelude::*;
+
+/// The shape the mesher uses to render a voxel material
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub enum VoxelShape {
+    /// A regular cube. Faces between neighbouring cubes are culled.
+    #[default]
+    Cube,
+
+    /// Two crossed, double-sided quads, for vegetation like grass and flowers. These are never
+    /// culled, and don't hide the faces of neighbouring voxels.
+    Cross,
+
+    /// The bottom half of a cube
+    Slab,
+
+    /// A bottom slab with a half-height step on top, rising towards +Z before rotation
+    Stairs,
+
+    /// A wedge sloping from the bottom edge at -Z up to the top edge at +Z before rotation
+    Ramp,
+
+    /// A user-defined model, registered in the `BlockModels` returned from
+    /// `VoxelWorldConfig::block_models`. For things like fences, torches and pipes.
+    Model(u16),
+}
+
+/// Rotation of a voxel shape around the Y axis, in quarter turns. Rotating by 90 degrees turns
+/// the +Z side of the shape towards +X.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VoxelRotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl VoxelRotation {
+    fn quarter_turns(&self) -> u8 {
+        match self {
+            VoxelRotation::Deg0 => 0,
+            VoxelRotation::Deg90 => 1,
+            VoxelRotation::Deg180 => 2,
+            VoxelRotation::Deg270 => 3,
+        }
+    }
+
+    fn from_quarter_turns(turns: u8) -> Self {
+        match turns % 4 {
+            0 => VoxelRotation::Deg0,
+            1 => VoxelRotation::Deg90,
+            2 => VoxelRotation::Deg180,
+            _ => VoxelRotation::Deg270,
+        }
+    }
+
+    /// This rotation followed by another one
+    pub fn then(&self, other: VoxelRotation) -> Self {
+        Self::from_quarter_turns(self.quarter_turns() + other.quarter_turns())
+    }
+
+    /// Rotate a position around the Y axis through the origin
+    pub fn rotate(&self, position: IVec3) -> IVec3 {
+        (0..self.quarter_turns()).fold(position, |p, _| IVec3::new(p.z, p.y, -p.x))
+    }
+}
+
+/// The direction the top of a voxel shape faces, for shapes that are tipped over, like logs
+/// lying on their side or pipes running along an axis. The shape is first rotated around its own
+/// up axis by its `VoxelRotation`, then tipped over to face this direction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VoxelFacing {
+    #[default]
+    Up,
+    Down,
+    PosX,
+    NegX,
+    PosZ,
+    NegZ,
+}
+
+impl VoxelFacing {
+    /// The facing with its top towards the given side, or `None` if it is not a unit axis
+    pub fn from_direction(direction: IVec3) -> Option<Self> {
+        match direction {
+            IVec3::Y => Some(VoxelFacing::Up),
+            IVec3::NEG_Y => Some(VoxelFacing::Down),
+            IVec3::X => Some(VoxelFacing::PosX),
+            IVec3::NEG_X => Some(VoxelFacing::NegX),
+            IVec3::Z => Some(VoxelFacing::PosZ),
+            IVec3::NEG_Z => Some(VoxelFacing::NegZ),
+            _ => None,
+        }
+    }
+
+    /// The side the top of the voxel faces
+    pub fn direction(&self) -> IVec3 {
+        match self {
+            VoxelFacing::Up => IVec3::Y,
+            VoxelFacing::Down => IVec3::NEG_Y,
+            VoxelFacing::PosX => IVec3::X,
+            VoxelFacing::NegX => IVec3::NEG_X,
+            VoxelFacing::PosZ => IVec3::Z,
+            VoxelFacing::NegZ => IVec3::NEG_Z,
+        }
+    }
+
+    /// The facing after rotating around the Y axis. Up and down are not affected.
+    pub fn rotated(&self, rotation: VoxelRotation) -> Self {
+        let direction = rotation.rotate(self.direction());
+        Self::from_direction(direction).unwrap_or(*self)
+    }
+
+    /// Tips a point in unit cube space, or a direction when `offset` is 0, over so that +Y faces
+    /// this direction. Uses `offset - v` for the negated axis, so that points on the boundary of
+    /// the voxel stay exactly on the boundary.
+    fn apply(&self, v: Vec3, offset: f32) -> Vec3 {
+        match self {
+            VoxelFacing::Up => v,
+            VoxelFacing::Down => Vec3::new(v.x, offset - v.y, offset - v.z),
+            VoxelFacing::PosX => Vec3::new(v.y, offset - v.x, v.z),
+            VoxelFacing::NegX => Vec3::new(offset - v.y, v.x, v.z),
+            VoxelFacing::PosZ => Vec3::new(v.x, offset - v.z, v.y),
+            VoxelFacing::NegZ => Vec3::new(v.x, v.z, offset - v.y),
+        }
+    }
+}
+
+impl VoxelShape {
+    /// True if the shape is a full cube, that can be meshed and culled as one.
+    pub fn is_cube(&self) -> bool {
+        *self == VoxelShape::Cube
+    }
+
+    /// The quads making up the shape, in unit cube space. Empty for `VoxelShape::Cube`, which
+    /// is meshed separately, and for `VoxelShape::Model`, which is looked up in `BlockModels`.
+    pub fn quads(&self) -> Vec<ShapeQuad> {
+        match self {
+            VoxelShape::Cube | VoxelShape::Model(_) => vec![],
+            VoxelShape::Cross => {
+                let diagonal_a = ShapeQuad::new(
+                    Vec3::ZERO,
+                    Vec3::new(1.0, 0.0, 1.0),
+                    Vec3::Y,
+                    Vec3::new(1.0, 0.0, -1.0).normalize(),
+                );
+                let diagonal_b = ShapeQuad::new(
+                    Vec3::X,
+                    Vec3::new(-1.0, 0.0, 1.0),
+                    Vec3::Y,
+                    Vec3::new(1.0, 0.0, 1.0).normalize(),
+                );
+                vec![
+                    diagonal_a,
+                    diagonal_a.flipped(),
+                    diagonal_b,
+                    diagonal_b.flipped(),
+                ]
+            }
+            VoxelShape::Slab => box_quads(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0)),
+            VoxelShape::Stairs => {
+                let mut quads = box_quads(Vec3::ZERO, Vec3::new(1.0, 0.5, 1.0));
+                quads.extend(box_quads(Vec3::new(0.0, 0.5, 0.5), Vec3::ONE));
+                quads
+            }
+            VoxelShape::Ramp => {
+                let [_top, bottom, right, left, back, _front] =
+                    box_quads(Vec3::ZERO, Vec3::ONE).try_into().unwrap();
+
+                // The sides are triangles, made by collapsing the corner that is cut away
+                let mut left = left;
+                left.positions[3] = left.positions[2];
+                let mut right = right;
+                right.positions[1] = right.positions[0];
+
+                let slope = ShapeQuad::new(
+                    Vec3::ZERO,
+                    Vec3::X,
+                    Vec3::new(0.0, 1.0, 1.0),
+                    Vec3::new(0.0, 1.0, -1.0).normalize(),
+                );
+
+                vec![bottom, right, left, back, slope]
+            }
+        }
+    }
+
+    /// The quads making up the shape, rotated around the center of the voxel
+    pub fn rotated_quads(&self, rotation: VoxelRotation) -> Vec<ShapeQuad> {
+        self.quads()
+            .iter()
+            .map(|quad| quad.rotated(rotation))
+            .collect()
+    }
+}
+
+/// A registry of user-defined block models. Each model is a list of quads in unit cube space,
+/// which the mesher adds to the chunk mesh for every voxel with that model's shape.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use bevy_voxel_world::prelude::*;
+/// let mut block_models = BlockModels::default();
+/// let post = block_models.register(BlockModels::cuboid(
+///     Vec3::new(0.375, 0.0, 0.375),
+///     Vec3::new(0.625, 1.0, 0.625),
+/// ));
+/// assert_eq!(post, VoxelShape::Model(0));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BlockModels {
+    models: Vec<Vec<ShapeQuad>>,
+}
+
+impl BlockModels {
+    /// Register a model, returning the shape to use for it in `VoxelWorldConfig::material_shapes`
+    pub fn register(&mut self, quads: impl IntoIterator<Item = ShapeQuad>) -> VoxelShape {
+        let id = self.models.len() as u16;
+        self.models.push(quads.into_iter().collect());
+        VoxelShape::Model(id)
+    }
+
+    /// The quads of a registered model, or `None` if there is no model with this id
+    pub fn get(&self, id: u16) -> Option<&[ShapeQuad]> {
+        self.models.get(id as usize).map(|quads| quads.as_slice())
+    }
+
+    /// The quads of any shape, including registered models, rotated around the center of the voxel
+    pub fn shape_quads(&self, shape: VoxelShape, rotation: VoxelRotation) -> Vec<ShapeQuad> {
+        match shape {
+            VoxelShape::Model(id) => self
+                .get(id)
+                .unwrap_or_default()
+                .iter()
+                .map(|quad| quad.rotated(rotation))
+                .collect(),
+            _ => shape.rotated_quads(rotation),
+        }
+    }
+
+    /// The quads of any shape, rotated around the center of the voxel and then tipped over to
+    /// face `facing`
+    pub fn faced_shape_quads(
+        &self,
+        shape: VoxelShape,
+        facing: VoxelFacing,
+        rotation: VoxelRotation,
+    ) -> Vec<ShapeQuad> {
+        self.shape_quads(shape, rotation)
+            .iter()
+            .map(|quad| quad.faced(facing))
+            .collect()
+    }
+
+    /// The six faces of a box between `min` and `max`, in unit cube space. A building block
+    /// for models.
+    pub fn cuboid(min: Vec3, max: Vec3) -> Vec<ShapeQuad> {
+        box_quads(min, max)
+    }
+}
+
+/// The six faces of a box in unit cube space, in the order top, bottom, +X, -X, +Z, -Z.
+/// Texture coordinates are projected from the positions, so textures are not stretched.
+fn box_quads(min: Vec3, max: Vec3) -> Vec<ShapeQuad> {
+    let size = max - min;
+    [
+        ShapeQuad::new(
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::X * size.x,
+            Vec3::Z * size.z,
+            Vec3::Y,
+        ),
+        ShapeQuad::new(min, Vec3::Z * size.z, Vec3::X * size.x, Vec3::NEG_Y),
+        ShapeQuad::new(
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::Z * size.z,
+            Vec3::Y * size.y,
+            Vec3::X,
+        ),
+        ShapeQuad::new(
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::NEG_Z * size.z,
+            Vec3::Y * size.y,
+            Vec3::NEG_X,
+        ),
+        ShapeQuad::new(
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::NEG_X * size.x,
+            Vec3::Y * size.y,
+            Vec3::Z,
+        ),
+        ShapeQuad::new(min, Vec3::X * size.x, Vec3::Y * size.y, Vec3::NEG_Z),
+    ]
+    .map(|quad| quad.with_projected_uvs())
+    .to_vec()
+}
+
+/// A quad in unit cube space, used to build voxel shapes that are not cubes.
+///
+/// The corners are ordered so that the triangles `[0, 1, 2]` and `[2, 1, 3]` are wound
+/// counter-clockwise when looking at the quad from the direction of its normal.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShapeQuad {
+    pub positions: [Vec3; 4],
+    pub uvs: [Vec2; 4],
+    pub normal: Vec3,
+}
+
+impl ShapeQuad {
+    /// Create a quad from an origin corner and two edges. `u` runs along the texture's U axis and
+    /// `v` runs upwards along the texture. The quad faces `normal`, which should equal the
+    /// normalized `v.cross(u)`.
+    pub fn new(origin: Vec3, u: Vec3, v: Vec3, normal: Vec3) -> Self {
+        Self {
+            positions: [origin, origin + v, origin + u, origin + u + v],
+            uvs: [
+                Vec2::new(0.0, 1.0),
+                Vec2::new(0.0, 0.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(1.0, 0.0),
+            ],
+            normal,
+        }
+    }
+
+    /// Replace the texture coordinates with ones projected from the positions along the
+    /// dominant axis of the normal.
+    pub fn with_projected_uvs(mut self) -> Self {
+        let n = self.normal.abs();
+        self.uvs = self.positions.map(|p| {
+            if n.y >= n.x && n.y >= n.z {
+                Vec2::new(p.x, p.z)
+            } else if n.x >= n.z {
+                Vec2::new(p.z, 1.0 - p.y)
+            } else {
+                Vec2::new(p.x, 1.0 - p.y)
+            }
+        });
+        self
+    }
+
+    /// The same quad rotated around the Y axis through the center of the voxel. Quarter turns
+    /// are applied exactly, so positions on the voxel boundary stay on the boundary.
+    pub fn rotated(&self, rotation: VoxelRotation) -> Self {
+        let mut quad = *self;
+        for _ in 0..rotation.quarter_turns() {
+            quad.positions = quad.positions.map(|p| Vec3::new(p.z, p.y, 1.0 - p.x));
+            quad.normal = Vec3::new(quad.normal.z, quad.normal.y, -quad.normal.x);
+        }
+        quad
+    }
+
+    /// The same quad tipped over around the center of the voxel, so that its +Y faces `facing`
+    pub fn faced(&self, facing: VoxelFacing) -> Self {
+        Self {
+            positions: self.positions.map(|p| facing.apply(p, 1.0)),
+            uvs: self.uvs,
+            normal: facing.apply(self.normal, 0.0),
+        }
+    }
+
+    /// If the quad lies flat on one of the sides of the unit cube, facing outwards, returns the
+    /// direction of that side. Such quads can be culled when the neighbour in that direction is
+    /// an opaque cube.
+    pub fn boundary_side(&self) -> Option<IVec3> {
+        let side = self.normal.round();
+        if side.abs().element_sum() != 1.0 || self.normal != side {
+            return None;
+        }
+        let boundary = side.max(Vec3::ZERO).dot(Vec3::ONE);
+        self.positions
+            .iter()
+            .all(|p| (p.dot(side.abs()) - boundary).abs() < 1e-5)
+            .then(|| side.as_ivec3())
+    }
+
+    /// The same quad facing the opposite direction
+    pub fn flipped(&self) -> Self {
+        let [p0, p1, p2, p3] = self.positions;
+        let [uv0, uv1, uv2, uv3] = self.uvs;
+        Self {
+            positions: [p1, p0, p3, p2],
+            uvs: [uv1, uv0, uv3, uv2],
+            normal: -self.normal,
+        }
+    }
+}
@@ -2,21 +2,55 @@
 /// VoxelWorld
 /// This module implements most of the public API for bevy_voxel_world.
 ///
+use std::io;
 use std::marker::PhantomData;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 
-use bevy::{ecs::system::SystemParam, math::bounding::RayCast3d, prelude::*};
+use bevy::{
+    ecs::system::SystemParam,
+    math::bounding::RayCast3d,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use ndshape::ConstShape;
 
 use crate::{
+    brush::VoxelBrush,
+    chunk::{ChunkData, ChunkTask, FillType, PaddedChunkShape, CHUNK_SIZE_I},
     chunk_map::ChunkMap,
-    configuration::VoxelWorldConfig,
+    configuration::{LodDownsampling, WorldConfig},
+    edit_history::EditHistory,
+    heightmap::Heightmap,
+    light::VoxelLight,
+    material_registry::{VoxelMaterialInfo, VoxelMaterialRegistry},
+    persistence::ChunkStorage,
+    replication::ReplicationMessage,
+    snapshot::WorldSnapshot,
     traversal_alg::voxel_line_traversal,
-    voxel::WorldVoxel,
-    voxel_world_internal::{get_chunk_voxel_position, ModifiedVoxels, VoxelWriteBuffer},
+    voxel::{VoxelFlags, WorldVoxel},
+    voxel_buffer::{CsgOperation, VoxelBuffer},
+    voxel_shape::VoxelRotation,
+    voxel_world_internal::{
+        get_chunk_voxel_position, ChunkRequests, ModifiedChunks, ModifiedVoxels, ProtectedRegions,
+        QueuedEdits, ScheduledEdits, StreamingPaused, VoxelFlagsMap, VoxelWriteBuffer,
+    },
+};
+#[cfg(feature = "render")]
+use crate::{
+    export::{ExportMesh, MeshExportFormat},
+    meshing::MeshingConfig,
+    voxelize,
 };
 
 /// This component is used to mark the Camera that bevy_voxel_world should use to determine
 /// which chunks to spawn and despawn.
+///
+/// Several cameras can be marked, for example for split-screen. The chunks needed by any of them
+/// are spawned, and chunks are only despawned once none of them needs them anymore. When
+/// `VoxelWorldConfig::lod_distances` is configured, each part of the world gets the finest level
+/// of detail that any of the cameras needs.
 #[derive(Component)]
 pub struct VoxelWorldCamera<C> {
     _marker: PhantomData<C>,
@@ -30,6 +64,96 @@ impl<C> Default for VoxelWorldCamera<C> {
     }
 }
 
+/// Keeps the chunks around an entity spawned, independent of any camera. This can be used on
+/// any entity, like players on a server, NPCs or projectiles, to load the world around them.
+///
+/// Anchors spawn full detail chunks within `radius` chunks, or `VoxelWorldConfig::spawning_distance`
+/// if no radius is set. Levels of detail are only streamed around the camera, so anchors are
+/// ignored with a warning when `VoxelWorldConfig::lod_distances` is configured. Without the
+/// `render` feature there are no levels of detail, and anchors are always used.
+#[derive(Component)]
+pub struct VoxelWorldStreamingAnchor<C> {
+    pub radius: Option<u32>,
+    _marker: PhantomData<C>,
+}
+
+impl<C> VoxelWorldStreamingAnchor<C> {
+    /// Keep the chunks within the given distance, in chunks, spawned
+    pub fn with_radius(radius: u32) -> Self {
+        Self {
+            radius: Some(radius),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> Default for VoxelWorldStreamingAnchor<C> {
+    fn default() -> Self {
+        Self {
+            radius: None,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Handle to a chunk requested with `VoxelWorld::request_chunk`. The chunk stays spawned until
+/// the handle is passed to `VoxelWorld::release_chunk`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkRequest {
+    pub chunk_position: IVec3,
+}
+
+/// Handle to an edit queued with `VoxelWorld::queue_edit`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct QueuedEdit {
+    pub id: u64,
+}
+
+/// Handle to a region protected with `VoxelWorld::protect_region`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ProtectedRegion {
+    pub id: u64,
+}
+
+/// Fired when a voxel could not be set because it is protected, see
+/// `VoxelWorld::protect_region` and `VoxelWorld::protect_chunk`
+#[derive(Event)]
+pub struct VoxelEditRejected<C> {
+    pub position: IVec3,
+    _marker: PhantomData<C>,
+}
+
+impl<C> VoxelEditRejected<C> {
+    pub fn new(position: IVec3) -> Self {
+        Self {
+            position,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Handle to an edit scheduled with `VoxelWorld::schedule_edit`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ScheduledEdit {
+    pub id: u64,
+}
+
+/// Fired when the last voxels of a queued edit have been written
+#[derive(Event)]
+pub struct QueuedEditFinished<C> {
+    pub edit: QueuedEdit,
+    _marker: PhantomData<C>,
+}
+
+impl<C> QueuedEditFinished<C> {
+    pub fn new(edit: QueuedEdit) -> Self {
+        Self {
+            edit,
+            _marker: PhantomData,
+        }
+    }
+}
+
 #[derive(Event)]
 pub struct ChunkEvent<C> {
     pub chunk_key: IVec3,
@@ -55,8 +179,32 @@ impl<C> ChunkEvent<C> {
     }
 }
 
-/// Fired when a chunk is about to be despawned.
-pub type ChunkWillDespawn<C> = ChunkEvent<C>;
+/// Fired when a chunk is about to be despawned. Full detail chunks carry their voxel data, so
+/// that it can be saved before it is dropped.
+#[derive(Event)]
+pub struct ChunkWillDespawn<C: WorldConfig> {
+    pub chunk_key: IVec3,
+    pub entity: Entity,
+    /// The voxels of the chunk, or None for chunks at coarser levels of detail, which aren't
+    /// kept in the chunk map
+    pub chunk_data: Option<ChunkData<C::MaterialIndex>>,
+    _marker: PhantomData<C>,
+}
+
+impl<C: WorldConfig> ChunkWillDespawn<C> {
+    pub fn new(
+        chunk_key: IVec3,
+        entity: Entity,
+        chunk_data: Option<ChunkData<C::MaterialIndex>>,
+    ) -> Self {
+        Self {
+            chunk_key,
+            entity,
+            chunk_data,
+            _marker: PhantomData,
+        }
+    }
+}
 
 /// Fired when a chunk is about to be spawned.
 pub type ChunkWillSpawn<C> = ChunkEvent<C>;
@@ -64,26 +212,27 @@ pub type ChunkWillSpawn<C> = ChunkEvent<C>;
 /// Fired when a chunk is about to be remeshed.
 pub type ChunkWillRemesh<C> = ChunkEvent<C>;
 
-pub trait FilterFn {
-    fn call(&self, input: (Vec3, WorldVoxel)) -> bool;
+pub trait FilterFn<I = u8> {
+    fn call(&self, input: (Vec3, WorldVoxel<I>)) -> bool;
 }
 
-impl<F: Fn((Vec3, WorldVoxel)) -> bool> FilterFn for F {
-    fn call(&self, input: (Vec3, WorldVoxel)) -> bool {
+impl<I, F: Fn((Vec3, WorldVoxel<I>)) -> bool> FilterFn<I> for F {
+    fn call(&self, input: (Vec3, WorldVoxel<I>)) -> bool {
         self(input)
     }
 }
 
-pub type RaycastFn = dyn Fn(Ray3d, &dyn FilterFn) -> Option<VoxelRaycastResult> + Send + Sync;
+pub type RaycastFn<I = u8> =
+    dyn Fn(Ray3d, &dyn FilterFn<I>) -> Option<VoxelRaycastResult<I>> + Send + Sync;
 
 #[derive(Default, Debug, PartialEq, Clone)]
-pub struct VoxelRaycastResult {
+pub struct VoxelRaycastResult<I = u8> {
     pub position: Vec3,
     pub normal: Option<Vec3>,
-    pub voxel: WorldVoxel,
+    pub voxel: WorldVoxel<I>,
 }
 
-impl VoxelRaycastResult {
+impl<I> VoxelRaycastResult<I> {
     /// Get the voxel position of the raycast result
     pub fn voxel_pos(&self) -> IVec3 {
         self.position.floor().as_ivec3()
@@ -95,46 +244,828 @@ impl VoxelRaycastResult {
     }
 }
 
+/// Edits made inside `VoxelWorld::edit`. Reads see the edits made so far in the transaction.
+pub struct VoxelTransaction<I = u8> {
+    get_voxel: Arc<dyn Fn(IVec3) -> WorldVoxel<I> + Send + Sync>,
+    writes: HashMap<IVec3, WorldVoxel<I>>,
+    aborted: bool,
+}
+
+impl<I: Copy + PartialEq> VoxelTransaction<I> {
+    pub fn get_voxel(&self, position: IVec3) -> WorldVoxel<I> {
+        self.writes
+            .get(&position)
+            .copied()
+            .unwrap_or_else(|| (self.get_voxel)(position))
+    }
+
+    pub fn set_voxel(&mut self, position: IVec3, voxel: WorldVoxel<I>) {
+        self.writes.insert(position, voxel);
+    }
+
+    pub fn set_voxels(&mut self, voxels: impl IntoIterator<Item = (IVec3, WorldVoxel<I>)>) {
+        self.writes.extend(voxels);
+    }
+
+    /// Discard all edits made in the transaction, instead of committing them
+    pub fn abort(&mut self) {
+        self.aborted = true;
+    }
+}
+
+/// All positions in the box between `min` and `max`, inclusive
+fn box_positions(min: IVec3, max: IVec3) -> impl Iterator<Item = IVec3> {
+    (min.x..=max.x).flat_map(move |x| {
+        (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z)))
+    })
+}
+
 /// Grants access to the VoxelWorld in systems
 #[derive(SystemParam)]
-pub struct VoxelWorld<'w, C: VoxelWorldConfig> {
+pub struct VoxelWorld<'w, C: WorldConfig> {
     chunk_map: Res<'w, ChunkMap<C>>,
     modified_voxels: Res<'w, ModifiedVoxels<C>>,
+    voxel_flags: Res<'w, VoxelFlagsMap<C>>,
     voxel_write_buffer: ResMut<'w, VoxelWriteBuffer<C>>,
-    #[allow(unused)]
+    edit_history: ResMut<'w, EditHistory<C>>,
+    queued_edits: ResMut<'w, QueuedEdits<C>>,
+    scheduled_edits: ResMut<'w, ScheduledEdits<C>>,
+    protected_regions: ResMut<'w, ProtectedRegions<C>>,
+    ev_edit_rejected: EventWriter<'w, VoxelEditRejected<C>>,
+    time: Res<'w, Time>,
+    streaming_paused: ResMut<'w, StreamingPaused<C>>,
+    chunk_requests: ResMut<'w, ChunkRequests<C>>,
+    modified_chunks: ResMut<'w, ModifiedChunks<C>>,
+    material_registry: Res<'w, VoxelMaterialRegistry<C>>,
+    chunk_storage: Option<Res<'w, ChunkStorage<C>>>,
     configuration: Res<'w, C>,
 }
 
-impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
+impl<'w, C: WorldConfig> VoxelWorld<'w, C> {
     /// Get the voxel at the given position. The voxel will be WorldVoxel::Unset if there is no voxel at that position
-    pub fn get_voxel(&self, position: IVec3) -> WorldVoxel {
-        self.get_voxel_fn()(position)
+    pub fn get_voxel(&self, position: IVec3) -> WorldVoxel<C::MaterialIndex> {
+        // A single lookup scans the write buffer, rather than taking a snapshot of it
+        self.voxel_write_buffer
+            .pending()
+            .rev()
+            .find(|(pos, _)| *pos == position)
+            .map_or_else(|| self.stored_voxel_fn()(position), |(_, voxel)| *voxel)
     }
 
     /// Set the voxel at the given position. This will create a new chunk if one does not exist at
     /// the given position.
-    pub fn set_voxel(&mut self, position: IVec3, voxel: WorldVoxel) {
-        self.voxel_write_buffer.push((position, voxel));
+    pub fn set_voxel(&mut self, position: IVec3, voxel: WorldVoxel<C::MaterialIndex>) {
+        self.set_voxels(std::iter::once((position, voxel)));
+    }
+
+    /// The registered properties of the material of the voxel at the given position, for example
+    /// its friction, see `VoxelMaterialRegistry`
+    pub fn get_material_info(&self, position: IVec3) -> Option<&VoxelMaterialInfo> {
+        self.get_voxel(position)
+            .material()
+            .and_then(|material| self.material_registry.get(material))
+    }
+
+    /// True if the voxel at the given position is solid, and its material is not render only.
+    /// Use this to decide which voxels get physics colliders.
+    pub fn collides_at(&self, position: IVec3) -> bool {
+        let voxel = self.get_voxel(position);
+        voxel.is_solid() && !voxel.material().is_some_and(|mt| self.render_only_fn()(mt))
+    }
+
+    /// Returns true for materials that nothing collides with, taken from the registry for
+    /// registered materials and from the configuration for others
+    fn render_only_fn(&self) -> Arc<dyn Fn(C::MaterialIndex) -> bool + Send + Sync> {
+        let registered = self.material_registry.materials();
+        let fallback = self.configuration.render_only_materials();
+        Arc::new(move |mt| match registered.get(&mt) {
+            Some(info) => info.render_only,
+            None => fallback(mt),
+        })
+    }
+
+    /// Get the flags of the voxel at the given position
+    pub fn get_flags(&self, position: IVec3) -> VoxelFlags {
+        let flags = self.voxel_flags.read().unwrap();
+        flags.get(&position).copied().unwrap_or_default()
+    }
+
+    /// Set the flags of the voxel at the given position. Flags are kept when the voxel itself
+    /// changes, so clear them when they no longer apply.
+    pub fn set_flags(&mut self, position: IVec3, flags: VoxelFlags) {
+        let mut voxel_flags = self.voxel_flags.write().unwrap();
+        if flags.is_empty() {
+            voxel_flags.remove(&position);
+        } else {
+            voxel_flags.insert(position, flags);
+        }
+    }
+
+    /// True if the voxel at the given position has all of the given flags
+    pub fn has_flags(&self, position: IVec3, flags: VoxelFlags) -> bool {
+        self.get_flags(position).contains(flags)
+    }
+
+    /// The positions of all voxels that have all of the given flags
+    pub fn voxels_with_flags(&self, flags: VoxelFlags) -> Vec<IVec3> {
+        let voxel_flags = self.voxel_flags.read().unwrap();
+        voxel_flags
+            .iter()
+            .filter(|(_, voxel)| voxel.contains(flags))
+            .map(|(position, _)| *position)
+            .collect()
+    }
+
+    /// The positions of the voxels between `min` and `max`, inclusive, that have all of the
+    /// given flags
+    pub fn voxels_with_flags_in(&self, min: IVec3, max: IVec3, flags: VoxelFlags) -> Vec<IVec3> {
+        self.voxels_with_flags(flags)
+            .into_iter()
+            .filter(|position| position.cmpge(min).all() && position.cmple(max).all())
+            .collect()
+    }
+
+    /// Get a sendable closure that returns the flags of the voxel at the given position, for
+    /// example to skip `NO_COLLIDE` voxels in a `raycast` filter
+    pub fn get_flags_fn(&self) -> Arc<dyn Fn(IVec3) -> VoxelFlags + Send + Sync> {
+        let voxel_flags = self.voxel_flags.clone();
+        Arc::new(move |position| {
+            let flags = voxel_flags.read().unwrap();
+            flags.get(&position).copied().unwrap_or_default()
+        })
+    }
+
+    /// Get the biome of the column at the given position, see
+    /// `VoxelWorldConfig::biome_lookup_delegate`. Zero for chunks that are not spawned.
+    pub fn get_biome(&self, position: IVec3) -> u8 {
+        let (chunk_pos, vox_pos) = get_chunk_voxel_position(position);
+        let read_lock = self.chunk_map.get_read_lock();
+        ChunkMap::<C>::get(&chunk_pos, &read_lock)
+            .map_or(0, |chunk_data| chunk_data.get_biome(vox_pos))
+    }
+
+    /// Get the light level of the voxel at the given position, for gameplay rules like mob
+    /// spawning or plant growth. Light is read from the spawned chunk, not from its mesh. With
+    /// `VoxelWorldLightingPlugin`, the light around edited voxels is updated in the next
+    /// `PreUpdate`. Voxels in chunks that are not spawned, or where no light has been set, are
+    /// dark.
+    pub fn get_light(&self, position: IVec3) -> VoxelLight {
+        self.try_get_light(position).unwrap_or_default()
+    }
+
+    /// Like `get_light`, but `None` for voxels in chunks that are not spawned, to tell voxels that
+    /// are dark apart from those whose light is not known
+    pub fn try_get_light(&self, position: IVec3) -> Option<VoxelLight> {
+        let (chunk_pos, vox_pos) = get_chunk_voxel_position(position);
+        let read_lock = self.chunk_map.get_read_lock();
+        ChunkMap::<C>::get(&chunk_pos, &read_lock).map(|chunk_data| chunk_data.get_light(vox_pos))
+    }
+
+    /// Set the light level of the voxel at the given position. Light is stored with the spawned
+    /// chunk, and is ignored for chunks that are not spawned. The light levels are not
+    /// propagated to neighbouring voxels, and the block light of chunks lit by
+    /// `VoxelWorldLightingPlugin` is replaced when they are lit again.
+    pub fn set_light(&mut self, position: IVec3, light: VoxelLight) {
+        let (chunk_pos, vox_pos) = get_chunk_voxel_position(position);
+        let map = self.chunk_map.get_map();
+        let mut write_lock = map.write().unwrap();
+        if let Some(chunk_data) = write_lock.get_mut(&chunk_pos) {
+            chunk_data.set_light(vox_pos, light);
+        }
+    }
+
+    /// Set many voxels at once, for example to fill a region. Each affected chunk is remeshed
+    /// once, however many of its voxels are set. When a position is set more than once, the last
+    /// voxel wins.
+    pub fn set_voxels(
+        &mut self,
+        voxels: impl IntoIterator<Item = (IVec3, WorldVoxel<C::MaterialIndex>)>,
+    ) {
+        let stored_voxel = self
+            .edit_history
+            .is_enabled()
+            .then(|| self.stored_voxel_fn());
+        let voxels = voxels.into_iter().filter(|(position, _)| {
+            let protected = self.protected_regions.contains(*position);
+            if protected {
+                self.ev_edit_rejected
+                    .send(VoxelEditRejected::new(*position));
+            }
+            !protected
+        });
+
+        let Some(stored_voxel) = stored_voxel else {
+            self.voxel_write_buffer.extend(voxels);
+            return;
+        };
+
+        // The previous voxels come from the write buffer, or else from the chunk map. Only the
+        // buffered writes to positions of this edit are looked up, in one pass over the buffer.
+        let voxels: Vec<_> = voxels.collect();
+        let positions: HashSet<IVec3> = voxels.iter().map(|(position, _)| *position).collect();
+        let mut previous: HashMap<IVec3, WorldVoxel<C::MaterialIndex>> = self
+            .voxel_write_buffer
+            .pending()
+            .filter(|(position, _)| positions.contains(position))
+            .copied()
+            .collect();
+
+        let mut edits = Vec::new();
+        for (position, voxel) in voxels {
+            let before = previous
+                .insert(position, voxel)
+                .unwrap_or_else(|| stored_voxel(position));
+            if before != voxel {
+                edits.push((position, before, voxel));
+            }
+            self.voxel_write_buffer.push((position, voxel));
+        }
+        self.edit_history.record(edits);
+    }
+
+    /// Make the box between `min` and `max`, inclusive, read-only, for example to protect the
+    /// spawn area of a multiplayer world. Attempts to set voxels inside it are dropped, and
+    /// reported with a `VoxelEditRejected` event.
+    pub fn protect_region(&mut self, min: IVec3, max: IVec3) -> ProtectedRegion {
+        self.protected_regions.protect_region(min, max)
+    }
+
+    /// Make a region writable again. Returns false if it wasn't protected.
+    pub fn unprotect_region(&mut self, region: ProtectedRegion) -> bool {
+        self.protected_regions.unprotect_region(region)
+    }
+
+    /// Make all voxels of the chunk at the given chunk position read-only, like `protect_region`
+    pub fn protect_chunk(&mut self, chunk_position: IVec3) {
+        self.protected_regions.protect_chunk(chunk_position);
+    }
+
+    /// Make a chunk writable again. Returns false if it wasn't protected.
+    pub fn unprotect_chunk(&mut self, chunk_position: IVec3) -> bool {
+        self.protected_regions.unprotect_chunk(chunk_position)
+    }
+
+    /// True if the voxel at the given position is in a protected region or chunk
+    pub fn is_protected(&self, position: IVec3) -> bool {
+        self.protected_regions.contains(position)
+    }
+
+    /// Undo the most recent step in the edit history, see `VoxelWorldConfig::edit_history_size`.
+    /// Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(writes) = self.edit_history.undo() else {
+            return false;
+        };
+        self.voxel_write_buffer.extend(writes);
+        true
+    }
+
+    /// Redo the most recently undone step. Returns false if there was nothing to redo. Any new
+    /// edit clears the steps that can be redone.
+    pub fn redo(&mut self) -> bool {
+        let Some(writes) = self.edit_history.redo() else {
+            return false;
+        };
+        self.voxel_write_buffer.extend(writes);
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.edit_history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.edit_history.can_redo()
+    }
+
+    /// Start grouping edits into a single undo step, for example for a brush stroke spanning
+    /// several frames. The group ends with `end_edit_group`, or with the next `undo` or `redo`.
+    pub fn begin_edit_group(&mut self) {
+        self.edit_history.begin_group();
+    }
+
+    pub fn end_edit_group(&mut self) {
+        self.edit_history.end_group();
+    }
+
+    pub fn clear_edit_history(&mut self) {
+        self.edit_history.clear();
+    }
+
+    /// Set many voxels over several frames, writing at most
+    /// `VoxelWorldConfig::max_queued_voxels_per_frame` voxels each frame. For huge edits, like
+    /// flattening a mountain, that would otherwise stall a frame. Voxels are written in the
+    /// order given, so grouping them by chunk keeps the number of remeshes down. Queued edits
+    /// are applied one after the other, and are not recorded in the edit history.
+    /// `QueuedEditFinished` is fired once all voxels of the edit have been written.
+    pub fn queue_edit(
+        &mut self,
+        voxels: impl IntoIterator<Item = (IVec3, WorldVoxel<C::MaterialIndex>)>,
+    ) -> QueuedEdit {
+        self.queued_edits.push(voxels.into_iter().collect())
+    }
+
+    /// Set voxels once `delay` has passed, for example to revert a block after 10 seconds. The
+    /// edit is kept by the world, so it is applied even if the chunks it touches were despawned
+    /// in the meantime.
+    pub fn schedule_edit(
+        &mut self,
+        delay: Duration,
+        voxels: impl IntoIterator<Item = (IVec3, WorldVoxel<C::MaterialIndex>)>,
+    ) -> ScheduledEdit {
+        let due = self.time.elapsed() + delay;
+        self.scheduled_edits.push(due, voxels.into_iter().collect())
+    }
+
+    /// Cancel a scheduled edit that hasn't been applied yet. Returns false if it was already
+    /// applied or cancelled.
+    pub fn cancel_scheduled_edit(&mut self, edit: ScheduledEdit) -> bool {
+        self.scheduled_edits.cancel(edit)
+    }
+
+    /// True until all voxels of the queued edit have been written
+    pub fn is_edit_queued(&self, edit: &QueuedEdit) -> bool {
+        self.queued_edits.contains(edit)
+    }
+
+    /// Make several edits as one. The edits are committed together when `edit_fn` returns,
+    /// unless the transaction is aborted, so other systems never see half of them. Each affected
+    /// chunk is remeshed at most once, and the edits are a single undo step.
+    ///
+    /// # Example
+    /// ```
+    /// use bevy::prelude::*;
+    /// use bevy_voxel_world::prelude::*;
+    ///
+    /// fn build_pillar(mut voxel_world: VoxelWorld<DefaultWorld>) {
+    ///     voxel_world.edit(|tx| {
+    ///         for y in 0..10 {
+    ///             let pos = IVec3::new(0, y, 0);
+    ///             if tx.get_voxel(pos).is_solid() {
+    ///                 // Leave the world as it was if anything is in the way
+    ///                 tx.abort();
+    ///                 return;
+    ///             }
+    ///             tx.set_voxel(pos, WorldVoxel::Solid(0));
+    ///         }
+    ///     });
+    /// }
+    /// ```
+    pub fn edit<R>(
+        &mut self,
+        edit_fn: impl FnOnce(&mut VoxelTransaction<C::MaterialIndex>) -> R,
+    ) -> R {
+        let mut transaction = VoxelTransaction {
+            get_voxel: self.get_voxel_fn(),
+            writes: HashMap::new(),
+            aborted: false,
+        };
+        let result = edit_fn(&mut transaction);
+        if !transaction.aborted {
+            self.set_voxels(transaction.writes);
+        }
+        result
+    }
+
+    /// Set all voxels in the box between `min` and `max`, inclusive
+    pub fn fill_box(&mut self, min: IVec3, max: IVec3, voxel: WorldVoxel<C::MaterialIndex>) {
+        self.set_voxels(box_positions(min, max).map(|position| (position, voxel)));
+    }
+
+    /// Set all voxels within `radius` of `center`, for example to dig out a crater
+    pub fn fill_sphere(&mut self, center: IVec3, radius: i32, voxel: WorldVoxel<C::MaterialIndex>) {
+        let extent = IVec3::splat(radius);
+        let positions = box_positions(center - extent, center + extent)
+            .filter(|position| position.distance_squared(center) <= radius.pow(2));
+        self.set_voxels(positions.map(|position| (position, voxel)));
+    }
+
+    /// Set all voxels in an upright cylinder, standing on `base` and reaching `height` voxels up
+    pub fn fill_cylinder(
+        &mut self,
+        base: IVec3,
+        radius: i32,
+        height: i32,
+        voxel: WorldVoxel<C::MaterialIndex>,
+    ) {
+        let min = base - IVec3::new(radius, 0, radius);
+        let max = base + IVec3::new(radius, height - 1, radius);
+        let positions = box_positions(min, max)
+            .filter(|position| position.xz().distance_squared(base.xz()) <= radius.pow(2));
+        self.set_voxels(positions.map(|position| (position, voxel)));
+    }
+
+    /// Stamp a buffer into the world, with its `UVec3::ZERO` corner at `origin` and rotated
+    /// around the Y axis through `origin`. `Unset` voxels in the buffer leave the world as it is.
+    /// All voxels are written together, so the structure appears at once, even when it spans
+    /// several chunks.
+    pub fn paste(
+        &mut self,
+        buffer: &VoxelBuffer<C::MaterialIndex>,
+        origin: IVec3,
+        rotation: VoxelRotation,
+    ) {
+        self.set_voxels(buffer.placed(origin, rotation));
+    }
+
+    /// Combine the world with the solid voxels of a buffer placed at `origin`, for boolean
+    /// modeling. `Union` adds the solid voxels of the buffer to the world, `Subtraction` carves
+    /// them out, and `Intersection` carves out everything inside the buffer's box that is not
+    /// solid in the buffer. Removed voxels become air.
+    pub fn combine(
+        &mut self,
+        operation: CsgOperation,
+        buffer: &VoxelBuffer<C::MaterialIndex>,
+        origin: IVec3,
+    ) {
+        let get_voxel = self.get_voxel_fn();
+        let edits: Vec<_> = buffer
+            .iter()
+            .filter_map(|(position, buffer_voxel)| {
+                let position = origin + position.as_ivec3();
+                let voxel = get_voxel(position);
+                let new = operation
+                    .apply(voxel, buffer_voxel)
+                    .unwrap_or(WorldVoxel::Air);
+                (new != voxel).then_some((position, new))
+            })
+            .collect();
+        self.set_voxels(edits);
+    }
+
+    /// Copy the voxels in the box between `min` and `max`, inclusive, into a buffer that can be
+    /// pasted elsewhere. Chunks that are not loaded are generated like they would be when
+    /// streamed in, with the world's `voxel_lookup_delegate` and `chunk_decorator`, together with
+    /// any voxels that were set there, so the copy is the same whether or not the region has been
+    /// streamed in.
+    pub fn copy_region(&self, min: IVec3, max: IVec3) -> VoxelBuffer<C::MaterialIndex> {
+        let mut buffer = VoxelBuffer::new((max - min + IVec3::ONE).max(IVec3::ZERO).as_uvec3());
+        // Voxels set this frame win over the stored ones, like in `get_voxel_fn`
+        let write_buffer: HashMap<IVec3, WorldVoxel<C::MaterialIndex>> =
+            self.voxel_write_buffer.pending().copied().collect();
+
+        let (min_chunk, _) = get_chunk_voxel_position(min);
+        let (max_chunk, _) = get_chunk_voxel_position(max);
+        for chunk_pos in box_positions(min_chunk, max_chunk) {
+            // The chunk map is only locked to fetch the chunk, and its voxels are copied in bulk
+            let loaded = ChunkMap::<C>::get(&chunk_pos, &self.chunk_map.get_read_lock());
+            let chunk_data = loaded.unwrap_or_else(|| {
+                let mut chunk_task = ChunkTask::<C>::new(
+                    Entity::PLACEHOLDER,
+                    chunk_pos,
+                    self.modified_voxels.clone(),
+                )
+                .with_decorator(self.configuration.chunk_decorator());
+                chunk_task.generate(
+                    self.configuration.voxel_lookup_delegate()(chunk_pos),
+                    LodDownsampling::Point,
+                );
+                chunk_task.chunk_data
+            });
+            let voxels = chunk_data.voxel_array();
+            let uniform = match chunk_data.fill_type {
+                FillType::Uniform(voxel) => voxel,
+                _ => WorldVoxel::Unset,
+            };
+
+            let chunk_min = chunk_pos * CHUNK_SIZE_I;
+            let chunk_max = chunk_min + IVec3::splat(CHUNK_SIZE_I - 1);
+            let modified_voxels = self.modified_voxels.read().unwrap();
+            for position in box_positions(min.max(chunk_min), max.min(chunk_max)) {
+                let voxel = write_buffer
+                    .get(&position)
+                    .or_else(|| modified_voxels.get(&position))
+                    .copied()
+                    .unwrap_or_else(|| {
+                        let (_, vox_pos) = get_chunk_voxel_position(position);
+                        voxels.as_ref().map_or(uniform, |voxels| {
+                            voxels[PaddedChunkShape::linearize(vox_pos.to_array()) as usize]
+                        })
+                    });
+                buffer.set((position - min).as_uvec3(), voxel);
+            }
+        }
+        buffer
+    }
+
+    /// Copy the voxels in the box between `min` and `max`, inclusive, and replace them with air
+    pub fn cut_region(&mut self, min: IVec3, max: IVec3) -> VoxelBuffer<C::MaterialIndex> {
+        let buffer = self.copy_region(min, max);
+        self.fill_box(min, max, WorldVoxel::Air);
+        buffer
+    }
+
+    /// Apply a brush at `center`, for example under the cursor of a sculpting tool. All affected
+    /// chunks are remeshed once.
+    pub fn apply_brush(&mut self, center: IVec3, brush: &VoxelBrush<C::MaterialIndex>) {
+        let get_voxel = self.get_voxel_fn();
+        let edits = brush.edits(center, |position| get_voxel(position));
+        self.set_voxels(edits);
+    }
+
+    /// Set all voxels along the segment from `start` to `end`, and within `radius` of it, for
+    /// roads, tunnels and beams. A radius of 0 sets a line one voxel thick, visiting every voxel
+    /// the segment passes through.
+    pub fn fill_line(
+        &mut self,
+        start: Vec3,
+        end: Vec3,
+        radius: i32,
+        voxel: WorldVoxel<C::MaterialIndex>,
+    ) {
+        let mut positions = HashSet::new();
+        let extent = IVec3::splat(radius);
+        voxel_line_traversal(start, end, |center, _, _| {
+            positions.extend(
+                box_positions(center - extent, center + extent)
+                    .filter(|position| position.distance_squared(center) <= radius.pow(2)),
+            );
+            true
+        });
+        self.set_voxels(positions.into_iter().map(|position| (position, voxel)));
+    }
+
+    /// Fill each column of the heightmap, placed with its first column at `origin`, from
+    /// `origin.y` up to the height of the column. Voxels above the heights are left as they are.
+    pub fn stamp_heightmap(
+        &mut self,
+        heightmap: &Heightmap,
+        origin: IVec3,
+        voxel: WorldVoxel<C::MaterialIndex>,
+    ) {
+        self.set_voxels(
+            heightmap
+                .filled_positions(origin)
+                .map(|position| (position, voxel)),
+        );
+    }
+
+    /// Rasterize a triangle mesh into the world, for example to turn an imported model into
+    /// destructible voxels. The mesh is placed with `transform`, where one unit is one voxel, and
+    /// every voxel it covers is set to `voxel`. Closed meshes are filled, open meshes only get
+    /// their surface voxelized. Only `PrimitiveTopology::TriangleList` meshes are supported.
+    #[cfg(feature = "render")]
+    pub fn voxelize_mesh(
+        &mut self,
+        mesh: &Mesh,
+        transform: &Transform,
+        voxel: WorldVoxel<C::MaterialIndex>,
+    ) {
+        let positions = voxelize::voxelize_mesh(mesh, transform);
+        self.set_voxels(positions.into_iter().map(|position| (position, voxel)));
+    }
+
+    /// Capture all voxels that were set in the world, in loaded and unloaded chunks, together
+    /// with the world's metadata. Voxels generated by the `voxel_lookup_delegate` are not part of
+    /// the snapshot, so it is applied on top of the same generator when it is loaded. With
+    /// `VoxelWorldPersistencePlugin`, the chunks that were saved and unloaded are read from the
+    /// storage.
+    pub fn snapshot(&self) -> WorldSnapshot
+    where
+        C::MaterialIndex: TryFrom<u32>,
+    {
+        // The saved voxels of loaded chunks are merged into the modified voxels, which are newer
+        let mut voxels: HashMap<IVec3, WorldVoxel<C::MaterialIndex>> = self
+            .chunk_storage
+            .as_ref()
+            .map(|storage| storage.load_all_chunks())
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        voxels.extend(self.modified_voxels.read().unwrap().iter());
+        voxels.extend(self.voxel_write_buffer.pending().copied());
+        WorldSnapshot::from_voxels(self.configuration.world_metadata(), voxels)
+    }
+
+    /// Write a snapshot of the world to a single file, see `snapshot`
+    pub fn snapshot_to_file(&self, path: impl AsRef<Path>) -> io::Result<()>
+    where
+        C::MaterialIndex: TryFrom<u32>,
+    {
+        self.snapshot().write_to_file(path)
+    }
+
+    /// Set all voxels of a snapshot written with `snapshot_to_file`. Voxels that are not in the
+    /// snapshot are left alone.
+    pub fn load_snapshot(&mut self, path: impl AsRef<Path>) -> io::Result<()>
+    where
+        C::MaterialIndex: TryFrom<u32>,
+    {
+        let snapshot = WorldSnapshot::read_from_file(path)?;
+        self.apply_snapshot(&snapshot)
+    }
+
+    /// Set all voxels of a snapshot, for example one taken from a storage with
+    /// `WorldSnapshot::from_storage`. Snapshots of worlds with another format version, chunk size,
+    /// seed or generator parameters are refused. The voxels are restored as they were, so they are
+    /// not recorded in the edit history and are written inside protected regions too.
+    pub fn apply_snapshot(&mut self, snapshot: &WorldSnapshot) -> io::Result<()>
+    where
+        C::MaterialIndex: TryFrom<u32>,
+    {
+        snapshot.metadata.check_compatible()?;
+        if snapshot.metadata != self.configuration.world_metadata() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "snapshot was taken of a world with another seed or generator parameters",
+            ));
+        }
+        let voxels = snapshot.voxels()?;
+        self.restore_voxels(voxels);
+        Ok(())
+    }
+
+    /// Write voxels without recording them in the edit history or checking protected regions,
+    /// for voxels that are set back to an earlier state
+    pub(crate) fn restore_voxels(
+        &mut self,
+        voxels: impl IntoIterator<Item = (IVec3, WorldVoxel<C::MaterialIndex>)>,
+    ) {
+        self.voxel_write_buffer.restored.extend(voxels);
+    }
+
+    /// Mesh all voxels in the box between `min` and `max`, inclusive, and write the mesh to a
+    /// `.obj` or `.glb` file. Voxels in chunks that aren't loaded come from the
+    /// `voxel_lookup_delegate`.
+    #[cfg(feature = "render")]
+    pub fn export_region(&self, min: IVec3, max: IVec3, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let format = MeshExportFormat::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "regions can only be exported to .obj or .glb files",
+            )
+        })?;
+
+        let mut meshing_config = MeshingConfig::from_config(&*self.configuration)
+            .with_material_registry(&self.material_registry);
+        meshing_config.skirt_depth = 0.0;
+        meshing_config.generate_tangents = false;
+        let get_voxel = self.get_voxel_fn();
+        let delegate = self.configuration.voxel_lookup_delegate();
+        let mesh = ExportMesh::from_region(
+            min.min(max),
+            min.max(max),
+            |chunk_position| {
+                let get_voxel = get_voxel.clone();
+                let mut lookup = delegate(chunk_position);
+                move |position| match get_voxel(position) {
+                    WorldVoxel::Unset => lookup(position, 0),
+                    voxel => voxel,
+                }
+            },
+            &meshing_config,
+        );
+        mesh.write(path, format)
+    }
+
+    /// A replication message with all voxels of a chunk, for a client that doesn't have the
+    /// chunk yet. Voxels in chunks that aren't loaded come from the `voxel_lookup_delegate`.
+    pub fn chunk_snapshot(&self, chunk_position: IVec3) -> ReplicationMessage<C::MaterialIndex> {
+        let get_voxel = self.get_voxel_fn();
+        let mut lookup = self.configuration.voxel_lookup_delegate()(chunk_position);
+        ReplicationMessage::snapshot(chunk_position, |position| match get_voxel(position) {
+            WorldVoxel::Unset => lookup(position, 0),
+            voxel => voxel,
+        })
+    }
+
+    /// Set the voxels of a replication message received from a server. Each voxel is compared
+    /// with the local world, or where nothing is set or loaded, with the voxel from the world's
+    /// `voxel_lookup_delegate`, and only voxels that differ are set. Air is kept, so voxels that
+    /// were removed on the server are removed here too, but unset voxels are skipped. The voxels
+    /// are applied as the server's, so they are not recorded in the edit history.
+    pub fn apply_replication_message(
+        &mut self,
+        message: &ReplicationMessage<C::MaterialIndex>,
+    ) -> io::Result<()> {
+        let voxels = message
+            .voxels()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "corrupt chunk snapshot"))?;
+        let get_voxel = self.get_voxel_fn();
+        let lookup = self.configuration.voxel_lookup_delegate();
+        let mut lookups = HashMap::new();
+        let changed: Vec<_> = voxels
+            .into_iter()
+            .filter(|(position, voxel)| {
+                if voxel.is_unset() {
+                    return false;
+                }
+                let current = match get_voxel(*position) {
+                    WorldVoxel::Unset => {
+                        let (chunk_pos, _) = get_chunk_voxel_position(*position);
+                        let lookup_voxel = lookups
+                            .entry(chunk_pos)
+                            .or_insert_with(|| lookup(chunk_pos));
+                        lookup_voxel(*position, 0)
+                    }
+                    current => current,
+                };
+                // Air and unset voxels are both empty
+                current != *voxel && !(current.is_unset() && voxel.is_air())
+            })
+            .collect();
+        self.voxel_write_buffer.remote.extend(changed);
+        Ok(())
+    }
+
+    /// The full detail chunks with voxels that were set since they were last saved or marked
+    /// clean, including edits that have not been applied yet. `VoxelWorldPersistencePlugin` marks
+    /// chunks clean when it saves them.
+    pub fn dirty_chunks(&self) -> Vec<IVec3> {
+        let mut chunks: HashSet<IVec3> = self.modified_chunks.iter().copied().collect();
+        chunks.extend(
+            self.voxel_write_buffer
+                .pending()
+                .map(|(position, _)| get_chunk_voxel_position(*position).0),
+        );
+        chunks.into_iter().collect()
+    }
+
+    /// True if any voxels were set since the world was last saved, for an "unsaved changes"
+    /// prompt
+    pub fn has_unsaved_changes(&self) -> bool {
+        !self.modified_chunks.is_empty() || self.voxel_write_buffer.has_pending()
+    }
+
+    /// Mark a chunk as saved, for games that save chunks themselves. Edits that have not been
+    /// applied yet still mark the chunk dirty when they are.
+    pub fn mark_chunk_clean(&mut self, chunk_position: IVec3) {
+        self.modified_chunks.remove(&chunk_position);
+    }
+
+    /// Mark all chunks as saved, see `mark_chunk_clean`
+    pub fn mark_all_clean(&mut self) {
+        self.modified_chunks.clear();
+    }
+
+    /// Pause spawning, despawning and remeshing of chunks, for example during loading screens or
+    /// cutscenes. Voxels can still be set while streaming is paused, and the affected chunks are
+    /// remeshed when streaming resumes.
+    pub fn pause_streaming(&mut self) {
+        **self.streaming_paused = true;
+    }
+
+    /// Resume spawning, despawning and remeshing of chunks after `pause_streaming`
+    pub fn resume_streaming(&mut self) {
+        **self.streaming_paused = false;
+    }
+
+    /// True if chunk streaming is paused
+    pub fn is_streaming_paused(&self) -> bool {
+        **self.streaming_paused
+    }
+
+    /// Load the chunk at the given chunk position ahead of the camera, for example around a
+    /// teleport destination, and keep it spawned until the returned handle is released.
+    /// Use `is_chunk_loaded` to check when its voxels are available.
+    ///
+    /// Like streaming anchors, requests don't spawn chunks when `VoxelWorldConfig::lod_distances`
+    /// is configured.
+    pub fn request_chunk(&mut self, chunk_position: IVec3) -> ChunkRequest {
+        self.chunk_requests.request(chunk_position);
+        ChunkRequest { chunk_position }
+    }
+
+    /// Release a chunk requested with `request_chunk`, so that it can be despawned again. With
+    /// `VoxelWorldConfig::manual_chunk_management`, the chunk is despawned once it is no longer
+    /// requested at all.
+    pub fn release_chunk(&mut self, request: ChunkRequest) {
+        self.chunk_requests.release(request.chunk_position);
+    }
+
+    /// True once the requested chunk has been generated
+    pub fn is_chunk_loaded(&self, request: &ChunkRequest) -> bool {
+        self.chunk_requests.is_loaded(&request.chunk_position)
     }
 
     /// Get a sendable closure that can be used to get the voxel at the given position
     /// This is useful for spawning tasks that need to access the voxel world
-    pub fn get_voxel_fn(&self) -> Arc<dyn Fn(IVec3) -> WorldVoxel + Send + Sync> {
+    pub fn get_voxel_fn(&self) -> Arc<dyn Fn(IVec3) -> WorldVoxel<C::MaterialIndex> + Send + Sync> {
+        // The voxels set this frame are indexed once, so that lookups don't scan them. When a
+        // position was set more than once, the last voxel wins.
+        let write_buffer: HashMap<IVec3, WorldVoxel<C::MaterialIndex>> =
+            self.voxel_write_buffer.pending().copied().collect();
+        let stored_voxel = self.stored_voxel_fn();
+
+        Arc::new(move |position| {
+            write_buffer
+                .get(&position)
+                .copied()
+                .unwrap_or_else(|| stored_voxel(position))
+        })
+    }
+
+    /// Get a closure that looks up voxels as of the last flush of the write buffer
+    fn stored_voxel_fn(
+        &self,
+    ) -> impl Fn(IVec3) -> WorldVoxel<C::MaterialIndex> + Send + Sync + 'static {
         let chunk_map = self.chunk_map.get_map();
-        let write_buffer = self.voxel_write_buffer.clone();
         let modified_voxels = self.modified_voxels.clone();
 
-        Arc::new(move |position| {
+        move |position| {
             let (chunk_pos, vox_pos) = get_chunk_voxel_position(position);
 
-            if let Some(voxel) = write_buffer
-                .iter()
-                .find(|(pos, _)| *pos == position)
-                .map(|(_, voxel)| *voxel)
-            {
-                return voxel;
-            }
-
             {
                 if let Some(voxel) = modified_voxels.get_voxel(&position) {
                     return voxel;
@@ -151,12 +1082,15 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
             } else {
                 WorldVoxel::Unset
             }
-        })
+        }
     }
 
     /// Get the closes surface voxel to the given position
     /// Returns None if there is no surface voxel at or below the given position
-    pub fn get_closest_surface_voxel(&self, position: IVec3) -> Option<(IVec3, WorldVoxel)> {
+    pub fn get_closest_surface_voxel(
+        &self,
+        position: IVec3,
+    ) -> Option<(IVec3, WorldVoxel<C::MaterialIndex>)> {
         let get_voxel = self.get_voxel_fn();
         let mut current_pos = position;
         let current_voxel = get_voxel(current_pos);
@@ -187,7 +1121,7 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
         &self,
         position: IVec3,
         radius: u32,
-    ) -> Option<(IVec3, WorldVoxel)> {
+    ) -> Option<(IVec3, WorldVoxel<C::MaterialIndex>)> {
         let mut tries = 0;
 
         while tries < 100 {
@@ -217,7 +1151,10 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
     }
 
     /// Get first surface voxel at the given Vec2 position
-    pub fn get_surface_voxel_at_2d_pos(&self, pos_2d: Vec2) -> Option<(IVec3, WorldVoxel)> {
+    pub fn get_surface_voxel_at_2d_pos(
+        &self,
+        pos_2d: Vec2,
+    ) -> Option<(IVec3, WorldVoxel<C::MaterialIndex>)> {
         self.get_closest_surface_voxel(IVec3 {
             x: pos_2d.x.floor() as i32,
             y: 256,
@@ -229,6 +1166,7 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
     /// The `filter` function can be used to filter out voxels that should not be considered for the raycast.
     ///
     /// Returns a `VoxelRaycastResult` with position, normal and voxel info. The position is given in world space.
+    /// Voxels of render only materials are passed through, see `VoxelWorldConfig::render_only_materials`.
     /// Returns `None` if no voxel was intersected
     ///
     /// # Example
@@ -257,16 +1195,17 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
     pub fn raycast(
         &self,
         ray: Ray3d,
-        filter: &impl Fn((Vec3, WorldVoxel)) -> bool,
-    ) -> Option<VoxelRaycastResult> {
+        filter: &impl Fn((Vec3, WorldVoxel<C::MaterialIndex>)) -> bool,
+    ) -> Option<VoxelRaycastResult<C::MaterialIndex>> {
         let raycast_fn = self.raycast_fn();
         raycast_fn(ray, filter)
     }
 
     /// Get a sendable closure that can be used to raycast into the voxel world
-    pub fn raycast_fn(&self) -> Arc<RaycastFn> {
+    pub fn raycast_fn(&self) -> Arc<RaycastFn<C::MaterialIndex>> {
         let chunk_map = self.chunk_map.get_map();
         let get_voxel = self.get_voxel_fn();
+        let render_only = self.render_only_fn();
 
         Arc::new(move |ray, filter| {
             let p = ray.origin;
@@ -301,7 +1240,7 @@ impl<'w, C: VoxelWorldConfig> VoxelWorld<'w, C> {
                 let voxel = get_voxel(voxel_coords);
 
                 if !voxel.is_unset() && filter.call((voxel_coords.as_vec3(), voxel)) {
-                    if voxel.is_solid() {
+                    if voxel.is_solid() && !voxel.material().is_some_and(|mt| render_only(mt)) {
                         raycast_result = Some(VoxelRaycastResult {
                             position: voxel_coords.as_vec3(),
                             normal: face.try_into().ok(),
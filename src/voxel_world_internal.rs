@@ -5,68 +5,551 @@
 use bevy::{
     ecs::system::SystemParam,
     prelude::*,
-    tasks::AsyncComputeTaskPool,
-    utils::{HashMap, HashSet},
+    tasks::{AsyncComputeTaskPool, TaskPool, TaskPoolBuilder},
+    utils::{HashMap, HashSet, Instant},
 };
 use futures_lite::future;
 use std::{
-    collections::VecDeque,
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
     marker::PhantomData,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use crate::{
     chunk::*,
     chunk_map::*,
-    configuration::{ChunkDespawnStrategy, ChunkSpawnStrategy, VoxelWorldConfig},
+    chunk_transport::RemoteChunks,
+    configuration::{VoxelTaskPool, WorldConfig},
+    edit_history::EditHistory,
+    light::LightUpdates,
+    lod::*,
+    material_registry::VoxelMaterialRegistry,
+    voxel::{VoxelFlags, WorldVoxel},
+    voxel_world::{
+        ChunkWillDespawn, ChunkWillRemesh, ChunkWillSpawn, ProtectedRegion, QueuedEdit,
+        QueuedEditFinished, ScheduledEdit, VoxelEditRejected, VoxelWorldStreamingAnchor,
+    },
+};
+#[cfg(feature = "render")]
+use crate::{
+    configuration::{ChunkDespawnStrategy, ChunkSpawnStrategy, ChunkStrategyInput},
+    light::{padded_light, LightArray},
     mesh_cache::*,
+    meshing::MeshingConfig,
     plugin::VoxelWorldMaterialHandle,
-    voxel::WorldVoxel,
     voxel_material::LoadingTexture,
-    voxel_world::{ChunkWillDespawn, ChunkWillRemesh, ChunkWillSpawn, VoxelWorldCamera},
+    voxel_world::VoxelWorldCamera,
 };
+#[cfg(feature = "render")]
+use bevy::pbr::NotShadowCaster;
+
+#[cfg(feature = "render")]
+#[derive(SystemParam, Deref)]
+pub struct CameraInfo<'w, 's, C: WorldConfig>(
+    Query<'w, 's, (Entity, &'static Camera, &'static GlobalTransform), With<VoxelWorldCamera<C>>>,
+);
+
+/// The visibility of a chunk, for despawn strategies. Headless worlds have none.
+#[cfg(feature = "render")]
+type ChunkVisibility = Option<&'static ViewVisibility>;
+#[cfg(not(feature = "render"))]
+type ChunkVisibility = ();
+
+/// Whether a retired chunk has a mesh, and its reference into the mesh cache
+#[cfg(feature = "render")]
+type RetiredChunkMesh = (Has<Handle<Mesh>>, Option<&'static MeshRef>);
+#[cfg(not(feature = "render"))]
+type RetiredChunkMesh = ();
 
 #[derive(SystemParam, Deref)]
-pub struct CameraInfo<'w, 's, C: VoxelWorldConfig>(
-    Query<'w, 's, (&'static Camera, &'static GlobalTransform), With<VoxelWorldCamera<C>>>,
+pub struct StreamingAnchors<'w, 's, C: WorldConfig>(
+    Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static VoxelWorldStreamingAnchor<C>,
+            &'static GlobalTransform,
+        ),
+    >,
 );
 
+impl<'w, 's, C: WorldConfig> StreamingAnchors<'w, 's, C> {
+    /// True if the anchors are ignored, because levels of detail are only streamed around the
+    /// camera. Headless worlds have no levels of detail.
+    fn ignored(configuration: &C) -> bool {
+        cfg!(feature = "render") && !configuration.lod_distances().is_empty()
+    }
+
+    /// The chunk position of each anchor, with the distance in chunks it keeps spawned.
+    /// Anchors are not used with levels of detail.
+    pub(crate) fn chunks(&self, configuration: &C) -> Vec<(Entity, IVec3, i32)> {
+        if Self::ignored(configuration) {
+            return Vec::new();
+        }
+        self.iter()
+            .map(|(entity, anchor, transform)| {
+                let (chunk_pos, _) =
+                    get_chunk_voxel_position(transform.translation().floor().as_ivec3());
+                let radius = anchor
+                    .radius
+                    .unwrap_or_else(|| configuration.spawning_distance());
+                (entity, chunk_pos, radius as i32)
+            })
+            .collect()
+    }
+}
+
 /// Holds a map of modified voxels that will persist between chunk spawn/despawn
 #[derive(Resource, Deref, DerefMut, Clone)]
-pub struct ModifiedVoxels<C>(
-    #[deref] Arc<RwLock<HashMap<IVec3, WorldVoxel>>>,
+pub struct ModifiedVoxels<C: WorldConfig>(
+    #[deref] Arc<RwLock<HashMap<IVec3, WorldVoxel<C::MaterialIndex>>>>,
     PhantomData<C>,
 );
 
-impl<C: VoxelWorldConfig> Default for ModifiedVoxels<C> {
+impl<C: WorldConfig> Default for ModifiedVoxels<C> {
     fn default() -> Self {
         Self(Arc::new(RwLock::new(HashMap::new())), PhantomData)
     }
 }
 
-impl<C: VoxelWorldConfig> ModifiedVoxels<C> {
-    pub fn get_voxel(&self, position: &IVec3) -> Option<WorldVoxel> {
+impl<C: WorldConfig> ModifiedVoxels<C> {
+    pub fn get_voxel(&self, position: &IVec3) -> Option<WorldVoxel<C::MaterialIndex>> {
         let modified_voxels = self.0.read().unwrap();
         modified_voxels.get(position).cloned()
     }
 }
 
+/// Holds the full detail chunks with voxels that were set since they were last saved or marked
+/// clean, see `VoxelWorld::dirty_chunks`
+#[derive(Resource, Deref, DerefMut)]
+pub struct ModifiedChunks<C>(#[deref] HashSet<IVec3>, PhantomData<C>);
+
+impl<C> Default for ModifiedChunks<C> {
+    fn default() -> Self {
+        Self(HashSet::new(), PhantomData)
+    }
+}
+
+/// Holds the flags of voxels, see `VoxelWorld::set_flags`. Like `ModifiedVoxels`, the flags
+/// persist between chunk spawn/despawn.
+#[derive(Resource, Deref, DerefMut, Clone)]
+pub struct VoxelFlagsMap<C>(
+    #[deref] Arc<RwLock<HashMap<IVec3, VoxelFlags>>>,
+    PhantomData<C>,
+);
+
+impl<C> Default for VoxelFlagsMap<C> {
+    fn default() -> Self {
+        Self(Arc::new(RwLock::new(HashMap::new())), PhantomData)
+    }
+}
+
 /// A temporary buffer for voxel modifications that will get flushed to the `ModifiedVoxels` resource
 /// at the end of the frame.
-#[derive(Resource, Deref, DerefMut, Default)]
-pub struct VoxelWriteBuffer<C>(#[deref] Vec<(IVec3, WorldVoxel)>, PhantomData<C>);
+#[derive(Resource, Deref, DerefMut)]
+pub struct VoxelWriteBuffer<C: WorldConfig> {
+    #[deref]
+    writes: Vec<(IVec3, WorldVoxel<C::MaterialIndex>)>,
+    /// Voxels received from a server or from other peers, which are written after the local
+    /// writes since they arrive later
+    pub(crate) remote: Vec<(IVec3, WorldVoxel<C::MaterialIndex>)>,
+    /// Voxels restored to an earlier state, like an imported snapshot, which are written before
+    /// the local writes and are neither recorded in the edit history nor checked against
+    /// protected regions
+    pub(crate) restored: Vec<(IVec3, WorldVoxel<C::MaterialIndex>)>,
+    _marker: PhantomData<C>,
+}
+
+impl<C: WorldConfig> Default for VoxelWriteBuffer<C> {
+    fn default() -> Self {
+        Self {
+            writes: Vec::new(),
+            remote: Vec::new(),
+            restored: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: WorldConfig> VoxelWriteBuffer<C> {
+    /// All buffered voxels, in the order they are written
+    pub(crate) fn pending(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = &(IVec3, WorldVoxel<C::MaterialIndex>)> {
+        self.restored
+            .iter()
+            .chain(self.writes.iter())
+            .chain(self.remote.iter())
+    }
+
+    /// True if the pending voxel at the index is restored, so that it skips the protected regions
+    pub(crate) fn is_restored(&self, index: usize) -> bool {
+        index < self.restored.len()
+    }
+
+    /// True if the pending voxel at the index was received from another peer
+    pub(crate) fn is_remote(&self, index: usize) -> bool {
+        index >= self.restored.len() + self.writes.len()
+    }
+
+    pub(crate) fn has_pending(&self) -> bool {
+        !self.remote.is_empty() || !self.restored.is_empty() || !self.writes.is_empty()
+    }
+}
+
+/// The voxels of an edit, by world position
+pub(crate) type VoxelEdits<I> = Vec<(IVec3, WorldVoxel<I>)>;
+
+/// Edits queued with `VoxelWorld::queue_edit`, with the number of voxels of the first edit that
+/// have been written so far
+#[derive(Resource)]
+pub(crate) struct QueuedEdits<C: WorldConfig> {
+    edits: VecDeque<(QueuedEdit, VoxelEdits<C::MaterialIndex>)>,
+    written: usize,
+    next_id: u64,
+}
+
+impl<C: WorldConfig> Default for QueuedEdits<C> {
+    fn default() -> Self {
+        Self {
+            edits: VecDeque::new(),
+            written: 0,
+            next_id: 0,
+        }
+    }
+}
+
+impl<C: WorldConfig> QueuedEdits<C> {
+    pub fn push(&mut self, voxels: Vec<(IVec3, WorldVoxel<C::MaterialIndex>)>) -> QueuedEdit {
+        let edit = QueuedEdit { id: self.next_id };
+        self.next_id += 1;
+        self.edits.push_back((edit, voxels));
+        edit
+    }
+
+    pub fn contains(&self, edit: &QueuedEdit) -> bool {
+        self.edits.iter().any(|(queued, _)| queued == edit)
+    }
+}
+
+/// Regions and chunks made read-only with `VoxelWorld::protect_region` and
+/// `VoxelWorld::protect_chunk`
+#[derive(Resource)]
+pub(crate) struct ProtectedRegions<C> {
+    regions: HashMap<u64, (IVec3, IVec3)>,
+    chunks: HashSet<IVec3>,
+    next_id: u64,
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for ProtectedRegions<C> {
+    fn default() -> Self {
+        Self {
+            regions: HashMap::new(),
+            chunks: HashSet::new(),
+            next_id: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C> ProtectedRegions<C> {
+    pub fn protect_region(&mut self, min: IVec3, max: IVec3) -> ProtectedRegion {
+        let region = ProtectedRegion { id: self.next_id };
+        self.next_id += 1;
+        self.regions.insert(region.id, (min.min(max), min.max(max)));
+        region
+    }
+
+    pub fn unprotect_region(&mut self, region: ProtectedRegion) -> bool {
+        self.regions.remove(&region.id).is_some()
+    }
+
+    pub fn protect_chunk(&mut self, chunk_position: IVec3) {
+        self.chunks.insert(chunk_position);
+    }
+
+    pub fn unprotect_chunk(&mut self, chunk_position: IVec3) -> bool {
+        self.chunks.remove(&chunk_position)
+    }
+
+    pub fn contains(&self, position: IVec3) -> bool {
+        if !self.chunks.is_empty() {
+            let (chunk_position, _) = get_chunk_voxel_position(position);
+            if self.chunks.contains(&chunk_position) {
+                return true;
+            }
+        }
+        self.regions
+            .values()
+            .any(|(min, max)| position.cmpge(*min).all() && position.cmple(*max).all())
+    }
+}
+
+/// Edits scheduled with `VoxelWorld::schedule_edit`, ordered by the time they are due
+#[derive(Resource)]
+pub(crate) struct ScheduledEdits<C: WorldConfig> {
+    due: BinaryHeap<Reverse<(Duration, u64)>>,
+    edits: HashMap<u64, VoxelEdits<C::MaterialIndex>>,
+    next_id: u64,
+}
+
+impl<C: WorldConfig> Default for ScheduledEdits<C> {
+    fn default() -> Self {
+        Self {
+            due: BinaryHeap::new(),
+            edits: HashMap::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<C: WorldConfig> ScheduledEdits<C> {
+    pub fn push(
+        &mut self,
+        due: Duration,
+        voxels: Vec<(IVec3, WorldVoxel<C::MaterialIndex>)>,
+    ) -> ScheduledEdit {
+        let edit = ScheduledEdit { id: self.next_id };
+        self.next_id += 1;
+        self.due.push(Reverse((due, edit.id)));
+        self.edits.insert(edit.id, voxels);
+        edit
+    }
+
+    pub fn cancel(&mut self, edit: ScheduledEdit) -> bool {
+        self.edits.remove(&edit.id).is_some()
+    }
+
+    /// Takes the voxels of the edits that are due at the given time, in the order they are due
+    fn take_due(&mut self, now: Duration) -> Vec<(IVec3, WorldVoxel<C::MaterialIndex>)> {
+        let mut voxels = Vec::new();
+        while let Some(Reverse((due, id))) = self.due.peek().copied() {
+            if due > now {
+                break;
+            }
+            self.due.pop();
+            // Cancelled edits are no longer in the map
+            if let Some(edit) = self.edits.remove(&id) {
+                voxels.extend(edit);
+            }
+        }
+        voxels
+    }
+}
 
+#[cfg(feature = "render")]
 #[derive(Component)]
 pub(crate) struct NeedsMaterial<C>(PhantomData<C>);
 
+/// The task pool for chunk generation and meshing, if the world doesn't use the
+/// `AsyncComputeTaskPool`. See `VoxelWorldConfig::task_pool`.
+#[derive(Resource)]
+pub(crate) struct ChunkTaskPool<C>(Option<Arc<TaskPool>>, PhantomData<C>);
+
+impl<C: WorldConfig> ChunkTaskPool<C> {
+    fn from_config(configuration: &C) -> Self {
+        let pool = match configuration.task_pool() {
+            VoxelTaskPool::AsyncCompute => None,
+            VoxelTaskPool::Threads(threads) => Some(Arc::new(
+                TaskPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name("Voxel World Task Pool".to_string())
+                    .build(),
+            )),
+            VoxelTaskPool::Pool(pool) => Some(pool),
+        };
+        Self(pool, PhantomData)
+    }
+
+    fn get(&self) -> &TaskPool {
+        self.0
+            .as_deref()
+            .unwrap_or_else(|| AsyncComputeTaskPool::get())
+    }
+}
+
+/// How the cameras moved since the last frame
+#[cfg(feature = "render")]
+#[derive(Resource, Default)]
+pub(crate) struct CameraMotion<C> {
+    previous_positions: HashMap<Entity, Vec3>,
+    velocities: HashMap<Entity, Vec3>,
+    /// The cameras that jumped further than `VoxelWorldConfig::teleport_distance` this frame
+    teleported: HashSet<Entity>,
+    _marker: PhantomData<C>,
+}
+
+#[cfg(feature = "render")]
+impl<C> CameraMotion<C> {
+    pub fn velocity(&self, camera: Entity) -> Vec3 {
+        self.velocities.get(&camera).copied().unwrap_or_default()
+    }
+
+    pub fn teleported(&self, camera: Entity) -> bool {
+        self.teleported.contains(&camera)
+    }
+
+    /// True if any of the cameras teleported this frame
+    pub fn any_teleported(&self) -> bool {
+        !self.teleported.is_empty()
+    }
+}
+
+/// Set while chunk streaming is paused, see `VoxelWorld::pause_streaming`
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct StreamingPaused<C>(#[deref] bool, PhantomData<C>);
+
+/// Chunks requested with `VoxelWorld::request_chunk`, with the number of requests holding each
+/// of them, the requested chunks that have finished generating, and the chunks released since
+/// the last frame
+#[derive(Resource, Default)]
+pub(crate) struct ChunkRequests<C> {
+    requested: HashMap<IVec3, usize>,
+    loaded: HashSet<IVec3>,
+    released: Vec<IVec3>,
+    _marker: PhantomData<C>,
+}
+
+impl<C: WorldConfig> ChunkRequests<C> {
+    pub fn request(&mut self, chunk_position: IVec3) {
+        *self.requested.entry(chunk_position).or_default() += 1;
+    }
+
+    pub fn release(&mut self, chunk_position: IVec3) {
+        let Some(count) = self.requested.get_mut(&chunk_position) else {
+            return;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.requested.remove(&chunk_position);
+            self.loaded.remove(&chunk_position);
+            self.released.push(chunk_position);
+        }
+    }
+
+    pub fn is_requested(&self, chunk_position: &IVec3) -> bool {
+        self.requested.contains_key(chunk_position)
+    }
+
+    pub fn is_loaded(&self, chunk_position: &IVec3) -> bool {
+        self.loaded.contains(chunk_position)
+    }
+}
+
+/// Chunks despawned to stay within `VoxelWorldConfig::chunk_memory_budget`. They are not spawned
+/// again until they come into view.
+#[cfg(feature = "render")]
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct EvictedChunks<C>(#[deref] HashSet<IVec3>, PhantomData<C>);
+
+/// Despawned chunk entities and mesh handles, kept for reuse by new chunks so that fast
+/// traversal doesn't keep spawning entities and allocating mesh assets.
+/// See `VoxelWorldConfig::chunk_pool_size`.
+#[derive(Resource)]
+pub(crate) struct ChunkPool<C> {
+    entities: Vec<Entity>,
+    #[cfg(feature = "render")]
+    meshes: Vec<Handle<Mesh>>,
+    max_size: usize,
+    _marker: PhantomData<C>,
+}
+
+impl<C: WorldConfig> ChunkPool<C> {
+    fn new(max_size: usize) -> Self {
+        Self {
+            entities: Vec::new(),
+            #[cfg(feature = "render")]
+            meshes: Vec::new(),
+            max_size,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Keep the mesh of a chunk that is about to be recycled for reuse, if no other chunk shares
+    /// it and the pool has room for the chunk
+    #[cfg(feature = "render")]
+    fn recycle_mesh(&mut self, mesh_ref: Option<&MeshRef>) {
+        if self.entities.len() >= self.max_size {
+            return;
+        }
+
+        if let Some(mesh_ref) = mesh_ref {
+            // Dropping the last `MeshRef` also drops the mesh from the mesh cache
+            if Arc::strong_count(&mesh_ref.0) == 1 && self.meshes.len() < self.max_size {
+                self.meshes.push((*mesh_ref.0).clone());
+            }
+        }
+    }
+
+    /// Strip a chunk entity of its chunk components and keep it for reuse. Despawns the entity if
+    /// the pool is full. Call `recycle_mesh` first to also keep its mesh.
+    fn recycle(&mut self, commands: &mut Commands, entity: Entity) {
+        if self.entities.len() >= self.max_size {
+            commands.entity(entity).despawn_recursive();
+            return;
+        }
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.despawn_descendants().remove::<(
+            Chunk<C>,
+            NeedsDespawn,
+            LodReplaced,
+            NeedsRemesh,
+            ChunkThread<C>,
+            LodFade<C>,
+        )>();
+        #[cfg(feature = "render")]
+        entity_commands
+            .remove::<(
+                NeedsMaterial<C>,
+                MeshRef,
+                ChunkMeshParts,
+                Handle<Mesh>,
+                bevy::render::primitives::Aabb,
+            )>()
+            .try_insert(Visibility::Hidden);
+        self.entities.push(entity);
+    }
+
+    /// Take an entity from the pool, or spawn a new one
+    fn spawn(&mut self, commands: &mut Commands, bundle: impl Bundle) -> Entity {
+        while let Some(entity) = self.entities.pop() {
+            if let Some(mut entity_commands) = commands.get_entity(entity) {
+                #[cfg(feature = "render")]
+                entity_commands.insert((bundle, Visibility::Inherited));
+                #[cfg(not(feature = "render"))]
+                entity_commands.insert(bundle);
+                return entity;
+            }
+        }
+        commands.spawn(bundle).id()
+    }
+
+    /// Add a mesh asset, reusing a pooled mesh handle if there is one
+    #[cfg(feature = "render")]
+    fn add_mesh(&mut self, mesh_assets: &mut Assets<Mesh>, mesh: Mesh) -> Handle<Mesh> {
+        match self.meshes.pop() {
+            Some(handle) => {
+                mesh_assets.insert(&handle, mesh);
+                handle
+            }
+            None => mesh_assets.add(mesh),
+        }
+    }
+}
+
 pub(crate) struct Internals<C>(PhantomData<C>);
 
 #[derive(Component)]
 pub struct WorldRoot<C>(PhantomData<C>);
 
-impl<C: VoxelWorldConfig> Internals<C>
+impl<C: WorldConfig> Internals<C>
 where
-    C: VoxelWorldConfig,
+    C: WorldConfig,
 {
     /// Init the resources used internally by bevy_voxel_world
     pub fn setup(mut commands: Commands, configuration: Res<C>) {
@@ -74,52 +557,151 @@ where
         commands.init_resource::<ChunkMapInsertBuffer<C>>();
         commands.init_resource::<ChunkMapUpdateBuffer<C>>();
         commands.init_resource::<ChunkMapRemoveBuffer<C>>();
+        #[cfg(feature = "render")]
         commands.init_resource::<MeshCache<C>>();
+        #[cfg(feature = "render")]
         commands.init_resource::<MeshCacheInsertBuffer<C>>();
         commands.init_resource::<ModifiedVoxels<C>>();
+        commands.init_resource::<ModifiedChunks<C>>();
+        commands.init_resource::<VoxelFlagsMap<C>>();
+        commands.init_resource::<LodChunks<C>>();
+        commands.insert_resource(ChunkPool::<C>::new(configuration.chunk_pool_size()));
         commands.init_resource::<VoxelWriteBuffer<C>>();
+        commands.insert_resource(EditHistory::<C>::new(configuration.edit_history_size()));
+        commands.init_resource::<QueuedEdits<C>>();
+        commands.init_resource::<ScheduledEdits<C>>();
+        commands.init_resource::<ProtectedRegions<C>>();
+        commands.init_resource::<StreamingPaused<C>>();
+        commands.init_resource::<ChunkRequests<C>>();
+        commands.init_resource::<VoxelMaterialRegistry<C>>();
+        #[cfg(feature = "render")]
+        commands.init_resource::<EvictedChunks<C>>();
+        #[cfg(feature = "render")]
+        commands.init_resource::<CameraMotion<C>>();
+        commands.insert_resource(ChunkTaskPool::<C>::from_config(&*configuration));
 
         // Create the root node and allow to modify it by the configuration.
         let world_root = commands
-            .spawn((
-                WorldRoot::<C>(PhantomData),
-                VisibilityBundle::default(),
-                TransformBundle::default(),
-            ))
+            .spawn((WorldRoot::<C>(PhantomData), TransformBundle::default()))
             .id();
+        #[cfg(feature = "render")]
+        commands
+            .entity(world_root)
+            .insert(VisibilityBundle::default());
         configuration.init_root(commands, world_root)
     }
 
+    /// Run condition for the systems that spawn, despawn and remesh chunks
+    pub fn streaming_active(paused: Option<Res<StreamingPaused<C>>>) -> bool {
+        !paused.is_some_and(|paused| **paused)
+    }
+
+    /// Run condition for the systems that spawn and despawn chunks on their own, see
+    /// `VoxelWorldConfig::manual_chunk_management`
+    pub fn automatic_streaming(configuration: Res<C>) -> bool {
+        !configuration.manual_chunk_management()
+    }
+
+    /// Tracks the velocity of the cameras, and detects when they teleport
+    #[cfg(feature = "render")]
+    pub fn track_camera_motion(
+        mut motion: ResMut<CameraMotion<C>>,
+        camera_info: CameraInfo<C>,
+        configuration: Res<C>,
+        time: Res<Time>,
+    ) {
+        motion.velocities.clear();
+        motion.teleported.clear();
+
+        let delta = time.delta_seconds();
+        let mut positions = HashMap::new();
+        for (camera_entity, _, cam_gtf) in camera_info.iter() {
+            let position = cam_gtf.translation();
+            if let Some(previous) = motion.previous_positions.get(&camera_entity).copied() {
+                let moved = position - previous;
+                if moved.length() > configuration.teleport_distance() as f32 * CHUNK_SIZE_F {
+                    motion.teleported.insert(camera_entity);
+                } else if delta > 0.0 {
+                    motion.velocities.insert(camera_entity, moved / delta);
+                }
+            }
+            positions.insert(camera_entity, position);
+        }
+        motion.previous_positions = positions;
+    }
+
     /// Find and spawn chunks in need of spawning
+    #[cfg(feature = "render")]
+    #[allow(clippy::too_many_arguments)]
     pub fn spawn_chunks(
         mut commands: Commands,
         mut chunk_map_insert_buffer: ResMut<ChunkMapInsertBuffer<C>>,
+        mut lod_chunks: ResMut<LodChunks<C>>,
+        mut chunk_pool: ResMut<ChunkPool<C>>,
         world_root: Query<Entity, With<WorldRoot<C>>>,
         chunk_map: Res<ChunkMap<C>>,
         configuration: Res<C>,
         camera_info: CameraInfo<C>,
+        camera_motion: Res<CameraMotion<C>>,
+        mut evicted_chunks: ResMut<EvictedChunks<C>>,
     ) {
+        // Finite worlds are spawned by `spawn_finite_world` instead
+        if configuration.world_extent().is_some() {
+            return;
+        }
+
+        let start = Instant::now();
+
         // Panic if no root exists as it is already inserted in the setup.
         let world_root = world_root.get_single().unwrap();
 
-        let (camera, cam_gtf) = camera_info.single();
-        let cam_pos = cam_gtf.translation().as_ivec3();
+        // When LOD levels are configured, they replace the spawning distances
+        let lod_rings = LodRings::from_config(&*configuration);
+        let max_lod_level = lod_rings.as_ref().map_or(0, |rings| rings.max_level());
+        let mut spawned_chunks = Vec::new();
 
         // Define spawning distances
         let spawning_min_distance = configuration.spawning_min_distance() as i32;
-        let spawning_max_distance = configuration.spawning_max_distance() as i32;
-
-        let viewport_size = camera.physical_viewport_size().unwrap_or_default();
-
-        let mut visited = HashSet::new();
-        let mut chunks_deque =
-            VecDeque::with_capacity(configuration.spawning_rays() * spawning_max_distance as usize);
-
+        let spawning_max_distance = lod_rings
+            .as_ref()
+            .map_or(configuration.spawning_max_distance(), |rings| {
+                rings.max_distance()
+            }) as i32;
+
+        // The chunks spawned this frame, which are not in the chunk map yet
+        let mut spawned_this_frame = HashSet::new();
         let chunk_map_read_lock = chunk_map.get_read_lock();
 
-        // Shoots a ray from the given point, and queue all (non-spawned) chunks intersecting the ray
-        let queue_chunks_intersecting_ray_from_point =
-            |point: Vec2, queue: &mut VecDeque<IVec3>| {
+        // Worlds can be streamed by anchors alone, without a camera. With several cameras, for
+        // example for split-screen, the chunks needed by each of them are spawned, and the frame
+        // budget is split between them. Levels of detail are picked for all cameras at once.
+        let cameras: Vec<_> = camera_info.iter().collect();
+        let lod_view = LodView::new(&cameras, &*lod_chunks);
+        let max_spawned_chunks = configuration.max_chunks_spawned_per_frame();
+        for (camera_index, (camera_entity, camera, cam_gtf)) in cameras.iter().copied().enumerate()
+        {
+            // Budget left over by the cameras before goes to the cameras after
+            let remaining_cameras = cameras.len() - camera_index;
+            let camera_budget =
+                (max_spawned_chunks - spawned_chunks.len()).div_ceil(remaining_cameras);
+            let spawned_before = spawned_chunks.len();
+            // Each camera visits the chunks by itself, since a chunk out of range of one camera
+            // can still be in range of another
+            let mut visited = HashSet::new();
+
+            let cam_pos = cam_gtf.translation().as_ivec3();
+            let cam_pos_f = cam_gtf.translation();
+            let viewport_size = camera.physical_viewport_size().unwrap_or_default();
+            let mut chunks_deque = SpawnQueue::new(cam_pos_f);
+
+            // The level and position of the chunk that covers a world position
+            let chunk_at = |point: Vec3| match &lod_rings {
+                Some(rings) => rings.chunk_at(point, &lod_view),
+                None => Some((0, point.as_ivec3() / CHUNK_SIZE_I)),
+            };
+
+            // Shoots a ray from the given point, and queue all (non-spawned) chunks intersecting the ray
+            let queue_chunks_intersecting_ray_from_point = |point: Vec2, queue: &mut SpawnQueue| {
                 let Some(ray) = camera.viewport_to_world(cam_gtf, point) else {
                     return;
                 };
@@ -127,183 +709,856 @@ where
                 let mut t = 0.0;
                 while t < (spawning_max_distance * CHUNK_SIZE_I * 20) as f32 {
                     // HACK REMOVE THE 20
-                    let chunk_pos = current.as_ivec3() / CHUNK_SIZE_I;
-                    if let Some(chunk) = ChunkMap::<C>::get(&chunk_pos, &chunk_map_read_lock) {
-                        if chunk.is_full {
-                            // If we hit a full chunk, we can stop the ray early
+                    let Some((level, chunk_pos)) = chunk_at(current) else {
+                        break;
+                    };
+                    if level > 0 {
+                        if !lod_chunks.spawned.contains_key(&(level, chunk_pos)) {
+                            queue.push((level, chunk_pos));
+                        }
+                    } else if let Some(chunk) = ChunkMap::<C>::get(&chunk_pos, &chunk_map_read_lock)
+                    {
+                        if chunk.is_opaque {
+                            // If we hit an opaque chunk, we can stop the ray early
                             break;
                         }
                     } else {
-                        queue.push_back(chunk_pos);
+                        queue.push((0, chunk_pos));
                     }
-                    t += CHUNK_SIZE_F;
+                    t += lod_chunk_size(level);
                     current = ray.origin + ray.direction * t;
                 }
             };
 
-        // Each frame we pick some random points on the screen
-        let margin = configuration.spawning_ray_margin();
-        for _ in 0..configuration.spawning_rays() {
-            let random_point_in_viewport = {
-                let x =
-                    rand::random::<f32>() * (viewport_size.x + margin * 2) as f32 - margin as f32;
-                let y =
-                    rand::random::<f32>() * (viewport_size.y + margin * 2) as f32 - margin as f32;
-                Vec2::new(x, y)
-            };
+            // Each frame we pick some random points on the screen
+            let margin = configuration.spawning_ray_margin();
+            for _ in 0..configuration.spawning_rays() {
+                let random_point_in_viewport = {
+                    let x = rand::random::<f32>() * (viewport_size.x + margin * 2) as f32
+                        - margin as f32;
+                    let y = rand::random::<f32>() * (viewport_size.y + margin * 2) as f32
+                        - margin as f32;
+                    Vec2::new(x, y)
+                };
 
-            // Then, for each point, we cast a ray, picking up any unspawned chunks along the ray
-            queue_chunks_intersecting_ray_from_point(random_point_in_viewport, &mut chunks_deque);
-        }
+                // Then, for each point, we cast a ray, picking up any unspawned chunks along the ray
+                queue_chunks_intersecting_ray_from_point(
+                    random_point_in_viewport,
+                    &mut chunks_deque,
+                );
+            }
 
-        // We also queue the chunks closest to the camera to make sure they will always spawn early
-        let chunk_at_camera = cam_pos / CHUNK_SIZE_I;
-        for x in -1..=1 {
-            for y in -1..=1 {
-                for z in -1..=1 {
-                    let queue_pos = chunk_at_camera + IVec3::new(x, y, z);
-                    chunks_deque.push_back(queue_pos);
+            // We also queue the chunks closest to the camera to make sure they will always spawn early
+            let chunk_at_camera = cam_pos / CHUNK_SIZE_I;
+            for x in -1..=1 {
+                for y in -1..=1 {
+                    for z in -1..=1 {
+                        let offset = IVec3::new(x, y, z);
+                        if lod_rings.is_some() {
+                            chunks_deque
+                                .extend(chunk_at(cam_pos_f + offset.as_vec3() * CHUNK_SIZE_F));
+                        } else {
+                            chunks_deque.push((0, chunk_at_camera + offset));
+                        }
+                    }
                 }
             }
-        }
 
-        // Then, when we have a queue of chunks, we can set them up for spawning
-        while let Some(chunk_position) = chunks_deque.pop_front() {
-            if visited.contains(&chunk_position)
-                || chunks_deque.len() > configuration.max_spawn_per_frame()
-            {
-                continue;
+            // When the camera teleports, the chunks around the camera's new position are spawned
+            // before anything else
+            if camera_motion.teleported(camera_entity) {
+                chunks_deque.flush();
             }
-            visited.insert(chunk_position);
 
-            let chunk_position = chunk_position;
-            // Calculate the Chebyshev distance between the chunk and the camera
-            let dist = (chunk_position - chunk_at_camera).abs();
-            let chebyshev_dist = dist.x.max(dist.y).max(dist.z);
+            // Chunks along the path of a moving camera are moved up in the queue, so that fast
+            // cameras don't outrun chunk generation
+            let lookahead =
+                camera_motion.velocity(camera_entity) * configuration.spawning_prefetch_time();
+            for chunk in prefetched_chunks(
+                cam_pos_f,
+                lookahead,
+                configuration.spawning_prefetch_max_chunks(),
+                chunk_at,
+            ) {
+                chunks_deque.push_ahead(chunk);
+            }
+
+            // Then, when we have a queue of chunks, we can set them up for spawning, as long as the
+            // frame budget allows
+            let time_budget = configuration.max_streaming_time_per_frame();
+            let spawn_strategy = configuration.chunk_spawn_strategy();
+            while let Some((level, chunk_position)) = chunks_deque.pop() {
+                if spawned_chunks.len() - spawned_before >= camera_budget
+                    || time_budget.is_some_and(|budget| start.elapsed() >= budget)
+                {
+                    break;
+                }
+
+                if visited.contains(&(level, chunk_position))
+                    || chunks_deque.len() > configuration.max_spawn_per_frame()
+                {
+                    continue;
+                }
+                visited.insert((level, chunk_position));
+
+                // Check if chunk is within the spawning distance range
+                let in_range = if let Some(rings) = &lod_rings {
+                    rings.is_wanted(level, chunk_position, &lod_view)
+                } else if let ChunkSpawnStrategy::Custom(_) = spawn_strategy {
+                    true
+                } else {
+                    // Calculate the Chebyshev distance between the chunk and the camera
+                    let dist = (chunk_position - chunk_at_camera).abs();
+                    let chebyshev_dist = dist.x.max(dist.y).max(dist.z);
+
+                    chebyshev_dist >= spawning_min_distance
+                        && chebyshev_dist <= spawning_max_distance
+                };
+
+                let in_range = in_range
+                    && match &spawn_strategy {
+                        ChunkSpawnStrategy::Custom(should_spawn) => should_spawn(
+                            &chunk_strategy_input(chunk_position, level, camera, cam_gtf, None),
+                        ),
+                        _ => true,
+                    };
+
+                if !in_range {
+                    continue;
+                }
+
+                // Chunks evicted to stay within the memory budget wait until they are visible
+                if level == 0 && evicted_chunks.contains(&chunk_position) {
+                    let chunk_center = (chunk_position.as_vec3() + 0.5) * CHUNK_SIZE_F;
+                    if !is_in_view(chunk_center, camera, cam_gtf) {
+                        continue;
+                    }
+                    evicted_chunks.remove(&chunk_position);
+                }
+
+                let has_chunk = spawned_this_frame.contains(&(level, chunk_position))
+                    || if level > 0 {
+                        lod_chunks.spawned.contains_key(&(level, chunk_position))
+                    } else {
+                        ChunkMap::<C>::contains_chunk(&chunk_position, &chunk_map_read_lock)
+                    };
+
+                if !has_chunk {
+                    spawned_this_frame.insert((level, chunk_position));
+                    let chunk_entity = chunk_pool.spawn(&mut commands, NeedsRemesh);
+                    commands.entity(world_root).add_child(chunk_entity);
+                    let chunk = Chunk::<C>::new(chunk_position, chunk_entity).with_lod_level(level);
+
+                    // The LOD chunks are updated after the loop, since the LOD view borrows them
+                    spawned_chunks.push((level, chunk_position, chunk.entity));
+                    if level == 0 {
+                        chunk_map_insert_buffer
+                            .push((chunk_position, ChunkData::with_entity(chunk.entity)));
+                    }
+
+                    // Voxels of coarser levels are scaled up, so the chunk mesh is scaled with them
+                    let scale = lod_scale(level) as f32;
+                    let origin = lod_chunk_origin(chunk_position, level).as_vec3();
+                    commands.entity(chunk.entity).try_insert((
+                        chunk,
+                        Transform::from_translation(origin - scale).with_scale(Vec3::splat(scale)),
+                    ));
+                } else {
+                    continue;
+                }
+
+                if !matches!(
+                    spawn_strategy,
+                    ChunkSpawnStrategy::Close | ChunkSpawnStrategy::Custom(_)
+                ) {
+                    continue;
+                }
+
+                // If we get here, we queue the neighbors
+                let chunk_size = lod_chunk_size(level);
+                let chunk_center = (chunk_position.as_vec3() + 0.5) * chunk_size;
+                for x in -1..=1 {
+                    for y in -1..=1 {
+                        for z in -1..=1 {
+                            let offset = IVec3::new(x, y, z);
+                            if offset == IVec3::ZERO {
+                                continue;
+                            }
+                            if lod_rings.is_some() {
+                                chunks_deque
+                                    .extend(chunk_at(chunk_center + offset.as_vec3() * chunk_size));
+                            } else {
+                                chunks_deque.push((0, chunk_position + offset));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for (level, chunk_position, entity) in spawned_chunks {
+            lod_chunks.insert(level, chunk_position, entity, max_lod_level);
+        }
+    }
+
+    /// Spawns the chunks around streaming anchors, closest chunks first.
+    /// See `VoxelWorldStreamingAnchor`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn_anchored_chunks(
+        mut commands: Commands,
+        mut chunk_map_insert_buffer: ResMut<ChunkMapInsertBuffer<C>>,
+        mut chunk_pool: ResMut<ChunkPool<C>>,
+        world_root: Query<Entity, With<WorldRoot<C>>>,
+        chunk_map: Res<ChunkMap<C>>,
+        configuration: Res<C>,
+        anchors: StreamingAnchors<C>,
+        mut completed_anchors: Local<HashMap<Entity, (IVec3, i32)>>,
+        mut warned_lod: Local<bool>,
+        mut ev_chunk_will_despawn: EventReader<ChunkWillDespawn<C>>,
+    ) {
+        if configuration.world_extent().is_some() {
+            return;
+        }
+
+        if StreamingAnchors::<C>::ignored(&configuration) {
+            if !*warned_lod && !anchors.is_empty() {
+                warn!("VoxelWorldStreamingAnchor is ignored in worlds with levels of detail");
+                *warned_lod = true;
+            }
+            return;
+        }
 
-            // Check if chunk is within the spawning distance range
-            let chebyshev_approves =
-                chebyshev_dist < spawning_min_distance || chebyshev_dist > spawning_max_distance;
+        let start = Instant::now();
+        let anchor_chunks = anchors.chunks(&configuration);
+
+        // Anchors that have all chunks around them spawned are skipped until they move to
+        // another chunk, their radius changes, or one of their chunks is despawned
+        completed_anchors.retain(|entity, completed| {
+            anchor_chunks.iter().any(|(anchor, anchor_chunk, radius)| {
+                anchor == entity && (*anchor_chunk, *radius) == *completed
+            })
+        });
+        for event in ev_chunk_will_despawn.read() {
+            completed_anchors.retain(|_, (anchor_chunk, radius)| {
+                (event.chunk_key - *anchor_chunk).abs().max_element() > *radius
+            });
+        }
+        if anchor_chunks.len() == completed_anchors.len() {
+            return;
+        }
 
-            // Check if chunk is within the spawning distance range
-            if chebyshev_approves {
+        let world_root = world_root.get_single().unwrap();
+        let chunk_map_read_lock = chunk_map.get_read_lock();
+        let max_spawned_chunks = configuration.max_chunks_spawned_per_frame();
+        let time_budget = configuration.max_streaming_time_per_frame();
+
+        // Chunks queued by `spawn_chunks` this frame are not in the chunk map yet
+        let mut queued: HashSet<IVec3> = chunk_map_insert_buffer
+            .iter()
+            .map(|(chunk_pos, _)| *chunk_pos)
+            .collect();
+        let mut spawned = 0;
+
+        for (entity, anchor_chunk, radius) in anchor_chunks {
+            if completed_anchors.contains_key(&entity) {
                 continue;
             }
 
-            let has_chunk = ChunkMap::<C>::contains_chunk(&chunk_position, &chunk_map_read_lock);
+            let mut missing: Vec<IVec3> = Vec::new();
+            for x in -radius..=radius {
+                for y in -radius..=radius {
+                    for z in -radius..=radius {
+                        let chunk_position = anchor_chunk + IVec3::new(x, y, z);
+                        if !queued.contains(&chunk_position)
+                            && !ChunkMap::<C>::contains_chunk(&chunk_position, &chunk_map_read_lock)
+                        {
+                            missing.push(chunk_position);
+                        }
+                    }
+                }
+            }
+            missing
+                .sort_by_key(|chunk_position| (*chunk_position - anchor_chunk).abs().max_element());
+
+            let mut completed = true;
+            for chunk_position in missing {
+                if spawned >= max_spawned_chunks
+                    || time_budget.is_some_and(|budget| start.elapsed() >= budget)
+                {
+                    completed = false;
+                    break;
+                }
 
-            if !has_chunk {
-                let chunk_entity = commands.spawn(NeedsRemesh).id();
+                let chunk_entity = chunk_pool.spawn(&mut commands, NeedsRemesh);
                 commands.entity(world_root).add_child(chunk_entity);
                 let chunk = Chunk::<C>::new(chunk_position, chunk_entity);
-
                 chunk_map_insert_buffer
                     .push((chunk_position, ChunkData::with_entity(chunk.entity)));
-
                 commands.entity(chunk.entity).try_insert((
                     chunk,
-                    Transform::from_translation(chunk_position.as_vec3() * CHUNK_SIZE_F - 1.0),
+                    Transform::from_translation(
+                        lod_chunk_origin(chunk_position, 0).as_vec3() - 1.0,
+                    ),
                 ));
-            } else {
-                continue;
+                queued.insert(chunk_position);
+                spawned += 1;
             }
 
-            if configuration.chunk_spawn_strategy() != ChunkSpawnStrategy::Close {
+            if completed {
+                completed_anchors.insert(entity, (anchor_chunk, radius));
+            }
+        }
+    }
+
+    /// Spawns the chunks requested with `VoxelWorld::request_chunk`, and keeps track of which of
+    /// them have finished generating. With `VoxelWorldConfig::manual_chunk_management`, released
+    /// chunks are despawned here as well.
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn spawn_requested_chunks(
+        mut commands: Commands,
+        mut chunk_map_insert_buffer: ResMut<ChunkMapInsertBuffer<C>>,
+        mut chunk_pool: ResMut<ChunkPool<C>>,
+        mut chunk_requests: ResMut<ChunkRequests<C>>,
+        world_root: Query<Entity, With<WorldRoot<C>>>,
+        chunk_map: Res<ChunkMap<C>>,
+        configuration: Res<C>,
+        pending_chunks: Query<
+            (),
+            Or<(With<NeedsRemesh>, With<ChunkThread<C>>, With<NeedsDespawn>)>,
+        >,
+        mut ev_chunk_will_despawn: EventWriter<ChunkWillDespawn<C>>,
+    ) {
+        let chunk_map_read_lock = chunk_map.get_read_lock();
+        let manual = configuration.manual_chunk_management();
+
+        // Without automatic despawning, released chunks are despawned right away
+        let released = std::mem::take(&mut chunk_requests.released);
+        if manual {
+            for chunk_position in released {
+                if chunk_requests.is_requested(&chunk_position) {
+                    continue;
+                }
+                let Some(chunk_data) = ChunkMap::<C>::get(&chunk_position, &chunk_map_read_lock)
+                else {
+                    continue;
+                };
+                commands.entity(chunk_data.entity).try_insert(NeedsDespawn);
+                ev_chunk_will_despawn.send(ChunkWillDespawn::<C>::new(
+                    chunk_position,
+                    chunk_data.entity,
+                    Some(chunk_data),
+                ));
+            }
+        }
+
+        // With levels of detail, requested chunks spawned by the camera can still be replaced by
+        // coarser chunks
+        chunk_requests.loaded.retain(|chunk_position| {
+            ChunkMap::<C>::contains_chunk(chunk_position, &chunk_map_read_lock)
+        });
+        if chunk_requests.requested.len() == chunk_requests.loaded.len() {
+            return;
+        }
+
+        // Like streaming anchors, requests don't spawn chunks in finite worlds or with levels of
+        // detail, but are still marked as loaded once the chunk is spawned
+        let can_spawn = manual
+            || (configuration.world_extent().is_none() && configuration.lod_distances().is_empty());
+        let world_root = world_root.get_single().unwrap();
+
+        // Chunks queued by other systems this frame are not in the chunk map yet
+        let queued: HashSet<IVec3> = chunk_map_insert_buffer
+            .iter()
+            .map(|(chunk_position, _)| *chunk_position)
+            .collect();
+
+        let mut loaded = Vec::new();
+        for chunk_position in chunk_requests.requested.keys() {
+            if chunk_requests.loaded.contains(chunk_position) || queued.contains(chunk_position) {
                 continue;
             }
 
-            // If we get here, we queue the neighbors
-            for x in -1..=1 {
-                for y in -1..=1 {
-                    for z in -1..=1 {
-                        let queue_pos = chunk_position + IVec3::new(x, y, z);
-                        if queue_pos == chunk_position {
-                            continue;
-                        }
-                        chunks_deque.push_back(queue_pos);
-                    }
+            if let Some(chunk_data) = ChunkMap::<C>::get(chunk_position, &chunk_map_read_lock) {
+                if !pending_chunks.contains(chunk_data.entity) {
+                    loaded.push(*chunk_position);
                 }
+                continue;
+            }
+
+            if !can_spawn {
+                continue;
             }
+
+            let chunk_entity = chunk_pool.spawn(&mut commands, NeedsRemesh);
+            commands.entity(world_root).add_child(chunk_entity);
+            let chunk = Chunk::<C>::new(*chunk_position, chunk_entity);
+            chunk_map_insert_buffer.push((*chunk_position, ChunkData::with_entity(chunk.entity)));
+            commands.entity(chunk.entity).try_insert((
+                chunk,
+                Transform::from_translation(lod_chunk_origin(*chunk_position, 0).as_vec3() - 1.0),
+            ));
         }
+        chunk_requests.loaded.extend(loaded);
     }
 
-    /// Tags chunks that are eligible for despawning
+    /// Spawns every chunk of a finite world, once. The chunks are spawned in order, as fast as the
+    /// frame budget allows. When the world root is recreated or the configuration changes, the
+    /// chunks are gone through again, and the missing ones are spawned. See
+    /// `VoxelWorldConfig::world_extent`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn spawn_finite_world(
+        mut commands: Commands,
+        mut chunk_map_insert_buffer: ResMut<ChunkMapInsertBuffer<C>>,
+        mut chunk_pool: ResMut<ChunkPool<C>>,
+        world_root: Query<Entity, With<WorldRoot<C>>>,
+        chunk_map: Res<ChunkMap<C>>,
+        configuration: Res<C>,
+        mut spawned_under: Local<Option<Entity>>,
+        mut next_index: Local<usize>,
+    ) {
+        let Some((min, max)) = configuration.world_extent() else {
+            return;
+        };
+        let Ok(world_root) = world_root.get_single() else {
+            return;
+        };
+
+        if *spawned_under != Some(world_root) || configuration.is_changed() {
+            *spawned_under = Some(world_root);
+            *next_index = 0;
+        }
+
+        let size = (max - min + IVec3::ONE).max(IVec3::ZERO);
+        let chunk_count = (size.x * size.y * size.z) as usize;
+        if *next_index >= chunk_count {
+            return;
+        }
+
+        let start = Instant::now();
+        let chunk_map_read_lock = chunk_map.get_read_lock();
+        let max_spawned_chunks = configuration.max_chunks_spawned_per_frame();
+        let time_budget = configuration.max_streaming_time_per_frame();
+        let mut spawned = 0;
+
+        while *next_index < chunk_count {
+            if spawned >= max_spawned_chunks
+                || time_budget.is_some_and(|budget| start.elapsed() >= budget)
+            {
+                break;
+            }
+
+            let index = *next_index as i32;
+            *next_index += 1;
+            let chunk_position = min
+                + IVec3::new(
+                    index % size.x,
+                    (index / size.x) % size.y,
+                    index / (size.x * size.y),
+                );
+
+            if ChunkMap::<C>::contains_chunk(&chunk_position, &chunk_map_read_lock) {
+                continue;
+            }
+
+            let chunk_entity = chunk_pool.spawn(&mut commands, NeedsRemesh);
+            commands.entity(world_root).add_child(chunk_entity);
+            let chunk = Chunk::<C>::new(chunk_position, chunk_entity);
+            chunk_map_insert_buffer.push((chunk_position, ChunkData::with_entity(chunk.entity)));
+            commands.entity(chunk.entity).try_insert((
+                chunk,
+                Transform::from_translation(lod_chunk_origin(chunk_position, 0).as_vec3() - 1.0),
+            ));
+            spawned += 1;
+        }
+    }
+
+    /// Tags chunks that are eligible for despawning. Chunks are kept while a camera, a streaming
+    /// anchor or a request needs them. Headless worlds have no cameras, so anchors and requests
+    /// are all that keep their chunks spawned.
+    #[allow(clippy::too_many_arguments)]
     pub fn retire_chunks(
         mut commands: Commands,
-        all_chunks: Query<(&Chunk<C>, Option<&ViewVisibility>)>,
+        all_chunks: Query<(&Chunk<C>, ChunkVisibility), Without<NeedsDespawn>>,
         configuration: Res<C>,
-        camera_info: CameraInfo<C>,
+        #[cfg(feature = "render")] camera_info: CameraInfo<C>,
+        anchors: StreamingAnchors<C>,
+        chunk_requests: Res<ChunkRequests<C>>,
+        chunk_map: Res<ChunkMap<C>>,
+        #[cfg(feature = "render")] mut lod_chunks: ResMut<LodChunks<C>>,
+        #[cfg(feature = "render")] time: Res<Time>,
+        #[cfg(feature = "render")] mut out_of_range_since: Local<HashMap<Entity, Duration>>,
         mut ev_chunk_will_despawn: EventWriter<ChunkWillDespawn<C>>,
     ) {
+        // The chunks of finite worlds are never despawned
+        if configuration.world_extent().is_some() {
+            return;
+        }
+
+        let hysteresis = configuration.lod_hysteresis() as i32;
+
+        // Chunks around streaming anchors, and requested chunks, are kept regardless of the camera.
+        // Like anchors, requests are not used with levels of detail.
+        let anchor_chunks = anchors.chunks(&configuration);
+        let requests_used = !StreamingAnchors::<C>::ignored(&configuration);
+        let is_anchored = |chunk: &Chunk<C>| {
+            chunk.lod_level == 0
+                && ((requests_used && chunk_requests.is_requested(&chunk.position))
+                    || anchor_chunks.iter().any(|(_, anchor_chunk, radius)| {
+                        (chunk.position - *anchor_chunk).abs().max_element() <= radius + hysteresis
+                    }))
+        };
+
+        #[cfg(feature = "render")]
+        let cameras: Vec<_> = camera_info.iter().collect();
+        #[cfg(feature = "render")]
+        let chunks_to_remove = if cameras.is_empty() {
+            if anchor_chunks.is_empty() && chunk_requests.requested.is_empty() {
+                warn!("No camera found with VoxelWorldCamera component.");
+                return;
+            }
+            None
+        } else {
+            Some(Self::chunks_out_of_view(
+                &all_chunks,
+                &cameras,
+                &configuration,
+                &mut lod_chunks,
+                &mut out_of_range_since,
+                time.elapsed(),
+                &is_anchored,
+            ))
+        };
+        #[cfg(not(feature = "render"))]
+        let chunks_to_remove: Option<Vec<_>> = None;
+
+        // Without a camera, only the anchors and requests keep chunks spawned
+        let chunks_to_remove = chunks_to_remove.unwrap_or_else(|| {
+            all_chunks
+                .iter()
+                .filter(|(chunk, _)| !is_anchored(chunk))
+                .map(|(chunk, _)| (chunk, false))
+                .collect()
+        });
+
+        // The voxels of despawned chunks are handed out with the event, for persistence
+        let chunk_map_read_lock = chunk_map.get_read_lock();
+        for (chunk, replaced) in chunks_to_remove {
+            commands.entity(chunk.entity).try_insert(NeedsDespawn);
+            if replaced {
+                commands.entity(chunk.entity).try_insert(LodReplaced);
+            }
+            let chunk_data = (chunk.lod_level == 0)
+                .then(|| ChunkMap::<C>::get(&chunk.position, &chunk_map_read_lock))
+                .flatten();
+            ev_chunk_will_despawn.send(ChunkWillDespawn::<C>::new(
+                chunk.position,
+                chunk.entity,
+                chunk_data,
+            ));
+        }
+    }
+
+    /// The chunks that none of the cameras need anymore, each with whether it is replaced by
+    /// chunks at a different level of detail
+    #[cfg(feature = "render")]
+    fn chunks_out_of_view<'a>(
+        all_chunks: &'a Query<(&Chunk<C>, ChunkVisibility), Without<NeedsDespawn>>,
+        cameras: &[(Entity, &Camera, &GlobalTransform)],
+        configuration: &C,
+        lod_chunks: &mut LodChunks<C>,
+        out_of_range_since: &mut HashMap<Entity, Duration>,
+        now: Duration,
+        is_anchored: impl Fn(&Chunk<C>) -> bool,
+    ) -> Vec<(&'a Chunk<C>, bool)> {
         let spawning_max_distance = configuration.spawning_max_distance() as i32;
         let spawning_min_distance = configuration.spawning_min_distance() as i32;
+        let lod_rings = LodRings::from_config(configuration);
+        let hysteresis = configuration.lod_hysteresis() as i32;
+
+        // With several cameras, chunks are only despawned when no camera needs them anymore
+        let lod_view = LodView::new(cameras, lod_chunks);
+        let chunks_at_cameras: Vec<IVec3> = cameras
+            .iter()
+            .map(|(_, _, cam_gtf)| cam_gtf.translation().as_ivec3() / CHUNK_SIZE_I)
+            .collect();
+        let mut still_out_of_range = HashMap::new();
+        let despawn_strategy = configuration.chunk_despawn_strategy();
+
+        let mut remove = Vec::with_capacity(1000);
+        for (chunk, view_visibility) in all_chunks.iter() {
+            if is_anchored(chunk) {
+                continue;
+            }
+
+            // Determine if the chunk should be culled based on despawn strategy
+            let should_be_culled = match &despawn_strategy {
+                ChunkDespawnStrategy::Distance(_) => false,
+                ChunkDespawnStrategy::FarAway => false,
+                ChunkDespawnStrategy::FarAwayAfter(_) => false,
+                ChunkDespawnStrategy::Custom(should_despawn) => {
+                    cameras.iter().all(|(_, camera, cam_gtf)| {
+                        should_despawn(&chunk_strategy_input(
+                            chunk.position,
+                            chunk.lod_level,
+                            camera,
+                            cam_gtf,
+                            view_visibility,
+                        ))
+                    })
+                }
+                ChunkDespawnStrategy::FarAwayOrOutOfView => {
+                    if let Some(visibility) = view_visibility {
+                        !visibility.get()
+                    } else {
+                        false
+                    }
+                }
+            };
+
+            let chunk_position = chunk.position;
+
+            let (out_of_range, replaced) = if let Some(rings) = &lod_rings {
+                // Outside of the rings, or covered by chunks at another level
+                let in_range = rings.is_in_range(chunk.lod_level, chunk_position, &lod_view);
+                let wanted = rings.is_wanted(chunk.lod_level, chunk_position, &lod_view);
+                (!in_range, in_range && !wanted)
+            } else {
+                let out_of_range = chunks_at_cameras.iter().all(|chunk_at_camera| {
+                    // Calculate the Chebyshev distance between the chunk and the camera
+                    let dist = (chunk_position - *chunk_at_camera).abs();
+                    let chebyshev_dist = dist.x.max(dist.y).max(dist.z);
+
+                    // Check if chunk is within the spawning distance range, with some margin
+                    // so that chunks don't despawn and respawn when the camera moves back and
+                    // forth
+                    chebyshev_dist < spawning_min_distance - hysteresis
+                        || chebyshev_dist > spawning_max_distance + hysteresis
+                });
+                (out_of_range, false)
+            };
+
+            // With a grace period, chunks are only despawned once they have been out of
+            // range for long enough
+            let chebyshev_approves = replaced
+                || match &despawn_strategy {
+                    // Custom strategies decide about the distance themselves
+                    ChunkDespawnStrategy::Custom(_) => false,
+                    ChunkDespawnStrategy::FarAwayAfter(grace_period) if out_of_range => {
+                        let since = out_of_range_since
+                            .get(&chunk.entity)
+                            .copied()
+                            .unwrap_or(now);
+                        still_out_of_range.insert(chunk.entity, since);
+                        now - since >= *grace_period
+                    }
+                    _ => out_of_range,
+                };
+
+            // Despawn if:
+            // 1. Should be culled based on despawn strategy.
+            // 2. Outside the spawning_max_distance, or the LOD rings.
+            // 3. Inside the spawning_min_distance (if desired).
+            if should_be_culled || chebyshev_approves {
+                remove.push((chunk, replaced));
+            }
+        }
+        *out_of_range_since = still_out_of_range;
+
+        // Forget the geometric errors of chunks that left the rings, whether or not they were
+        // ever spawned, so they don't pile up as the camera moves through the world
+        if let Some(rings) = &lod_rings {
+            let out_of_range: Vec<(u8, IVec3)> = lod_chunks
+                .geometric_errors
+                .keys()
+                .filter(|(level, chunk_position)| {
+                    !rings.is_in_range(*level, *chunk_position, &lod_view)
+                })
+                .copied()
+                .collect();
+            for key in out_of_range {
+                lod_chunks.geometric_errors.remove(&key);
+            }
+        }
+
+        remove
+    }
+
+    /// Despawns the chunks that were visible least recently while the voxel data of the spawned
+    /// chunks exceeds `VoxelWorldConfig::chunk_memory_budget`
+    #[cfg(feature = "render")]
+    #[allow(clippy::too_many_arguments, clippy::type_complexity)]
+    pub fn evict_chunks(
+        mut commands: Commands,
+        chunks: Query<(&Chunk<C>, ChunkVisibility), Without<NeedsDespawn>>,
+        chunk_map: Res<ChunkMap<C>>,
+        configuration: Res<C>,
+        camera_info: CameraInfo<C>,
+        anchors: StreamingAnchors<C>,
+        chunk_requests: Res<ChunkRequests<C>>,
+        time: Res<Time>,
+        mut evicted_chunks: ResMut<EvictedChunks<C>>,
+        mut last_visible: Local<HashMap<Entity, Duration>>,
+        mut ev_chunk_will_despawn: EventWriter<ChunkWillDespawn<C>>,
+    ) {
+        let Some(budget) = configuration.chunk_memory_budget() else {
+            return;
+        };
 
-        let (_, cam_gtf) = match camera_info.get_single() {
-            Ok(info) => info,
-            Err(_) => {
-                warn!("No camera found with VoxelWorldCamera component.");
-                return;
-            }
+        // The chunks of finite worlds are never despawned
+        if configuration.world_extent().is_some() {
+            return;
+        }
+
+        // Evicted chunks that are out of range would not be spawned anyway
+        let max_distance =
+            (configuration.spawning_max_distance() + configuration.lod_hysteresis()) as i32;
+        evicted_chunks.retain(|chunk_position| {
+            camera_info.iter().any(|(_, _, cam_gtf)| {
+                let chunk_at_camera = cam_gtf.translation().as_ivec3() / CHUNK_SIZE_I;
+                (*chunk_position - chunk_at_camera).abs().max_element() <= max_distance
+            })
+        });
+
+        // Newly spawned chunks count as visible
+        let now = time.elapsed();
+        let mut still_spawned = HashMap::new();
+        for (chunk, view_visibility) in chunks.iter() {
+            let visible = view_visibility.is_some_and(|visibility| visibility.get());
+            let since = match last_visible.get(&chunk.entity) {
+                Some(since) if !visible => *since,
+                _ => now,
+            };
+            still_spawned.insert(chunk.entity, since);
+        }
+        *last_visible = still_spawned;
+
+        // Chunks kept by anchors or requests are never evicted
+        let anchor_chunks = anchors.chunks(&configuration);
+        let is_kept = |chunk: &Chunk<C>| {
+            chunk_requests.is_requested(&chunk.position)
+                || anchor_chunks.iter().any(|(_, anchor_chunk, radius)| {
+                    (chunk.position - *anchor_chunk).abs().max_element() <= *radius
+                })
         };
-        let cam_pos = cam_gtf.translation().as_ivec3();
-
-        let chunk_at_camera = cam_pos / CHUNK_SIZE_I;
-
-        let chunks_to_remove = {
-            let mut remove = Vec::with_capacity(1000);
-            for (chunk, view_visibility) in all_chunks.iter() {
-                // Determine if the chunk should be culled based on despawn strategy
-                let should_be_culled = match configuration.chunk_despawn_strategy() {
-                    ChunkDespawnStrategy::Distance(_) => false,
-                    ChunkDespawnStrategy::FarAway => false,
-                    ChunkDespawnStrategy::FarAwayOrOutOfView => {
-                        if let Some(visibility) = view_visibility {
-                            !visibility.get()
-                        } else {
-                            false
-                        }
-                    }
-                };
 
-                let chunk_position = chunk.position;
-                // Calculate the Chebyshev distance between the chunk and the camera
-                let dist = (chunk_position - chunk_at_camera).abs();
-                let chebyshev_dist = dist.x.max(dist.y).max(dist.z);
+        // Only full detail chunks keep their voxels around
+        let chunk_map_read_lock = chunk_map.get_read_lock();
+        let mut resident = 0;
+        let mut candidates = Vec::new();
+        for (chunk, view_visibility) in chunks.iter() {
+            if chunk.lod_level > 0 {
+                continue;
+            }
+            let Some(chunk_data) = ChunkMap::<C>::get(&chunk.position, &chunk_map_read_lock) else {
+                continue;
+            };
+            let chunk_size = chunk_data.voxels_size();
+            if chunk_size == 0 {
+                continue;
+            }
+            resident += chunk_size;
 
-                // Check if chunk is within the spawning distance range
-                let chebyshev_approves = chebyshev_dist < spawning_min_distance
-                    || chebyshev_dist > spawning_max_distance;
-
-                // Despawn if:
-                // 1. Should be culled based on despawn strategy.
-                // 2. Outside the spawning_max_distance.
-                // 3. Inside the spawning_min_distance (if desired).
-                if should_be_culled || chebyshev_approves {
-                    remove.push(chunk);
-                }
+            let visible = view_visibility.is_some_and(|visibility| visibility.get());
+            if !visible && !is_kept(chunk) {
+                candidates.push((last_visible[&chunk.entity], chunk_size, chunk, chunk_data));
             }
-            remove
-        };
+        }
 
-        for chunk in chunks_to_remove {
+        if resident <= budget {
+            return;
+        }
+
+        candidates.sort_by_key(|(since, _, _, _)| *since);
+        for (_, chunk_size, chunk, chunk_data) in candidates {
+            if resident <= budget {
+                break;
+            }
+            resident -= chunk_size;
+            evicted_chunks.insert(chunk.position);
             commands.entity(chunk.entity).try_insert(NeedsDespawn);
-            ev_chunk_will_despawn.send(ChunkWillDespawn::<C>::new(chunk.position, chunk.entity));
+            ev_chunk_will_despawn.send(ChunkWillDespawn::<C>::new(
+                chunk.position,
+                chunk.entity,
+                Some(chunk_data),
+            ));
         }
     }
 
     /// Despawns chunks that have been tagged for despawning
+    #[allow(clippy::type_complexity)]
     pub fn despawn_retired_chunks(
         mut commands: Commands,
         mut chunk_map_remove_buffer: ResMut<ChunkMapRemoveBuffer<C>>,
+        mut lod_chunks: ResMut<LodChunks<C>>,
+        mut chunk_pool: ResMut<ChunkPool<C>>,
         chunk_map: Res<ChunkMap<C>>,
-        retired_chunks: Query<(Entity, &Chunk<C>), With<NeedsDespawn>>,
+        retired_chunks: Query<
+            (Entity, &Chunk<C>, Has<LodReplaced>, RetiredChunkMesh),
+            With<NeedsDespawn>,
+        >,
+        configuration: Res<C>,
     ) {
         let read_lock = chunk_map.get_read_lock();
-        for (entity, chunk) in retired_chunks.iter() {
-            if ChunkMap::<C>::contains_chunk(&chunk.position, &read_lock) {
-                commands.entity(entity).despawn_recursive();
+        let max_lod_level =
+            LodRings::from_config(&*configuration).map_or(0, |rings| rings.max_level());
+        let lod_fade = lod_fade_enabled(&*configuration);
+
+        for (entity, chunk, replaced, mesh) in retired_chunks.iter() {
+            let spawned = if chunk.lod_level > 0 {
+                lod_chunks
+                    .spawned
+                    .contains_key(&(chunk.lod_level, chunk.position))
+            } else {
+                ChunkMap::<C>::contains_chunk(&chunk.position, &read_lock)
+            };
+            if !spawned {
+                continue;
+            }
+            lod_chunks.remove(chunk.lod_level, chunk.position, max_lod_level);
+            if chunk.lod_level == 0 {
                 chunk_map_remove_buffer.push(chunk.position);
             }
+
+            #[cfg(feature = "render")]
+            let (has_mesh, mesh_ref) = mesh;
+            #[cfg(not(feature = "render"))]
+            let (has_mesh, ()) = (false, mesh);
+
+            // Only chunks replaced by a different level of detail cross-fade
+            if lod_fade && has_mesh && replaced {
+                // Keep the chunk around without its chunk components, so it can dither out
+                // while its replacement dithers in
+                commands
+                    .entity(entity)
+                    .remove::<(
+                        Chunk<C>,
+                        NeedsDespawn,
+                        LodReplaced,
+                        NeedsRemesh,
+                        ChunkThread<C>,
+                    )>()
+                    .try_insert(LodFade::<C>::fade_out(chunk.lod_level, chunk.position));
+            } else {
+                #[cfg(feature = "render")]
+                chunk_pool.recycle_mesh(mesh_ref);
+                chunk_pool.recycle(&mut commands, entity);
+            }
+        }
+    }
+
+    /// Remesh all chunks when the material registry changes, since their textures, tints or
+    /// transparency may have changed
+    #[cfg(feature = "render")]
+    pub fn remesh_changed_materials(
+        mut commands: Commands,
+        material_registry: Res<VoxelMaterialRegistry<C>>,
+        chunks: Query<Entity, With<Chunk<C>>>,
+    ) {
+        if !material_registry.is_changed() || material_registry.is_added() {
+            return;
+        }
+        for entity in chunks.iter() {
+            commands.entity(entity).try_insert(NeedsRemesh);
         }
     }
 
@@ -313,35 +1568,139 @@ where
         mut commands: Commands,
         mut ev_chunk_will_remesh: EventWriter<ChunkWillRemesh<C>>,
         dirty_chunks: Query<&Chunk<C>, With<NeedsRemesh>>,
-        mesh_cache: Res<MeshCache<C>>,
+        #[cfg(feature = "render")] mesh_cache: Res<MeshCache<C>>,
+        #[cfg(feature = "render")] material_registry: Res<VoxelMaterialRegistry<C>>,
         modified_voxels: Res<ModifiedVoxels<C>>,
         configuration: Res<C>,
+        #[cfg(feature = "render")] camera_info: CameraInfo<C>,
+        task_pool: Res<ChunkTaskPool<C>>,
+        remote_chunks: Option<Res<RemoteChunks<C>>>,
+        #[cfg(feature = "render")] lighting: (Option<Res<LightUpdates<C>>>, Res<ChunkMap<C>>),
+        #[cfg(feature = "render")] mut light_scratch: Local<Option<Arc<LightArray>>>,
     ) {
-        let thread_pool = AsyncComputeTaskPool::get();
+        let start = Instant::now();
+        let thread_pool = task_pool.get();
+
+        #[cfg_attr(not(feature = "render"), allow(unused_mut))]
+        let mut dirty_chunks: Vec<_> = dirty_chunks.iter().collect();
+        let max_tasks = configuration.max_meshing_tasks_per_frame();
+        let time_budget = configuration.max_streaming_time_per_frame();
+
+        // Chunks are started in expanding rings around the cameras, so the closest chunks go
+        // first when the budget is limited, or after a camera teleported
+        #[cfg(feature = "render")]
+        let camera_positions: Vec<Vec3> = camera_info
+            .iter()
+            .map(|(_, _, cam_gtf)| cam_gtf.translation())
+            .collect();
+        #[cfg(feature = "render")]
+        if !camera_positions.is_empty() {
+            dirty_chunks.sort_by_cached_key(|chunk| {
+                let ring = camera_positions
+                    .iter()
+                    .map(|cam_pos| {
+                        let camera_chunk = chunk_position_at_level(*cam_pos, chunk.lod_level);
+                        (chunk.position - camera_chunk).abs().max_element() << chunk.lod_level
+                    })
+                    .min();
+                (ring, chunk.lod_level, chunk.position.to_array())
+            });
+        }
+
+        // The meshing config is the same for all chunks, each task gets a clone of its handles
+        #[cfg(feature = "render")]
+        let meshing_config =
+            MeshingConfig::from_config(&*configuration).with_material_registry(&material_registry);
 
-        for chunk in dirty_chunks.iter() {
-            let voxel_data_fn = (configuration.voxel_lookup_delegate())(chunk.position);
-            let texture_index_mapper = configuration.texture_index_mapper().clone();
+        for chunk in dirty_chunks.into_iter().take(max_tasks) {
+            if time_budget.is_some_and(|budget| start.elapsed() >= budget) {
+                break;
+            }
+
+            // The delegate is given the position of the chunk in full detail chunk coordinates
+            let delegate_position =
+                lod_chunk_origin(chunk.position, chunk.lod_level) / CHUNK_SIZE_I;
+            // Clients get their full detail chunks from the server, see `VoxelWorldClientPlugin`
+            let voxel_data_fn = match &remote_chunks {
+                Some(remote_chunks) if chunk.lod_level == 0 => {
+                    remote_chunks.lookup_fn(chunk.position)
+                }
+                _ => (configuration.voxel_lookup_delegate_for_lod(chunk.lod_level))(
+                    delegate_position,
+                ),
+            };
+            let biome_fn = configuration
+                .biome_lookup_delegate()
+                .map(|delegate| delegate(delegate_position));
+            let shared_voxel_data = configuration.shared_voxel_data();
+            #[cfg(feature = "render")]
+            let meshing_config = meshing_config.clone();
+            // Enclosed chunks can still be seen from inside, by a camera in a cave
+            #[cfg(feature = "render")]
+            let occlusion_culling = configuration.occlusion_culling()
+                && !camera_positions.iter().any(|cam_pos| {
+                    chunk_position_at_level(*cam_pos, chunk.lod_level) == chunk.position
+                });
+            let downsampling = configuration.lod_downsampling();
+            // Light is baked into full detail chunks, from the chunk and the voxels around it
+            #[cfg(feature = "render")]
+            let light = match &lighting {
+                (Some(_), chunk_map) if chunk.lod_level == 0 => padded_light(
+                    chunk.position,
+                    &chunk_map.get_read_lock(),
+                    &mut light_scratch,
+                ),
+                _ => None,
+            };
 
             let mut chunk_task =
-                ChunkTask::<C>::new(chunk.entity, chunk.position, modified_voxels.clone());
+                ChunkTask::<C>::new(chunk.entity, chunk.position, modified_voxels.clone())
+                    .with_lod_level(chunk.lod_level)
+                    .with_decorator(configuration.chunk_decorator());
+            #[cfg(feature = "render")]
+            {
+                chunk_task = chunk_task.with_light(light);
+            }
 
+            let cancelled = chunk_task.cancellation_flag();
+            let generated = chunk_task.generated_flag();
+            #[cfg(feature = "render")]
             let mesh_map = Arc::new(mesh_cache.get_map());
             let thread = thread_pool.spawn(async move {
-                chunk_task.generate(voxel_data_fn);
-
-                // No need to mesh if the chunk is empty or full
-                if chunk_task.is_empty() || chunk_task.is_full() {
-                    return chunk_task;
+                if let Some(biome_fn) = biome_fn {
+                    chunk_task.generate_biomes(biome_fn);
+                }
+                chunk_task.generate(voxel_data_fn, downsampling);
+                if let Some(shared_voxel_data) = shared_voxel_data {
+                    chunk_task.chunk_data.share_voxels(&shared_voxel_data);
                 }
 
-                // Also no need to mesh if a matching mesh is already cached
-                let mesh_cache_hit = mesh_map
-                    .read()
-                    .unwrap()
-                    .contains_key(&chunk_task.voxels_hash());
-                if !mesh_cache_hit {
-                    chunk_task.mesh(texture_index_mapper);
+                // Headless worlds only need the voxels
+                #[cfg(feature = "render")]
+                {
+                    chunk_task.find_opaque(&meshing_config);
+
+                    // No need to mesh if the chunk is empty or opaque all the way through, or no
+                    // longer needed. Full chunks of fluid or glass still need a mesh.
+                    if chunk_task.is_empty() || chunk_task.is_opaque() || chunk_task.is_cancelled()
+                    {
+                        return chunk_task;
+                    }
+
+                    // Nor if the voxels around it hide it completely
+                    if occlusion_culling && chunk_task.is_enclosed(&meshing_config) {
+                        chunk_task.occluded = true;
+                        return chunk_task;
+                    }
+
+                    // Also no need to mesh if a matching mesh is already cached
+                    let mesh_cache_hit = mesh_map
+                        .read()
+                        .unwrap()
+                        .contains_key(&chunk_task.mesh_hash());
+                    if !mesh_cache_hit {
+                        chunk_task.mesh(&meshing_config);
+                    }
                 }
 
                 chunk_task
@@ -349,19 +1708,58 @@ where
 
             commands
                 .entity(chunk.entity)
-                .try_insert(ChunkThread::<C>::new(thread, chunk.position))
+                .try_insert(ChunkThread::<C>::new(
+                    thread,
+                    chunk.position,
+                    cancelled,
+                    generated,
+                ))
                 .remove::<NeedsRemesh>();
 
             ev_chunk_will_remesh.send(ChunkWillRemesh::<C>::new(chunk.position, chunk.entity));
         }
     }
 
-    /// Inserts new meshes for chunks that have just finished remeshing
+    /// Stores the voxels of chunks that have just finished generating in the chunk map. Headless
+    /// worlds don't mesh their chunks, so this takes the place of `spawn_meshes`.
+    #[cfg(not(feature = "render"))]
     #[allow(clippy::type_complexity)]
+    pub fn store_chunk_voxels(
+        mut commands: Commands,
+        mut chunking_threads: Query<(Entity, &mut ChunkThread<C>, &Chunk<C>), Without<NeedsRemesh>>,
+        mut chunk_map_update_buffer: ResMut<ChunkMapUpdateBuffer<C>>,
+    ) {
+        for (entity, mut thread, chunk) in &mut chunking_threads {
+            let Some(chunk_task) = future::block_on(future::poll_once(&mut thread.0)) else {
+                continue;
+            };
+
+            if !chunk_task.is_empty() {
+                chunk_map_update_buffer.push((
+                    chunk.position,
+                    chunk_task.chunk_data,
+                    ChunkWillSpawn::<C>::new(chunk_task.position, entity),
+                ));
+            }
+
+            commands.entity(entity).remove::<ChunkThread<C>>();
+        }
+    }
+
+    /// Inserts new meshes for chunks that have just finished remeshing
+    #[cfg(feature = "render")]
+    #[allow(clippy::type_complexity, clippy::too_many_arguments)]
     pub fn spawn_meshes(
         mut commands: Commands,
         mut chunking_threads: Query<
-            (Entity, &mut ChunkThread<C>, &mut Chunk<C>, &Transform),
+            (
+                Entity,
+                &mut ChunkThread<C>,
+                &mut Chunk<C>,
+                &Transform,
+                Option<&ChunkMeshParts>,
+                Has<Handle<Mesh>>,
+            ),
             Without<NeedsRemesh>,
         >,
         mut mesh_assets: ResMut<Assets<Mesh>>,
@@ -369,9 +1767,12 @@ where
             ResMut<ChunkMapUpdateBuffer<C>>,
             ResMut<MeshCacheInsertBuffer<C>>,
         ),
-        res: (Res<MeshCache<C>>, Res<LoadingTexture>),
+        res: (Res<MeshCache<C>>, Res<LoadingTexture<C>>, Res<C>),
+        mut ev_chunk_will_spawn: EventWriter<ChunkWillSpawn<C>>,
+        mut lod_chunks: ResMut<LodChunks<C>>,
+        mut chunk_pool: ResMut<ChunkPool<C>>,
     ) {
-        let (mesh_cache, loading_texture) = res;
+        let (mesh_cache, loading_texture, configuration) = res;
 
         if !loading_texture.is_loaded {
             return;
@@ -379,19 +1780,81 @@ where
 
         let (mut chunk_map_update_buffer, mut mesh_cache_insert_buffer) = buffers;
 
-        for (entity, mut thread, chunk, transform) in &mut chunking_threads {
+        let lod_fade = lod_fade_enabled(&*configuration);
+
+        for (entity, mut thread, chunk, transform, mesh_parts, has_mesh) in &mut chunking_threads {
             let thread_result = future::block_on(future::poll_once(&mut thread.0));
 
             if thread_result.is_none() {
                 continue;
             }
 
-            let chunk_task = thread_result.unwrap();
+            let mut chunk_task = thread_result.unwrap();
+
+            if let Some(error) = chunk_task.geometric_error {
+                lod_chunks
+                    .geometric_errors
+                    .insert((chunk.lod_level, chunk.position), error);
+            }
+
+            // The transparent and fluid parts of the chunk live on child entities, which are
+            // replaced every time the chunk is remeshed.
+            if let Some(mesh_parts) = mesh_parts {
+                for part in mesh_parts.0.iter() {
+                    commands.entity(*part).despawn_recursive();
+                }
+                commands.entity(entity).remove::<ChunkMeshParts>();
+            }
+
+            let mut parts = Vec::new();
+
+            if let Some(transparent_mesh) = chunk_task.transparent_mesh.take() {
+                let mesh_ref = Arc::new(mesh_assets.add(transparent_mesh));
+                parts.push(
+                    commands
+                        .spawn((
+                            Transform::IDENTITY,
+                            MeshRef(mesh_ref),
+                            NeedsMaterial::<C>(PhantomData),
+                            TransparentPart,
+                        ))
+                        .id(),
+                );
+            }
+
+            if let Some(fluid_mesh) = chunk_task.fluid_mesh.take() {
+                let mesh_handle = mesh_assets.add(fluid_mesh);
+                let mut fluid_entity = commands.spawn((
+                    SpatialBundle::default(),
+                    mesh_handle.clone(),
+                    MeshRef(Arc::new(mesh_handle)),
+                    VoxelFluidSurface::<C>::new(chunk.position),
+                ));
+                if !configuration.custom_fluid_material() {
+                    fluid_entity.insert((NeedsMaterial::<C>(PhantomData), TransparentPart));
+                }
+                parts.push(fluid_entity.id());
+            }
+
+            // Chunks with transparent or fluid parts are not cached, since the cache only holds
+            // the opaque mesh.
+            let has_mesh_parts = !parts.is_empty();
+            if has_mesh_parts {
+                commands
+                    .entity(entity)
+                    .push_children(&parts)
+                    .try_insert(ChunkMeshParts(parts));
+            }
 
             if !chunk_task.is_empty() {
-                if !chunk_task.is_full() {
+                if chunk_task.occluded {
+                    commands
+                        .entity(entity)
+                        .remove::<Handle<Mesh>>()
+                        .remove::<MeshRef>();
+                } else if !chunk_task.is_opaque() {
                     let mesh_handle = {
-                        if let Some(mesh_handle) = mesh_cache.get(&chunk_task.voxels_hash()) {
+                        if let Some(mesh_handle) = mesh_cache.get(&chunk_task.mesh_hash()) {
                             mesh_handle
                         } else {
                             if chunk_task.mesh.is_none() {
@@ -401,9 +1864,12 @@ where
                                     .remove::<ChunkThread<C>>();
                                 continue;
                             }
-                            let hash = chunk_task.voxels_hash();
-                            let mesh_ref = Arc::new(mesh_assets.add(chunk_task.mesh.unwrap()));
-                            mesh_cache_insert_buffer.push((hash, mesh_ref.clone()));
+                            let hash = chunk_task.mesh_hash();
+                            let mesh = chunk_task.mesh.unwrap();
+                            let mesh_ref = Arc::new(chunk_pool.add_mesh(&mut mesh_assets, mesh));
+                            if !has_mesh_parts {
+                                mesh_cache_insert_buffer.push((hash, mesh_ref.clone()));
+                            }
                             mesh_ref
                         }
                     };
@@ -416,13 +1882,25 @@ where
                             NeedsMaterial::<C>(PhantomData),
                         ))
                         .remove::<bevy::render::primitives::Aabb>();
+
+                    // Newly spawned chunks dither in over the chunks they replace
+                    if lod_fade && !has_mesh {
+                        commands
+                            .entity(entity)
+                            .try_insert(LodFade::<C>::fade_in(chunk.lod_level, chunk.position));
+                    }
                 }
 
-                chunk_map_update_buffer.push((
-                    chunk.position,
-                    chunk_task.chunk_data,
-                    ChunkWillSpawn::<C>::new(chunk_task.position, entity),
-                ));
+                // Only full detail chunks are kept in the chunk map
+                if chunk.lod_level > 0 {
+                    ev_chunk_will_spawn.send(ChunkWillSpawn::<C>::new(chunk_task.position, entity));
+                } else {
+                    chunk_map_update_buffer.push((
+                        chunk.position,
+                        chunk_task.chunk_data,
+                        ChunkWillSpawn::<C>::new(chunk_task.position, entity),
+                    ));
+                }
             } else {
                 commands
                     .entity(entity)
@@ -434,29 +1912,124 @@ where
         }
     }
 
+    /// Moves the voxels of scheduled edits that are due into the write buffer
+    pub fn apply_scheduled_edits(
+        mut scheduled_edits: ResMut<ScheduledEdits<C>>,
+        mut buffer: ResMut<VoxelWriteBuffer<C>>,
+        time: Res<Time>,
+    ) {
+        let voxels = scheduled_edits.take_due(time.elapsed());
+        buffer.extend(voxels);
+    }
+
+    /// Moves up to `VoxelWorldConfig::max_queued_voxels_per_frame` voxels of the queued edits
+    /// into the write buffer, and fires `QueuedEditFinished` for the edits that are done
+    pub fn apply_queued_edits(
+        mut queued_edits: ResMut<QueuedEdits<C>>,
+        mut buffer: ResMut<VoxelWriteBuffer<C>>,
+        mut ev_finished: EventWriter<QueuedEditFinished<C>>,
+        configuration: Res<C>,
+    ) {
+        let mut budget = configuration.max_queued_voxels_per_frame();
+        while budget > 0 {
+            let written = queued_edits.written;
+            let Some((edit, voxels)) = queued_edits.edits.front() else {
+                break;
+            };
+            let end = voxels.len().min(written + budget);
+            buffer.extend_from_slice(&voxels[written..end]);
+            budget -= end - written;
+
+            if end == voxels.len() {
+                ev_finished.send(QueuedEditFinished::new(*edit));
+                queued_edits.edits.pop_front();
+                queued_edits.written = 0;
+            } else {
+                queued_edits.written = end;
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn flush_voxel_write_buffer(
         mut commands: Commands,
         mut buffer: ResMut<VoxelWriteBuffer<C>>,
         chunk_map: Res<ChunkMap<C>>,
+        lod_chunks: Res<LodChunks<C>>,
         modified_voxels: ResMut<ModifiedVoxels<C>>,
+        mut modified_chunks: ResMut<ModifiedChunks<C>>,
+        protected_regions: Res<ProtectedRegions<C>>,
+        mut ev_edit_rejected: EventWriter<VoxelEditRejected<C>>,
+        mut light_updates: Option<ResMut<LightUpdates<C>>>,
+        configuration: Res<C>,
     ) {
+        // Chunk tasks read the modified voxels while generating, so only wait for them when
+        // there is something to write
+        if !buffer.has_pending() {
+            return;
+        }
+
         let chunk_map_read_lock = chunk_map.get_read_lock();
         let mut modified_voxels = modified_voxels.write().unwrap();
-
-        for (position, voxel) in buffer.iter() {
-            let (chunk_pos, _vox_pos) = get_chunk_voxel_position(*position);
+        let max_lod_level =
+            LodRings::from_config(&*configuration).map_or(0, |rings| rings.max_level());
+        let occlusion_culling = configuration.occlusion_culling();
+
+        // Each touched chunk is only marked once, however many of its voxels changed
+        let mut touched_chunks = HashSet::new();
+        for (i, (position, voxel)) in buffer.pending().enumerate() {
+            // Queued and scheduled edits are checked here, when they are applied
+            if !buffer.is_restored(i) && protected_regions.contains(*position) {
+                ev_edit_rejected.send(VoxelEditRejected::new(*position));
+                continue;
+            }
+            let (chunk_pos, vox_pos) = get_chunk_voxel_position(*position);
             modified_voxels.insert(*position, *voxel);
+            modified_chunks.insert(chunk_pos);
+            touched_chunks.insert((0, chunk_pos));
+            if let Some(light_updates) = light_updates.as_mut() {
+                light_updates.edits.push(*position);
+            }
 
-            // Mark the chunk as needing remeshing or spawn a new chunk if it doesn't exist
-            if let Some(chunk_data) = ChunkMap::<C>::get(&chunk_pos, &chunk_map_read_lock) {
-                if let Some(mut ent) = commands.get_entity(chunk_data.entity) {
-                    ent.try_insert(NeedsRemesh);
+            // The chunk across the border may have been culled behind the voxel
+            if occlusion_culling {
+                for axis in 0..3 {
+                    let mut step = IVec3::ZERO;
+                    if vox_pos[axis] == 1 {
+                        step[axis] = -1;
+                    } else if vox_pos[axis] == CHUNK_SIZE_U {
+                        step[axis] = 1;
+                    } else {
+                        continue;
+                    }
+                    touched_chunks.insert((0, chunk_pos + step));
                 }
             }
+
+            // Coarser chunks covering the voxel also need remeshing
+            for level in 1..=max_lod_level {
+                touched_chunks.insert((level, chunk_position_at_level(position.as_vec3(), level)));
+            }
         }
         buffer.clear();
+        buffer.remote.clear();
+        buffer.restored.clear();
+
+        for (level, chunk_pos) in touched_chunks {
+            // Mark the chunk as needing remeshing or spawn a new chunk if it doesn't exist
+            let entity = if level == 0 {
+                ChunkMap::<C>::get(&chunk_pos, &chunk_map_read_lock)
+                    .map(|chunk_data| chunk_data.entity)
+            } else {
+                lod_chunks.spawned.get(&(level, chunk_pos)).copied()
+            };
+            if let Some(mut ent) = entity.and_then(|entity| commands.get_entity(entity)) {
+                ent.try_insert(NeedsRemesh);
+            }
+        }
     }
 
+    #[cfg(feature = "render")]
     pub fn flush_mesh_cache_buffers(
         mut mesh_cache_insert_buffer: ResMut<MeshCacheInsertBuffer<C>>,
         mesh_cache: Res<MeshCache<C>>,
@@ -479,32 +2052,340 @@ where
         );
     }
 
+    /// Run-length encodes the voxel data of chunks that haven't changed for
+    /// `VoxelWorldConfig::chunk_compression_delay`
+    pub fn compress_idle_chunks(
+        chunk_map: Res<ChunkMap<C>>,
+        configuration: Res<C>,
+        time: Res<Time>,
+        mut changed_at: Local<HashMap<IVec3, (u64, Duration)>>,
+    ) {
+        let Some(delay) = configuration.chunk_compression_delay() else {
+            return;
+        };
+
+        let map = chunk_map.get_map();
+        let Ok(mut write_lock) = map.try_write() else {
+            return;
+        };
+
+        // A chunk counts as changed when its voxels are replaced by a remesh
+        let now = time.elapsed();
+        let mut still_spawned = HashMap::new();
+        for (position, chunk_data) in write_lock.iter_mut() {
+            if chunk_data.voxels.is_none() {
+                continue;
+            }
+            let since = match changed_at.get(position) {
+                Some((hash, since)) if *hash == chunk_data.voxels_hash => *since,
+                _ => now,
+            };
+            if now.saturating_sub(since) >= delay {
+                chunk_data.compress();
+            } else {
+                still_spawned.insert(*position, (chunk_data.voxels_hash, since));
+            }
+        }
+        *changed_at = still_spawned;
+    }
+
+    #[cfg(feature = "render")]
+    #[allow(clippy::type_complexity)]
     pub(crate) fn assign_material<M: Material>(
         mut commands: Commands,
-        mut needs_material: Query<(Entity, &MeshRef, &Transform), With<NeedsMaterial<C>>>,
-        material_handle: Option<Res<VoxelWorldMaterialHandle<M>>>,
+        mut needs_material: Query<
+            (Entity, &MeshRef, &Transform, Has<TransparentPart>),
+            With<NeedsMaterial<C>>,
+        >,
+        material_handle: Option<Res<VoxelWorldMaterialHandle<C, M>>>,
     ) {
         let Some(material_handle) = material_handle else {
             return;
         };
 
-        for (entity, mesh_ref, transform) in needs_material.iter_mut() {
+        for (entity, mesh_ref, transform, is_transparent) in needs_material.iter_mut() {
+            let material = if is_transparent {
+                material_handle
+                    .transparent_handle
+                    .clone()
+                    .unwrap_or_else(|| material_handle.handle.clone())
+            } else {
+                material_handle.handle.clone()
+            };
+
             commands
                 .entity(entity)
                 .try_insert(MaterialMeshBundle {
                     mesh: (*mesh_ref.0).clone(),
-                    material: material_handle.handle.clone(),
+                    material,
                     transform: *transform,
                     ..default()
                 })
                 .remove::<NeedsMaterial<C>>();
         }
     }
+
+    /// Marks chunks that should not cast shadows with `NotShadowCaster`, see
+    /// `VoxelWorldConfig::cast_shadows` and `VoxelWorldConfig::shadow_distance`
+    #[cfg(feature = "render")]
+    pub(crate) fn update_shadow_casters(
+        mut commands: Commands,
+        chunks: Query<(Entity, &Chunk<C>, Has<NotShadowCaster>)>,
+        configuration: Res<C>,
+        camera_info: CameraInfo<C>,
+    ) {
+        let shadow_distance = configuration.shadow_distance();
+        let cast_shadows = configuration.cast_shadows();
+        if cast_shadows && shadow_distance.is_none() {
+            return;
+        }
+        let camera_position = camera_info
+            .iter()
+            .next()
+            .map(|(_, _, cam_gtf)| cam_gtf.translation());
+
+        for (entity, chunk, not_shadow_caster) in chunks.iter() {
+            let casts_shadow = cast_shadows
+                && match (shadow_distance, camera_position) {
+                    (Some(max_distance), Some(camera_position)) => {
+                        let camera_chunk =
+                            chunk_position_at_level(camera_position, chunk.lod_level);
+                        let distance =
+                            (chunk.position - camera_chunk).abs().max_element() << chunk.lod_level;
+                        distance as u32 <= max_distance
+                    }
+                    _ => true,
+                };
+            if casts_shadow && not_shadow_caster {
+                commands.entity(entity).remove::<NotShadowCaster>();
+            } else if !casts_shadow && !not_shadow_caster {
+                commands.entity(entity).try_insert(NotShadowCaster);
+            }
+        }
+    }
+
+    /// Steps chunks through the dithering materials while they cross-fade between levels of
+    /// detail, and despawns chunks that have faded out
+    #[cfg(feature = "render")]
+    #[allow(clippy::type_complexity, clippy::too_many_arguments)]
+    pub(crate) fn update_lod_fades<M: Material>(
+        mut commands: Commands,
+        mut fading_chunks: Query<(Entity, &mut LodFade<C>, Option<&MeshRef>)>,
+        pending_chunks: Query<&Chunk<C>, Or<(With<NeedsRemesh>, With<ChunkThread<C>>)>>,
+        material_handle: Option<Res<VoxelWorldMaterialHandle<C, M>>>,
+        mut chunk_pool: ResMut<ChunkPool<C>>,
+        configuration: Res<C>,
+        camera_motion: Res<CameraMotion<C>>,
+        time: Res<Time>,
+    ) {
+        let Some(material_handle) = material_handle else {
+            return;
+        };
+
+        let duration = configuration.lod_fade_duration();
+        let delta = time.delta_seconds();
+        let pending: Vec<(u8, IVec3)> = pending_chunks
+            .iter()
+            .map(|chunk| (chunk.lod_level, chunk.position))
+            .collect();
+        let fading_out: Vec<(u8, IVec3)> = fading_chunks
+            .iter()
+            .filter(|(_, fade, _)| fade.fading_out)
+            .map(|(_, fade, _)| fade.chunk)
+            .collect();
+        let overlaps = |chunks: &[(u8, IVec3)], chunk: (u8, IVec3)| {
+            chunks.iter().any(|other| lod_chunks_overlap(*other, chunk))
+        };
+
+        for (entity, mut fade, mesh_ref) in fading_chunks.iter_mut() {
+            let materials = if fade.fading_out {
+                &material_handle.lod_fade_out
+            } else {
+                &material_handle.lod_fade_in
+            };
+
+            // There is nothing to cross-fade between when the camera teleports
+            if camera_motion.any_teleported() {
+                fade.progress = 1.0;
+            } else if fade.fading_out && fade.progress == 0.0 {
+                // Retired chunks stay fully visible until the chunks replacing them have been
+                // meshed, so that no holes open up. Replacements that take longer than the fade
+                // itself are not waited for, and the chunk is dropped without fading.
+                fade.waited += delta;
+                if overlaps(&pending, fade.chunk) {
+                    if fade.waited < duration {
+                        continue;
+                    }
+                    fade.progress = 1.0;
+                }
+            } else if !fade.fading_out && fade.progress == 0.0 && !overlaps(&fading_out, fade.chunk)
+            {
+                // Chunks that don't replace a fading chunk have nothing to cross-fade with
+                fade.progress = 1.0;
+            }
+
+            fade.progress += if duration > 0.0 {
+                delta / duration
+            } else {
+                1.0
+            };
+
+            if fade.progress >= 1.0 || materials.is_empty() {
+                if fade.fading_out {
+                    chunk_pool.recycle_mesh(mesh_ref);
+                    chunk_pool.recycle(&mut commands, entity);
+                } else {
+                    commands
+                        .entity(entity)
+                        .remove::<LodFade<C>>()
+                        .try_insert(material_handle.handle.clone());
+                }
+                continue;
+            }
+
+            let step = ((fade.progress * materials.len() as f32) as usize).min(materials.len() - 1);
+            commands.entity(entity).try_insert(materials[step].clone());
+        }
+    }
+}
+
+/// The chunks along the path of a camera moving by `lookahead`, nearest first. At most
+/// `max_chunks` chunks are returned, so that fast cameras don't flood the spawn queue.
+#[cfg(feature = "render")]
+pub(crate) fn prefetched_chunks(
+    camera_position: Vec3,
+    lookahead: Vec3,
+    max_chunks: usize,
+    chunk_at: impl Fn(Vec3) -> Option<(u8, IVec3)>,
+) -> Vec<(u8, IVec3)> {
+    let direction = lookahead.normalize_or_zero();
+    let lookahead_steps = ((lookahead.length() / CHUNK_SIZE_F).floor() as usize).min(max_chunks);
+
+    let mut chunks = Vec::new();
+    let mut seen = HashSet::new();
+    for step in 1..=lookahead_steps {
+        let point = camera_position + direction * (step as f32 * CHUNK_SIZE_F);
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    if chunks.len() >= max_chunks {
+                        return chunks;
+                    }
+                    let offset = IVec3::new(x, y, z).as_vec3() * CHUNK_SIZE_F;
+                    if let Some(chunk) = chunk_at(point + offset) {
+                        if seen.insert(chunk) {
+                            chunks.push(chunk);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    chunks
+}
+
+/// The input for custom spawn and despawn strategies. Chunks without a `ViewVisibility` are
+/// checked for being in view by their center.
+#[cfg(feature = "render")]
+fn chunk_strategy_input(
+    chunk_position: IVec3,
+    lod_level: u8,
+    camera: &Camera,
+    cam_gtf: &GlobalTransform,
+    view_visibility: Option<&ViewVisibility>,
+) -> ChunkStrategyInput {
+    let camera_position = cam_gtf.translation();
+    let camera_chunk = chunk_position_at_level(camera_position, lod_level);
+    let distance = (chunk_position - camera_chunk).abs().max_element() << lod_level;
+    let in_view = match view_visibility {
+        Some(visibility) => visibility.get(),
+        None => {
+            let center = (chunk_position.as_vec3() + 0.5) * lod_chunk_size(lod_level);
+            is_in_view(center, camera, cam_gtf)
+        }
+    };
+
+    ChunkStrategyInput {
+        chunk_position,
+        lod_level,
+        camera_position,
+        distance: distance as u32,
+        in_view,
+    }
+}
+
+/// Chunks waiting to be spawned, ordered in expanding rings around the camera. Chunks in the
+/// same ring are ordered by position, so the order doesn't depend on how they were queued.
+#[cfg(feature = "render")]
+pub(crate) struct SpawnQueue {
+    heap: BinaryHeap<Reverse<(i32, u8, [i32; 3])>>,
+    camera_position: Vec3,
+}
+
+#[cfg(feature = "render")]
+impl SpawnQueue {
+    pub(crate) fn new(camera_position: Vec3) -> Self {
+        Self {
+            heap: BinaryHeap::new(),
+            camera_position,
+        }
+    }
+
+    /// Chebyshev distance, in full detail chunks, between the camera's chunk and the chunk
+    fn ring(&self, level: u8, chunk_position: IVec3) -> i32 {
+        let camera_chunk = chunk_position_at_level(self.camera_position, level);
+        (chunk_position - camera_chunk).abs().max_element() << level
+    }
+
+    pub(crate) fn push(&mut self, (level, chunk_position): (u8, IVec3)) {
+        let ring = self.ring(level, chunk_position);
+        self.heap
+            .push(Reverse((ring, level, chunk_position.to_array())));
+    }
+
+    /// Queue a chunk as if it was half as far from the camera
+    fn push_ahead(&mut self, (level, chunk_position): (u8, IVec3)) {
+        let ring = self.ring(level, chunk_position) / 2;
+        self.heap
+            .push(Reverse((ring, level, chunk_position.to_array())));
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<(u8, IVec3)> {
+        self.heap
+            .pop()
+            .map(|Reverse((_, level, position))| (level, IVec3::from_array(position)))
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    /// Drop the queued chunks that are not right around the camera. Used when the camera
+    /// teleported, since the chunks queued for its old surroundings are no longer needed.
+    pub(crate) fn flush(&mut self) {
+        let camera_position = self.camera_position;
+        self.heap.retain(|Reverse((_, level, position))| {
+            let camera_chunk = chunk_position_at_level(camera_position, *level);
+            (IVec3::from_array(*position) - camera_chunk)
+                .abs()
+                .max_element()
+                <= 1
+        });
+    }
+}
+
+#[cfg(feature = "render")]
+impl Extend<(u8, IVec3)> for SpawnQueue {
+    fn extend<T: IntoIterator<Item = (u8, IVec3)>>(&mut self, iter: T) {
+        for chunk in iter {
+            self.push(chunk);
+        }
+    }
 }
 
 /// Check if the given world point is within the camera's view
 #[inline]
-#[allow(dead_code)]
+#[cfg(feature = "render")]
 fn is_in_view(world_point: Vec3, camera: &Camera, cam_global_transform: &GlobalTransform) -> bool {
     if let Some(chunk_vp) = camera.world_to_ndc(cam_global_transform, world_point) {
         // When the position is within the viewport the values returned will be between
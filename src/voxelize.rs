@@ -0,0 +1,102 @@
+///
+/// Mesh voxelization
+/// Converts triangle meshes into the positions of the voxels they cover, see
+/// `VoxelWorld::voxelize_mesh`.
+///
+use bevy::{
+    prelude::*,
+    render::{mesh::VertexAttributeValues, render_resource::PrimitiveTopology},
+    utils::{HashMap, HashSet},
+};
+
+/// The positions of the voxels covered by a mesh with the given transform. The surface of the
+/// mesh is always voxelized. Closed meshes are filled as well, by casting a ray up through the
+/// center of each column and filling the voxels between each pair of crossings. Meshes that are
+/// not triangle lists, or that have no positions, cover no voxels.
+pub(crate) fn voxelize_mesh(mesh: &Mesh, transform: &Transform) -> HashSet<IVec3> {
+    let mut positions = HashSet::new();
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return positions;
+    }
+    let Some(VertexAttributeValues::Float32x3(vertices)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return positions;
+    };
+
+    let matrix = transform.compute_matrix();
+    let vertices: Vec<Vec3> = vertices
+        .iter()
+        .map(|v| matrix.transform_point3(Vec3::from_array(*v)))
+        .collect();
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(indices) => indices.iter().collect(),
+        None => (0..vertices.len()).collect(),
+    };
+
+    // The heights at which the mesh crosses the vertical line through the center of each column
+    let mut crossings: HashMap<IVec2, Vec<f32>> = HashMap::new();
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i]]);
+        voxelize_surface(a, b, c, &mut positions);
+        add_column_crossings(a, b, c, &mut crossings);
+    }
+
+    for (column, mut heights) in crossings {
+        heights.sort_by(f32::total_cmp);
+        // Lines through an edge shared by two triangles cross both of them
+        heights.dedup_by(|a, b| (*a - *b).abs() < 1e-4);
+        for pair in heights.chunks_exact(2) {
+            // Fill the voxels whose centers lie between the two crossings
+            let bottom = (pair[0] - 0.5).ceil() as i32;
+            let top = (pair[1] - 0.5).floor() as i32;
+            positions.extend((bottom..=top).map(|y| IVec3::new(column.x, y, column.y)));
+        }
+    }
+
+    positions
+}
+
+/// Sample the triangle at least twice per voxel along each edge, and add the voxels of the
+/// samples. Samples are nudged against the triangle's normal, so that faces lying exactly on a
+/// voxel boundary end up in the voxels behind them.
+fn voxelize_surface(a: Vec3, b: Vec3, c: Vec3, positions: &mut HashSet<IVec3>) {
+    let nudge = -(b - a).cross(c - a).normalize_or_zero() * 1e-3;
+    let longest_edge = a.distance(b).max(b.distance(c)).max(c.distance(a));
+    let steps = (longest_edge * 2.0).ceil().max(1.0) as u32;
+    for i in 0..=steps {
+        for j in 0..=steps - i {
+            let u = i as f32 / steps as f32;
+            let v = j as f32 / steps as f32;
+            let point = a + (b - a) * u + (c - a) * v;
+            positions.insert((point + nudge).floor().as_ivec3());
+        }
+    }
+}
+
+/// Add the heights at which the triangle crosses the vertical lines through the column centers
+/// it covers
+fn add_column_crossings(a: Vec3, b: Vec3, c: Vec3, crossings: &mut HashMap<IVec2, Vec<f32>>) {
+    let (a2, b2, c2) = (a.xz(), b.xz(), c.xz());
+    let area = (b2 - a2).perp_dot(c2 - a2);
+    if area.abs() < f32::EPSILON {
+        // Vertical triangles are not crossed by vertical lines
+        return;
+    }
+
+    let min = a2.min(b2).min(c2);
+    let max = a2.max(b2).max(c2);
+    for x in (min.x - 0.5).ceil() as i32..=(max.x - 0.5).floor() as i32 {
+        for z in (min.y - 0.5).ceil() as i32..=(max.y - 0.5).floor() as i32 {
+            let point = Vec2::new(x as f32 + 0.5, z as f32 + 0.5);
+            let w_b = (point - a2).perp_dot(c2 - a2) / area;
+            let w_c = (b2 - a2).perp_dot(point - a2) / area;
+            let w_a = 1.0 - w_b - w_c;
+            if w_a < 0.0 || w_b < 0.0 || w_c < 0.0 {
+                continue;
+            }
+            let height = a.y * w_a + b.y * w_b + c.y * w_c;
+            crossings.entry(IVec2::new(x, z)).or_default().push(height);
+        }
+    }
+}
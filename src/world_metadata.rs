@@ -0,0 +1,139 @@
+///
+/// World metadata
+/// World level information that is saved alongside the chunks of a persisted world, like the
+/// seed and generator parameters, so that the world's configuration can be recreated when it is
+/// loaded again. See `VoxelWorldConfig::world_metadata`.
+///
+use std::{collections::BTreeMap, io};
+
+use crate::{
+    chunk::CHUNK_SIZE_U,
+    chunk_storage::{ChunkStorageProvider, Reader},
+};
+
+/// The version of the layout of saved chunks and regions
+pub const SAVE_FORMAT_VERSION: u32 = 1;
+
+const METADATA_MAGIC: &[u8; 4] = b"BVWM";
+
+/// Describes a saved world. The format version and chunk size are filled in by the crate, the
+/// seed and parameters are up to the world's configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WorldMetadata {
+    pub format_version: u32,
+    pub chunk_size: u32,
+    pub seed: u64,
+    /// Named generator parameters, like noise scales or the name of a biome preset
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl Default for WorldMetadata {
+    fn default() -> Self {
+        Self {
+            format_version: SAVE_FORMAT_VERSION,
+            chunk_size: CHUNK_SIZE_U,
+            seed: 0,
+            parameters: BTreeMap::new(),
+        }
+    }
+}
+
+impl WorldMetadata {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_parameter(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.parameters.insert(name.into(), value.to_string());
+        self
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters.get(name).map(String::as_str)
+    }
+
+    /// Parse a parameter, for example a noise scale saved with `with_parameter`
+    pub fn parse_parameter<T: std::str::FromStr>(&self, name: &str) -> Option<T> {
+        self.parameter(name)?.parse().ok()
+    }
+
+    /// True if chunks saved with this metadata can be loaded by this version of the crate
+    pub fn is_compatible(&self) -> bool {
+        self.format_version == SAVE_FORMAT_VERSION && self.chunk_size == CHUNK_SIZE_U
+    }
+
+    /// Like `is_compatible`, but with an error that says why the world can't be loaded
+    pub fn check_compatible(&self) -> io::Result<()> {
+        if self.is_compatible() {
+            return Ok(());
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "saved world has format version {} and chunk size {}, expected {} and {}",
+                self.format_version, self.chunk_size, SAVE_FORMAT_VERSION, CHUNK_SIZE_U
+            ),
+        ))
+    }
+
+    /// The metadata saved in a storage, or `None` if the storage has no world saved. Pass it to
+    /// `VoxelWorldConfig::from_world_metadata` to recreate the world's configuration.
+    pub fn load(storage: &dyn ChunkStorageProvider) -> io::Result<Option<Self>> {
+        let Some(bytes) = storage.load_metadata()? else {
+            return Ok(None);
+        };
+        Self::decode(&bytes)
+            .map(Some)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "corrupt world metadata"))
+    }
+
+    pub fn save(&self, storage: &dyn ChunkStorageProvider) -> io::Result<()> {
+        storage.save_metadata(self.encode())
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(METADATA_MAGIC);
+        bytes.extend_from_slice(&self.format_version.to_le_bytes());
+        bytes.extend_from_slice(&self.chunk_size.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.parameters.len() as u32).to_le_bytes());
+        for (name, value) in &self.parameters {
+            for text in [name, value] {
+                bytes.extend_from_slice(&(text.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(text.as_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub(crate) fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut reader = Reader(bytes);
+        if reader.take(4)? != METADATA_MAGIC {
+            return None;
+        }
+        let format_version = reader.u32()?;
+        let chunk_size = reader.u32()?;
+        let seed = u64::from_le_bytes(reader.take(8)?.try_into().ok()?);
+        let count = reader.u32()?;
+        let mut parameters = BTreeMap::new();
+        for _ in 0..count {
+            let mut text = || {
+                let len = reader.u32()? as usize;
+                String::from_utf8(reader.take(len)?.to_vec()).ok()
+            };
+            let name = text()?;
+            let value = text()?;
+            parameters.insert(name, value);
+        }
+        Some(Self {
+            format_version,
+            chunk_size,
+            seed,
+            parameters,
+        })
+    }
+}